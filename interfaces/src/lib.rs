@@ -5,7 +5,7 @@ use serde::{Deserialize, Serialize};
 use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
 use tungstenite::Message;
 
-use crate::types::{BlockLocation, Selection2D};
+use crate::types::{BlockLocation, Location, Selection2D, Selection3D, SimpleType};
 
 pub mod types;
 
@@ -31,16 +31,233 @@ pub struct Attack {
     pub name: String,
 }
 
+/// Build the schematic file `path` in the schematic directory with its lowest
+/// corner at `origin`.
+/// A global command. The process should allocate appropriately to children.
 #[derive(Serialize, Deserialize, Debug)]
+pub struct Build {
+    pub path: String,
+    pub origin: BlockLocation,
+}
+
+/// Export the world the bots have seen as a singleplayer world
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Export {
+    pub name: Option<String>,
+}
+
+/// Save the blocks the bots have seen in a selection as a `WorldEdit`
+/// schematic named `name`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Schematic {
+    pub sel: Selection3D,
+    pub name: String,
+}
+
+/// Stop every task of the selected bots
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Stop {}
+
+/// The bots nearest to a location
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Nearest {
+    pub count: usize,
+    /// the player who sent a chat command if not given
+    pub location: Option<BlockLocation>,
+}
+
+/// Which bots run a command. A bot has to match every filter which is given.
+/// The nearest bots and then a random sample are picked from the bots which
+/// match. The default is every bot
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct Selector {
+    /// bots with one of these usernames
+    pub names: Vec<String>,
+    /// bots with one of these tags or in one of these groups
+    pub tags: Vec<String>,
+    /// only bots without a task
+    pub idle: bool,
+    pub nearest: Option<Nearest>,
+    /// a random sample of this many bots
+    pub random: Option<usize>,
+}
+
+impl Selector {
+    /// Add the chat argument `arg` to the selector. Arguments are `@name=a,b`,
+    /// `@tag=a,b`, `@idle`, `@near=count[,x,y,z]` and `@random=count`. Returns
+    /// `None` if `arg` is not one of them
+    pub fn add_chat_arg(&mut self, arg: &str) -> Option<()> {
+        let arg = arg.strip_prefix('@')?;
+        let (key, value) = arg.split_once('=').unwrap_or((arg, ""));
+        let list = || value.split(',').map(ToString::to_string).collect();
+
+        match key {
+            "name" => self.names = list(),
+            "tag" => self.tags = list(),
+            "idle" => self.idle = true,
+            "near" => {
+                let mut values = value.split(',');
+                let count = values.next()?.parse().ok()?;
+
+                let location = match (values.next(), values.next(), values.next()) {
+                    (Some(x), Some(y), Some(z)) => Some(BlockLocation::new(
+                        x.parse().ok()?,
+                        y.parse().ok()?,
+                        z.parse().ok()?,
+                    )),
+                    (None, None, None) => None,
+                    _ => return None,
+                };
+
+                self.nearest = Some(Nearest { count, location });
+            }
+            "random" => self.random = Some(value.parse().ok()?),
+            _ => return None,
+        }
+
+        Some(())
+    }
+}
+
+/// Cancel the command `id`. Sent back once the command is cancelled
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Cancelled {
     pub id: Id,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Sent once every bot is done with the command `id`
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Finished {
     pub id: Id,
 }
 
+/// Sent when the command `id` could not be run
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Failed {
+    pub id: Id,
+    pub reason: String,
+}
+
+/// Sent to the client which sent a command, with the id it is tracked by
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Accepted {
+    pub id: Id,
+}
+
+/// How far along a bot is with the command `id`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Progress {
+    pub id: Id,
+    /// the username of the bot
+    pub bot: String,
+    /// the fraction of the whole command which has been handed out to bots,
+    /// for commands which are split between bots
+    pub done: Option<f64>,
+    /// the number of moves left on the path the bot is following
+    pub path_remaining: Option<usize>,
+}
+
+/// Sent by a websocket client to pick the events it gets besides the ones
+/// about its commands. Replaces the previous subscription
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Subscribe {
+    /// the state of every bot, every second
+    pub telemetry: bool,
+    /// only the state of these bots. Every bot if empty
+    pub bots: Vec<String>,
+    /// what happens in the world, see [`WorldEvent`]
+    pub world: bool,
+}
+
+/// If a bot is logged in
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+#[serde(tag = "state")]
+pub enum Connection {
+    Online,
+    /// the state is the last one before the bot disconnected
+    Reconnecting {
+        attempt: u32,
+    },
+}
+
+/// The number of items of a kind a bot has
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ItemCount {
+    pub id: u32,
+    pub count: u32,
+}
+
+/// The state of a bot
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Telemetry {
+    pub bot: String,
+    pub connection: Connection,
+    pub location: Location,
+    pub dimension: String,
+    pub health: f32,
+    pub food: u8,
+    /// the name of the running task
+    pub task: Option<String>,
+    /// ordered by id
+    pub inventory: Vec<ItemCount>,
+}
+
+/// Something which happened in the world of the bots
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "kind")]
+pub enum WorldEvent {
+    PlayerJoined {
+        name: String,
+    },
+    PlayerLeft {
+        name: String,
+    },
+    Chat {
+        /// the player who sent a chat message or whisper
+        from: Option<String>,
+        /// the bot a whisper was sent to
+        to: Option<String>,
+        message: String,
+    },
+    BotJoined {
+        bot: String,
+    },
+    BotDied {
+        bot: String,
+        /// the death message as a JSON chat component, if the server sent one
+        message: Option<String>,
+    },
+    BotDisconnected {
+        bot: String,
+        reason: Option<String>,
+        reconnecting: bool,
+    },
+    /// the bot ran out of attempts to reconnect
+    BotGaveUp {
+        bot: String,
+        attempts: u32,
+    },
+}
+
+/// Messages sent to websocket clients about the commands they sent and what
+/// they subscribed to
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "lowercase")]
+#[serde(tag = "path")]
+pub enum Event {
+    Accepted(Accepted),
+    Progress(Progress),
+    Finished(Finished),
+    Cancelled(Cancelled),
+    Failed(Failed),
+    Telemetry(Telemetry),
+    World(WorldEvent),
+}
+
 macro_rules! commands {
     (
         $($command: ident),*
@@ -57,13 +274,50 @@ macro_rules! commands {
 }
 
 commands! {
-    Mine, GoTo, Attack, Cancelled, Finished
+    Mine, GoTo, Attack, Build, Export, Schematic, Stop, Cancelled, Finished
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Command {
-    id: u64,
-    data: CommandData,
+    pub id: u64,
+    /// the bots which run the command
+    #[serde(default)]
+    pub select: Selector,
+    pub data: CommandData,
+}
+
+/// Start `count` more bots from the account pool. The body of `POST /bots` on
+/// the management API, which responds with how many can actually start. The
+/// bots join every server in turn
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Spawn {
+    pub count: usize,
+}
+
+/// A block the bots know of
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BlockInfo {
+    pub location: BlockLocation,
+    /// the block state, unless only an estimate of the block is known
+    pub state: Option<u32>,
+    /// the block id of the state
+    pub id: Option<u32>,
+    pub simple: SimpleType,
+}
+
+/// A chunk column the bots know of
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChunkInfo {
+    pub x: i32,
+    pub z: i32,
+    /// the number of bots which have the column loaded
+    pub holders: usize,
+    /// if the column was loaded from a save and not sent by the server since
+    pub stale: bool,
+    /// if only estimates of the blocks are kept
+    pub low_memory: bool,
+    /// the approximate size in bytes
+    pub memory: usize,
 }
 
 pub struct Comm {
@@ -176,12 +430,15 @@ impl Comm {
 
 #[cfg(test)]
 mod tests {
-    use crate::{Attack, Command, CommandData};
+    use crate::{
+        types::BlockLocation, Attack, Command, CommandData, Event, Nearest, Selector, WorldEvent,
+    };
 
     #[test]
     fn test() {
         let command = Command {
             id: 123,
+            select: Selector::default(),
             data: CommandData::Attack(Attack {
                 name: "hello".to_string(),
             }),
@@ -189,4 +446,46 @@ mod tests {
 
         serde_json::to_string(&command).unwrap();
     }
+
+    #[test]
+    fn test_chat_selector() {
+        let mut selector = Selector::default();
+
+        for arg in [
+            "@tag=miners,builders",
+            "@idle",
+            "@near=3,1,2,3",
+            "@random=2",
+        ] {
+            assert!(selector.add_chat_arg(arg).is_some());
+        }
+
+        assert_eq!(selector.tags, ["miners", "builders"]);
+        assert!(selector.idle);
+        assert_eq!(
+            selector.nearest,
+            Some(Nearest {
+                count: 3,
+                location: Some(BlockLocation::new(1, 2, 3))
+            })
+        );
+        assert_eq!(selector.random, Some(2));
+
+        assert!(selector.add_chat_arg("@near=3,1").is_none());
+        assert!(selector.add_chat_arg("@unknown").is_none());
+        assert!(selector.add_chat_arg("10").is_none());
+    }
+
+    #[test]
+    fn test_world_event() {
+        let event = Event::World(WorldEvent::BotDied {
+            bot: "Bot0000".to_string(),
+            message: None,
+        });
+
+        let value = serde_json::to_value(&event).unwrap();
+        assert_eq!(value["path"], "world");
+        assert_eq!(value["kind"], "bot_died");
+        assert_eq!(value["bot"], "Bot0000");
+    }
 }
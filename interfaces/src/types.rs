@@ -2,6 +2,7 @@ use std::{
     f32::consts::PI,
     fmt::{Debug, Display, Formatter},
     ops::{Add, AddAssign, Index, Mul, MulAssign, Neg, Sub},
+    str::FromStr,
 };
 
 use colored::Colorize;
@@ -15,9 +16,12 @@ use swarm_bot_packets::{
     *,
 };
 
-use crate::types::{
-    block_data::{Block, BlockData},
-    Origin::{Abs, Rel},
+use crate::{
+    types::{
+        block_data::{Block, BlockData},
+        Origin::{Abs, Rel},
+    },
+    Selector,
 };
 
 pub mod block_data;
@@ -52,6 +56,16 @@ pub struct Chat {
 }
 
 impl Chat {
+    /// the text without any formatting
+    pub fn plain(&self) -> String {
+        let extra = self
+            .extra
+            .iter()
+            .flatten()
+            .map(|section| section.text.as_str());
+        self.text.iter().map(String::as_str).chain(extra).collect()
+    }
+
     pub fn colorize(self) -> String {
         if let Some(extra) = self.extra {
             extra.into_iter().map(|section| section.colorize()).join("")
@@ -104,6 +118,8 @@ impl ChatSection {
 pub struct Command {
     pub player: String,
     pub command: String,
+    /// the bots which run the command, from the `@` arguments right after it
+    pub selector: Selector,
     pub args: Vec<String>,
 }
 
@@ -123,15 +139,23 @@ impl PlayerMessage {
         let command = capture.get(1)?.as_str().to_string();
         let args = capture.get(2)?.as_str().to_string();
 
-        let args = if args.is_empty() {
+        let mut args = if args.is_empty() {
             Vec::new()
         } else {
             args.split(' ').map(|x| x.to_string()).collect()
         };
 
+        let mut selector = Selector::default();
+        let selectors = args
+            .iter()
+            .take_while(|arg| selector.add_chat_arg(arg).is_some())
+            .count();
+        args.drain(..selectors);
+
         Some(Command {
             player: self.player,
             command,
+            selector,
             args,
         })
     }
@@ -655,6 +679,19 @@ impl Display for Dimension {
     }
 }
 
+impl FromStr for Dimension {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nether" => Ok(Dimension::Nether),
+            "overworld" => Ok(Dimension::Overworld),
+            "end" => Ok(Dimension::End),
+            s => Err(format!("unknown dimension {s}")),
+        }
+    }
+}
+
 impl ByteReadable for Dimension {
     fn read_from_bytes(byte_reader: &mut ByteReader) -> Self {
         use Dimension::*;
@@ -731,7 +768,33 @@ impl Selection2D {
     }
 }
 
-#[derive(Writable, Readable, Debug, Copy, Clone, Default, PartialEq)]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Selection3D {
+    pub from: BlockLocation,
+    pub to: BlockLocation,
+}
+
+impl Selection3D {
+    /// Normalize so that the **from** coordinate is always smaller than the
+    /// **to** coord.
+    pub fn normalize(self) -> Self {
+        let from = BlockLocation::new(
+            self.from.x.min(self.to.x),
+            self.from.y.min(self.to.y),
+            self.from.z.min(self.to.z),
+        );
+
+        let to = BlockLocation::new(
+            self.from.x.max(self.to.x),
+            self.from.y.max(self.to.y),
+            self.from.z.max(self.to.z),
+        );
+
+        Selection3D { from, to }
+    }
+}
+
+#[derive(Writable, Readable, Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct Location {
     pub x: f64,
     pub y: f64,
@@ -770,7 +833,7 @@ impl Sub<Displacement> for Location {
     }
 }
 
-#[derive(Copy, Clone, Hash, Eq, PartialEq)]
+#[derive(Copy, Clone, Hash, Eq, PartialEq, Debug)]
 pub struct ChunkLocation(pub i32, pub i32);
 
 impl From<BlockLocation> for ChunkLocation {
@@ -1004,7 +1067,8 @@ impl BlockApprox {
     }
 }
 
-#[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SimpleType {
     Solid,
     Water,
@@ -237,22 +237,27 @@ impl ByteReadable for VarInt {
 }
 
 impl VarInt {
-    pub async fn read_async<R: AsyncRead>(mut reader: Pin<&mut R>) -> VarInt {
+    /// read a VarInt from `reader`. Fails if the reader ends (such as when a
+    /// socket is closed) or the VarInt is longer than 5 bytes
+    pub async fn read_async<R: AsyncRead>(mut reader: Pin<&mut R>) -> std::io::Result<VarInt> {
         const PART: u32 = 0x7F;
         let mut size = 0;
         let mut val = 0u32;
         loop {
-            let b = reader.read_u8().await.unwrap() as u32;
+            let b = reader.read_u8().await? as u32;
             val |= (b & PART) << (size * 7);
             size += 1;
             if size > 5 {
-                panic!("oop");
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "VarInt is longer than 5 bytes",
+                ));
             }
             if (b & 0x80) == 0 {
                 break;
             }
         }
-        VarInt(val as i32)
+        Ok(VarInt(val as i32))
     }
 }
 
@@ -262,3 +267,42 @@ impl ByteReadable for VarUInt {
         VarUInt(contents as usize)
     }
 }
+
+#[derive(Copy, Clone, Debug)]
+pub struct VarLong(pub i64);
+
+impl ByteWritable for VarLong {
+    fn write_to_bytes(self, writer: &mut ByteWriter) {
+        const PART: u64 = 0x7F;
+        let mut val = self.0 as u64;
+        loop {
+            if (val & !PART) == 0 {
+                writer.write(val as u8);
+                return;
+            }
+            writer.write(((val & PART) | 0x80) as u8);
+            val >>= 7;
+        }
+    }
+}
+
+impl ByteReadable for VarLong {
+    fn read_from_bytes(byte_reader: &mut ByteReader) -> Self {
+        const PART: u64 = 0x7F;
+        let mut size = 0;
+        let mut val = 0u64;
+        loop {
+            let b: u8 = byte_reader.read();
+            let b = b as u64;
+            val |= (b & PART) << (size * 7);
+            size += 1;
+            if size > 10 {
+                panic!("oop");
+            }
+            if (b & 0x80) == 0 {
+                break;
+            }
+        }
+        VarLong(val as i64)
+    }
+}
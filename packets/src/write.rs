@@ -48,6 +48,18 @@ impl ByteWritable for i16 {
     }
 }
 
+impl ByteWritable for i32 {
+    fn write_to_bytes(self, writer: &mut ByteWriter) {
+        writer.bytes.put_i32(self);
+    }
+}
+
+impl ByteWritable for u32 {
+    fn write_to_bytes(self, writer: &mut ByteWriter) {
+        writer.bytes.put_u32(self);
+    }
+}
+
 impl ByteWritable for f64 {
     fn write_to_bytes(self, writer: &mut ByteWriter) {
         writer.bytes.put_f64(self);
@@ -0,0 +1,417 @@
+//! The launcher configuration file, `swarmbot.toml`. Every option is optional
+//! and options given on the command line override the ones in the file.
+//!
+//! ```toml
+//! servers = ["localhost:25565"]
+//! count = 10
+//! users_file = "users.csv"
+//! delay_ms = 500
+//! ws_host = "127.0.0.1"
+//! ws_port = 8080
+//! api_address = "127.0.0.1:8081"
+//! api_token = "change me"
+//! metrics_address = "127.0.0.1:9100"
+//!
+//! [path]
+//! parkour = true
+//!
+//! [path.costs]
+//! mine_unrelated = 20.0
+//!
+//! # bots in a group use [path] with the group's changes on top
+//! [groups.builders]
+//! bots = ["Bot0000", "Bot0001"]
+//!
+//! [groups.builders.path]
+//! parkour = false
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use toml::Value;
+
+use crate::{
+    bootstrap::{
+        opts::{AuthMethod, CliOptions},
+        proxy_pool::ProxyStrategy,
+    },
+    client::pathfind::context::{PathConfig, PathConfigs},
+};
+
+/// the file the configuration is read from if `--config` is not given
+pub const DEFAULT_CONFIG_FILE: &str = "swarmbot.toml";
+
+/// the port of a server given without one
+const DEFAULT_PORT: u16 = 25565;
+
+/// The effective launcher configuration
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// the servers bots join, each `host[:port]`
+    pub servers: Vec<String>,
+
+    /// the number of bots that will be launched on each server
+    pub count: usize,
+
+    /// the protocol version. Detected by pinging the server if not given
+    pub version: Option<usize>,
+
+    /// the file users are read from
+    pub users_file: String,
+
+    /// the file proxies are read from
+    pub proxies_file: String,
+
+    /// if proxies are used
+    pub proxy: bool,
+
+    /// how proxies are assigned to bots
+    pub proxy_strategy: ProxyStrategy,
+
+    /// the most bots which use the same proxy. Unlimited if not given
+    pub max_bots_per_proxy: Option<usize>,
+
+    /// how often proxies are health checked. 0 only checks them on startup
+    pub proxy_check_secs: u64,
+
+    /// if bots are launched in offline mode
+    pub offline: bool,
+
+    /// how online users are authenticated
+    pub auth: AuthMethod,
+
+    /// the delay between logging in successive bots
+    pub delay_ms: u64,
+
+    /// the address the websocket binds to
+    pub ws_host: String,
+
+    /// the port of the websocket
+    pub ws_port: u16,
+
+    /// the address the management API binds to. It is not served if not given
+    pub api_address: Option<String>,
+
+    /// the bearer token requests to the management API need. It is a secret,
+    /// so it is left out of the printed config
+    #[serde(skip_serializing)]
+    pub api_token: Option<String>,
+
+    /// the address Prometheus metrics are served on. They are not served if
+    /// not given
+    pub metrics_address: Option<String>,
+
+    /// how bots which are not in a group find paths
+    pub path: PathConfig,
+
+    /// groups of bots, by name
+    pub groups: BTreeMap<String, Group>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            servers: Vec::new(),
+            count: 1,
+            version: None,
+            users_file: "users.csv".to_string(),
+            proxies_file: "proxies.csv".to_string(),
+            proxy: false,
+            proxy_strategy: ProxyStrategy::default(),
+            max_bots_per_proxy: None,
+            proxy_check_secs: 300,
+            offline: false,
+            auth: AuthMethod::default(),
+            delay_ms: 500,
+            ws_host: "127.0.0.1".to_string(),
+            ws_port: 8080,
+            api_address: None,
+            api_token: None,
+            metrics_address: None,
+            path: PathConfig::default(),
+            groups: BTreeMap::new(),
+        }
+    }
+}
+
+/// A group of bots which find paths differently
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Group {
+    /// the usernames of the bots in the group
+    pub bots: Vec<String>,
+
+    /// `[path]` with the changes of the group on top
+    pub path: PathConfig,
+}
+
+/// Recursively merge `overlay` into `base`
+fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Table(base), Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// `host[:port]` split into the host and port
+fn split_server(server: &str) -> anyhow::Result<(String, u16)> {
+    match server.rsplit_once(':') {
+        Some((host, port)) => {
+            let port = port
+                .parse()
+                .with_context(|| format!("invalid port in server {server}"))?;
+            Ok((host.to_string(), port))
+        }
+        None => Ok((server.to_string(), DEFAULT_PORT)),
+    }
+}
+
+impl Config {
+    /// Parse a configuration file. Groups inherit everything from `[path]`
+    /// which they do not change
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut value: Value = toml::from_str(text)?;
+
+        if let Some(table) = value.as_table_mut() {
+            let path = table
+                .get("path")
+                .cloned()
+                .unwrap_or_else(|| Value::Table(toml::value::Table::new()));
+
+            if let Some(Value::Table(groups)) = table.get_mut("groups") {
+                for (_, group) in groups.iter_mut() {
+                    let Value::Table(group) = group else {
+                        bail!("groups must be tables");
+                    };
+                    let mut group_path = path.clone();
+                    if let Some(overlay) = group.remove("path") {
+                        merge(&mut group_path, overlay);
+                    }
+                    group.insert("path".to_string(), group_path);
+                }
+            }
+        }
+
+        Ok(value.try_into()?)
+    }
+
+    /// Load the configuration file at `path`, or [`DEFAULT_CONFIG_FILE`] if it
+    /// exists. Without either the defaults are used
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Self> {
+        let given = path.is_some();
+        let path = path.map_or_else(|| PathBuf::from(DEFAULT_CONFIG_FILE), Path::to_path_buf);
+
+        // only a config file which was asked for must exist
+        if !given && !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("could not read config file {}", path.display()))?;
+
+        Self::parse(&text).with_context(|| format!("invalid config file {}", path.display()))
+    }
+
+    /// Override the configuration with the options given on the command line
+    pub fn apply(&mut self, cli: &CliOptions) {
+        match (&cli.host, cli.port) {
+            (Some(host), port) => {
+                let port = port.unwrap_or(DEFAULT_PORT);
+                self.servers = vec![format!("{host}:{port}")];
+            }
+            (None, Some(port)) => {
+                for server in &mut self.servers {
+                    let host = server
+                        .rsplit_once(':')
+                        .map_or(server.as_str(), |(host, _)| host);
+                    *server = format!("{host}:{port}");
+                }
+            }
+            (None, None) => {}
+        }
+
+        if let Some(count) = cli.count {
+            self.count = count;
+        }
+        if cli.version.is_some() {
+            self.version = cli.version;
+        }
+        if let Some(users_file) = &cli.users_file {
+            self.users_file = users_file.clone();
+        }
+        if let Some(proxies_file) = &cli.proxies_file {
+            self.proxies_file = proxies_file.clone();
+        }
+        if cli.proxy {
+            self.proxy = true;
+        }
+        if let Some(proxy_strategy) = cli.proxy_strategy {
+            self.proxy_strategy = proxy_strategy;
+        }
+        if cli.max_bots_per_proxy.is_some() {
+            self.max_bots_per_proxy = cli.max_bots_per_proxy;
+        }
+        if let Some(proxy_check_secs) = cli.proxy_check_secs {
+            self.proxy_check_secs = proxy_check_secs;
+        }
+        if cli.offline {
+            self.offline = true;
+        }
+        if let Some(auth) = cli.auth {
+            self.auth = auth;
+        }
+        if let Some(delay_ms) = cli.delay_ms {
+            self.delay_ms = delay_ms;
+        }
+        if let Some(ws_host) = &cli.ws_host {
+            self.ws_host = ws_host.clone();
+        }
+        if let Some(ws_port) = cli.ws_port {
+            self.ws_port = ws_port;
+        }
+        if cli.api_address.is_some() {
+            self.api_address = cli.api_address.clone();
+        }
+        if cli.api_token.is_some() {
+            self.api_token = cli.api_token.clone();
+        }
+        if cli.metrics_address.is_some() {
+            self.metrics_address = cli.metrics_address.clone();
+        }
+    }
+
+    /// the servers as host and port
+    pub fn servers(&self) -> anyhow::Result<Vec<(String, u16)>> {
+        self.servers
+            .iter()
+            .map(|server| split_server(server))
+            .collect()
+    }
+
+    /// the websocket address, `host:port`
+    pub fn ws_address(&self) -> String {
+        format!("{}:{}", self.ws_host, self.ws_port)
+    }
+
+    /// the path configs of bots and groups
+    pub fn path_configs(&self) -> anyhow::Result<PathConfigs> {
+        let mut configs = PathConfigs {
+            default: self.path.clone(),
+            ..PathConfigs::default()
+        };
+
+        for (name, group) in &self.groups {
+            configs.groups.insert(name.clone(), group.path.clone());
+            for bot in &group.bots {
+                if let Some(other) = configs.members.insert(bot.clone(), name.clone()) {
+                    bail!("{bot} is in both group {other} and group {name}");
+                }
+            }
+        }
+
+        Ok(configs)
+    }
+
+    /// The configuration as TOML
+    pub fn to_toml(&self) -> anyhow::Result<String> {
+        // going through a Value puts plain values before tables, which TOML
+        // requires
+        let value = Value::try_from(self)?;
+        Ok(toml::to_string(&value)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use crate::bootstrap::{config::Config, opts::CliOptions};
+
+    const CONFIG: &str = r#"
+        servers = ["localhost", "example.com:25566"]
+        count = 5
+        delay_ms = 100
+
+        [path]
+        parkour = false
+
+        [path.costs]
+        mine_unrelated = 10.0
+
+        [groups.builders]
+        bots = ["Bot0001"]
+
+        [groups.builders.path.costs]
+        place_unrelated = 2.0
+    "#;
+
+    #[test]
+    fn test_parse() {
+        let config = Config::parse(CONFIG).unwrap();
+
+        assert_eq!(
+            config.servers().unwrap(),
+            [
+                ("localhost".to_string(), 25565),
+                ("example.com".to_string(), 25566)
+            ]
+        );
+        assert_eq!(config.count, 5);
+        assert_eq!(config.users_file, "users.csv");
+
+        let configs = config.path_configs().unwrap();
+
+        let default = configs.get("Bot0000");
+        assert!(!default.parkour);
+        assert!((default.costs.mine_unrelated - 10.0).abs() < f64::EPSILON);
+        assert!((default.costs.place_unrelated - 20.0).abs() < f64::EPSILON);
+
+        // groups inherit [path]
+        let builder = configs.get("Bot0001");
+        assert!(!builder.parkour);
+        assert!((builder.costs.mine_unrelated - 10.0).abs() < f64::EPSILON);
+        assert!((builder.costs.place_unrelated - 2.0).abs() < f64::EPSILON);
+
+        assert!(Config::parse("cout = 5").is_err());
+    }
+
+    #[test]
+    fn test_cli_overrides() {
+        let mut config = Config::parse(CONFIG).unwrap();
+
+        let cli = CliOptions::parse_from(["swarm-bot", "2b2t.org", "--count", "2"]);
+        config.apply(&cli);
+
+        assert_eq!(config.servers, ["2b2t.org:25565"]);
+        assert_eq!(config.count, 2);
+        assert_eq!(config.delay_ms, 100);
+
+        // a port alone applies to every server of the file
+        let mut config = Config::parse(CONFIG).unwrap();
+        config.apply(&CliOptions::parse_from(["swarm-bot", "--port", "25570"]));
+        assert_eq!(config.servers, ["localhost:25570", "example.com:25570"]);
+
+        // the printed config is read back the same, without the API token
+        config.api_token = Some("secret".to_string());
+        let printed = config.to_toml().unwrap();
+        assert!(!printed.contains("secret"));
+        let reparsed = Config::parse(&printed).unwrap();
+        assert_eq!(reparsed.to_toml().unwrap(), printed);
+    }
+}
@@ -1,16 +1,22 @@
 //! module for modifying CSVs
 
-use std::fs::File;
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+};
 
+use anyhow::Context;
 use serde::de::DeserializeOwned;
 
-use crate::bootstrap::{CSVUser, Proxy};
+use crate::bootstrap::{proxy::Proxy, CSVUser};
 
 /// read CSV file into `Vec` of the desired type
 fn read_csv<T: DeserializeOwned>(file: File) -> anyhow::Result<Vec<T>> {
     csv::ReaderBuilder::new()
         .delimiter(b':')
         .has_headers(false)
+        // trailing optional fields can be left out
+        .flexible(true)
         .from_reader(file)
         .deserialize()
         .map(|res| {
@@ -25,7 +31,22 @@ pub fn read_users(file: File) -> anyhow::Result<Vec<CSVUser>> {
     read_csv(file)
 }
 
-/// read proxies from a CSV file
+/// read proxies from a CSV file. Lines are `[scheme://]host:port[:user:pass]`,
+/// which is why they are not parsed as plain CSV
 pub fn read_proxies(file: File) -> anyhow::Result<Vec<Proxy>> {
-    read_csv(file)
+    let mut proxies = Vec::new();
+
+    for (idx, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let proxy = line
+            .parse()
+            .with_context(|| format!("invalid proxy on line {}: {line}", idx + 1))?;
+        proxies.push(proxy);
+    }
+
+    Ok(proxies)
 }
@@ -0,0 +1,161 @@
+//! Encryption of the user cache at rest. An encrypted cache is
+//!
+//! `MAGIC | VERSION | salt | nonce | ciphertext`
+//!
+//! where the key is derived from a passphrase with Argon2id and the
+//! ciphertext is the plain cache sealed with ChaCha20-Poly1305. The header is
+//! authenticated as associated data.
+
+use std::env;
+
+use anyhow::{bail, ensure, Context};
+use argon2::Argon2;
+use chacha20poly1305::{
+    aead::{Aead, Payload},
+    ChaCha20Poly1305, Key, KeyInit, Nonce,
+};
+use rand::RngCore;
+
+/// the environment variable the passphrase is read from before prompting
+pub const PASSPHRASE_VAR: &str = "SWARMBOT_CACHE_PASSPHRASE";
+
+/// the environment variable the new passphrase is read from when rotating
+pub const NEW_PASSPHRASE_VAR: &str = "SWARMBOT_NEW_CACHE_PASSPHRASE";
+
+const MAGIC: &[u8; 4] = b"SBEC";
+const VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const HEADER_LEN: usize = MAGIC.len() + 1 + SALT_LEN + NONCE_LEN;
+
+/// if `bytes` were produced by [`encrypt`]. Caches written before encryption
+/// was supported are plain bincode which never starts with [`MAGIC`]
+pub fn is_encrypted(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> anyhow::Result<Key> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|err| anyhow::anyhow!("could not derive key: {err}"))?;
+    Ok(key)
+}
+
+/// Encrypt `plain` with a fresh salt and nonce
+pub fn encrypt(plain: &[u8], passphrase: &str) -> anyhow::Result<Vec<u8>> {
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(MAGIC);
+    header.push(VERSION);
+
+    let mut salt_and_nonce = [0; SALT_LEN + NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut salt_and_nonce);
+    header.extend_from_slice(&salt_and_nonce);
+
+    let (salt, nonce) = salt_and_nonce.split_at(SALT_LEN);
+    let key = derive_key(passphrase, salt)?;
+
+    let ciphertext = ChaCha20Poly1305::new(&key)
+        .encrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: plain,
+                aad: &header,
+            },
+        )
+        .map_err(|_| anyhow::anyhow!("could not encrypt the user cache"))?;
+
+    header.extend_from_slice(&ciphertext);
+    Ok(header)
+}
+
+/// Decrypt bytes produced by [`encrypt`]
+pub fn decrypt(bytes: &[u8], passphrase: &str) -> anyhow::Result<Vec<u8>> {
+    ensure!(is_encrypted(bytes), "the user cache is not encrypted");
+    ensure!(bytes.len() >= HEADER_LEN, "the user cache is truncated");
+
+    let (header, ciphertext) = bytes.split_at(HEADER_LEN);
+
+    let version = header[MAGIC.len()];
+    if version != VERSION {
+        bail!("the user cache is encrypted with unsupported version {version}");
+    }
+
+    let (salt, nonce) = header[MAGIC.len() + 1..].split_at(SALT_LEN);
+    let key = derive_key(passphrase, salt)?;
+
+    ChaCha20Poly1305::new(&key)
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|_| anyhow::anyhow!("wrong passphrase or corrupted user cache"))
+}
+
+/// The passphrase in `var`, or else prompted for. If `confirm` the prompt is
+/// repeated to catch typos
+fn read_passphrase(var: &str, prompt: &str, confirm: bool) -> anyhow::Result<String> {
+    if let Ok(passphrase) = env::var(var) {
+        ensure!(!passphrase.is_empty(), "{var} is empty");
+        return Ok(passphrase);
+    }
+
+    let passphrase = rpassword::prompt_password(prompt)
+        .with_context(|| format!("could not prompt for a passphrase. Set {var} instead"))?;
+
+    ensure!(!passphrase.is_empty(), "the passphrase must not be empty");
+
+    if confirm {
+        let again = rpassword::prompt_password("repeat the passphrase: ")
+            .context("could not prompt for a passphrase")?;
+        ensure!(passphrase == again, "the passphrases do not match");
+    }
+
+    Ok(passphrase)
+}
+
+/// The passphrase of the user cache. `new` is true if the cache is being
+/// encrypted for the first time
+pub fn passphrase(new: bool) -> anyhow::Result<String> {
+    read_passphrase(PASSPHRASE_VAR, "user cache passphrase: ", new)
+}
+
+/// The passphrase the user cache is re-encrypted with when rotating
+pub fn new_passphrase() -> anyhow::Result<String> {
+    read_passphrase(NEW_PASSPHRASE_VAR, "new user cache passphrase: ", true)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bootstrap::encryption::{decrypt, encrypt, is_encrypted};
+
+    #[test]
+    fn test_round_trip() {
+        let plain = b"alt@example.com:hunter2";
+
+        let encrypted = encrypt(plain, "passphrase").unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert!(!encrypted.windows(plain.len()).any(|window| window == plain));
+
+        assert_eq!(decrypt(&encrypted, "passphrase").unwrap(), plain);
+
+        // every encryption uses a new salt and nonce
+        assert_ne!(encrypt(plain, "passphrase").unwrap(), encrypted);
+
+        let err = decrypt(&encrypted, "wrong").unwrap_err();
+        assert!(err.to_string().contains("wrong passphrase"));
+
+        // the ciphertext is authenticated
+        let mut tampered = encrypted.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(decrypt(&tampered, "passphrase").is_err());
+
+        // so is the salt the key is derived with
+        let mut tampered = encrypted;
+        tampered[6] ^= 1;
+        assert!(decrypt(&tampered, "passphrase").is_err());
+    }
+}
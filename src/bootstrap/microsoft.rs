@@ -0,0 +1,524 @@
+//! Authenticating Microsoft accounts. Mojang accounts which were migrated can
+//! no longer use the Yggdrasil endpoints in [`crate::bootstrap::mojang`].
+//!
+//! The flow is: device code → MSA token → Xbox Live (XBL) token → XSTS token →
+//! Minecraft services token → profile.
+//!
+//! <https://wiki.vg/Microsoft_Authentication_Scheme>
+
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::json;
+
+use crate::bootstrap::mojang::MojangClient;
+
+/// The endpoints and client used for Microsoft authentication. These can be
+/// changed so a local stand-in server can be used.
+#[derive(Clone, Debug)]
+pub struct MicrosoftEndpoints {
+    /// the id of the OAuth application we are signing in as
+    pub client_id: String,
+
+    /// the OAuth scope requested
+    pub scope: String,
+
+    pub device_code: String,
+    pub token: String,
+    pub xbl: String,
+    pub xsts: String,
+    pub minecraft: String,
+    pub profile: String,
+}
+
+impl Default for MicrosoftEndpoints {
+    fn default() -> Self {
+        Self {
+            // the client id of the Minecraft launcher
+            client_id: "00000000402b5328".to_string(),
+            scope: "service::user.auth.xboxlive.com::MBI_SSL".to_string(),
+            device_code: "https://login.live.com/oauth20_connect.srf".to_string(),
+            token: "https://login.live.com/oauth20_token.srf".to_string(),
+            xbl: "https://user.auth.xboxlive.com/user/authenticate".to_string(),
+            xsts: "https://xsts.auth.xboxlive.com/xsts/authorize".to_string(),
+            minecraft: "https://api.minecraftservices.com/authentication/login_with_xbox"
+                .to_string(),
+            profile: "https://api.minecraftservices.com/minecraft/profile".to_string(),
+        }
+    }
+}
+
+/// The code the user has to enter at `verification_uri` to sign in
+#[derive(Deserialize, Debug)]
+pub struct DeviceCode {
+    pub user_code: String,
+    pub device_code: String,
+    pub verification_uri: String,
+
+    /// seconds until the code expires
+    pub expires_in: u64,
+
+    /// seconds to wait between polling for the token
+    #[serde(default)]
+    pub interval: u64,
+}
+
+/// A Microsoft account (MSA) token
+#[derive(Deserialize, Debug)]
+pub struct MsaToken {
+    pub access_token: String,
+    pub refresh_token: String,
+}
+
+#[derive(Deserialize)]
+struct OAuthError {
+    error: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct XboxToken {
+    token: String,
+    display_claims: DisplayClaims,
+}
+
+#[derive(Deserialize)]
+struct DisplayClaims {
+    xui: Vec<UserHash>,
+}
+
+#[derive(Deserialize)]
+struct UserHash {
+    uhs: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct XstsError {
+    x_err: u64,
+}
+
+#[derive(Deserialize)]
+struct MinecraftToken {
+    access_token: String,
+    expires_in: u64,
+}
+
+#[derive(Deserialize)]
+struct Profile {
+    id: String,
+    name: String,
+}
+
+/// A Minecraft session obtained through a Microsoft account
+#[derive(Debug)]
+pub struct MicrosoftSession {
+    pub username: String,
+    pub uuid: String,
+
+    /// the Minecraft services token used to join servers
+    pub access_token: String,
+
+    /// seconds until `access_token` expires
+    pub expires_in: u64,
+
+    /// used to get a new session without signing in again
+    pub refresh_token: String,
+}
+
+/// Authenticates Microsoft accounts through the proxy of a [`MojangClient`]
+pub struct MicrosoftAuth<'a> {
+    client: &'a reqwest::Client,
+    endpoints: &'a MicrosoftEndpoints,
+}
+
+/// parse a JSON response, including the body in the error if it is not a
+/// success
+async fn json<T: DeserializeOwned>(res: reqwest::Response, what: &str) -> anyhow::Result<T> {
+    let status = res.status();
+    let body = res.text().await?;
+
+    if !status.is_success() {
+        bail!("{what} failed. Error code: {status}, info: {body}");
+    }
+
+    serde_json::from_str(&body).with_context(|| format!("invalid {what} response: {body}"))
+}
+
+impl<'a> MicrosoftAuth<'a> {
+    pub const fn new(mojang: &'a MojangClient, endpoints: &'a MicrosoftEndpoints) -> Self {
+        Self {
+            client: mojang.http(),
+            endpoints,
+        }
+    }
+
+    /// Sign in `email` with the device code flow. The user has to open the
+    /// printed link and enter the code.
+    pub async fn login(&self, email: &str) -> anyhow::Result<MicrosoftSession> {
+        let code = self.device_code().await?;
+
+        println!(
+            "To sign in {email}, open {} and enter the code {}",
+            code.verification_uri, code.user_code
+        );
+
+        let msa = self.poll(&code).await?;
+        self.minecraft(msa).await
+    }
+
+    /// Get a new session from the refresh token of a previous one
+    pub async fn refresh(&self, refresh_token: &str) -> anyhow::Result<MicrosoftSession> {
+        let res = self
+            .client
+            .post(&self.endpoints.token)
+            .form(&[
+                ("client_id", self.endpoints.client_id.as_str()),
+                ("scope", &self.endpoints.scope),
+                ("grant_type", "refresh_token"),
+                ("refresh_token", refresh_token),
+            ])
+            .send()
+            .await?;
+
+        let msa = json(res, "refreshing the Microsoft token").await?;
+        self.minecraft(msa).await
+    }
+
+    pub async fn device_code(&self) -> anyhow::Result<DeviceCode> {
+        let res = self
+            .client
+            .post(&self.endpoints.device_code)
+            .form(&[
+                ("client_id", self.endpoints.client_id.as_str()),
+                ("scope", &self.endpoints.scope),
+                ("response_type", "device_code"),
+            ])
+            .send()
+            .await?;
+
+        json(res, "requesting a device code").await
+    }
+
+    /// wait until the user has entered the device code
+    pub async fn poll(&self, code: &DeviceCode) -> anyhow::Result<MsaToken> {
+        let mut interval = Duration::from_secs(code.interval);
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(code.expires_in);
+
+        while tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(interval).await;
+
+            let res = self
+                .client
+                .post(&self.endpoints.token)
+                .form(&[
+                    ("client_id", self.endpoints.client_id.as_str()),
+                    ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+                    ("device_code", &code.device_code),
+                ])
+                .send()
+                .await?;
+
+            if res.status().is_success() {
+                return json(res, "polling the device code").await;
+            }
+
+            let OAuthError { error } = json_error(res).await?;
+
+            match error.as_str() {
+                "authorization_pending" => {}
+                "slow_down" => interval += Duration::from_secs(5),
+                "authorization_declined" => bail!("the sign in was declined"),
+                "expired_token" => break,
+                other => bail!("polling the device code failed: {other}"),
+            }
+        }
+
+        bail!("the device code expired before it was entered")
+    }
+
+    /// XBL → XSTS → Minecraft services token → profile
+    pub async fn minecraft(&self, msa: MsaToken) -> anyhow::Result<MicrosoftSession> {
+        let res = self
+            .client
+            .post(&self.endpoints.xbl)
+            .json(&json!({
+                "Properties": {
+                    "AuthMethod": "RPS",
+                    "SiteName": "user.auth.xboxlive.com",
+                    "RpsTicket": msa.access_token,
+                },
+                "RelyingParty": "http://auth.xboxlive.com",
+                "TokenType": "JWT",
+            }))
+            .send()
+            .await?;
+
+        let xbl: XboxToken = json(res, "Xbox Live authentication").await?;
+
+        let res = self
+            .client
+            .post(&self.endpoints.xsts)
+            .json(&json!({
+                "Properties": {
+                    "SandboxId": "RETAIL",
+                    "UserTokens": [xbl.token],
+                },
+                "RelyingParty": "rp://api.minecraftservices.com/",
+                "TokenType": "JWT",
+            }))
+            .send()
+            .await?;
+
+        if res.status() == reqwest::StatusCode::UNAUTHORIZED {
+            let XstsError { x_err } = json_error(res).await?;
+            match x_err {
+                2_148_916_233 => bail!("the account does not have an Xbox account"),
+                2_148_916_238 => bail!("the account is a child account"),
+                other => bail!("XSTS authorization failed with XErr {other}"),
+            }
+        }
+
+        let xsts: XboxToken = json(res, "XSTS authorization").await?;
+
+        let user_hash = &xsts
+            .display_claims
+            .xui
+            .first()
+            .context("XSTS response has no user hash")?
+            .uhs;
+
+        let res = self
+            .client
+            .post(&self.endpoints.minecraft)
+            .json(&json!({
+                "identityToken": format!("XBL3.0 x={user_hash};{}", xsts.token),
+            }))
+            .send()
+            .await?;
+
+        let minecraft: MinecraftToken = json(res, "Minecraft authentication").await?;
+
+        let res = self
+            .client
+            .get(&self.endpoints.profile)
+            .bearer_auth(&minecraft.access_token)
+            .send()
+            .await?;
+
+        if res.status() == reqwest::StatusCode::NOT_FOUND {
+            bail!("the account does not own Minecraft");
+        }
+
+        let profile: Profile = json(res, "getting the Minecraft profile").await?;
+
+        Ok(MicrosoftSession {
+            username: profile.name,
+            uuid: profile.id,
+            access_token: minecraft.access_token,
+            expires_in: minecraft.expires_in,
+            refresh_token: msa.refresh_token,
+        })
+    }
+}
+
+/// parse the body of an error response
+async fn json_error<T: DeserializeOwned>(res: reqwest::Response) -> anyhow::Result<T> {
+    let status = res.status();
+    let body = res.text().await?;
+    serde_json::from_str(&body).with_context(|| format!("unexpected response {status}: {body}"))
+}
+
+/// A local stand-in for the Microsoft, Xbox Live and Minecraft services
+/// endpoints
+#[cfg(test)]
+pub mod stand_in {
+    use std::{cell::Cell, rc::Rc};
+
+    use serde_json::{json, Value};
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+    };
+
+    use crate::bootstrap::microsoft::MicrosoftEndpoints;
+
+    /// the uuid of the profile the stand-in returns
+    pub const UUID: &str = "069a79f444e94726a5befca90e38aaf5";
+
+    /// The state of the stand-in
+    #[derive(Default)]
+    pub struct StandIn {
+        /// the number of times the device code was polled
+        pub polls: Cell<usize>,
+
+        /// the number of times a token was refreshed
+        pub refreshes: Cell<usize>,
+
+        /// if the account has no Xbox account
+        pub no_xbox: Cell<bool>,
+    }
+
+    impl StandIn {
+        /// `headers` are lowercase
+        fn respond(&self, path: &str, headers: &str, body: &str) -> (u16, Value) {
+            match path {
+                "/devicecode" => (
+                    200,
+                    json!({
+                        "user_code": "ABCD-1234",
+                        "device_code": "device",
+                        "verification_uri": "https://microsoft.com/link",
+                        "expires_in": 10,
+                        "interval": 0,
+                    }),
+                ),
+                "/token" if body.contains("grant_type=refresh_token") => {
+                    self.refreshes.set(self.refreshes.get() + 1);
+                    if body.contains("refresh_token=refresh") {
+                        let refresh = format!("refresh{}", self.refreshes.get());
+                        (
+                            200,
+                            json!({ "access_token": "msa", "refresh_token": refresh }),
+                        )
+                    } else {
+                        (400, json!({ "error": "invalid_grant" }))
+                    }
+                }
+                "/token" => {
+                    self.polls.set(self.polls.get() + 1);
+
+                    // the user enters the code after the first poll
+                    if self.polls.get() == 1 {
+                        (400, json!({ "error": "authorization_pending" }))
+                    } else {
+                        (
+                            200,
+                            json!({ "access_token": "msa", "refresh_token": "refresh" }),
+                        )
+                    }
+                }
+                "/xbl" if body.contains(r#""RpsTicket":"msa""#) => (
+                    200,
+                    json!({ "Token": "xbl", "DisplayClaims": { "xui": [{ "uhs": "hash" }] } }),
+                ),
+                "/xsts" if self.no_xbox.get() => (401, json!({ "XErr": 2_148_916_233_u64 })),
+                "/xsts" if body.contains(r#""UserTokens":["xbl"]"#) => (
+                    200,
+                    json!({ "Token": "xsts", "DisplayClaims": { "xui": [{ "uhs": "hash" }] } }),
+                ),
+                "/minecraft" if body.contains("XBL3.0 x=hash;xsts") => (
+                    200,
+                    json!({ "access_token": "minecraft", "expires_in": 86400 }),
+                ),
+                "/profile" if headers.contains("authorization: bearer minecraft") => {
+                    (200, json!({ "id": UUID, "name": "Notch" }))
+                }
+                _ => (
+                    400,
+                    json!({ "error": format!("unexpected request to {path}") }),
+                ),
+            }
+        }
+    }
+
+    /// start the stand-in on localhost. Must be called from within a
+    /// [`tokio::task::LocalSet`].
+    pub async fn start() -> (MicrosoftEndpoints, Rc<StandIn>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+
+        let state = Rc::new(StandIn::default());
+
+        {
+            let state = state.clone();
+            tokio::task::spawn_local(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let mut stream = BufReader::new(stream);
+
+                    let mut request_line = String::new();
+                    stream.read_line(&mut request_line).await.unwrap();
+                    let path = request_line.split_whitespace().nth(1).unwrap().to_string();
+
+                    let mut headers = String::new();
+                    let mut content_length = 0;
+                    loop {
+                        let mut line = String::new();
+                        stream.read_line(&mut line).await.unwrap();
+                        if line == "\r\n" {
+                            break;
+                        }
+                        let lower = line.to_ascii_lowercase();
+                        if let Some(len) = lower.strip_prefix("content-length:") {
+                            content_length = len.trim().parse().unwrap();
+                        }
+                        headers.push_str(&lower);
+                    }
+
+                    let mut body = vec![0; content_length];
+                    stream.read_exact(&mut body).await.unwrap();
+                    let body = String::from_utf8(body).unwrap();
+
+                    let (status, json) = state.respond(&path, &headers, &body);
+                    let json = json.to_string();
+
+                    let response = format!(
+                        "HTTP/1.1 {status} X\r\nContent-Type: application/json\r\nContent-Length: \
+                         {}\r\nConnection: close\r\n\r\n{json}",
+                        json.len()
+                    );
+                    stream.write_all(response.as_bytes()).await.unwrap();
+                }
+            });
+        }
+
+        let endpoints = MicrosoftEndpoints {
+            device_code: format!("{base}/devicecode"),
+            token: format!("{base}/token"),
+            xbl: format!("{base}/xbl"),
+            xsts: format!("{base}/xsts"),
+            minecraft: format!("{base}/minecraft"),
+            profile: format!("{base}/profile"),
+            ..MicrosoftEndpoints::default()
+        };
+
+        (endpoints, state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{runtime::Runtime, task::LocalSet};
+
+    use crate::bootstrap::{
+        microsoft::{stand_in, MicrosoftAuth},
+        mojang::MojangClient,
+    };
+
+    #[test]
+    fn test_login() {
+        let rt = Runtime::new().unwrap();
+        LocalSet::new().block_on(&rt, async {
+            let (endpoints, stand_in) = stand_in::start().await;
+            let mojang = MojangClient::default();
+            let auth = MicrosoftAuth::new(&mojang, &endpoints);
+
+            let session = auth.login("user@outlook.com").await.unwrap();
+            assert_eq!(stand_in.polls.get(), 2);
+            assert_eq!(session.username, "Notch");
+            assert_eq!(session.uuid, stand_in::UUID);
+            assert_eq!(session.access_token, "minecraft");
+            assert_eq!(session.expires_in, 86400);
+
+            let session = auth.refresh(&session.refresh_token).await.unwrap();
+            assert_eq!(session.refresh_token, "refresh1");
+
+            assert!(auth.refresh("revoked").await.is_err());
+
+            stand_in.no_xbox.set(true);
+            let err = auth.refresh("refresh").await.unwrap_err();
+            assert!(err.to_string().contains("Xbox account"));
+        });
+    }
+}
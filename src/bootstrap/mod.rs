@@ -9,14 +9,24 @@ use tokio::{
     },
     sync::mpsc::Receiver,
 };
-use tokio_socks::tcp::Socks5Stream;
 
-use crate::bootstrap::storage::{BotConnectionData, BotData};
+use crate::bootstrap::{
+    proxy::Proxy,
+    proxy_pool::SharedProxyPool,
+    storage::{BotConnectionData, BotData},
+};
 
+pub mod bridge;
+pub mod config;
 pub mod csv;
 pub mod dns;
+pub mod encryption;
+pub mod microsoft;
 pub mod mojang;
 pub mod opts;
+pub mod proxy;
+pub mod proxy_pool;
+pub mod quota;
 pub mod storage;
 
 /// A server address
@@ -43,6 +53,13 @@ pub struct BotConnection {
     /// the address being logged into
     pub server_address: Address,
 
+    /// the proxy the connection goes through. Kept so the bot can reconnect
+    /// through the same proxy
+    pub proxy: Option<Proxy>,
+
+    /// the tags of the account
+    pub tags: Vec<String>,
+
     /// A read stream (from the server)
     pub read: OwnedReadHalf,
 
@@ -51,53 +68,64 @@ pub struct BotConnection {
 }
 
 /// Obtain a concrete TCP connection to the sever `address`. This only
-/// establishes a connection and does not anything involving
-async fn obtain_connection(
+/// establishes a connection and does not anything involving. How the proxy
+/// (if any) did is recorded in `proxies`
+pub async fn obtain_connection(
     user: BotConnectionData,
     server_address: Address,
+    proxies: &SharedProxyPool,
 ) -> anyhow::Result<BotConnection> {
-    let BotConnectionData { bot, proxy } = user;
+    let BotConnectionData { bot, proxy, tags } = user;
 
     let target = String::from(&server_address);
 
-    let conn = if let Some(proxy) = proxy {
-        let address = proxy.address();
-        let conn = Socks5Stream::connect_with_password(
-            proxy.address().as_str(),
-            target.as_str(),
-            &proxy.user,
-            &proxy.pass,
-        )
-        .await
-        .with_context(|| format!("could not create to socks {address}"))?;
-
-        conn.into_inner()
+    let conn = if let Some(proxy) = &proxy {
+        let start = std::time::Instant::now();
+        let conn = proxy.connect(&server_address).await;
+
+        match &conn {
+            Ok(_) => proxies.borrow_mut().record_success(proxy, start.elapsed()),
+            Err(_) => proxies.borrow_mut().record_failure(proxy),
+        }
+
+        conn.with_context(|| format!("could not connect to {target} through {}", proxy.url()))?
     } else {
-        TcpStream::connect(target.as_str()).await.unwrap()
+        TcpStream::connect(target.as_str())
+            .await
+            .with_context(|| format!("could not connect to {target}"))?
     };
 
     let (read, write) = conn.into_split();
     Ok(BotConnection {
         bot,
         server_address,
+        proxy,
+        tags,
         read,
         write,
     })
 }
 
 impl BotConnection {
-    /// Generates connections given [`BotConnectionData`] and an address
+    /// Generates connections given [`BotConnectionData`] and an address.
+    /// Users which cannot connect give their proxy back to `proxies`
     pub fn stream(
         server_address: Address,
         mut users: impl Stream<Item = BotConnectionData> + Unpin + 'static,
+        proxies: SharedProxyPool,
     ) -> Receiver<anyhow::Result<Self>> {
         let (tx, rx) = tokio::sync::mpsc::channel(1);
         tokio::task::spawn_local(async move {
             while let Some(user) = users.next().await {
                 let tx = tx.clone();
                 let address = server_address.clone();
+                let proxies = proxies.clone();
                 tokio::task::spawn_local(async move {
-                    let connection = obtain_connection(user, address).await;
+                    let proxy = user.proxy.clone();
+                    let connection = obtain_connection(user, address, &proxies).await;
+                    if connection.is_err() {
+                        proxies.borrow_mut().release(proxy.as_ref());
+                    }
                     tx.send(connection).await.unwrap();
                 });
             }
@@ -107,22 +135,41 @@ impl BotConnection {
     }
 }
 
+/// Split `items` between `targets` receivers, handing them out in turn
+pub fn distribute<T: 'static>(
+    mut items: impl Stream<Item = T> + Unpin + 'static,
+    targets: usize,
+) -> Vec<Receiver<T>> {
+    let (senders, receivers): (Vec<_>, Vec<_>) =
+        (0..targets).map(|_| tokio::sync::mpsc::channel(1)).unzip();
+
+    tokio::task::spawn_local(async move {
+        for sender in senders.iter().cycle() {
+            let Some(item) = items.next().await else { return };
+
+            // the target is gone. The item is dropped
+            let _ = sender.send(item).await;
+        }
+    });
+
+    receivers
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct CSVUser {
     pub email: String,
     pub password: String,
+    /// comma separated tags commands can select the bot by
+    pub tags: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
-pub struct Proxy {
-    pub host: String,
-    pub port: u32,
-    pub user: String,
-    pub pass: String,
-}
-
-impl Proxy {
-    pub fn address(&self) -> String {
-        format!("{}:{}", self.host, self.port)
+impl CSVUser {
+    pub fn tags(&self) -> Vec<String> {
+        let tags = self.tags.as_deref().unwrap_or_default();
+        tags.split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(ToString::to_string)
+            .collect()
     }
 }
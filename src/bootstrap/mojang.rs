@@ -1,23 +1,41 @@
 //! Module for interacting with Mojang
 
-use std::{convert::TryFrom, default::default};
+use std::{convert::TryFrom, default::default, sync::Arc};
 
+use anyhow::Context;
 use num_bigint::BigInt;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use sha1::{Digest, Sha1};
 use swarm_bot_packets::types::UUID;
 
-use crate::bootstrap::Proxy;
+use crate::bootstrap::{
+    bridge::Bridge,
+    proxy::{Proxy, ProxyScheme},
+};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MojangClient {
     client: reqwest::Client,
+
+    /// the bridge requests go through if the proxy is SOCKS4. It stops once
+    /// every clone of the client is dropped
+    _bridge: Option<Arc<Bridge>>,
 }
 
 impl Default for MojangClient {
     fn default() -> Self {
-        Self { client: default() }
+        Self {
+            client: default(),
+            _bridge: None,
+        }
+    }
+}
+
+impl MojangClient {
+    /// the HTTP client, which goes through the proxy if there is one
+    pub const fn http(&self) -> &reqwest::Client {
+        &self.client
     }
 }
 
@@ -25,16 +43,43 @@ impl TryFrom<&Proxy> for MojangClient {
     type Error = anyhow::Error;
 
     fn try_from(proxy: &Proxy) -> Result<Self, Self::Error> {
-        let address = proxy.address();
-        let user = &proxy.user;
-        let pass = &proxy.pass;
-        let full_address = format!("socks5://{address}");
+        // reqwest only speaks SOCKS5 and HTTP. Falling back to a direct connection
+        // would leak our IP to Mojang, so requests go through a local HTTP proxy
+        // which tunnels through the SOCKS4 proxy instead
+        if proxy.scheme == ProxyScheme::Socks4 {
+            let bridge = Bridge::start(proxy.clone())?;
+            let url = reqwest::Url::parse(bridge.url())?;
+            let client = reqwest::Client::builder()
+                .proxy(reqwest::Proxy::https(url)?)
+                .build()?;
+
+            return Ok(Self {
+                client,
+                _bridge: Some(Arc::new(bridge)),
+            });
+        }
 
-        let proxy = reqwest::Proxy::https(full_address)?.basic_auth(user, pass);
+        let mut url = reqwest::Url::parse(&proxy.url())
+            .with_context(|| format!("invalid proxy {}", proxy.url()))?;
 
-        let client = reqwest::Client::builder().proxy(proxy).build()?;
+        // reqwest reads the credentials of both SOCKS5 and HTTP proxies from the URL
+        if let Some(user) = &proxy.user {
+            let credentials = url
+                .set_username(user)
+                .and_then(|_| url.set_password(proxy.pass.as_deref()));
+
+            if credentials.is_err() {
+                anyhow::bail!("invalid credentials for proxy {}", proxy.url());
+            }
+        }
 
-        Ok(Self { client })
+        let reqwest_proxy = reqwest::Proxy::https(url)?;
+        let client = reqwest::Client::builder().proxy(reqwest_proxy).build()?;
+
+        Ok(Self {
+            client,
+            _bridge: None,
+        })
     }
 }
 
@@ -209,8 +254,12 @@ impl MojangClient {
 #[cfg(test)]
 mod tests {
     use sha1::{Digest, Sha1};
+    use tokio::runtime::Runtime;
 
-    use crate::bootstrap::mojang::hexdigest;
+    use crate::bootstrap::{
+        mojang::{hexdigest, MojangClient},
+        proxy::Proxy,
+    };
 
     fn sha1(input: &[u8]) -> String {
         let mut sha1 = Sha1::new();
@@ -224,4 +273,19 @@ mod tests {
         assert_eq!(sha1(b"simon"), "88e16a1019277b15d58faf0541e11910eb756f6");
         assert_eq!(sha1(b"Notch"), "4ed1f46bbe04bc756bcb17c0c7ce3e4632f06a48");
     }
+
+    #[test]
+    fn test_proxy_schemes() {
+        for proxy in ["socks5://1.2.3.4:1080:user:pass", "http://1.2.3.4:8080"] {
+            let proxy: Proxy = proxy.parse().unwrap();
+            assert!(MojangClient::try_from(&proxy).is_ok());
+        }
+
+        // SOCKS4 proxies are bridged, which needs a runtime
+        let runtime = Runtime::new().unwrap();
+        let _guard = runtime.enter();
+
+        let proxy: Proxy = "socks4://1.2.3.4:1080".parse().unwrap();
+        assert!(MojangClient::try_from(&proxy).is_ok());
+    }
 }
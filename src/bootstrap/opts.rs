@@ -1,17 +1,60 @@
 //! Module to interact with cargo options
 
-use clap::Parser;
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+
+use crate::bootstrap::proxy_pool::ProxyStrategy;
+
+/// How online users are authenticated
+#[derive(ValueEnum, Serialize, Deserialize, Copy, Clone, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthMethod {
+    /// Mojang accounts, with the email and password in the users file
+    #[default]
+    Mojang,
+
+    /// Microsoft accounts. A link and code to sign in are printed for each
+    /// user which is not cached
+    Microsoft,
+}
+
+/// Commands which are run instead of launching bots
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Re-encrypt the user cache with a new passphrase. The current passphrase
+    /// is read from SWARMBOT_CACHE_PASSPHRASE and the new one from
+    /// SWARMBOT_NEW_CACHE_PASSPHRASE, or else prompted for. A cache which is
+    /// not encrypted yet is encrypted
+    RotatePassphrase,
+}
 
 /// Options parsed from CLI
 #[derive(Parser, Debug)]
 #[clap(version = "1.0", author = "Andrew Gazelka", disable_version_flag = true)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct CliOptions {
-    /// The host which the bot will connect to
-    pub host: String,
+    #[clap(subcommand)]
+    pub command: Option<Command>,
 
-    /// The number of bots that will be launched
-    #[clap(short, long, default_value = "1")]
-    pub count: usize,
+    /// The host which the bot will connect to. Replaces the servers in the
+    /// config file
+    pub host: Option<String>,
+
+    /// The config file. Defaults to swarmbot.toml if it exists. Options given
+    /// on the command line override the ones in the file
+    #[clap(long)]
+    pub config: Option<PathBuf>,
+
+    /// Print the effective configuration, after applying the options given on
+    /// the command line to the config file, and exit
+    #[clap(long)]
+    pub print_config: bool,
+
+    /// The number of bots that will be launched on each server [default: 1]
+    #[clap(short, long)]
+    pub count: Option<usize>,
 
     /// If a proxy will be used to log the bots in and join the server.
     /// This is recommended as Mojang API has rate limits as do most
@@ -23,28 +66,64 @@ pub struct CliOptions {
     #[clap(short)]
     pub proxy: bool,
 
-    /// The port of the server which is being connected to
-    #[clap(long, default_value = "25565")]
-    pub port: u16,
+    /// How proxies are assigned to bots [default: round-robin]
+    #[clap(long, value_enum)]
+    pub proxy_strategy: Option<ProxyStrategy>,
+
+    /// The most bots which use the same proxy. Unlimited if not given
+    #[clap(long)]
+    pub max_bots_per_proxy: Option<usize>,
+
+    /// How often (in seconds) proxies are health checked. Proxies are always
+    /// checked on startup. Proxies which fail too often are quarantined. 0
+    /// disables periodic checks [default: 300]
+    #[clap(long)]
+    pub proxy_check_secs: Option<u64>,
+
+    /// The port of the server which is being connected to [default: 25565].
+    /// Without a host, it replaces the port of every server in the config file
+    #[clap(long)]
+    pub port: Option<u16>,
 
     /// The port of the web socket that is used to communicate bot commands
     /// to. This is used to interface with the SwarmBot mod, although it
-    /// can be used for anything.
-    #[clap(long, default_value = "8080")]
-    pub ws_port: u16,
+    /// can be used for anything. [default: 8080]
+    #[clap(long)]
+    pub ws_port: Option<u16>,
 
-    /// The delay for launching the bots
-    #[clap(short, long, default_value = "500")]
-    pub delay_ms: u64,
+    /// The address the web socket binds to [default: 127.0.0.1]
+    #[clap(long)]
+    pub ws_host: Option<String>,
+
+    /// The address (`host:port`) of the HTTP API which lists, spawns,
+    /// disconnects and commands bots. It is not served if not given
+    #[clap(long)]
+    pub api_address: Option<String>,
+
+    /// The token requests to the HTTP API must send as
+    /// `Authorization: Bearer <token>`. Required to serve the API
+    #[clap(long)]
+    pub api_token: Option<String>,
+
+    /// The address (`host:port`) Prometheus metrics are served on at
+    /// `/metrics`. They are not served if not given
+    #[clap(long)]
+    pub metrics_address: Option<String>,
+
+    /// The delay for launching the bots [default: 500]
+    #[clap(short, long)]
+    pub delay_ms: Option<u64>,
 
     /// The file that the users will be read from. This is a CSV file of
     /// the form of
     ///
-    /// email@gmail.com:password
+    /// email@gmail.com:password[:tag1,tag2]
     ///
-    /// Note, instead of commas as a delimiter, colons are used
-    #[clap(long, default_value = "users.csv")]
-    pub users_file: String,
+    /// Note, instead of commas as a delimiter, colons are used. The tags are
+    /// optional and let commands select the bot
+    /// [default: users.csv]
+    #[clap(long)]
+    pub users_file: Option<String>,
 
     /// The file that the proxies will be read from. This is a CSV file of
     /// the form of
@@ -52,15 +131,91 @@ pub struct CliOptions {
     /// 111.111.11.11:3333:username:password
     ///
     /// Note, instead of commas as a delimiter, colons are used
-    #[clap(long, default_value = "proxies.csv")]
-    pub proxies_file: String,
+    /// [default: proxies.csv]
+    #[clap(long)]
+    pub proxies_file: Option<String>,
 
     /// The version number that the bots will be launched on. To see a list
     /// of versions see
     ///
     /// <https://minecraft.fandom.com/wiki/Protocol_version>
-    #[clap(short, long, default_value = "340")]
-    pub version: usize,
+    ///
+    /// If not specified, the version is detected by pinging the server
+    #[clap(short, long)]
+    pub version: Option<usize>,
+
+    /// Only ping the server and print its version, player count and latency.
+    /// No bots are logged in.
+    #[clap(long)]
+    pub ping: bool,
+
+    /// Capture every packet each bot receives to `<dir>/<username>.cap` so
+    /// bugs can be replayed offline. Only supported on 1.12.2
+    #[clap(long)]
+    pub capture: Option<PathBuf>,
+
+    /// The most memory (in MiB) chunks may use. Once exceeded, chunks far
+    /// away from every bot are reduced to a low memory representation and
+    /// then dropped. Unlimited if not given
+    #[clap(long)]
+    pub max_world_mb: Option<usize>,
+
+    /// Save the blocks and entities bots have seen to `<dir>/<server>` and
+    /// load them on startup, so paths can be planned through chunks before
+    /// the server sends them
+    #[clap(long)]
+    pub world_dir: Option<PathBuf>,
+
+    /// How often the world is saved to `--world-dir`. It is also saved on
+    /// ctrl-c
+    #[clap(long, default_value = "60")]
+    pub world_save_secs: u64,
+
+    /// The directory `#export` and the export websocket command write
+    /// singleplayer worlds to
+    #[clap(long, default_value = "exports")]
+    pub export_dir: PathBuf,
+
+    /// The directory `#build` and the build websocket command read schematics
+    /// from. Only names of files in it are accepted
+    #[clap(long, default_value = "schematics")]
+    pub schematic_dir: PathBuf,
+
+    /// How many times in a row a disconnected bot is logged back in before
+    /// giving up. 0 disables reconnecting
+    #[clap(long, default_value = "5")]
+    pub reconnect_attempts: u32,
+
+    /// The delay before the first reconnect attempt. It doubles after every
+    /// failed attempt
+    #[clap(long, default_value = "5000")]
+    pub reconnect_delay_ms: u64,
+
+    /// The longest delay between two reconnect attempts
+    #[clap(long, default_value = "300000")]
+    pub max_reconnect_delay_ms: u64,
+
+    /// Bots are not reconnected if the reason they were disconnected for
+    /// contains this (case insensitive). Can be given multiple times, which
+    /// replaces the default
+    #[clap(long, default_value = "banned")]
+    pub no_reconnect_on: Vec<String>,
+
+    /// How users in the users file are authenticated [default: mojang]
+    #[clap(long, value_enum)]
+    pub auth: Option<AuthMethod>,
+
+    /// The OAuth client id used for Microsoft authentication. Defaults to the
+    /// one of the Minecraft launcher
+    #[clap(long)]
+    pub msa_client_id: Option<String>,
+
+    /// Encrypt the user cache with a passphrase from SWARMBOT_CACHE_PASSPHRASE
+    /// or a prompt. An existing cache which is not encrypted is migrated. A
+    /// cache which is already encrypted is always decrypted, with or without
+    /// this flag
+    #[clap(long)]
+    pub encrypt_cache: bool,
 
     /// if we are launching in offline mode
     #[clap(long)]
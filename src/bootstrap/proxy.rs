@@ -0,0 +1,386 @@
+//! Proxies bots connect through. Each line of `proxies.csv` is
+//! `[scheme://]host:port[:user:pass]` where the scheme is `socks5` (the
+//! default), `socks4` or `http`.
+
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{bail, Context};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+};
+use tokio_socks::tcp::Socks5Stream;
+
+use crate::bootstrap::Address;
+
+/// The protocol used to talk to a [`Proxy`]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum ProxyScheme {
+    #[default]
+    Socks5,
+
+    /// SOCKS4a. The user (if any) is sent as the user id
+    Socks4,
+
+    /// HTTP `CONNECT` tunnelling
+    Http,
+}
+
+impl ProxyScheme {
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Socks5 => "socks5",
+            Self::Socks4 => "socks4",
+            Self::Http => "http",
+        }
+    }
+}
+
+impl Display for ProxyScheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ProxyScheme {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "socks5" | "socks5h" => Ok(Self::Socks5),
+            "socks4" | "socks4a" => Ok(Self::Socks4),
+            "http" => Ok(Self::Http),
+            other => bail!("unknown proxy scheme {other}. Expected socks5, socks4 or http"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Proxy {
+    pub scheme: ProxyScheme,
+    pub host: String,
+    pub port: u16,
+    pub user: Option<String>,
+    pub pass: Option<String>,
+}
+
+impl Proxy {
+    pub fn address(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+
+    /// the proxy as a URL without credentials, e.g. `socks4://1.2.3.4:1080`
+    pub fn url(&self) -> String {
+        format!("{}://{}", self.scheme, self.address())
+    }
+
+    /// Open a TCP tunnel to `target` through the proxy
+    pub async fn connect(&self, target: &Address) -> anyhow::Result<TcpStream> {
+        let address = self.address();
+        let target_str = String::from(target);
+
+        let stream = match self.scheme {
+            ProxyScheme::Socks5 => {
+                let stream = match (&self.user, &self.pass) {
+                    (Some(user), Some(pass)) => {
+                        Socks5Stream::connect_with_password(
+                            address.as_str(),
+                            target_str.as_str(),
+                            user,
+                            pass,
+                        )
+                        .await
+                    }
+                    _ => Socks5Stream::connect(address.as_str(), target_str.as_str()).await,
+                };
+                stream?.into_inner()
+            }
+            ProxyScheme::Socks4 => {
+                let mut stream = TcpStream::connect(address.as_str()).await?;
+                socks4_handshake(&mut stream, target, self.user.as_deref()).await?;
+                stream
+            }
+            ProxyScheme::Http => {
+                let mut stream = TcpStream::connect(address.as_str()).await?;
+                let auth = self.user.as_ref().map(|user| {
+                    let pass = self.pass.as_deref().unwrap_or_default();
+                    base64::encode(format!("{user}:{pass}"))
+                });
+                http_connect(&mut stream, &target_str, auth.as_deref()).await?;
+                stream
+            }
+        };
+
+        Ok(stream)
+    }
+}
+
+impl FromStr for Proxy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        let (scheme, rest) = match s.split_once("://") {
+            Some((scheme, rest)) => (scheme.parse()?, rest),
+            None => (ProxyScheme::default(), s),
+        };
+
+        let parts: Vec<_> = rest.split(':').collect();
+
+        let (host, port, user, pass) = match parts.as_slice() {
+            [host, port] => (host, port, None, None),
+            [host, port, user] => (host, port, Some(user), None),
+            [host, port, user, pass] => (host, port, Some(user), Some(pass)),
+            _ => bail!("expected [scheme://]host:port[:user:pass]"),
+        };
+
+        let port = port
+            .parse()
+            .with_context(|| format!("invalid port {port}"))?;
+
+        Ok(Self {
+            scheme,
+            host: (*host).to_string(),
+            port,
+            user: user.map(ToString::to_string),
+            pass: pass.map(ToString::to_string),
+        })
+    }
+}
+
+/// <https://www.openssh.com/txt/socks4.protocol> and
+/// <https://www.openssh.com/txt/socks4a.protocol>
+async fn socks4_handshake(
+    stream: &mut TcpStream,
+    target: &Address,
+    user_id: Option<&str>,
+) -> anyhow::Result<()> {
+    const VERSION: u8 = 4;
+    const CONNECT: u8 = 1;
+    const GRANTED: u8 = 90;
+
+    let ip = target.host.parse::<std::net::Ipv4Addr>().ok();
+
+    let mut request = vec![VERSION, CONNECT];
+    request.extend_from_slice(&target.port.to_be_bytes());
+
+    // SOCKS4a: an ip of 0.0.0.x means the host name follows the user id
+    request.extend_from_slice(&ip.map_or([0, 0, 0, 1], |ip| ip.octets()));
+    request.extend_from_slice(user_id.unwrap_or_default().as_bytes());
+    request.push(0);
+
+    if ip.is_none() {
+        request.extend_from_slice(target.host.as_bytes());
+        request.push(0);
+    }
+
+    stream.write_all(&request).await?;
+
+    let mut response = [0_u8; 8];
+    stream
+        .read_exact(&mut response)
+        .await
+        .context("SOCKS4 proxy closed the connection")?;
+
+    let [_, status, ..] = response;
+
+    match status {
+        GRANTED => Ok(()),
+        91 => bail!("SOCKS4 proxy rejected the request"),
+        92 | 93 => bail!("SOCKS4 proxy could not verify the user id"),
+        other => bail!("SOCKS4 proxy responded with unknown status {other}"),
+    }
+}
+
+/// <https://developer.mozilla.org/en-US/docs/Web/HTTP/Methods/CONNECT>
+async fn http_connect(
+    stream: &mut TcpStream,
+    target: &str,
+    auth: Option<&str>,
+) -> anyhow::Result<()> {
+    let auth = auth
+        .map(|auth| format!("Proxy-Authorization: Basic {auth}\r\n"))
+        .unwrap_or_default();
+
+    let request = format!("CONNECT {target} HTTP/1.1\r\nHost: {target}\r\n{auth}\r\n");
+
+    stream.write_all(request.as_bytes()).await?;
+
+    // we must not read past the end of the response as everything after it
+    // belongs to the tunnel
+    let mut reader = BufReader::with_capacity(1, stream);
+
+    let mut status_line = String::new();
+    reader.read_line(&mut status_line).await?;
+
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .context("HTTP proxy closed the connection")?;
+
+    if !status.starts_with('2') {
+        bail!("HTTP proxy responded with {}", status_line.trim());
+    }
+
+    // skip headers
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 || line == "\r\n" || line == "\n" {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        runtime::Runtime,
+    };
+
+    use crate::bootstrap::{
+        proxy::{Proxy, ProxyScheme},
+        Address,
+    };
+
+    #[test]
+    fn test_parse() {
+        let proxy: Proxy = "111.111.11.11:3333:username:password".parse().unwrap();
+        assert_eq!(proxy.scheme, ProxyScheme::Socks5);
+        assert_eq!(proxy.address(), "111.111.11.11:3333");
+        assert_eq!(proxy.user.as_deref(), Some("username"));
+        assert_eq!(proxy.pass.as_deref(), Some("password"));
+
+        let proxy: Proxy = "socks4://1.2.3.4:1080".parse().unwrap();
+        assert_eq!(proxy.scheme, ProxyScheme::Socks4);
+        assert_eq!(proxy.user, None);
+        assert_eq!(proxy.url(), "socks4://1.2.3.4:1080");
+
+        let proxy: Proxy = "http://1.2.3.4:8080:user:pass".parse().unwrap();
+        assert_eq!(proxy.scheme, ProxyScheme::Http);
+
+        assert!("ftp://1.2.3.4:21".parse::<Proxy>().is_err());
+        assert!("1.2.3.4".parse::<Proxy>().is_err());
+        assert!("1.2.3.4:port".parse::<Proxy>().is_err());
+    }
+
+    /// start a proxy on localhost which answers a single handshake with
+    /// `response` and then echoes. Returns the port and the received handshake.
+    async fn fake_proxy(
+        response: &'static [u8],
+        handshake_len: usize,
+    ) -> (u16, tokio::task::JoinHandle<Vec<u8>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut handshake = vec![0; handshake_len];
+            stream.read_exact(&mut handshake).await.unwrap();
+            stream.write_all(response).await.unwrap();
+
+            // echo a byte if the client accepted the handshake
+            let mut byte = [0];
+            if stream.read_exact(&mut byte).await.is_ok() {
+                stream.write_all(&byte).await.unwrap();
+            }
+            handshake
+        });
+
+        (port, handle)
+    }
+
+    fn proxy(scheme: ProxyScheme, port: u16, user: Option<&str>) -> Proxy {
+        Proxy {
+            scheme,
+            host: "127.0.0.1".to_string(),
+            port,
+            user: user.map(ToString::to_string),
+            pass: user.map(|_| "pass".to_string()),
+        }
+    }
+
+    async fn echo(stream: &mut tokio::net::TcpStream) -> u8 {
+        stream.write_all(&[42]).await.unwrap();
+        let mut byte = [0];
+        stream.read_exact(&mut byte).await.unwrap();
+        byte[0]
+    }
+
+    #[test]
+    fn test_socks4() {
+        Runtime::new().unwrap().block_on(async {
+            let target = Address {
+                host: "10.0.0.1".to_string(),
+                port: 25565,
+            };
+
+            // version, command, port, ip, "bot", null
+            let (port, handle) = fake_proxy(&[0, 90, 0, 0, 0, 0, 0, 0], 12).await;
+            let mut stream = proxy(ProxyScheme::Socks4, port, Some("bot"))
+                .connect(&target)
+                .await
+                .unwrap();
+            assert_eq!(echo(&mut stream).await, 42);
+
+            let handshake = handle.await.unwrap();
+            assert_eq!(
+                handshake,
+                [4, 1, 0x63, 0xdd, 10, 0, 0, 1, b'b', b'o', b't', 0]
+            );
+
+            let (port, _handle) = fake_proxy(&[0, 91, 0, 0, 0, 0, 0, 0], 9).await;
+            let err = proxy(ProxyScheme::Socks4, port, None)
+                .connect(&target)
+                .await
+                .unwrap_err();
+            assert!(err.to_string().contains("rejected"));
+        });
+    }
+
+    #[test]
+    fn test_http_connect() {
+        Runtime::new().unwrap().block_on(async {
+            let target = Address {
+                host: "example.com".to_string(),
+                port: 25565,
+            };
+
+            let expected = "CONNECT example.com:25565 HTTP/1.1\r\nHost: \
+                            example.com:25565\r\nProxy-Authorization: Basic \
+                            dXNlcjpwYXNz\r\n\r\n";
+
+            let (port, handle) = fake_proxy(
+                b"HTTP/1.1 200 Connection established\r\nVia: test\r\n\r\n",
+                expected.len(),
+            )
+            .await;
+
+            let mut stream = proxy(ProxyScheme::Http, port, Some("user"))
+                .connect(&target)
+                .await
+                .unwrap();
+            assert_eq!(echo(&mut stream).await, 42);
+
+            let handshake = handle.await.unwrap();
+            assert_eq!(String::from_utf8(handshake).unwrap(), expected);
+
+            let expected = "CONNECT example.com:25565 HTTP/1.1\r\nHost: example.com:25565\r\n\r\n";
+            let (port, _handle) = fake_proxy(
+                b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n",
+                expected.len(),
+            )
+            .await;
+
+            let err = proxy(ProxyScheme::Http, port, None)
+                .connect(&target)
+                .await
+                .unwrap_err();
+            assert!(err.to_string().contains("407"));
+        });
+    }
+}
@@ -0,0 +1,434 @@
+//! Health checking proxies and assigning them to bots
+
+use std::{
+    cell::RefCell,
+    fmt::{Display, Formatter},
+    fs::File,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::bootstrap::{csv, proxy::Proxy, Address};
+
+/// How a proxy is chosen for a bot
+#[derive(ValueEnum, Serialize, Deserialize, Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProxyStrategy {
+    /// each proxy in turn
+    #[default]
+    RoundRobin,
+
+    /// the proxy with the fewest bots
+    LeastUsed,
+
+    /// the same proxy for an account every time, as long as it is healthy
+    Sticky,
+}
+
+/// How proxies are checked and assigned
+#[derive(Clone, Debug)]
+pub struct PoolOptions {
+    pub strategy: ProxyStrategy,
+
+    /// the most bots assigned to one proxy. Unlimited if `None`
+    pub max_bots_per_proxy: Option<usize>,
+
+    /// failures in a row before a proxy is quarantined
+    pub quarantine_after: u32,
+
+    /// how long a quarantined proxy is not used unless a probe succeeds
+    pub quarantine_for: Duration,
+
+    /// how long a probe may take before it counts as a failure
+    pub probe_timeout: Duration,
+}
+
+impl Default for PoolOptions {
+    fn default() -> Self {
+        Self {
+            strategy: ProxyStrategy::default(),
+            max_bots_per_proxy: None,
+            quarantine_after: 3,
+            quarantine_for: Duration::from_secs(10 * 60),
+            probe_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// What is known about a proxy
+#[derive(Clone, Debug, Default)]
+pub struct ProxyStats {
+    /// successful probes and connections
+    pub successes: u64,
+
+    /// failed probes and connections
+    pub failures: u64,
+
+    /// failures since the last success
+    pub consecutive_failures: u32,
+
+    /// the time the last successful probe or connection took
+    pub latency: Option<Duration>,
+
+    /// the bots currently assigned to the proxy
+    pub bots: usize,
+
+    /// the proxy is not used until then
+    pub quarantined_until: Option<Instant>,
+}
+
+impl ProxyStats {
+    pub fn is_quarantined(&self) -> bool {
+        self.quarantined_until
+            .map_or(false, |until| Instant::now() < until)
+    }
+}
+
+impl Display for ProxyStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let state = if self.is_quarantined() {
+            "quarantined"
+        } else {
+            "healthy"
+        };
+
+        let latency = self.latency.map_or_else(
+            || "-".to_string(),
+            |latency| format!("{}ms", latency.as_millis()),
+        );
+
+        write!(
+            f,
+            "{state}, {latency}, {} bots, {} ok / {} failed",
+            self.bots, self.successes, self.failures
+        )
+    }
+}
+
+/// Proxies along with their health. Without any proxies bots connect directly
+#[derive(Default)]
+pub struct ProxyPool {
+    proxies: Vec<(Proxy, ProxyStats)>,
+    options: PoolOptions,
+
+    /// where the next round robin search starts
+    next: usize,
+}
+
+pub type SharedProxyPool = Rc<RefCell<ProxyPool>>;
+
+/// FNV-1a, which unlike the std hasher is the same across builds so sticky
+/// assignments survive restarts
+fn stable_hash(s: &str) -> u64 {
+    s.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100_0000_01b3)
+    })
+}
+
+impl ProxyPool {
+    pub fn new(proxies: Vec<Proxy>, options: PoolOptions) -> Self {
+        Self {
+            proxies: proxies
+                .into_iter()
+                .map(|proxy| (proxy, ProxyStats::default()))
+                .collect(),
+            options,
+            next: 0,
+        }
+    }
+
+    /// read the proxies in `proxies_file`
+    pub fn load(proxies_file: &str, options: PoolOptions) -> anyhow::Result<Self> {
+        let file = File::open(proxies_file)
+            .with_context(|| format!("could not open proxies file {proxies_file}"))?;
+        let proxies = csv::read_proxies(file).context("could not open proxies file")?;
+
+        if proxies.is_empty() {
+            bail!("there are no proxies in {proxies_file}");
+        }
+
+        Ok(Self::new(proxies, options))
+    }
+
+    pub fn shared(self) -> SharedProxyPool {
+        Rc::new(RefCell::new(self))
+    }
+
+    fn stats_mut(&mut self, proxy: &Proxy) -> Option<&mut ProxyStats> {
+        self.proxies
+            .iter_mut()
+            .find(|(other, _)| other == proxy)
+            .map(|(_, stats)| stats)
+    }
+
+    fn is_available(&self, idx: usize) -> bool {
+        let (_, stats) = &self.proxies[idx];
+        let full = self
+            .options
+            .max_bots_per_proxy
+            .map_or(false, |max| stats.bots >= max);
+        !full && !stats.is_quarantined()
+    }
+
+    /// The proxy a bot logging in as `account` uses. `None` if there are no
+    /// proxies. Fails if every proxy is quarantined or full
+    pub fn assign(&mut self, account: &str) -> anyhow::Result<Option<Proxy>> {
+        let len = self.proxies.len();
+        if len == 0 {
+            return Ok(None);
+        }
+
+        let idx = match self.options.strategy {
+            ProxyStrategy::RoundRobin => {
+                let idx = (0..len)
+                    .map(|offset| (self.next + offset) % len)
+                    .find(|&idx| self.is_available(idx));
+                if let Some(idx) = idx {
+                    self.next = idx + 1;
+                }
+                idx
+            }
+            ProxyStrategy::LeastUsed => (0..len)
+                .filter(|&idx| self.is_available(idx))
+                .min_by_key(|&idx| self.proxies[idx].1.bots),
+            ProxyStrategy::Sticky => {
+                // the account's proxy, or the next available one after it
+                #[allow(clippy::cast_possible_truncation)]
+                let start = (stable_hash(account) % len as u64) as usize;
+                (0..len)
+                    .map(|offset| (start + offset) % len)
+                    .find(|&idx| self.is_available(idx))
+            }
+        };
+
+        let Some(idx) = idx else {
+            bail!("every proxy is quarantined or has the most bots allowed");
+        };
+
+        let (proxy, stats) = &mut self.proxies[idx];
+        stats.bots += 1;
+        Ok(Some(proxy.clone()))
+    }
+
+    /// a bot using `proxy` is gone for good
+    pub fn release(&mut self, proxy: Option<&Proxy>) {
+        let Some(stats) = proxy.and_then(|proxy| self.stats_mut(proxy)) else { return };
+        stats.bots = stats.bots.saturating_sub(1);
+    }
+
+    /// `proxy` connected in `latency`. Lifts its quarantine
+    pub fn record_success(&mut self, proxy: &Proxy, latency: Duration) {
+        let Some(stats) = self.stats_mut(proxy) else { return };
+        stats.successes += 1;
+        stats.consecutive_failures = 0;
+        stats.latency = Some(latency);
+        stats.quarantined_until = None;
+    }
+
+    /// `proxy` could not connect. Quarantines it after too many failures in a
+    /// row
+    pub fn record_failure(&mut self, proxy: &Proxy) {
+        let PoolOptions {
+            quarantine_after,
+            quarantine_for,
+            ..
+        } = self.options;
+
+        let Some(stats) = self.stats_mut(proxy) else { return };
+        stats.failures += 1;
+        stats.consecutive_failures += 1;
+
+        if stats.consecutive_failures >= quarantine_after && !stats.is_quarantined() {
+            println!(
+                "quarantining proxy {} for {}s after {} failures in a row",
+                proxy.url(),
+                quarantine_for.as_secs(),
+                stats.consecutive_failures
+            );
+            stats.quarantined_until = Some(Instant::now() + quarantine_for);
+        }
+    }
+
+    pub fn is_quarantined(&self, proxy: &Proxy) -> bool {
+        self.proxies
+            .iter()
+            .any(|(other, stats)| other == proxy && stats.is_quarantined())
+    }
+
+    pub fn stats(&self) -> impl Iterator<Item = (&Proxy, &ProxyStats)> {
+        self.proxies.iter().map(|(proxy, stats)| (proxy, stats))
+    }
+
+    /// one line describing the health of all proxies
+    pub fn summary(&self) -> String {
+        let quarantined = self
+            .proxies
+            .iter()
+            .filter(|(_, stats)| stats.is_quarantined())
+            .count();
+
+        let latencies: Vec<_> = self
+            .proxies
+            .iter()
+            .filter_map(|(_, stats)| stats.latency)
+            .collect();
+
+        let mut summary = format!(
+            "proxies: {} healthy, {quarantined} quarantined",
+            self.proxies.len() - quarantined
+        );
+
+        if !latencies.is_empty() {
+            let count = u32::try_from(latencies.len()).unwrap_or(u32::MAX);
+            let average = latencies.iter().sum::<Duration>() / count;
+            summary.push_str(&format!(", {}ms average latency", average.as_millis()));
+        }
+
+        summary
+    }
+
+    /// print the stats of every proxy
+    pub fn print_stats(&self) {
+        for (proxy, stats) in self.stats() {
+            println!("{}: {stats}", proxy.url());
+        }
+        println!("{}", self.summary());
+    }
+}
+
+/// the time it took to open a tunnel to `target` through `proxy`, or `None` if
+/// it failed
+async fn tunnel(proxy: &Proxy, target: &Address, timeout: Duration) -> Option<Duration> {
+    let start = Instant::now();
+    match tokio::time::timeout(timeout, proxy.connect(target)).await {
+        Ok(Ok(_)) => Some(start.elapsed()),
+        _ => None,
+    }
+}
+
+/// Open a tunnel to each of `targets` through every proxy and record which
+/// work. The bots of every server share the proxies, so a proxy only works if
+/// it reaches all of them. Its latency is that of the slowest server
+pub async fn probe(pool: &SharedProxyPool, targets: &[Address]) {
+    let (proxies, timeout) = {
+        let pool = pool.borrow();
+        let proxies: Vec<_> = pool.stats().map(|(proxy, _)| proxy.clone()).collect();
+        (proxies, pool.options.probe_timeout)
+    };
+
+    let results = futures::future::join_all(proxies.iter().map(|proxy| async move {
+        let tunnels = targets.iter().map(|target| tunnel(proxy, target, timeout));
+        let latencies: Option<Vec<_>> = futures::future::join_all(tunnels)
+            .await
+            .into_iter()
+            .collect();
+        latencies.map(|latencies| latencies.into_iter().max().unwrap_or_default())
+    }))
+    .await;
+
+    let mut pool = pool.borrow_mut();
+    for (proxy, result) in proxies.iter().zip(results) {
+        match result {
+            Some(latency) => pool.record_success(proxy, latency),
+            None => pool.record_failure(proxy),
+        }
+    }
+}
+
+/// Probe all proxies against `targets` every `every` and print how healthy they
+/// are
+pub fn spawn_health_checks(pool: SharedProxyPool, targets: Vec<Address>, every: Duration) {
+    tokio::task::spawn_local(async move {
+        loop {
+            tokio::time::sleep(every).await;
+            probe(&pool, &targets).await;
+            println!("{}", pool.borrow().summary());
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::bootstrap::{
+        proxy::Proxy,
+        proxy_pool::{PoolOptions, ProxyPool, ProxyStrategy},
+    };
+
+    fn pool(strategy: ProxyStrategy, max_bots_per_proxy: Option<usize>) -> ProxyPool {
+        let proxies = ["1.1.1.1:1080", "2.2.2.2:1080", "3.3.3.3:1080"]
+            .iter()
+            .map(|proxy| proxy.parse().unwrap())
+            .collect();
+
+        ProxyPool::new(
+            proxies,
+            PoolOptions {
+                strategy,
+                max_bots_per_proxy,
+                ..PoolOptions::default()
+            },
+        )
+    }
+
+    fn host(proxy: Option<Proxy>) -> String {
+        proxy.unwrap().host
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let mut pool = pool(ProxyStrategy::RoundRobin, None);
+        let hosts: Vec<_> = (0..4).map(|_| host(pool.assign("").unwrap())).collect();
+        assert_eq!(hosts, ["1.1.1.1", "2.2.2.2", "3.3.3.3", "1.1.1.1"]);
+
+        assert!(ProxyPool::default().assign("").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_least_used_and_max() {
+        let mut pool = pool(ProxyStrategy::LeastUsed, Some(1));
+        let first = pool.assign("").unwrap();
+        assert_ne!(pool.assign("").unwrap(), first);
+        pool.assign("").unwrap();
+
+        // every proxy has the most bots allowed
+        assert!(pool.assign("").is_err());
+
+        pool.release(first.as_ref());
+        assert_eq!(pool.assign("").unwrap(), first);
+    }
+
+    #[test]
+    fn test_sticky_and_quarantine() {
+        let mut pool = pool(ProxyStrategy::Sticky, None);
+        let proxy = pool.assign("alt@example.com").unwrap().unwrap();
+        assert_eq!(
+            pool.assign("alt@example.com").unwrap().as_ref(),
+            Some(&proxy)
+        );
+
+        for _ in 0..3 {
+            pool.record_failure(&proxy);
+        }
+        assert!(pool.is_quarantined(&proxy));
+        assert!(pool.summary().contains("1 quarantined"));
+
+        // the account moves on while its proxy is quarantined
+        assert_ne!(
+            pool.assign("alt@example.com").unwrap().as_ref(),
+            Some(&proxy)
+        );
+
+        pool.record_success(&proxy, Duration::from_millis(20));
+        assert!(!pool.is_quarantined(&proxy));
+        assert_eq!(
+            pool.assign("alt@example.com").unwrap().as_ref(),
+            Some(&proxy)
+        );
+    }
+}
@@ -0,0 +1,137 @@
+//! How many bots may be started from the account pool
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use futures::{Stream, StreamExt};
+use tokio::sync::Semaphore;
+
+/// The number of bots which may still be started. It starts at the count
+/// bots are launched with and is raised to start more while running
+#[derive(Clone, Debug)]
+pub struct Quota {
+    permits: Arc<Semaphore>,
+    /// the accounts of the pool which have not been used yet. Offline
+    /// accounts never run out
+    accounts: Arc<AtomicUsize>,
+}
+
+impl Quota {
+    pub fn new(count: usize) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(count)),
+            accounts: Arc::new(AtomicUsize::new(usize::MAX)),
+        }
+    }
+
+    /// the account pool has `count` accounts which have not been used yet
+    pub fn set_accounts(&self, count: usize) {
+        self.accounts.store(count, Ordering::Relaxed);
+    }
+
+    /// count an account of the pool as used, whether it was valid or not
+    pub fn use_account(&self) {
+        let _ = self
+            .accounts
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |left| {
+                left.checked_sub(1)
+            });
+    }
+
+    /// allow up to `count` more bots to be started. Returns how many may
+    /// actually start, as the account pool may run out first. Fails if that
+    /// is more than the quota can count
+    pub fn add(&self, count: usize) -> anyhow::Result<usize> {
+        // the accounts which are not already waiting for a permit
+        let accounts = self.accounts.load(Ordering::Relaxed);
+        let count = count.min(accounts.saturating_sub(self.available()));
+
+        let total = self.available().checked_add(count);
+        anyhow::ensure!(
+            total.map_or(false, |total| total <= Semaphore::MAX_PERMITS),
+            "cannot start {count} more bots, at most {} bots may be waiting to start",
+            Semaphore::MAX_PERMITS
+        );
+
+        self.permits.add_permits(count);
+        Ok(count)
+    }
+
+    /// give back the permit taken for a bot which could not be started
+    pub fn release(&self) {
+        self.permits.add_permits(1);
+    }
+
+    /// the number of bots which may be started without waiting
+    pub fn available(&self) -> usize {
+        self.permits.available_permits()
+    }
+
+    /// wait until another bot may be started and count it
+    pub async fn take(&self) {
+        // the semaphore is never closed
+        if let Ok(permit) = self.permits.acquire().await {
+            permit.forget();
+        }
+    }
+
+    /// only take an item of `items` once another bot may be started
+    pub fn gate<T>(self, items: impl Stream<Item = T> + Unpin) -> impl Stream<Item = T> {
+        futures::stream::unfold((self, items), |(quota, mut items)| async move {
+            quota.take().await;
+            let item = items.next().await?;
+            Some((item, (quota, items)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+    use tokio::runtime::Runtime;
+
+    use crate::bootstrap::quota::Quota;
+
+    #[test]
+    fn test_gate() {
+        let rt = Runtime::new().unwrap();
+        rt.block_on(async {
+            let quota = Quota::new(2);
+            let mut items = Box::pin(quota.clone().gate(futures::stream::iter(0..10)));
+
+            assert_eq!(items.next().await, Some(0));
+            assert_eq!(items.next().await, Some(1));
+            assert_eq!(quota.available(), 0);
+
+            // no more items until the quota is raised
+            assert!(futures::poll!(items.next()).is_pending());
+
+            assert_eq!(quota.add(1).unwrap(), 1);
+            assert_eq!(items.next().await, Some(2));
+
+            // too many to count
+            assert!(quota.add(usize::MAX).is_err());
+            assert_eq!(quota.available(), 0);
+        });
+    }
+
+    #[test]
+    fn test_accounts() {
+        let quota = Quota::new(1);
+        quota.set_accounts(3);
+
+        // one account is already waiting for the permit it has
+        assert_eq!(quota.add(5).unwrap(), 2);
+        assert_eq!(quota.available(), 3);
+
+        for _ in 0..3 {
+            quota.use_account();
+        }
+        assert_eq!(quota.add(1).unwrap(), 0);
+
+        // counting an account of an empty pool does nothing
+        quota.use_account();
+        assert_eq!(quota.add(1).unwrap(), 0);
+    }
+}
@@ -4,13 +4,12 @@ use std::{
     collections::HashMap,
     convert::TryFrom,
     fs,
-    fs::{File, OpenOptions},
-    io::{Read, Write},
+    fs::File,
     path::PathBuf,
     time::{SystemTime, UNIX_EPOCH},
 };
 
-use anyhow::Context;
+use anyhow::{bail, ensure, Context};
 use bincode::{Decode, Encode};
 use swarm_bot_packets::types::UUID;
 use tokio::sync::mpsc::Receiver;
@@ -18,27 +17,39 @@ use tokio_stream::Stream;
 
 use crate::{
     bootstrap,
-    bootstrap::{mojang::MojangClient, CSVUser, Proxy},
+    bootstrap::{
+        encryption,
+        microsoft::{MicrosoftAuth, MicrosoftEndpoints},
+        mojang::MojangClient,
+        proxy::Proxy,
+        proxy_pool::SharedProxyPool,
+        quota::Quota,
+        CSVUser,
+    },
 };
 
+/// the file users are cached in
+pub const CACHE_FILE: &str = "cache.db";
+
 #[derive(Encode, Decode, Debug)]
 struct Root {
     users: Vec<User>,
 }
 
-#[derive(Encode, Decode, Debug)]
+#[derive(Encode, Decode, Clone, Debug)]
 enum User {
     Valid(OnlineUser),
     Invalid(InvalidUser),
+    Microsoft(MicrosoftUser),
 }
 
-#[derive(Encode, Decode, Debug)]
+#[derive(Encode, Decode, Clone, Debug)]
 struct InvalidUser {
     email: String,
     password: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct OfflineUser {
     pub username: String,
 }
@@ -60,12 +71,57 @@ impl OnlineUser {
     }
 }
 
+/// A user signed in with a Microsoft account
+#[derive(Encode, Decode, Clone, Debug)]
+pub struct MicrosoftUser {
+    pub email: String,
+    pub username: String,
+    pub uuid: String,
+
+    /// the Minecraft services token used to join servers
+    pub access_token: String,
+
+    /// the unix time `access_token` expires at
+    pub expires_at: u64,
+
+    /// the Microsoft refresh token used to get a new `access_token`
+    pub refresh_token: String,
+}
+
+/// Microsoft users join servers exactly like Mojang users once they have a
+/// Minecraft access token
+impl From<&MicrosoftUser> for OnlineUser {
+    fn from(user: &MicrosoftUser) -> Self {
+        Self {
+            email: user.email.clone(),
+            username: user.username.clone(),
+            password: String::new(),
+            last_checked: time(),
+            uuid: user.uuid.clone(),
+            access_id: user.access_token.clone(),
+            client_id: String::new(),
+        }
+    }
+}
+
+/// How online users are authenticated
+#[derive(Clone, Debug, Default)]
+pub enum Authentication {
+    /// the legacy Yggdrasil endpoints, using the password in the users file
+    #[default]
+    Mojang,
+
+    /// the Microsoft device code flow. The password in the users file is not
+    /// used
+    Microsoft(MicrosoftEndpoints),
+}
+
 impl User {
     const fn email(&self) -> &String {
         match self {
-            Self::Invalid(InvalidUser { email, .. }) | Self::Valid(OnlineUser { email, .. }) => {
-                email
-            }
+            Self::Invalid(InvalidUser { email, .. })
+            | Self::Valid(OnlineUser { email, .. })
+            | Self::Microsoft(MicrosoftUser { email, .. }) => email,
         }
     }
 }
@@ -73,9 +129,12 @@ impl User {
 pub struct UserCache {
     file_path: PathBuf,
     cache: HashMap<String, User>,
+
+    /// the passphrase the cache is encrypted with. Stored in plain if `None`
+    passphrase: Option<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum BotData {
     Online {
         /// the online user
@@ -97,15 +156,26 @@ impl BotData {
             Self::Offline { user } => &user.username,
         }
     }
+
+    /// the email of online users and the username of offline ones. Proxies are
+    /// assigned by it
+    pub fn account(&self) -> &str {
+        match self {
+            Self::Online { user, .. } => &user.email,
+            Self::Offline { user } => &user.username,
+        }
+    }
 }
 
 /// A bot data holds the "Mojang" object used in cache to verify that the user
 /// is valid along with data about what the proxy address is and the valid user
 /// information
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BotConnectionData {
     pub bot: BotData,
     pub proxy: Option<Proxy>,
+    /// the tags of the account from the users file
+    pub tags: Vec<String>,
 }
 
 impl BotConnectionData {
@@ -118,6 +188,7 @@ impl BotConnectionData {
                     user: OfflineUser { username },
                 },
                 proxy: None,
+                tags: Vec::new(),
             };
 
             idx += 1;
@@ -129,9 +200,10 @@ impl BotConnectionData {
     }
     pub fn load_from_files(
         users_file: &str,
-        proxies_file: &str,
-        proxy: bool,
-        count: usize,
+        proxies: SharedProxyPool,
+        quota: Quota,
+        auth: Authentication,
+        encrypt_cache: bool,
     ) -> anyhow::Result<Receiver<Self>> {
         let csv_file = File::open(users_file)
             .with_context(|| format!("could not open users file {users_file}"))?;
@@ -139,21 +211,21 @@ impl BotConnectionData {
         let csv_users =
             bootstrap::csv::read_users(csv_file).context("could not open users file")?;
 
-        let proxies = if proxy {
-            let proxies_file = File::open(proxies_file)
-                .with_context(|| format!("could not open proxies file {proxies_file}"))?;
-            bootstrap::csv::read_proxies(proxies_file)
-                .context("could not open proxies file")?
-                .into_iter()
-                .map(Some)
-                .collect()
-        } else {
-            vec![None]
-        };
+        let cache = UserCache::load(CACHE_FILE.into(), encrypt_cache)?;
 
-        let cache = UserCache::load("cache.db".into())?;
+        Ok(cache.obtain_users(quota, csv_users, proxies, auth))
+    }
+}
 
-        Ok(cache.obtain_users(count, csv_users, proxies))
+/// a [`MojangClient`] using `proxy`. If the proxy cannot be used the error is
+/// printed and the user is skipped
+fn mojang_client(proxy: Option<&Proxy>, email: &str) -> Option<MojangClient> {
+    match MojangClient::try_from(proxy) {
+        Ok(client) => Some(client),
+        Err(err) => {
+            println!("skipping {email}: {err:#}");
+            None
+        }
     }
 }
 
@@ -164,29 +236,125 @@ fn time() -> u64 {
 }
 
 impl UserCache {
-    pub fn load(file_path: PathBuf) -> anyhow::Result<Self> {
+    /// Load the cache at `file_path`. An encrypted cache is decrypted with the
+    /// passphrase from [`encryption::PASSPHRASE_VAR`] or a prompt. A plain
+    /// cache is migrated to an encrypted one when it is saved if `encrypt`
+    pub fn load(file_path: PathBuf, encrypt: bool) -> anyhow::Result<Self> {
         let exists = fs::try_exists(&file_path)
             .with_context(|| format!("cannot load user from: {file_path:?} (DNE)"))?;
-        let res = if exists {
-            let file = File::open(&file_path).context("could not load file")?;
-            let bytes: Result<Vec<_>, _> = file.bytes().collect();
-            let bytes = bytes.context("could not load bytes")?;
-            let config = bincode::config::standard();
-            let (Root { users }, _) = bincode::decode_from_slice(&bytes, config)
-                .context("could not decode from slice")?;
-
-            let cache: HashMap<_, _> = users
-                .into_iter()
-                .map(|user| (user.email().clone(), user))
-                .collect();
-            Self { file_path, cache }
+
+        let bytes = if exists {
+            Some(fs::read(&file_path).context("could not load file")?)
+        } else {
+            None
+        };
+
+        let encrypted = bytes.as_deref().map_or(false, encryption::is_encrypted);
+
+        let passphrase = if encrypted || encrypt {
+            Some(encryption::passphrase(!encrypted)?)
         } else {
-            Self {
+            None
+        };
+
+        if encrypt && !encrypted && bytes.is_some() {
+            println!("the user cache {file_path:?} will be encrypted when it is saved");
+        }
+
+        Self::from_bytes(file_path, bytes.as_deref(), passphrase)
+    }
+
+    /// The cache stored as `bytes`, or an empty cache if there are none
+    fn from_bytes(
+        file_path: PathBuf,
+        bytes: Option<&[u8]>,
+        passphrase: Option<String>,
+    ) -> anyhow::Result<Self> {
+        let Some(bytes) = bytes else {
+            return Ok(Self {
                 file_path,
                 cache: HashMap::new(),
-            }
+                passphrase,
+            });
+        };
+
+        let decrypted;
+        let bytes = if encryption::is_encrypted(bytes) {
+            let passphrase = passphrase
+                .as_deref()
+                .context("the user cache is encrypted but no passphrase was given")?;
+            decrypted = encryption::decrypt(bytes, passphrase)?;
+            &decrypted
+        } else {
+            bytes
         };
-        Ok(res)
+
+        let config = bincode::config::standard();
+        let (Root { users }, _) =
+            bincode::decode_from_slice(bytes, config).context("could not decode from slice")?;
+
+        let cache: HashMap<_, _> = users
+            .into_iter()
+            .map(|user| (user.email().clone(), user))
+            .collect();
+
+        Ok(Self {
+            file_path,
+            cache,
+            passphrase,
+        })
+    }
+
+    /// The cache as it is stored, encrypted if there is a passphrase
+    fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        let users = self.cache.values().cloned().collect();
+        let root = Root { users };
+
+        let data = bincode::encode_to_vec(&root, bincode::config::standard())
+            .context("could not encode the user cache")?;
+
+        match &self.passphrase {
+            Some(passphrase) => encryption::encrypt(&data, passphrase),
+            None => Ok(data),
+        }
+    }
+
+    /// Write the cache back to its file. The old cache is only replaced once
+    /// the new one is written completely, so a crash cannot lose the users
+    pub fn save(&self) -> anyhow::Result<()> {
+        let file_path = &self.file_path;
+        let data = self.to_bytes()?;
+
+        let mut partial = file_path.clone().into_os_string();
+        partial.push(".partial");
+        let partial = PathBuf::from(partial);
+
+        fs::write(&partial, data)
+            .with_context(|| format!("could not write the user cache to {partial:?}"))?;
+        fs::rename(&partial, file_path)
+            .with_context(|| format!("could not replace the user cache at {file_path:?}"))
+    }
+
+    /// Re-encrypt the cache at `file_path` with a new passphrase. A plain
+    /// cache is encrypted for the first time
+    pub fn rotate_passphrase(file_path: PathBuf) -> anyhow::Result<()> {
+        ensure!(
+            fs::try_exists(&file_path).unwrap_or_default(),
+            "there is no user cache at {file_path:?}"
+        );
+
+        let mut cache = Self::load(file_path, false)?;
+        let new_passphrase = encryption::new_passphrase()?;
+
+        if cache.passphrase.as_ref() == Some(&new_passphrase) {
+            bail!("the new passphrase is the same as the current one");
+        }
+
+        cache.passphrase = Some(new_passphrase);
+        cache.save()?;
+
+        println!("the user cache has been encrypted with the new passphrase");
+        Ok(())
     }
 
     /// Takes a [`CSVUser`] and returns the user's data along with the proxy
@@ -194,12 +362,99 @@ impl UserCache {
     async fn get_or_put(
         &mut self,
         user: &CSVUser,
-        iter: &mut impl Iterator<Item = Option<Proxy>>,
+        proxy: Option<Proxy>,
+        auth: &Authentication,
+    ) -> Option<(MojangClient, Option<Proxy>, OnlineUser)> {
+        match auth {
+            Authentication::Mojang => self.get_or_put_mojang(user, proxy).await,
+            Authentication::Microsoft(endpoints) => {
+                self.get_or_put_microsoft(user, proxy, endpoints).await
+            }
+        }
+    }
+
+    /// Sign `user` in with their Microsoft account. Cached sessions are used
+    /// until they expire and are then refreshed. If that fails the user has
+    /// to sign in again.
+    async fn get_or_put_microsoft(
+        &mut self,
+        user: &CSVUser,
+        proxy: Option<Proxy>,
+        endpoints: &MicrosoftEndpoints,
     ) -> Option<(MojangClient, Option<Proxy>, OnlineUser)> {
+        let mojang = mojang_client(proxy.as_ref(), &user.email)?;
+
+        let cached = match self.cache.get(&user.email) {
+            Some(User::Microsoft(cached)) => Some(cached),
+            _ => None,
+        };
+
+        // still valid for at least a minute
+        if let Some(cached) = cached.filter(|cached| cached.expires_at > time() + 60) {
+            return Some((mojang, proxy, OnlineUser::from(cached)));
+        }
+
+        let refresh_token = cached.map(|cached| cached.refresh_token.clone());
+
+        let session = {
+            let auth = MicrosoftAuth::new(&mojang, endpoints);
+
+            let refreshed = match refresh_token {
+                Some(token) => {
+                    println!("refreshing Microsoft session of {}", user.email);
+                    auth.refresh(&token)
+                        .await
+                        .map_err(|e| println!("failed refreshing {} .. {e:#}", user.email))
+                        .ok()
+                }
+                None => None,
+            };
+
+            match refreshed {
+                Some(session) => Ok(session),
+                None => auth.login(&user.email).await,
+            }
+        };
+
+        match session {
+            Ok(session) => {
+                let microsoft = MicrosoftUser {
+                    email: user.email.clone(),
+                    username: session.username,
+                    uuid: session.uuid,
+                    access_token: session.access_token,
+                    expires_at: time() + session.expires_in,
+                    refresh_token: session.refresh_token,
+                };
+                let online = OnlineUser::from(&microsoft);
+                self.cache
+                    .insert(microsoft.email.clone(), User::Microsoft(microsoft));
+                Some((mojang, proxy, online))
+            }
+            Err(e) => {
+                println!(
+                    "failed Microsoft authentication for {} .. {e:#}",
+                    user.email
+                );
+                None
+            }
+        }
+    }
+
+    /// Authenticate `user` with the legacy Yggdrasil endpoints
+    async fn get_or_put_mojang(
+        &mut self,
+        user: &CSVUser,
+        proxy: Option<Proxy>,
+    ) -> Option<(MojangClient, Option<Proxy>, OnlineUser)> {
+        // signed in with a Microsoft account before. Treat them as new
+        if let Some(User::Microsoft(_)) = self.cache.get(&user.email) {
+            self.cache.remove(&user.email);
+        }
+
         match self.cache.get_mut(&user.email) {
             None => {
-                let proxy = iter.next().unwrap();
-                let mojang = MojangClient::try_from(proxy.as_ref()).unwrap();
+                let mojang = mojang_client(proxy.as_ref(), &user.email)?;
                 match mojang.authenticate(&user.email, &user.password).await {
                     Ok(res) => {
                         let valid_user = OnlineUser {
@@ -233,8 +488,7 @@ impl UserCache {
             Some(cached) => {
                 match cached {
                     User::Valid(valid) => {
-                        let proxy = iter.next().unwrap();
-                        let mojang = MojangClient::try_from(proxy.as_ref()).unwrap();
+                        let mojang = mojang_client(proxy.as_ref(), &user.email)?;
 
                         // if verified in last day don't even check to verify
                         if time() - valid.last_checked < 3600 * 24 {
@@ -287,7 +541,8 @@ impl UserCache {
                             }
                         }
                     }
-                    User::Invalid(_invalid) => {}
+                    // Microsoft users are removed above
+                    User::Invalid(_) | User::Microsoft(_) => {}
                 }
 
                 println!("user {} is cached as invalid. If this user **is** valid, delete cache.db and re-run", user.email);
@@ -296,56 +551,172 @@ impl UserCache {
         }
     }
 
+    /// Validate `users` and yield them as long as the `quota` allows. The
+    /// cache is saved whenever the quota runs out
     pub fn obtain_users(
         mut self,
-        count: usize,
+        quota: Quota,
         users: Vec<CSVUser>,
-        proxies: Vec<Option<Proxy>>,
+        proxies: SharedProxyPool,
+        auth: Authentication,
     ) -> Receiver<BotConnectionData> {
-        let mut proxies = proxies.into_iter().cycle();
-
         let (tx, rx) = tokio::sync::mpsc::channel(32);
 
         // spawn the receiver that will yield players
         tokio::task::spawn_local(async move {
-            let mut local_count = 0;
+            quota.set_accounts(users.len());
+
+            for csv_user in users {
+                if quota.available() == 0 {
+                    if let Err(err) = self.save() {
+                        println!("{err:#}");
+                    }
+                }
+
+                quota.take().await;
+                quota.use_account();
 
-            'user_loop: for csv_user in users {
-                if let Some((mojang, proxy, user)) = self.get_or_put(&csv_user, &mut proxies).await
+                let assigned = proxies.borrow_mut().assign(&csv_user.email);
+                let proxy = match assigned {
+                    Ok(proxy) => proxy,
+                    Err(err) => {
+                        println!("skipping {}: {err}", csv_user.email);
+                        quota.release();
+                        continue;
+                    }
+                };
+
+                if let Some((mojang, proxy, user)) =
+                    self.get_or_put(&csv_user, proxy.clone(), &auth).await
                 {
-                    local_count += 1;
                     println!("valid user {}", user.email);
                     tx.send(BotConnectionData {
                         bot: BotData::Online { user, mojang },
                         proxy,
+                        tags: csv_user.tags(),
                     })
                     .await
                     .unwrap();
                 } else {
                     println!("invalid user {}", csv_user.email);
+                    proxies.borrow_mut().release(proxy.as_ref());
+                    quota.release();
                 }
+            }
 
-                if local_count >= count {
-                    break 'user_loop;
-                }
+            println!("every user in the users file has been used");
+
+            if let Err(err) = self.save() {
+                println!("{err:#}");
             }
+        });
+
+        rx
+    }
+}
 
-            let mut file = OpenOptions::new()
-                .create(true)
-                .append(false)
-                .write(true)
-                .open(&self.file_path)
-                .unwrap();
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, path::PathBuf};
 
-            let users = self.cache.drain().map(|(_, v)| v).collect();
+    use tokio::{runtime::Runtime, task::LocalSet};
 
-            let root = Root { users };
+    use crate::bootstrap::{
+        encryption,
+        microsoft::stand_in,
+        storage::{Authentication, InvalidUser, User, UserCache},
+        CSVUser,
+    };
 
-            let data = bincode::encode_to_vec(&root, bincode::config::standard()).unwrap();
-            file.write_all(&data).unwrap();
-            file.flush().unwrap();
+    #[test]
+    fn test_microsoft_cache() {
+        let rt = Runtime::new().unwrap();
+        LocalSet::new().block_on(&rt, async {
+            let (endpoints, stand_in) = stand_in::start().await;
+            let auth = Authentication::Microsoft(endpoints);
+
+            let mut cache = UserCache {
+                file_path: PathBuf::new(),
+                cache: HashMap::new(),
+                passphrase: None,
+            };
+
+            let user = CSVUser {
+                email: "user@outlook.com".to_string(),
+                password: String::new(),
+                tags: None,
+            };
+
+            // signing in the first time uses the device code
+            let (_, _, online) = cache.get_or_put(&user, None, &auth).await.unwrap();
+            assert_eq!(online.username, "Notch");
+            assert_eq!(online.access_id, "minecraft");
+            assert_eq!(stand_in.polls.get(), 2);
+
+            // the cached session is used
+            cache.get_or_put(&user, None, &auth).await.unwrap();
+            assert_eq!(stand_in.polls.get(), 2);
+            assert_eq!(stand_in.refreshes.get(), 0);
+
+            // an expired session is refreshed
+            let Some(User::Microsoft(cached)) = cache.cache.get_mut(&user.email) else {
+                panic!("user is not cached");
+            };
+            cached.expires_at = 0;
+
+            cache.get_or_put(&user, None, &auth).await.unwrap();
+            assert_eq!(stand_in.polls.get(), 2);
+            assert_eq!(stand_in.refreshes.get(), 1);
+
+            let Some(User::Microsoft(cached)) = cache.cache.get(&user.email) else {
+                panic!("user is not cached");
+            };
+            assert_eq!(cached.refresh_token, "refresh1");
         });
+    }
 
-        rx
+    #[test]
+    fn test_encrypted_cache() {
+        let email = "alt@example.com".to_string();
+        let user = User::Invalid(InvalidUser {
+            email: email.clone(),
+            password: "hunter2".to_string(),
+        });
+
+        let plain = UserCache {
+            file_path: PathBuf::new(),
+            cache: HashMap::from([(email.clone(), user)]),
+            passphrase: None,
+        }
+        .to_bytes()
+        .unwrap();
+        assert!(!encryption::is_encrypted(&plain));
+
+        // an existing plain cache is migrated by saving it with a passphrase
+        let mut cache = UserCache::from_bytes(PathBuf::new(), Some(&plain), None).unwrap();
+        assert!(cache.cache.contains_key(&email));
+        cache.passphrase = Some("passphrase".to_string());
+
+        let encrypted = cache.to_bytes().unwrap();
+        assert!(encryption::is_encrypted(&encrypted));
+        assert!(!encrypted.windows(7).any(|window| window == b"hunter2"));
+
+        assert!(UserCache::from_bytes(PathBuf::new(), Some(&encrypted), None).is_err());
+        assert!(
+            UserCache::from_bytes(PathBuf::new(), Some(&encrypted), Some("wrong".to_string()))
+                .is_err()
+        );
+
+        let cache = UserCache::from_bytes(
+            PathBuf::new(),
+            Some(&encrypted),
+            Some("passphrase".to_string()),
+        )
+        .unwrap();
+
+        let Some(User::Invalid(cached)) = cache.cache.get(&email) else {
+            panic!("user is not cached");
+        };
+        assert_eq!(cached.password, "hunter2");
     }
 }
@@ -0,0 +1,374 @@
+//! A local HTTP API to manage the bots of every server. Every request needs
+//! the header `Authorization: Bearer <token>`.
+//!
+//! - `GET /bots` — the state of every bot
+//! - `POST /bots` — start more bots from the account pool, see [`Spawn`].
+//!   Responds with how many can actually start
+//! - `DELETE /bots/{name}` — disconnect a bot for good
+//! - `POST /commands` — run a command, with the same body as on the websocket
+//! - `GET /blocks/{x}/{y}/{z}` — a block, see [`BlockInfo`]
+//! - `GET /chunks/{x}/{z}` — a chunk column, see [`ChunkInfo`]
+//!
+//! Requests go to the first server unless they have a `server` query
+//! parameter. Blocks and chunks are looked up in the overworld unless there is
+//! a `dimension` parameter. Spawning is the exception: the account pool is
+//! shared, so new bots join every server in turn whatever the `server`.
+
+use std::{collections::HashMap, convert::Infallible, sync::Arc, time::Duration};
+
+use anyhow::Context;
+use hyper::{
+    header::{AUTHORIZATION, CONTENT_TYPE},
+    server::conn::Http,
+    service::service_fn,
+    Body, HeaderMap, Method, Response, StatusCode,
+};
+use interfaces::{
+    types::{BlockLocation, ChunkLocation, Dimension},
+    BlockInfo, ChunkInfo, Command, Event, Spawn, Telemetry,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use tokio::{
+    net::TcpListener,
+    sync::{
+        mpsc::{self, UnboundedReceiver, UnboundedSender},
+        oneshot,
+    },
+};
+
+use crate::{bootstrap::quota::Quota, client::commands};
+
+/// A request the runner of a server answers at the start of a tick
+pub enum Request {
+    /// the state of every bot, including the ones which are reconnecting
+    Bots(oneshot::Sender<Vec<Telemetry>>),
+    /// disconnect the bot with the username without reconnecting it. Replies
+    /// if the bot was connected
+    Disconnect(String, oneshot::Sender<bool>),
+    /// run a command. Replies with the event it is accepted with
+    Command(Command, oneshot::Sender<Event>),
+    Block(Dimension, BlockLocation, oneshot::Sender<Option<BlockInfo>>),
+    Chunk(Dimension, ChunkLocation, oneshot::Sender<Option<ChunkInfo>>),
+}
+
+/// The routes of the API
+#[derive(Debug, PartialEq, Eq)]
+enum Route {
+    Bots,
+    Spawn,
+    Disconnect(String),
+    Command,
+    Block(BlockLocation),
+    Chunk(ChunkLocation),
+}
+
+impl Route {
+    fn parse(method: &Method, path: &str) -> Option<Self> {
+        let segments: Vec<_> = path.trim_matches('/').split('/').collect();
+
+        let route = match (method, segments.as_slice()) {
+            (&Method::GET, ["bots"]) => Self::Bots,
+            (&Method::POST, ["bots"]) => Self::Spawn,
+            (&Method::DELETE, ["bots", name]) => Self::Disconnect((*name).to_string()),
+            (&Method::POST, ["commands"]) => Self::Command,
+            (&Method::GET, ["blocks", x, y, z]) => Self::Block(BlockLocation::new(
+                x.parse().ok()?,
+                y.parse().ok()?,
+                z.parse().ok()?,
+            )),
+            (&Method::GET, ["chunks", x, z]) => {
+                Self::Chunk(ChunkLocation(x.parse().ok()?, z.parse().ok()?))
+            }
+            _ => return None,
+        };
+
+        Some(route)
+    }
+}
+
+/// the value of the query parameter `key`
+fn query_param<'a>(query: Option<&'a str>, key: &str) -> Option<&'a str> {
+    query?
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(k, _)| *k == key)
+        .map(|(_, value)| value)
+}
+
+/// A request which could not be answered
+struct Rejection {
+    status: StatusCode,
+    reason: String,
+}
+
+impl Rejection {
+    fn new(status: StatusCode, reason: impl Into<String>) -> Self {
+        Self {
+            status,
+            reason: reason.into(),
+        }
+    }
+
+    fn into_response(self) -> Response<Body> {
+        let body = serde_json::json!({ "error": self.reason });
+        json(self.status, &body).unwrap_or_else(|rejection| {
+            let mut response = Response::new(Body::from(rejection.reason));
+            *response.status_mut() = rejection.status;
+            response
+        })
+    }
+}
+
+fn json(status: StatusCode, value: &impl Serialize) -> Result<Response<Body>, Rejection> {
+    let body = serde_json::to_string(value)
+        .map_err(|err| Rejection::new(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .map_err(|err| Rejection::new(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))
+}
+
+async fn read_json<T: DeserializeOwned>(body: Body) -> Result<T, Rejection> {
+    let bytes = hyper::body::to_bytes(body)
+        .await
+        .map_err(|err| Rejection::new(StatusCode::BAD_REQUEST, err.to_string()))?;
+
+    serde_json::from_slice(&bytes)
+        .map_err(|err| Rejection::new(StatusCode::BAD_REQUEST, format!("invalid body: {err}")))
+}
+
+/// if `token` is the bearer token of `headers`. Takes as long for every
+/// token of the same length. An empty token never matches
+fn authorized(headers: &HeaderMap, token: &str) -> bool {
+    if token.is_empty() {
+        return false;
+    }
+
+    let given = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let Some(given) = given else { return false };
+
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// send the request made by `request` to `runner` and wait for the reply
+async fn ask<T>(
+    runner: &UnboundedSender<Request>,
+    request: impl FnOnce(oneshot::Sender<T>) -> Request,
+) -> Result<T, Rejection> {
+    let unavailable = || Rejection::new(StatusCode::SERVICE_UNAVAILABLE, "the server stopped");
+
+    let (tx, rx) = oneshot::channel();
+    runner.send(request(tx)).map_err(|_| unavailable())?;
+    rx.await.map_err(|_| unavailable())
+}
+
+/// The state shared by every connection to the API
+struct Api {
+    token: String,
+    /// the requests to the runner of each server
+    runners: HashMap<String, UnboundedSender<Request>>,
+    /// the server of requests without a `server` parameter
+    default: String,
+    quota: Quota,
+}
+
+impl Api {
+    async fn respond(&self, request: hyper::Request<Body>) -> Result<Response<Body>, Rejection> {
+        if !authorized(request.headers(), &self.token) {
+            return Err(Rejection::new(
+                StatusCode::UNAUTHORIZED,
+                "missing or invalid token",
+            ));
+        }
+
+        let (parts, body) = request.into_parts();
+        let query = parts.uri.query();
+
+        let route = Route::parse(&parts.method, parts.uri.path())
+            .ok_or_else(|| Rejection::new(StatusCode::NOT_FOUND, "unknown route"))?;
+
+        let server = query_param(query, "server").unwrap_or(self.default.as_str());
+        let runner = self.runners.get(server).ok_or_else(|| {
+            Rejection::new(StatusCode::NOT_FOUND, format!("unknown server {server}"))
+        })?;
+
+        let dimension = match query_param(query, "dimension") {
+            Some(dimension) => dimension
+                .parse()
+                .map_err(|err| Rejection::new(StatusCode::BAD_REQUEST, err))?,
+            None => Dimension::Overworld,
+        };
+
+        match route {
+            Route::Bots => json(StatusCode::OK, &ask(runner, Request::Bots).await?),
+            // the accounts are handed to every server in turn by the bootstrap
+            Route::Spawn => {
+                let spawn: Spawn = read_json(body).await?;
+                let count = self
+                    .quota
+                    .add(spawn.count)
+                    .map_err(|err| Rejection::new(StatusCode::BAD_REQUEST, format!("{err:#}")))?;
+
+                if count == 0 && spawn.count > 0 {
+                    return Err(Rejection::new(
+                        StatusCode::CONFLICT,
+                        "the account pool has no accounts left",
+                    ));
+                }
+
+                json(StatusCode::ACCEPTED, &Spawn { count })
+            }
+            Route::Disconnect(name) => {
+                if !ask(runner, |reply| Request::Disconnect(name.clone(), reply)).await? {
+                    let reason = format!("{name} is not connected");
+                    return Err(Rejection::new(StatusCode::NOT_FOUND, reason));
+                }
+
+                let mut response = Response::default();
+                *response.status_mut() = StatusCode::NO_CONTENT;
+                Ok(response)
+            }
+            Route::Command => {
+                let value: Value = read_json(body).await?;
+                let command = commands::parse_command(value)
+                    .map_err(|err| Rejection::new(StatusCode::BAD_REQUEST, format!("{err:#}")))?;
+
+                let event = ask(runner, |reply| Request::Command(command, reply)).await?;
+                json(StatusCode::OK, &event)
+            }
+            Route::Block(location) => {
+                let block = ask(runner, |reply| Request::Block(dimension, location, reply))
+                    .await?
+                    .ok_or_else(|| Rejection::new(StatusCode::NOT_FOUND, "the block is unknown"))?;
+                json(StatusCode::OK, &block)
+            }
+            Route::Chunk(location) => {
+                let chunk = ask(runner, |reply| Request::Chunk(dimension, location, reply))
+                    .await?
+                    .ok_or_else(|| Rejection::new(StatusCode::NOT_FOUND, "the chunk is unknown"))?;
+                json(StatusCode::OK, &chunk)
+            }
+        }
+    }
+}
+
+/// Serve the API on `address`, raising `quota` to start more bots. Returns
+/// the requests to the runner of each of `namespaces`
+pub async fn init(
+    address: &str,
+    token: String,
+    namespaces: &[String],
+    quota: Quota,
+) -> anyhow::Result<Vec<UnboundedReceiver<Request>>> {
+    let (senders, receivers): (Vec<_>, Vec<_>) =
+        namespaces.iter().map(|_| mpsc::unbounded_channel()).unzip();
+
+    let api = Arc::new(Api {
+        token,
+        runners: namespaces.iter().cloned().zip(senders).collect(),
+        default: namespaces.first().cloned().unwrap_or_default(),
+        quota,
+    });
+
+    let server = TcpListener::bind(address)
+        .await
+        .with_context(|| format!("could not bind the API to {address}"))?;
+
+    tokio::task::spawn_local(async move {
+        loop {
+            let (stream, _) = match server.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    // e.g. too many open files, which does not clear up at once
+                    println!("error accepting API connection: {e}");
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            };
+
+            let api = api.clone();
+            let service = service_fn(move |request| {
+                let api = api.clone();
+                async move {
+                    let response = api
+                        .respond(request)
+                        .await
+                        .unwrap_or_else(Rejection::into_response);
+                    Ok::<_, Infallible>(response)
+                }
+            });
+
+            tokio::task::spawn_local(async move {
+                if let Err(e) = Http::new().serve_connection(stream, service).await {
+                    println!("error with API connection: {e}");
+                }
+            });
+        }
+    });
+
+    Ok(receivers)
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{header::AUTHORIZATION, HeaderMap, Method};
+    use interfaces::types::{BlockLocation, ChunkLocation};
+
+    use crate::client::api::{authorized, query_param, Route};
+
+    #[test]
+    fn test_routes() {
+        let parse = |method: Method, path: &str| Route::parse(&method, path);
+
+        assert_eq!(parse(Method::GET, "/bots"), Some(Route::Bots));
+        assert_eq!(parse(Method::POST, "/bots/"), Some(Route::Spawn));
+        assert_eq!(
+            parse(Method::DELETE, "/bots/Bot0001"),
+            Some(Route::Disconnect("Bot0001".to_string()))
+        );
+        assert_eq!(
+            parse(Method::GET, "/blocks/1/-64/3"),
+            Some(Route::Block(BlockLocation::new(1, -64, 3)))
+        );
+        assert_eq!(
+            parse(Method::GET, "/chunks/-1/2"),
+            Some(Route::Chunk(ChunkLocation(-1, 2)))
+        );
+
+        assert_eq!(parse(Method::GET, "/blocks/1/2"), None);
+        assert_eq!(parse(Method::GET, "/chunks/a/2"), None);
+        assert_eq!(parse(Method::GET, "/commands"), None);
+
+        let query = Some("server=hub&dimension=nether");
+        assert_eq!(query_param(query, "dimension"), Some("nether"));
+        assert_eq!(query_param(query, "count"), None);
+        assert_eq!(query_param(None, "server"), None);
+    }
+
+    #[test]
+    fn test_authorized() {
+        let headers = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(AUTHORIZATION, value.parse().unwrap());
+            headers
+        };
+
+        assert!(authorized(&headers("Bearer secret"), "secret"));
+        assert!(!authorized(&headers("Bearer secreT"), "secret"));
+        assert!(!authorized(&headers("Bearer secrets"), "secret"));
+        assert!(!authorized(&headers("secret"), "secret"));
+        assert!(!authorized(&HeaderMap::new(), "secret"));
+        assert!(!authorized(&headers("Bearer "), ""));
+    }
+}
@@ -2,38 +2,223 @@ use std::time::Instant;
 
 use crossterm::style::Stylize;
 use float_ord::FloatOrd;
-use interfaces::types::BlockLocation;
+use interfaces::types::{BlockLocation, BlockLocation2D};
 use itertools::Itertools;
 
 use crate::{
     client::{
-        state::{global::GlobalState, local::LocalState},
+        state::{
+            global::{mine_alloc::MinePreference, GlobalState},
+            local::LocalState,
+        },
         tasks::{
-            compound::CompoundTask, eat::EatTask, fall_bucket::FallBucketTask, mine::MineTask,
+            build::Build, compound::CompoundTask, eat::EatTask, fall_bucket::FallBucketTask,
+            lazy_stream::LazyStream, mine::MineTask, mine_region::MineRegion,
             navigate::BlockTravelTask, Task, TaskTrait,
         },
     },
     protocol::{EventQueue, Face, InterfaceOut},
+    storage::anvil::ExportRequest,
     types::Displacement,
 };
 
-/// The current [`Task`] we are trying to achieve. If we are not aiming at
-/// achieving a task, this is [`None`].
+/// How important a task is. Scheduling a task suspends the tasks of a lower
+/// priority until it is done
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// tasks which are given by commands, such as mining or building
+    Background,
+    /// tasks which keep the bot alive, such as eating or bucket falling
+    Urgent,
+}
+
+/// A [`Task`] in the [`ActionState`]
+pub struct ScheduledTask {
+    priority: Priority,
+    /// the type of the task, for debugging
+    name: String,
+    /// the id of the websocket command the task is for
+    command: Option<u64>,
+    /// if another task ran since this one last ticked
+    suspended: bool,
+    task: Task,
+}
+
+/// shorten a type name by removing the module paths, e.g.
+/// `swarm_bot::client::tasks::eat::EatTask` to `EatTask`
+fn short_type_name(name: &str) -> String {
+    name.split_inclusive(['<', '>', ',', ' ', '(', ')', '[', ']', ';', '&'])
+        .map(|part| part.rsplit("::").next().unwrap_or(part))
+        .collect()
+}
+
+/// The [`Task`]s we are trying to achieve, ordered by [`Priority`]. Only the
+/// task with the highest priority runs, and the others resume once it is done.
+/// There is at most one background task. Urgent tasks run in the order they
+/// were scheduled.
 #[derive(Default)]
 pub struct ActionState {
-    /// the task. In the future, this might change to be a priority queue of
-    /// tasks
-    task: Option<Task>,
+    /// the tasks ordered by priority. The last one is running
+    stack: Vec<ScheduledTask>,
+
+    /// websocket commands whose task ended, and if it finished rather than
+    /// being replaced or cleared
+    ended: Vec<(u64, bool)>,
 }
 
 impl ActionState {
-    /// schedule a task
+    /// schedule a background task, replacing the current background task
     pub fn schedule<T: Into<Task>>(&mut self, task: T) {
-        self.task = Some(task.into());
+        self.schedule_with(Priority::Background, task);
+    }
+
+    /// schedule an urgent task, suspending the background task until it is
+    /// done. It runs after the urgent tasks which are already scheduled
+    pub fn schedule_urgent<T: Into<Task>>(&mut self, task: T) {
+        self.schedule_with(Priority::Urgent, task);
     }
+
+    /// schedule a background task for the websocket command `id`
+    pub fn schedule_command<T: Into<Task>>(&mut self, id: u64, task: T) {
+        self.push(Priority::Background, Some(id), task);
+    }
+
+    /// schedule a task. A background task replaces the background task, and
+    /// an urgent task runs after the urgent tasks which are already scheduled
+    pub fn schedule_with<T: Into<Task>>(&mut self, priority: Priority, task: T) {
+        self.push(priority, None, task);
+    }
+
+    fn push<T: Into<Task>>(&mut self, priority: Priority, command: Option<u64>, task: T) {
+        let name = short_type_name(std::any::type_name::<T>());
+        self.resume(ScheduledTask {
+            priority,
+            name,
+            command,
+            suspended: false,
+            task: task.into(),
+        });
+    }
+
+    /// schedule a task which was taken with [`ActionState::take_task`]
+    pub fn resume(&mut self, scheduled: ScheduledTask) {
+        if scheduled.priority == Priority::Background {
+            let ended = &mut self.ended;
+            self.stack.retain(|other| {
+                let replaced = other.priority == Priority::Background;
+                if let (true, Some(id)) = (replaced, other.command) {
+                    ended.push((id, false));
+                }
+                !replaced
+            });
+        }
+
+        // below the tasks of the same priority so they run first
+        let idx = self
+            .stack
+            .partition_point(|other| other.priority < scheduled.priority);
+        self.stack.insert(idx, scheduled);
+
+        // the task which was running is suspended
+        if idx + 1 == self.stack.len() {
+            if let Some(previous) = idx.checked_sub(1).and_then(|idx| self.stack.get_mut(idx)) {
+                previous.suspended = true;
+            }
+        }
+    }
+
     /// clear the task list
     pub fn clear(&mut self) {
-        self.task = None;
+        let commands = self
+            .stack
+            .drain(..)
+            .filter_map(|scheduled| scheduled.command);
+        self.ended.extend(commands.map(|id| (id, false)));
+    }
+
+    /// remove the tasks of the websocket command `id`
+    pub fn cancel(&mut self, id: u64) {
+        self.stack.retain(|scheduled| scheduled.command != Some(id));
+    }
+
+    /// if there are no tasks
+    pub fn is_idle(&self) -> bool {
+        self.stack.is_empty()
+    }
+
+    /// if a task which `is_kind` matches is scheduled, e.g.
+    /// `|task| matches!(task, Task::EatTask(_))`
+    pub fn is_scheduled(&self, is_kind: impl Fn(&Task) -> bool) -> bool {
+        self.stack.iter().any(|scheduled| is_kind(&scheduled.task))
+    }
+
+    /// the websocket command the bot is working on
+    pub fn command(&self) -> Option<u64> {
+        self.stack.iter().find_map(|scheduled| scheduled.command)
+    }
+
+    /// the websocket commands whose task ended since the last call, and if
+    /// the task finished rather than being replaced or cleared
+    pub fn take_ended(&mut self) -> Vec<(u64, bool)> {
+        std::mem::take(&mut self.ended)
+    }
+
+    /// the number of moves left on the path of the running task
+    pub fn path_remaining(&self) -> Option<usize> {
+        self.stack.last()?.task.path_remaining()
+    }
+
+    /// remove the background task and clear the rest. Used to restore it after
+    /// a reconnect, where it resumes as if it was suspended
+    pub fn take_task(&mut self) -> Option<ScheduledTask> {
+        let idx = self
+            .stack
+            .iter()
+            .position(|scheduled| scheduled.priority == Priority::Background);
+
+        let mut task = idx.map(|idx| self.stack.remove(idx));
+        if let Some(task) = task.as_mut() {
+            task.suspended = true;
+        }
+
+        self.stack.clear();
+        task
+    }
+
+    /// the priority and name of every task, the running one first
+    pub fn stack(&self) -> impl Iterator<Item = (Priority, &str)> + '_ {
+        self.stack
+            .iter()
+            .rev()
+            .map(|scheduled| (scheduled.priority, scheduled.name.as_str()))
+    }
+
+    /// tick the running task, removing it once it is done so the next one
+    /// resumes
+    fn tick(
+        &mut self,
+        out: &mut impl InterfaceOut,
+        local: &mut LocalState,
+        global: &mut GlobalState,
+    ) {
+        let Some(current) = self.stack.last_mut() else { return };
+
+        if current.suspended {
+            current.suspended = false;
+            current.task.resume(out, local, global);
+        }
+
+        if current.task.tick(out, local, global) {
+            if let Some(id) = current.command {
+                self.ended.push((id, true));
+            }
+            self.stack.pop();
+        }
+    }
+
+    /// the running task
+    fn current(&mut self) -> Option<&mut Task> {
+        self.stack.last_mut().map(|scheduled| &mut scheduled.task)
     }
 }
 
@@ -53,18 +238,11 @@ pub struct Bot<Queue: EventQueue, Out: InterfaceOut> {
 
 impl<Queue: EventQueue, Out: InterfaceOut> Bot<Queue, Out> {
     pub fn run_sync(&mut self, global: &mut GlobalState) {
-        match self.actions.task.as_mut() {
-            None => {}
-            Some(task) => {
-                if task.tick(&mut self.out, &mut self.state, global) {
-                    self.actions.task = None;
-                }
-            }
-        }
-        let actions = self
-            .state
-            .physics
-            .tick(&mut global.blocks, &self.state.inventory);
+        self.actions.tick(&mut self.out, &mut self.state, global);
+        let actions = self.state.physics.tick(
+            &mut global.blocks[self.state.dimension],
+            &self.state.inventory,
+        );
         let physics = &self.state.physics;
         self.out
             .teleport_and_look(physics.location(), physics.direction(), physics.on_ground());
@@ -151,8 +329,7 @@ pub fn process_command(
         //     // TODO: try to kill themself by fall damage/lava/etc
         // }
         "eat" => {
-            let eat_task = EatTask::default();
-            actions.task = Some(eat_task.into());
+            actions.schedule_urgent(EatTask::default());
         }
         "slot" => {
             if let [number] = args {
@@ -167,7 +344,7 @@ pub fn process_command(
             let fall = FallBucketTask::default();
             let mut compound = CompoundTask::default();
             compound.add(mine).add(fall);
-            actions.schedule(compound);
+            actions.schedule_urgent(compound);
         }
         "drop" => {
             local.inventory.drop_hotbar(out);
@@ -182,7 +359,12 @@ pub fn process_command(
             }
         }
         "stop" => {
-            actions.task = None;
+            actions.clear();
+        }
+        "tasks" => {
+            for (priority, name) in actions.stack() {
+                msg!("{priority:?} {name}");
+            }
         }
         "loc" => {
             msg!(
@@ -198,7 +380,10 @@ pub fn process_command(
                     msg!("on ground {}", local.physics.on_ground());
                     let below_loc =
                         BlockLocation::from(local.physics.location() - Displacement::EPSILON_Y);
-                    msg!("below kind {:?}", global.blocks.get_block_kind(below_loc));
+                    msg!(
+                        "below kind {:?}",
+                        global.blocks[local.dimension].get_block_kind(below_loc)
+                    );
                     msg!("inventory slots {:?}", local.inventory.hotbar());
                 }
             }
@@ -210,7 +395,10 @@ pub fn process_command(
                 let z = c.parse()?;
                 let location = BlockLocation::new(x, y, z);
 
-                msg!("The block is {:?}", global.blocks.get_block(location));
+                msg!(
+                    "The block is {:?}",
+                    global.blocks[local.dimension].get_block(location)
+                );
             }
         }
         "place" => {
@@ -232,17 +420,39 @@ pub fn process_command(
                 out.place_block(location, Face::from(best_loc_idx as u8));
             }
         }
-        // "mine" => {
-        //     let origin = local.physics.location() + Displacement::EYE_HEIGHT;
-        //
-        //     let closest = global.blocks.closest_in_chunk(origin.into(), |state|
-        // state.kind().mineable(&global.block_data));
-        //
-        //     if let Some(closest) = closest {
-        //         let mine_task = MineTask::new(closest, local, global);
-        //         actions.schedule(mine_task);
-        //     }
-        // }
+        "export" => {
+            // every bot reads the command but the world is only exported once
+            let name = args.first().map(ToString::to_string);
+            global.export = Some(ExportRequest { name });
+        }
+        "mine" => {
+            // mine the region between two corners. Every bot reads the command but only the
+            // first one allocates the regions
+            if let [x1, z1, x2, z2] = args {
+                let (x1, z1, x2, z2): (i32, i32, i32, i32) =
+                    (x1.parse()?, z1.parse()?, x2.parse()?, z2.parse()?);
+
+                let from = BlockLocation2D::new(x1.min(x2), z1.min(z2));
+                let to = BlockLocation2D::new(x1.max(x2), z1.max(z2));
+
+                global.mine.mine(from, to, Some(MinePreference::FromDist));
+                actions.schedule(LazyStream::from(MineRegion));
+            }
+        }
+        "build" => {
+            // build a schematic from the schematic directory with its lowest corner at a
+            // location. Every bot reads the command but only the first one loads the
+            // schematic
+            if let [name, x, y, z] = args {
+                let origin = BlockLocation::new(x.parse()?, y.parse()?, z.parse()?);
+
+                if global.build.is_done() {
+                    global.build.build_file(name, origin)?;
+                }
+
+                actions.schedule(LazyStream::from(Build));
+            }
+        }
         _ => {}
     }
 
@@ -256,7 +466,114 @@ pub fn run_threaded(
     global: &GlobalState,
     end_by: Instant,
 ) {
-    if let Some(task) = actions.task.as_mut() {
+    if let Some(task) = actions.current() {
         task.expensive(end_by, local, global);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::client::{
+        bot::{short_type_name, ActionState, Priority},
+        tasks::{
+            delay::DelayTask, eat::EatTask, fall_bucket::FallBucketTask, mine_region::MineRegion,
+            MineRegionTask, Task,
+        },
+    };
+
+    #[test]
+    fn test_short_type_name() {
+        assert_eq!(
+            short_type_name(std::any::type_name::<MineRegionTask>()),
+            "LazyStream<MineRegion>"
+        );
+    }
+
+    #[test]
+    fn test_priorities() {
+        let mut actions = ActionState::default();
+
+        actions.schedule(MineRegionTask::from(MineRegion));
+        actions.schedule_urgent(EatTask::default());
+
+        // the urgent task runs first and the background task is suspended
+        let stack: Vec<_> = actions.stack().collect();
+        assert_eq!(
+            stack,
+            [
+                (Priority::Urgent, "EatTask"),
+                (Priority::Background, "LazyStream<MineRegion>")
+            ]
+        );
+
+        // a new background task replaces the old one without interrupting the
+        // urgent task
+        actions.schedule(DelayTask::new(5));
+        let stack: Vec<_> = actions.stack().collect();
+        assert_eq!(
+            stack,
+            [
+                (Priority::Urgent, "EatTask"),
+                (Priority::Background, "DelayTask")
+            ]
+        );
+
+        // only the background task is kept across reconnects
+        let task = actions.take_task().unwrap();
+        assert_eq!(actions.stack().count(), 0);
+
+        actions.resume(task);
+        let stack: Vec<_> = actions.stack().collect();
+        assert_eq!(stack, [(Priority::Background, "DelayTask")]);
+    }
+
+    #[test]
+    fn test_eat_during_fall_bucket() {
+        let mut actions = ActionState::default();
+
+        actions.schedule(DelayTask::new(5));
+        actions.schedule_urgent(FallBucketTask::default());
+        assert!(!actions.is_scheduled(|task| matches!(task, Task::EatTask(_))));
+
+        // eating waits until the bot has landed rather than replacing the fall
+        actions.schedule_urgent(EatTask::default());
+        assert!(actions.is_scheduled(|task| matches!(task, Task::EatTask(_))));
+
+        let stack: Vec<_> = actions.stack().collect();
+        assert_eq!(
+            stack,
+            [
+                (Priority::Urgent, "FallBucketTask"),
+                (Priority::Urgent, "EatTask"),
+                (Priority::Background, "DelayTask")
+            ]
+        );
+
+        // the suspended background task resumes once both are done
+        assert!(actions.stack.iter().any(|scheduled| scheduled.suspended));
+        assert!(!actions.stack.last().unwrap().suspended);
+    }
+
+    #[test]
+    fn test_commands() {
+        let mut actions = ActionState::default();
+
+        actions.schedule_command(1, DelayTask::new(5));
+        actions.schedule_urgent(EatTask::default());
+        assert_eq!(actions.command(), Some(1));
+
+        // replacing the task of a command ends it without finishing it
+        actions.schedule_command(2, DelayTask::new(5));
+        assert_eq!(actions.take_ended(), [(1, false)]);
+        assert_eq!(actions.command(), Some(2));
+
+        // cancelled commands are removed silently
+        actions.cancel(2);
+        assert_eq!(actions.command(), None);
+        assert!(actions.take_ended().is_empty());
+
+        actions.schedule_command(3, DelayTask::new(5));
+        actions.clear();
+        assert_eq!(actions.take_ended(), [(3, false)]);
+    }
+}
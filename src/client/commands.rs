@@ -1,78 +1,532 @@
-use std::sync::mpsc::{Receiver, Sender};
-use anyhow::{bail, Context};
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{Receiver, Sender},
+    },
+};
 
-use futures::StreamExt;
-use interfaces::CommandData;
+use anyhow::{bail, Context};
+use futures::{SinkExt, StreamExt};
+use interfaces::{
+    Accepted, Cancelled, Command, CommandData, Event, Failed, Finished, Selector, Subscribe,
+};
 use serde_json::Value;
-use tokio::net::{TcpListener, TcpStream};
-use tokio_tungstenite::WebSocketStream;
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::broadcast::{self, error::RecvError},
+};
+use tokio_tungstenite::{
+    tungstenite::{
+        handshake::server::{ErrorResponse, Request, Response},
+        http::StatusCode,
+        Message,
+    },
+    WebSocketStream,
+};
 
 /// commands received over websocket (typically forge mod)
 pub struct CommandReceiver {
-    pub pending: Receiver<CommandData>,
+    pub pending: Receiver<Command>,
+
+    /// events sent to the clients connected to this receiver
+    events: Publisher,
 }
 
-fn process(path: &str, value: Value) -> Option<CommandData> {
-    macro_rules! parse {
-        () => {{
-            serde_json::from_value(value).unwrap()
-        }};
+/// The clients an update is sent to
+#[derive(Clone, Debug)]
+enum Topic {
+    /// clients subscribed to the telemetry of the bot
+    Telemetry(String),
+    /// clients subscribed to world events
+    World,
+}
+
+impl Topic {
+    /// the topic of `event`, or `None` if it is about a command and goes to
+    /// every client
+    fn of(event: &Event) -> Option<Self> {
+        match event {
+            Event::Telemetry(telemetry) => Some(Self::Telemetry(telemetry.bot.clone())),
+            Event::World(_) => Some(Self::World),
+            _ => None,
+        }
     }
 
-    match path {
-        "mine" => Some(CommandData::Mine(parse!())),
-        "goto" => Some(CommandData::GoTo(parse!())),
-        "attack" => Some(CommandData::Attack(parse!())),
+    /// if a client with `subscription` gets events of the topic
+    fn wanted_by(&self, subscription: &Subscribe) -> bool {
+        match self {
+            Self::Telemetry(bot) => {
+                subscription.telemetry
+                    && (subscription.bots.is_empty() || subscription.bots.contains(bot))
+            }
+            Self::World => subscription.world,
+        }
+    }
+}
+
+/// Sends events to the clients of a [`CommandReceiver`]. Can be cloned into
+/// tasks which outlive a tick.
+///
+/// Command events and updates (telemetry and world events) have their own
+/// channels so a client which lags behind the many updates still gets every
+/// command event.
+#[derive(Clone)]
+pub struct Publisher {
+    commands: broadcast::Sender<String>,
+    updates: broadcast::Sender<(Topic, String)>,
+}
+
+impl Publisher {
+    /// the number of events of each channel kept for clients which are behind
+    const CAPACITY: usize = 1024;
 
-        path => {
-            println!("invalid {path}");
-            None
+    fn new() -> Self {
+        Self {
+            commands: broadcast::channel(Self::CAPACITY).0,
+            updates: broadcast::channel(Self::CAPACITY).0,
         }
     }
+
+    /// send `event` to every connected client which wants it
+    pub fn send(&self, event: &Event) {
+        let Ok(text) = serde_json::to_string(event) else { return };
+
+        // there may be no clients connected
+        let _ = match Topic::of(event) {
+            Some(topic) => self.updates.send((topic, text)).map(drop),
+            None => self.commands.send(text).map(drop),
+        };
+    }
+}
+
+/// Work which is split between bots by an allocator in the
+/// [`GlobalState`](crate::client::state::global::GlobalState)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SharedWork {
+    Mine,
+    Build,
 }
 
-async fn command_receiver(tx: Sender<CommandData>, mut ws: WebSocketStream<TcpStream>) -> anyhow::Result<()> {
-    'wloop: while let Some(msg) = ws.next().await {
-        let msg = msg.context("error reading next web socket message (websocket disconnect?)")?;
+/// A command bots are working on
+#[derive(Debug)]
+pub struct Tracked {
+    /// the bots which have not stopped working on the command
+    bots: HashSet<u32>,
 
-        let text = msg.into_text().unwrap();
+    /// if any bot finished its part of the command
+    finished: bool,
 
-        let mut v: Value = match serde_json::from_str(&text) {
-            Ok(v) => v,
-            Err(_e) => continue 'wloop,
+    pub shared: Option<SharedWork>,
+}
+
+/// Tracks the bots working on each websocket command so clients can be told
+/// when it is done
+#[derive(Default, Debug)]
+pub struct CommandTracker {
+    commands: HashMap<u64, Tracked>,
+}
+
+impl CommandTracker {
+    /// `bots` started working on the command `id`. A command no bot works on is
+    /// finished straight away
+    pub fn start(
+        &mut self,
+        id: u64,
+        shared: Option<SharedWork>,
+        bots: HashSet<u32>,
+    ) -> Option<Event> {
+        if bots.is_empty() {
+            return Some(Event::Finished(Finished { id }));
+        }
+
+        let tracked = Tracked {
+            bots,
+            finished: false,
+            shared,
         };
 
-        let Value::Object(map) = &mut v else { bail!("invalid value") };
+        self.commands.insert(id, tracked);
+        None
+    }
+
+    /// `bot_id` stopped working on the command `id`, either because it
+    /// `finished` or because its task was replaced. Once no bot is left the
+    /// command is finished if any bot finished, else cancelled
+    pub fn stopped(&mut self, id: u64, bot_id: u32, finished: bool) -> Option<Event> {
+        let tracked = self.commands.get_mut(&id)?;
+        tracked.bots.remove(&bot_id);
+        tracked.finished |= finished;
+
+        if !tracked.bots.is_empty() {
+            return None;
+        }
+
+        let tracked = self.commands.remove(&id)?;
+        let event = if tracked.finished {
+            Event::Finished(Finished { id })
+        } else {
+            Event::Cancelled(Cancelled { id })
+        };
+
+        Some(event)
+    }
+
+    /// `bot_id` is gone for good and stopped working on every command
+    pub fn remove_bot(&mut self, bot_id: u32) -> Vec<Event> {
+        let ids: Vec<_> = self
+            .commands
+            .iter()
+            .filter(|(_, tracked)| tracked.bots.contains(&bot_id))
+            .map(|(&id, _)| id)
+            .collect();
+
+        ids.into_iter()
+            .filter_map(|id| self.stopped(id, bot_id, false))
+            .collect()
+    }
+
+    /// if bots are working on the command `id`
+    pub fn is_running(&self, id: u64) -> bool {
+        self.commands.contains_key(&id)
+    }
+
+    /// stop tracking the command `id`
+    pub fn cancel(&mut self, id: u64) -> Option<Tracked> {
+        self.commands.remove(&id)
+    }
+
+    /// the work the command `id` is splitting between bots, if it is running
+    pub fn shared(&self, id: u64) -> Option<Option<SharedWork>> {
+        self.commands.get(&id).map(|tracked| tracked.shared)
+    }
+}
+
+/// the id the next command without one gets. Shared by every client so ids
+/// are unique
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// the id of the command `value`, or the next free one if it has none. Ids
+/// given by clients are skipped by the ids given out later
+fn command_id(value: &Value) -> u64 {
+    match value.get("id").and_then(Value::as_u64) {
+        Some(id) => {
+            NEXT_ID.fetch_max(id.saturating_add(1), Ordering::Relaxed);
+            id
+        }
+        None => NEXT_ID.fetch_add(1, Ordering::Relaxed),
+    }
+}
+
+fn process(path: &str, value: Value) -> anyhow::Result<CommandData> {
+    macro_rules! parse {
+        () => {{
+            serde_json::from_value(value).with_context(|| format!("invalid {path} command"))?
+        }};
+    }
+
+    let data = match path {
+        "mine" => CommandData::Mine(parse!()),
+        "goto" => CommandData::GoTo(parse!()),
+        "attack" => CommandData::Attack(parse!()),
+        "build" => CommandData::Build(parse!()),
+        "export" => CommandData::Export(parse!()),
+        "schematic" => CommandData::Schematic(parse!()),
+        "stop" => CommandData::Stop(parse!()),
+        "cancel" | "cancelled" => CommandData::Cancelled(parse!()),
+        path => bail!("invalid {path}"),
+    };
+
+    Ok(data)
+}
+
+/// Parse a command sent by a client. It has the fields of the command along
+/// with its `path` and optionally an `id` and the bots to `select`. Commands
+/// without an id are given the next free one
+pub fn parse_command(value: Value) -> anyhow::Result<Command> {
+    let id = command_id(&value);
+    parse_command_with_id(id, value)
+}
+
+/// parse a command sent by a client, which is tracked by `id`
+fn parse_command_with_id(id: u64, mut value: Value) -> anyhow::Result<Command> {
+    let Value::Object(map) = &mut value else { bail!("invalid value") };
+
+    let Some(Value::String(path)) = map.remove("path") else { bail!("invalid path") };
+
+    // every bot runs commands without a selector
+    let select = match map.remove("select") {
+        Some(select) => serde_json::from_value(select).context("invalid selector")?,
+        None => Selector::default(),
+    };
+
+    let data = process(&path, value)?;
+
+    Ok(Command { id, select, data })
+}
+
+fn to_message(event: &Event) -> Message {
+    Message::Text(serde_json::to_string(event).unwrap_or_default())
+}
+
+/// the reply to a message which is not a valid command
+fn failed(id: u64, err: &anyhow::Error) -> Message {
+    to_message(&Event::Failed(Failed {
+        id,
+        reason: format!("{err:#}"),
+    }))
+}
+
+/// Receive commands from `ws` and send it the events of `publisher`. Messages
+/// which are not valid commands are answered with [`Failed`]
+async fn command_receiver(
+    tx: Sender<Command>,
+    publisher: &Publisher,
+    mut ws: WebSocketStream<TcpStream>,
+) -> anyhow::Result<()> {
+    let mut commands = publisher.commands.subscribe();
+    let mut updates = publisher.updates.subscribe();
+    let mut subscription = Subscribe::default();
+
+    'wloop: loop {
+        tokio::select! {
+            msg = ws.next() => {
+                let Some(msg) = msg else { return Ok(()) };
+                let msg = msg.context("error reading next web socket message (websocket disconnect?)")?;
+
+                let text = match msg {
+                    Message::Text(text) => text,
+                    Message::Close(_) => return Ok(()),
+                    _ => continue 'wloop,
+                };
 
-        let Value::String(path) = map.remove("path").expect("no path elem") else { bail!("invalid path") };
+                let v: Value = match serde_json::from_str(&text).context("invalid json") {
+                    Ok(v) => v,
+                    Err(err) => {
+                        ws.send(failed(command_id(&Value::Null), &err)).await?;
+                        continue 'wloop;
+                    }
+                };
+
+                // subscriptions only concern this client
+                if v.get("path").and_then(Value::as_str) == Some("subscribe") {
+                    let id = command_id(&v);
+                    match serde_json::from_value(v).context("invalid subscription") {
+                        Ok(subscribe) => subscription = subscribe,
+                        Err(err) => ws.send(failed(id, &err)).await?,
+                    }
+                    continue 'wloop;
+                }
+
+                let id = command_id(&v);
+                let command = match parse_command_with_id(id, v) {
+                    Ok(command) => command,
+                    Err(err) => {
+                        ws.send(failed(id, &err)).await?;
+                        continue 'wloop;
+                    }
+                };
+
+                ws.send(to_message(&Event::Accepted(Accepted { id: command.id }))).await?;
 
-        let command = process(&path, v).expect("invalid command");
-        tx.send(command).unwrap();
+                // the runner stopped
+                if tx.send(command).is_err() {
+                    return Ok(());
+                }
+            }
+            event = commands.recv() => {
+                match event {
+                    Ok(text) => ws.send(Message::Text(text)).await?,
+                    Err(RecvError::Lagged(missed)) => {
+                        println!("a websocket client missed {missed} command events");
+                    }
+                    Err(RecvError::Closed) => return Ok(()),
+                }
+            }
+            update = updates.recv() => {
+                match update {
+                    Ok((topic, text)) if topic.wanted_by(&subscription) => {
+                        ws.send(Message::Text(text)).await?;
+                    }
+                    Ok(_) => continue 'wloop,
+                    // the client is too slow to keep up with every update. The
+                    // next telemetry replaces the missed one
+                    Err(RecvError::Lagged(_)) => continue 'wloop,
+                    Err(RecvError::Closed) => return Ok(()),
+                }
+            }
+        }
     }
-    Ok(())
 }
 
 impl CommandReceiver {
-    pub async fn init(port: u16) -> anyhow::Result<Self> {
-        let (tx, rx) = std::sync::mpsc::channel();
+    /// send `event` to every connected client which wants it
+    pub fn send(&self, event: &Event) {
+        self.events.send(event);
+    }
+
+    /// a [`Publisher`] to the clients of this receiver
+    pub fn publisher(&self) -> Publisher {
+        self.events.clone()
+    }
 
-        let server = TcpListener::bind(format!("127.0.0.1:{port}")).await?;
+    /// Listen for websockets on `address`. There is a receiver for each of
+    /// `namespaces`, which a client picks by connecting to `/<namespace>`.
+    /// Clients connecting to `/` use the first namespace
+    pub async fn init(address: &str, namespaces: &[String]) -> anyhow::Result<Vec<Self>> {
+        let (senders, receivers): (Vec<_>, Vec<_>) = namespaces
+            .iter()
+            .map(|_| std::sync::mpsc::channel())
+            .unzip();
+
+        let events: Vec<_> = namespaces.iter().map(|_| Publisher::new()).collect();
+
+        let routes: HashMap<_, _> = namespaces
+            .iter()
+            .cloned()
+            .zip(senders.into_iter().zip(events.iter().cloned()))
+            .collect();
+        let routes = Rc::new(routes);
+        let default = namespaces.first().cloned().unwrap_or_default();
+
+        let server = TcpListener::bind(address)
+            .await
+            .with_context(|| format!("could not bind the websocket to {address}"))?;
 
         tokio::task::spawn_local(async move {
             loop {
                 let (stream, _) = server.accept().await.unwrap();
-                let ws = tokio_tungstenite::accept_async(stream).await.unwrap();
 
-                let tx = tx.clone();
+                let routes = routes.clone();
+                let default = default.clone();
 
                 tokio::task::spawn_local(async move {
-                    if let Err(e) = command_receiver(tx, ws).await {
+                    let mut tx = None;
+
+                    let route = |request: &Request, response: Response| {
+                        let path = request.uri().path().trim_start_matches('/');
+                        let namespace = if path.is_empty() {
+                            default.as_str()
+                        } else {
+                            path
+                        };
+
+                        let Some(route) = routes.get(namespace) else {
+                            let mut response =
+                                ErrorResponse::new(Some(format!("unknown server {namespace}")));
+                            *response.status_mut() = StatusCode::NOT_FOUND;
+                            return Err(response);
+                        };
+
+                        tx = Some(route.clone());
+                        Ok(response)
+                    };
+
+                    let ws = match tokio_tungstenite::accept_hdr_async(stream, route).await {
+                        Ok(ws) => ws,
+                        Err(e) => {
+                            println!("error accepting websocket: {e}");
+                            return;
+                        }
+                    };
+
+                    let Some((tx, events)) = tx else { return };
+
+                    if let Err(e) = command_receiver(tx, &events, ws).await {
                         println!("error with websocket: {e}");
                     }
                 });
             }
         });
 
-        Ok(Self { pending: rx })
+        Ok(receivers
+            .into_iter()
+            .zip(events)
+            .map(|(pending, events)| Self { pending, events })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use interfaces::{CommandData, Event, Finished, WorldEvent};
+    use serde_json::json;
+    use tokio::sync::broadcast::error::TryRecvError;
+
+    use crate::client::commands::{parse_command, CommandTracker, Publisher, SharedWork};
+
+    #[test]
+    fn test_parse_command() {
+        let command = parse_command(json!({
+            "path": "goto",
+            "id": 7,
+            "select": {"tags": ["miners"]},
+            "location": {"x": 1, "y": 2, "z": 3},
+        }))
+        .unwrap();
+
+        assert_eq!(command.id, 7);
+        assert_eq!(command.select.tags, ["miners"]);
+        assert!(matches!(command.data, CommandData::GoTo(_)));
+
+        // commands without an id get a new one
+        let first = parse_command(json!({"path": "stop"})).unwrap();
+        let second = parse_command(json!({"path": "stop"})).unwrap();
+        assert_ne!(first.id, second.id);
+        assert!(first.select.names.is_empty());
+
+        // ids given out later skip the ids of clients
+        parse_command(json!({"path": "stop", "id": 1_000_000})).unwrap();
+        assert!(parse_command(json!({"path": "stop"})).unwrap().id > 1_000_000);
+
+        assert!(parse_command(json!({"path": "goto"})).is_err());
+        assert!(parse_command(json!({"path": "unknown"})).is_err());
+        assert!(parse_command(json!({"location": {"x": 1, "y": 2, "z": 3}})).is_err());
+    }
+
+    #[test]
+    fn test_tracker() {
+        let mut tracker = CommandTracker::default();
+
+        // a command no bot works on is done straight away
+        let event = tracker.start(1, None, [].into());
+        assert!(matches!(event, Some(Event::Finished(f)) if f.id == 1));
+
+        assert!(tracker
+            .start(2, Some(SharedWork::Mine), [0, 1].into())
+            .is_none());
+        assert_eq!(tracker.shared(2), Some(Some(SharedWork::Mine)));
+        assert!(tracker.is_running(2));
+
+        // finished once the last bot stops if any bot finished
+        assert!(tracker.stopped(2, 0, true).is_none());
+        let event = tracker.stopped(2, 1, false);
+        assert!(matches!(event, Some(Event::Finished(f)) if f.id == 2));
+        assert_eq!(tracker.shared(2), None);
+
+        // cancelled if every bot's task was replaced or the bot left
+        assert!(tracker.start(3, None, [0, 1].into()).is_none());
+        assert!(tracker.stopped(3, 0, false).is_none());
+        let events = tracker.remove_bot(1);
+        assert!(matches!(events[..], [Event::Cancelled(ref c)] if c.id == 3));
+    }
+
+    #[test]
+    fn test_publisher() {
+        let publisher = Publisher::new();
+        let mut commands = publisher.commands.subscribe();
+        let mut updates = publisher.updates.subscribe();
+
+        publisher.send(&Event::Finished(Finished { id: 1 }));
+        for _ in 0..=Publisher::CAPACITY {
+            publisher.send(&Event::World(WorldEvent::PlayerJoined {
+                name: "Bot0000".to_string(),
+            }));
+        }
+
+        // a client which lags behind the updates still gets every command event
+        assert!(matches!(updates.try_recv(), Err(TryRecvError::Lagged(1))));
+        assert!(commands.try_recv().unwrap().contains("finished"));
     }
 }
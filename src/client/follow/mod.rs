@@ -167,6 +167,11 @@ impl Follower {
         self.ticks = 0;
     }
 
+    /// the number of locations we still need to reach
+    pub fn remaining(&self) -> usize {
+        self.xs.len()
+    }
+
     /// if we should recalcualte the path
     pub fn should_recalc(&mut self) -> bool {
         // we should only recalc if this is not complete
@@ -364,12 +369,12 @@ mod tests {
         let mut local_state = LocalState::mock();
         let mut global_state = GlobalState::init();
 
-        global_state.blocks.paste(&course);
+        global_state.blocks[local_state.dimension].paste(&course);
 
         let start = BlockLocation::new(-162, 82, -357);
         let end = BlockLocation::new(-152, 80, -338);
 
-        let world = &global_state.blocks;
+        let world = &global_state.blocks[local_state.dimension];
         let start_below = world
             .get_block(start.below())
             .context("could not get below block")?
@@ -407,9 +412,10 @@ mod tests {
         while let Result::InProgress =
             follower.follow_iteration(&mut local_state, &mut global_state)
         {
-            local_state
-                .physics
-                .tick(&mut global_state.blocks, &PlayerInventory::default());
+            local_state.physics.tick(
+                &mut global_state.blocks[local_state.dimension],
+                &PlayerInventory::default(),
+            );
             assert!(
                 local_state.physics.location().y > 79.0,
                 "the player fell... location was {}",
@@ -437,7 +443,7 @@ mod tests {
         let start = BlockLocation::new(0, 1, 0);
         let end = BlockLocation::new(950, 1, 950);
 
-        let world = &mut global_state.blocks;
+        let world = &mut global_state.blocks[local_state.dimension];
         world.set_random_floor();
 
         let mut problem = TravelProblem::navigate_block(start, end);
@@ -470,9 +476,10 @@ mod tests {
         while let Result::InProgress =
             follower.follow_iteration(&mut local_state, &mut global_state)
         {
-            local_state
-                .physics
-                .tick(&mut global_state.blocks, &local_state.inventory);
+            local_state.physics.tick(
+                &mut global_state.blocks[local_state.dimension],
+                &local_state.inventory,
+            );
             assert!(
                 local_state.physics.location().y >= 0.0,
                 "the player fell... location was {} front was {:?} left {}",
@@ -1,11 +1,14 @@
 //! Everything related to a client
+pub mod api;
 pub mod bot;
-mod commands;
+pub mod commands;
 mod follow;
 pub mod pathfind;
 pub mod physics;
 pub mod processor;
+pub mod reconnect;
 pub mod runner;
+pub mod select;
 pub mod state;
 mod tasks;
 mod timing;
@@ -1,12 +1,17 @@
 //! global context information that is applicable to all bots
-use std::hash::{Hash, Hasher};
+use std::{
+    collections::HashMap,
+    hash::{Hash, Hasher},
+};
 
 use interfaces::types::BlockLocation;
+use serde::{Deserialize, Serialize};
 
 use crate::{client::pathfind::incremental::Node, storage::blocks::WorldBlocks};
 
 /// The costs of doing everything (used for pathfinding)
-#[derive(Clone)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Costs {
     /// cost to walk one block
     pub block_walk: f64,
@@ -36,7 +41,25 @@ pub struct Costs {
     pub fall: f64,
 }
 
+impl Default for Costs {
+    fn default() -> Self {
+        Self {
+            block_walk: 1.0,
+            block_parkour: 1.5,
+            mine_unrelated: 20.0,
+            ascend: 1.0,
+            no_breathe_mult: 3.0,
+            fall: 1.0,
+            place_unrelated: 20.0,
+            mine_required: 1.0,
+            place_required: 1.0,
+        }
+    }
+}
+
 /// The configuration for finding paths
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct PathConfig {
     /// The [`Costs`] config
     pub costs: Costs,
@@ -47,22 +70,36 @@ pub struct PathConfig {
 impl Default for PathConfig {
     fn default() -> Self {
         Self {
-            costs: Costs {
-                block_walk: 1.0,
-                block_parkour: 1.5,
-                mine_unrelated: 20.0,
-                ascend: 1.0,
-                no_breathe_mult: 3.0,
-                fall: 1.0,
-                place_unrelated: 20.0,
-                mine_required: 1.0,
-                place_required: 1.0,
-            },
+            costs: Costs::default(),
             parkour: true,
         }
     }
 }
 
+/// The [`PathConfig`] of every bot. Bots can be put in groups which find
+/// paths differently
+#[derive(Clone, Default)]
+pub struct PathConfigs {
+    /// used by bots which are not in a group
+    pub default: PathConfig,
+
+    /// the config of each group
+    pub groups: HashMap<String, PathConfig>,
+
+    /// the group of each bot, by username
+    pub members: HashMap<String, String>,
+}
+
+impl PathConfigs {
+    /// the config the bot with `username` finds paths with
+    pub fn get(&self, username: &str) -> &PathConfig {
+        self.members
+            .get(username)
+            .and_then(|group| self.groups.get(group))
+            .unwrap_or(&self.default)
+    }
+}
+
 /// The global context for path traversal
 #[derive(Clone)]
 pub struct GlobalContext<'a> {
@@ -67,12 +67,12 @@ impl<H: Heuristic<MoveNode> + Send + Sync, G: GoalCheck<MoveNode> + Send + Sync>
     fn iterate_until(
         &mut self,
         end_at: Instant,
-        _: &mut LocalState,
+        local: &mut LocalState,
         global: &GlobalState,
     ) -> Increment<PathResult<MoveRecord>> {
         let ctx = GlobalContext {
-            path_config: &global.travel_config,
-            world: &global.blocks,
+            path_config: global.travel_config.get(&local.info.username),
+            world: &global.blocks[local.dimension],
         };
         let progressor = GenericProgressor { ctx };
         self.a_star
@@ -9,12 +9,15 @@ use std::{
     time::Instant,
 };
 
-use crate::client::{
-    pathfind::{
-        traits::{GoalCheck, Heuristic, Progression, Progressor},
-        MinHeapNode,
+use crate::{
+    client::{
+        pathfind::{
+            traits::{GoalCheck, Heuristic, Progression, Progressor},
+            MinHeapNode,
+        },
+        timing::Increment,
     },
-    timing::Increment,
+    metrics::metrics,
 };
 
 /// credit baritone
@@ -213,10 +216,14 @@ impl<T: Node> AStar<T> {
     ) -> Increment<PathResult<T::Record>> {
         let iter_start = Instant::now();
 
+        // counted once at the end as many threads run A* at the same time
+        let mut expanded = 0;
+
         loop {
             let now = Instant::now();
 
             if now >= end_at {
+                metrics().astar_nodes.inc_by(expanded);
                 let iter_duration = now.duration_since(iter_start);
                 let state = self.state.as_mut().unwrap();
                 let dur = &mut state.total_duration_ms;
@@ -229,8 +236,11 @@ impl<T: Node> AStar<T> {
                 };
             }
 
+            expanded += 1;
+
             match self.iterate(heuristic, progressor, goal_check) {
                 Increment::Finished(res) => {
+                    metrics().astar_nodes.inc_by(expanded);
                     return Increment::Finished(res);
                 }
                 Increment::InProgress => {}
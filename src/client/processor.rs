@@ -1,4 +1,12 @@
-use interfaces::types::{BlockLocation, BlockState, ChunkLocation};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use interfaces::{
+    types::{BlockLocation, BlockState, ChunkLocation},
+    Selector, WorldEvent,
+};
 
 use crate::{
     client::{
@@ -7,7 +15,7 @@ use crate::{
             global::{world_players::Player, GlobalState},
             local::{inventory::ItemStack, LocalState},
         },
-        tasks::eat::EatTask,
+        tasks::{eat::EatTask, Task},
     },
     protocol::InterfaceOut,
     storage::{chunk::Column, entities::EntityKind},
@@ -16,6 +24,7 @@ use crate::{
 
 pub trait InterfaceIn {
     fn on_chat(&mut self, message: Chat);
+    fn on_death_message(&mut self, message: String);
     fn on_pickup_item(&mut self, idx: usize, item: ItemStack);
     fn on_lose_item(&mut self, idx: usize);
     fn on_death(&mut self);
@@ -24,6 +33,7 @@ pub trait InterfaceIn {
     fn on_join(&mut self);
     fn on_move(&mut self, location: Location);
     fn on_recv_chunk(&mut self, location: ChunkLocation, column: Column, new: bool);
+    fn on_unload_chunk(&mut self, location: ChunkLocation);
     fn on_entity_move(&mut self, id: u32, location: LocationOrigin);
     fn on_block_change(&mut self, location: BlockLocation, state: BlockState);
     fn on_entity_destroy(&mut self, id: u32);
@@ -55,14 +65,70 @@ impl<'a, I: InterfaceOut> SimpleInterfaceIn<'a, I> {
             out,
         }
     }
+
+    /// if `selector` of a chat command sent by `player` picks this bot. The
+    /// nearest bots are the ones nearest to the player
+    fn selects_us(&mut self, player: &str, selector: &Selector, seed: u64) -> anyhow::Result<bool> {
+        if selector == &Selector::default() {
+            return Ok(true);
+        }
+
+        let uuid = self
+            .global
+            .players
+            .by_name(player)
+            .map(|player| player.uuid);
+        let entities = &self.global.entities[self.local.dimension];
+        let origin = uuid
+            .and_then(|uuid| entities.by_player_uuid(uuid))
+            .and_then(|entity_id| entities.by_id(entity_id))
+            .map(|entity| entity.location);
+
+        let selected = self.global.roster.select(selector, origin, seed)?;
+        Ok(selected.contains(&self.local.bot_id))
+    }
 }
 
 impl<'a, I: InterfaceOut> InterfaceIn for SimpleInterfaceIn<'a, I> {
     fn on_chat(&mut self, message: Chat) {
         println!("{}", message.clone().colorize());
 
+        let player_message = message.player_message();
+        let player_dm = message.player_dm();
+
+        // every bot hears public messages but only one of them reports them
+        if player_dm.is_some() || self.global.roster.first() == Some(self.local.bot_id) {
+            let from = player_message
+                .as_ref()
+                .or(player_dm.as_ref())
+                .map(|msg| msg.player.clone());
+            let to = player_dm
+                .is_some()
+                .then(|| self.local.info.username.clone());
+
+            self.global.world_events.push(WorldEvent::Chat {
+                from,
+                to,
+                message: message.plain(),
+            });
+        }
+
         let mut process = |msg: PlayerMessage| {
+            // every bot hearing the message picks the same bots from the same seed
+            let mut hasher = DefaultHasher::new();
+            msg.message.hash(&mut hasher);
+            let seed = hasher.finish();
+
             if let Some(cmd) = msg.into_cmd() {
+                match self.selects_us(&cmd.player, &cmd.selector, seed) {
+                    Ok(true) => {}
+                    Ok(false) => return,
+                    Err(err) => {
+                        println!("could not select bots for {}. Reason: {err:#}", cmd.command);
+                        return;
+                    }
+                }
+
                 let name = cmd.command;
                 let args_str: Vec<&str> =
                     cmd.args.iter().map(std::string::String::as_str).collect();
@@ -79,13 +145,17 @@ impl<'a, I: InterfaceOut> InterfaceIn for SimpleInterfaceIn<'a, I> {
             }
         };
 
-        if let Some(msg) = message.player_message() {
+        if let Some(msg) = player_message {
             process(msg);
-        } else if let Some(msg) = message.player_dm() {
+        } else if let Some(msg) = player_dm {
             process(msg);
         }
     }
 
+    fn on_death_message(&mut self, message: String) {
+        self.local.death_message = Some(message);
+    }
+
     fn on_pickup_item(&mut self, idx: usize, item: ItemStack) {
         self.local.inventory.add(idx, item);
     }
@@ -95,6 +165,11 @@ impl<'a, I: InterfaceOut> InterfaceIn for SimpleInterfaceIn<'a, I> {
     }
 
     fn on_death(&mut self) {
+        self.global.world_events.push(WorldEvent::BotDied {
+            bot: self.local.info.username.clone(),
+            message: self.local.death_message.take(),
+        });
+
         self.actions.clear();
         self.out.respawn();
     }
@@ -105,20 +180,25 @@ impl<'a, I: InterfaceOut> InterfaceIn for SimpleInterfaceIn<'a, I> {
 
         println!("updated health {health} food is {food}");
 
-        // we should probably eat something
-        if food < 10 {
-            // if we could switch to food
-            if self
-                .local
-                .inventory
-                .switch_food(&self.global.block_data, self.out)
-            {
-                self.actions.schedule(EatTask::default());
-            }
+        // we should probably eat something. The task switches to the food once
+        // it runs
+        let eating = self
+            .actions
+            .is_scheduled(|task| matches!(task, Task::EatTask(_)));
+        if food < 10 && !eating && self.local.inventory.has_food(&self.global.block_data) {
+            self.actions.schedule_urgent(EatTask::default());
         }
     }
 
     fn on_dimension_change(&mut self, dimension: Dimension) {
+        let previous = self.local.dimension;
+        if previous != dimension {
+            // we no longer receive updates for the dimension we left
+            let bot_id = self.local.bot_id;
+            self.global.blocks[previous].release_all(bot_id);
+            self.global.entities[previous].release_all(bot_id);
+        }
+
         self.local.dimension = dimension;
     }
 
@@ -133,45 +213,68 @@ impl<'a, I: InterfaceOut> InterfaceIn for SimpleInterfaceIn<'a, I> {
     }
 
     fn on_recv_chunk(&mut self, location: ChunkLocation, column: Column, new: bool) {
+        let blocks = &mut self.global.blocks[self.local.dimension];
         if new {
-            self.global.blocks.add_column(location, column);
+            blocks.add_column(location, column);
         } else {
-            self.global.blocks.modify_column(location, column);
+            blocks.modify_column(location, column);
         }
+        blocks.hold(location, self.local.bot_id);
+    }
+
+    fn on_unload_chunk(&mut self, location: ChunkLocation) {
+        self.global.blocks[self.local.dimension].release(location, self.local.bot_id);
     }
 
     fn on_entity_move(&mut self, id: u32, location: LocationOrigin) {
-        self.global
-            .entities
-            .update_entity(id, self.local.bot_id, location);
+        self.global.entities[self.local.dimension].update_entity(id, self.local.bot_id, location);
     }
 
     fn on_block_change(&mut self, location: BlockLocation, state: BlockState) {
-        self.global.blocks.set_block(location, state);
+        self.global.blocks[self.local.dimension].set_block(location, state);
     }
 
     fn on_entity_destroy(&mut self, id: u32) {
-        self.global.entities.remove_entity(id, self.local.bot_id);
+        self.global.entities[self.local.dimension].remove_entity(id, self.local.bot_id);
     }
 
     fn on_entity_spawn(&mut self, id: u32, location: Location, kind: EntityKind) {
-        self.global
-            .entities
-            .put_entity(id, self.local.bot_id, location, kind);
+        self.global.entities[self.local.dimension].put_entity(
+            id,
+            self.local.bot_id,
+            location,
+            kind,
+        );
     }
 
     fn on_player_join(&mut self, uuid: u128, name: String) {
+        // every bot is told about the same players
+        if self.global.players.by_uuid(uuid).is_some() {
+            return;
+        }
+
+        let event = WorldEvent::PlayerJoined { name: name.clone() };
+        self.global.world_events.push(event);
         self.global.players.add(Player { name, uuid });
     }
 
     fn on_player_leave(&mut self, uuid: u128) {
+        let Some(player) = self.global.players.by_uuid(uuid) else { return };
+
+        let event = WorldEvent::PlayerLeft {
+            name: player.name.clone(),
+        };
+        self.global.world_events.push(event);
         self.global.players.remove(uuid);
     }
 
     fn on_disconnect(&mut self, reason: &str) {
         println!("disconnecting because {reason}");
         self.local.disconnected = true;
+        self.local.disconnect_reason = Some(reason.to_string());
     }
 
-    fn on_socket_close(&mut self) {}
+    fn on_socket_close(&mut self) {
+        self.local.disconnected = true;
+    }
 }
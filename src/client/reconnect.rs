@@ -0,0 +1,124 @@
+//! Logging bots back in after they are disconnected
+
+use std::time::{Duration, Instant};
+
+use crate::{
+    bootstrap::{storage::BotConnectionData, Address},
+    client::bot::ScheduledTask,
+};
+
+/// When and how often disconnected bots are logged back in
+#[derive(Clone, Debug)]
+pub struct ReconnectPolicy {
+    /// the delay before the first attempt. It doubles after every attempt
+    pub initial_delay: Duration,
+
+    /// the longest delay between two attempts
+    pub max_delay: Duration,
+
+    /// the number of attempts in a row before giving up on a bot. `0` disables
+    /// reconnecting
+    pub max_attempts: u32,
+
+    /// a bot which stays online for this long has its attempts reset
+    pub stable_after: Duration,
+
+    /// bots are never reconnected if their disconnect reason contains one of
+    /// these (case insensitive)
+    pub never_on: Vec<String>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(5),
+            max_delay: Duration::from_secs(5 * 60),
+            max_attempts: 5,
+            stable_after: Duration::from_secs(60),
+            never_on: vec!["banned".to_string()],
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// the delay before attempt number `attempt` (starting at 0)
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1_u32.checked_shl(attempt).unwrap_or(u32::MAX);
+        self.initial_delay
+            .checked_mul(factor)
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay))
+    }
+
+    /// if a bot disconnected because of `reason` should be logged back in
+    pub fn allows(&self, reason: &str) -> bool {
+        let reason = reason.to_lowercase();
+        !self
+            .never_on
+            .iter()
+            .any(|pattern| reason.contains(&pattern.to_lowercase()))
+    }
+}
+
+/// Everything needed to log a bot back in
+pub struct Rejoin {
+    /// the user and proxy the bot logged in with
+    pub data: BotConnectionData,
+
+    /// the server the bot logged into
+    pub address: Address,
+
+    /// the id the bot had. Bots keep their id across reconnects
+    pub bot_id: Option<u32>,
+
+    /// the task the bot was running when it disconnected
+    pub task: Option<ScheduledTask>,
+
+    /// failed or unstable attempts in a row
+    pub attempt: u32,
+
+    /// when the bot last joined
+    pub joined: Instant,
+}
+
+impl Rejoin {
+    pub fn new(data: BotConnectionData, address: Address) -> Self {
+        Self {
+            data,
+            address,
+            bot_id: None,
+            task: None,
+            attempt: 0,
+            joined: Instant::now(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::client::reconnect::ReconnectPolicy;
+
+    #[test]
+    fn test_delay() {
+        let policy = ReconnectPolicy {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(10),
+            ..ReconnectPolicy::default()
+        };
+
+        assert_eq!(policy.delay(0), Duration::from_secs(1));
+        assert_eq!(policy.delay(1), Duration::from_secs(2));
+        assert_eq!(policy.delay(3), Duration::from_secs(8));
+        assert_eq!(policy.delay(4), Duration::from_secs(10));
+        assert_eq!(policy.delay(100), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_allows() {
+        let policy = ReconnectPolicy::default();
+        assert!(policy.allows("Server closed"));
+        assert!(policy.allows(""));
+        assert!(!policy.allows(r#"{"text":"You are BANNED from this server"}"#));
+    }
+}
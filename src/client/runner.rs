@@ -1,33 +1,61 @@
 use std::{
     cell::RefCell,
+    collections::{HashMap, HashSet},
     default::default,
+    fs::File,
+    path::PathBuf,
     rc::Rc,
     sync::Arc,
     time::{Duration, Instant},
 };
 
 use anyhow::Context;
-use tokio::sync::Notify;
+use interfaces::{
+    types::{BlockApprox, BlockLocation, ChunkLocation, Selection2D, Selection3D},
+    Accepted, Attack, BlockInfo, Build, Cancelled, ChunkInfo, Command, CommandData, Connection,
+    Event, Export, Failed, Finished, GoTo, Progress, Schematic, Selector, Stop, Telemetry,
+    WorldEvent,
+};
+use prometheus::IntGauge;
+use tokio::{
+    sync::{mpsc::UnboundedReceiver, Notify},
+    task::JoinHandle,
+};
 use tokio_stream::{Stream, StreamExt};
-use interfaces::{Attack, CommandData, GoTo};
-use interfaces::types::Selection2D;
 
 use crate::{
-    bootstrap::BotConnection,
+    bootstrap::{
+        obtain_connection, proxy_pool::SharedProxyPool, storage::BotConnectionData, BotConnection,
+    },
     client::{
+        api,
         bot::{run_threaded, ActionState, Bot},
-        commands::{CommandReceiver},
+        commands::{CommandReceiver, CommandTracker, SharedWork},
+        pathfind::context::PathConfigs,
         processor::SimpleInterfaceIn,
+        reconnect::{ReconnectPolicy, Rejoin},
+        select::{Member, Roster},
         state::{
-            global::{mine_alloc::MinePreference, GlobalState},
+            global::{
+                build_alloc::BuildAlloc,
+                mine_alloc::{MineAlloc, MinePreference},
+                GlobalState,
+            },
             local::LocalState,
         },
         tasks::{
-            attack_entity::AttackEntity, lazy_stream::LazyStream, mine_region::MineRegion,
+            attack_entity::AttackEntity, build, lazy_stream::LazyStream, mine_region::MineRegion,
             navigate::BlockTravelTask,
         },
     },
+    metrics::metrics,
     protocol::{EventQueue, Login, Minecraft},
+    schematic,
+    storage::{
+        anvil,
+        persist::{self, WorldSave},
+    },
+    types::Dimension,
 };
 
 /// A wrapper around a raw pointer of [`GlobalState`] that is sync
@@ -61,7 +89,13 @@ unsafe impl Sync for SyncLocal {}
 /// TODO
 unsafe impl Send for SyncLocal {}
 
-pub type Logins<T> = Rc<RefCell<Vec<Login<<T as Minecraft>::Queue, <T as Minecraft>::Interface>>>>;
+/// a finished login along with what is needed to log the bot back in later
+pub type PendingLogin<T> = (
+    Login<<T as Minecraft>::Queue, <T as Minecraft>::Interface>,
+    Rejoin,
+);
+
+pub type Logins<T> = Rc<RefCell<Vec<PendingLogin<T>>>>;
 
 /// Runs the game loop and holds all bots.
 pub struct Runner<T: Minecraft> {
@@ -73,51 +107,199 @@ pub struct Runner<T: Minecraft> {
 
     command_receiver: CommandReceiver,
 
+    /// requests from the management API, if it is served
+    api: Option<UnboundedReceiver<api::Request>>,
+
+    /// the bots working on each websocket command
+    tracker: CommandTracker,
+
+    /// when progress was last sent to websocket clients
+    last_progress: Instant,
+
+    /// when telemetry was last sent to websocket clients
+    last_telemetry: Instant,
+
+    /// when the metrics of the world were last updated
+    last_metrics: Instant,
+
+    /// when the memory of the world was last checked against
+    /// `max_world_memory`
+    last_eviction: Instant,
+
+    /// the bots which are logging in or waiting to reconnect
+    pending: IntGauge,
+
+    /// the last state of each bot which is reconnecting, by bot id
+    away: Rc<RefCell<HashMap<u32, Telemetry>>>,
+
+    /// the ids of bots which were given up on while reconnecting. Their
+    /// commands are ended on the next tick
+    given_up: Rc<RefCell<Vec<u32>>>,
+
     /// the bots created by pending logins
     bots: Vec<Bot<T::Queue, T::Interface>>,
 
     /// An id counter that increases for each bot. Used as a unique identifier.
     id_on: u32,
+
+    /// the directory packet captures are written to, if capturing
+    capture_dir: Option<PathBuf>,
+
+    /// the most memory (in bytes) chunk columns may use
+    max_world_memory: Option<usize>,
+
+    /// if the columns bots hold alone use more than `max_world_memory`
+    over_world_memory: bool,
+
+    /// how disconnected bots are logged back in
+    reconnect: ReconnectPolicy,
+
+    /// what is needed to log each bot back in, by bot id
+    rejoins: HashMap<u32, Rejoin>,
+
+    /// the proxies bots connect through
+    proxies: SharedProxyPool,
+
+    /// where the world is saved, if it is
+    world_save: Option<WorldSave>,
+
+    /// the save of the world which is being written, if any
+    saving: Option<JoinHandle<()>>,
+
+    /// the name of the server, as it was given
+    server: String,
+
+    /// the directory worlds are exported to
+    export_dir: PathBuf,
 }
 
 /// Runner launch options
 pub struct RunnerOptions {
     /// The amount of milliseconds to wait between logging in successive users
     pub delay_ms: u64,
-    /// the websocket port
-    pub ws_port: u16,
+    /// commands sent to the bots of this runner
+    pub commands: CommandReceiver,
+    /// requests from the management API, if it is served
+    pub api: Option<UnboundedReceiver<api::Request>>,
+    /// how each group of bots finds paths
+    pub travel_config: PathConfigs,
+    /// if set, the packets each bot receives are captured to
+    /// `<capture_dir>/<username>.cap`
+    pub capture_dir: Option<PathBuf>,
+    /// if set, chunk columns far away from all bots are evicted once the world
+    /// uses more than this many bytes
+    pub max_world_memory: Option<usize>,
+    /// how disconnected bots are logged back in
+    pub reconnect: ReconnectPolicy,
+    /// the proxies bots connect through
+    pub proxies: SharedProxyPool,
+    /// if set, the world is loaded from and periodically saved to here
+    pub world_save: Option<WorldSave>,
+    /// the name of the server, as it was given
+    pub server: String,
+    /// the directory worlds are exported to
+    pub export_dir: PathBuf,
+    /// the directory schematics are built from
+    pub schematic_dir: PathBuf,
 }
 
 impl<T: Minecraft + 'static> Runner<T> {
+    /// how often websocket clients are told how far along bots are
+    const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// how often websocket clients are sent the state of bots
+    const TELEMETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// how often the metrics of the world are updated. Measuring the memory
+    /// of the world goes through every column
+    const METRICS_INTERVAL: Duration = Duration::from_secs(5);
+
+    /// how often the memory of the world is checked against the limit, which
+    /// also goes through every column
+    const EVICTION_INTERVAL: Duration = Duration::from_secs(2);
+
     /// Start the runner process
     pub async fn run(
         connections: impl Stream<Item = BotConnection> + 'static,
         opts: RunnerOptions,
     ) -> anyhow::Result<()> {
-        let mut runner = Self::init(connections, opts).await?;
+        let mut runner = Self::init(connections, opts)?;
         runner.game_loop().await;
         Ok(())
     }
 
     /// Initialize the runner. Go through the handshake process for each
     /// [`Connection`]
-    async fn init(
+    fn init(
         connections: impl Stream<Item = BotConnection> + 'static,
         opts: RunnerOptions,
     ) -> anyhow::Result<Self> {
-        let RunnerOptions { delay_ms, ws_port } = opts;
+        let RunnerOptions {
+            delay_ms,
+            commands,
+            api,
+            travel_config,
+            capture_dir,
+            max_world_memory,
+            reconnect,
+            proxies,
+            world_save,
+            server,
+            export_dir,
+            schematic_dir,
+        } = opts;
+
+        if let Some(dir) = &capture_dir {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("could not create capture directory {}", dir.display()))?;
+        }
+
+        let mut global_state = GlobalState {
+            travel_config,
+            build: BuildAlloc::new(schematic_dir),
+            ..GlobalState::init()
+        };
 
-        // commands received over websocket (typically forge mod)
-        let commands = CommandReceiver::init(ws_port).await?;
+        // a world which cannot be loaded is learned again instead
+        if let Some(world_save) = &world_save {
+            let GlobalState {
+                blocks, entities, ..
+            } = &mut global_state;
 
-        let pending_logins = Self::login_all(connections, delay_ms);
+            if let Err(err) = world_save.load(blocks, entities) {
+                println!("could not load the saved world -- {err:#}");
+            }
+        }
+
+        let pending = metrics().bots_pending.with_label_values(&[server.as_str()]);
+        let pending_logins =
+            Self::login_all(connections, delay_ms, proxies.clone(), pending.clone());
 
         Ok(Self {
             pending_logins,
-            global_state: GlobalState::init(),
+            global_state,
             command_receiver: commands,
+            api,
+            tracker: CommandTracker::default(),
+            last_progress: Instant::now(),
+            last_telemetry: Instant::now(),
+            last_metrics: Instant::now(),
+            last_eviction: Instant::now(),
+            pending,
+            away: default(),
+            given_up: default(),
             bots: Vec::new(),
             id_on: 0,
+            capture_dir,
+            max_world_memory,
+            over_world_memory: false,
+            reconnect,
+            rejoins: HashMap::new(),
+            proxies,
+            world_save,
+            saving: None,
+            server,
+            export_dir,
         })
     }
 
@@ -127,6 +309,8 @@ impl<T: Minecraft + 'static> Runner<T> {
     fn login_all(
         connections: impl Stream<Item = BotConnection> + 'static,
         delay_millis: u64,
+        proxies: SharedProxyPool,
+        pending: IntGauge,
     ) -> Logins<T> {
         let mut connections = Box::pin(connections);
         let pending_logins = Rc::new(RefCell::new(Vec::new()));
@@ -138,22 +322,37 @@ impl<T: Minecraft + 'static> Runner<T> {
             tokio::task::spawn_local(async move {
                 while let Some(connection) = connections.next().await {
                     let logins = pending_logins.clone();
+                    let proxies = proxies.clone();
+                    let pending = pending.clone();
 
                     // login task for an individual user
+                    pending.inc();
                     tokio::task::spawn_local(async move {
                         println!("Starting login of {}", connection.bot.username());
                         let username = connection.bot.username().to_string();
-                        let login = match T::login(connection).await {
+                        let rejoin = Rejoin::new(
+                            BotConnectionData {
+                                bot: connection.bot.clone(),
+                                proxy: connection.proxy.clone(),
+                                tags: connection.tags.clone(),
+                            },
+                            connection.server_address.clone(),
+                        );
+                        let login = T::login(connection).await;
+                        pending.dec();
+
+                        let login = match login {
                             Ok(res) => {
                                 println!("Finished logging in {username}");
                                 res
                             }
                             Err(err) => {
                                 println!("Error logging in {username} -- {err}");
+                                proxies.borrow_mut().release(rejoin.data.proxy.as_ref());
                                 return;
                             }
                         };
-                        logins.borrow_mut().push(login);
+                        logins.borrow_mut().push((login, rejoin));
                     });
 
                     // if we want a delay between logging in
@@ -168,11 +367,33 @@ impl<T: Minecraft + 'static> Runner<T> {
     pub async fn game_loop(&mut self) {
         let mut previous_goal = Instant::now();
 
+        let shutdown = tokio::signal::ctrl_c();
+        tokio::pin!(shutdown);
+
+        let tick_duration = metrics()
+            .tick_duration
+            .with_label_values(&[self.server.as_str()]);
+
         // a game loop repeating every 50 ms
         loop {
             let end_by = previous_goal + Duration::from_millis(50);
+
+            let start = Instant::now();
             self.game_iter(end_by).await;
-            tokio::time::sleep_until(tokio::time::Instant::from_std(end_by)).await;
+            tick_duration.observe(start.elapsed().as_secs_f64());
+
+            tokio::select! {
+                _ = tokio::time::sleep_until(tokio::time::Instant::from_std(end_by)) => {}
+                _ = &mut shutdown => {
+                    // the world is saved again once a save which is still being
+                    // written is done
+                    self.finish_saving().await;
+                    self.save_world();
+                    self.finish_saving().await;
+                    return;
+                }
+            }
+
             let now = Instant::now();
             let difference = now - end_by;
             let millis_off = difference.as_millis();
@@ -190,6 +411,7 @@ impl<T: Minecraft + 'static> Runner<T> {
         let old_count = self.bots.len();
         // first step: removing disconnected clients
         self.remove_disconnected();
+        self.remove_given_up();
 
         // second step: turning pending logins into clients
         self.pending_logins_to_client();
@@ -199,51 +421,709 @@ impl<T: Minecraft + 'static> Runner<T> {
         // log clients if they have changed
         if new_count != old_count {
             println!("{new_count} clients");
+            metrics()
+                .bots_connected
+                .with_label_values(&[self.server.as_str()])
+                .set(new_count as i64);
         }
 
+        self.update_roster();
+
         // process pending commands (from forge mod)
         self.process_forge_mod_commands();
 
+        self.process_api_requests();
+
         // fourth step: process packets from game loop
         self.process_incoming_minecraft_packets();
 
+        self.report_commands();
+        self.report_world();
+
+        if let Some(request) = self.global_state.export.take() {
+            self.export_world(None, request.name);
+        }
+
+        self.limit_world_memory();
+        self.update_world_metrics();
+
+        if self.world_save.as_ref().map_or(false, WorldSave::is_due) {
+            self.save_world();
+        }
+
         // fifth step: process packets from game loop
+        let start = Instant::now();
         self.run_expensive_tasks_multithreaded(end_by).await;
+        metrics()
+            .expensive_duration
+            .with_label_values(&[self.server.as_str()])
+            .observe(start.elapsed().as_secs_f64());
     }
 
-    /// remove disconnected clients
+    /// remove disconnected clients and start reconnecting them
     fn remove_disconnected(&mut self) {
-        self.bots.retain(|client| !client.state.disconnected);
+        let (disconnected, connected) = std::mem::take(&mut self.bots)
+            .into_iter()
+            .partition(|bot| bot.state.disconnected);
+
+        self.bots = connected;
+
+        for mut bot in disconnected {
+            let state = &bot.state;
+            self.global_state.blocks[state.dimension].release_all(state.bot_id);
+            self.global_state.entities[state.dimension].release_all(state.bot_id);
+            self.global_state.build.release(state.bot_id);
+
+            let Some(mut rejoin) = self.rejoins.remove(&state.bot_id) else {
+                self.publish_disconnect(state, false);
+                for event in self.tracker.remove_bot(state.bot_id) {
+                    self.command_receiver.send(&event);
+                }
+                continue;
+            };
+
+            let username = &state.info.username;
+            let reason = state.disconnect_reason.as_deref().unwrap_or_default();
+
+            if !self.reconnect.allows(reason) {
+                println!("not reconnecting {username} because they were disconnected for {reason}");
+                self.publish_disconnect(state, false);
+                for event in self.tracker.remove_bot(state.bot_id) {
+                    self.command_receiver.send(&event);
+                }
+                self.proxies
+                    .borrow_mut()
+                    .release(rejoin.data.proxy.as_ref());
+                continue;
+            }
+
+            if rejoin.joined.elapsed() >= self.reconnect.stable_after {
+                rejoin.attempt = 0;
+            }
+
+            self.publish_disconnect(state, true);
+
+            // clients keep getting the last state of the bot until it is back
+            let mut last = telemetry(state, &bot.actions);
+            last.connection = Connection::Reconnecting {
+                attempt: rejoin.attempt,
+            };
+            self.away.borrow_mut().insert(state.bot_id, last);
+
+            rejoin.task = bot.actions.take_task();
+            self.start_reconnect(rejoin, username.clone());
+        }
+    }
+
+    /// end the commands of bots which were given up on while reconnecting
+    fn remove_given_up(&mut self) {
+        let given_up = std::mem::take(&mut *self.given_up.borrow_mut());
+
+        for bot_id in given_up {
+            for event in self.tracker.remove_bot(bot_id) {
+                self.command_receiver.send(&event);
+            }
+        }
+    }
+
+    /// tell websocket clients the bot with `state` disconnected
+    fn publish_disconnect(&self, state: &LocalState, reconnecting: bool) {
+        let event = WorldEvent::BotDisconnected {
+            bot: state.info.username.clone(),
+            reason: state.disconnect_reason.clone(),
+            reconnecting,
+        };
+
+        self.command_receiver.send(&Event::World(event));
+    }
+
+    /// log a bot back in with exponential backoff until it succeeds or runs
+    /// out of attempts
+    fn start_reconnect(&self, mut rejoin: Rejoin, username: String) {
+        let policy = self.reconnect.clone();
+        let logins = self.pending_logins.clone();
+        let proxies = self.proxies.clone();
+        let away = self.away.clone();
+        let given_up = self.given_up.clone();
+        let publisher = self.command_receiver.publisher();
+        let pending = self.pending.clone();
+        let bot_id = rejoin.bot_id.unwrap_or_default();
+
+        pending.inc();
+        tokio::task::spawn_local(async move {
+            loop {
+                if rejoin.attempt >= policy.max_attempts {
+                    pending.dec();
+                    println!(
+                        "giving up on reconnecting {username} after {} attempts",
+                        rejoin.attempt
+                    );
+                    proxies.borrow_mut().release(rejoin.data.proxy.as_ref());
+                    away.borrow_mut().remove(&bot_id);
+                    given_up.borrow_mut().push(bot_id);
+
+                    let event = WorldEvent::BotGaveUp {
+                        bot: username,
+                        attempts: rejoin.attempt,
+                    };
+                    publisher.send(&Event::World(event));
+                    return;
+                }
+
+                let delay = policy.delay(rejoin.attempt);
+                rejoin.attempt += 1;
+
+                if let Some(last) = away.borrow_mut().get_mut(&bot_id) {
+                    last.connection = Connection::Reconnecting {
+                        attempt: rejoin.attempt,
+                    };
+                }
+
+                println!(
+                    "reconnecting {username} in {}ms (attempt {}/{})",
+                    delay.as_millis(),
+                    rejoin.attempt,
+                    policy.max_attempts
+                );
+                tokio::time::sleep(delay).await;
+
+                // the bot was disconnected for good through the API
+                if !away.borrow().contains_key(&bot_id) {
+                    break;
+                }
+
+                // move off a proxy which has been quarantined
+                if let Some(proxy) = rejoin.data.proxy.clone() {
+                    let mut pool = proxies.borrow_mut();
+                    if pool.is_quarantined(&proxy) {
+                        match pool.assign(rejoin.data.bot.account()) {
+                            Ok(new) => {
+                                pool.release(Some(&proxy));
+                                rejoin.data.proxy = new;
+                            }
+                            Err(err) => {
+                                println!("cannot move {username} off proxy {}: {err}", proxy.url());
+                            }
+                        }
+                    }
+                }
+
+                let connection =
+                    obtain_connection(rejoin.data.clone(), rejoin.address.clone(), &proxies);
+                let login = match connection.await {
+                    Ok(connection) => T::login(connection).await,
+                    Err(err) => Err(err),
+                };
+
+                match login {
+                    Ok(_) if !away.borrow().contains_key(&bot_id) => break,
+                    Ok(login) => {
+                        println!("reconnected {username}");
+                        pending.dec();
+                        logins.borrow_mut().push((login, rejoin));
+                        return;
+                    }
+                    Err(err) => println!("Error reconnecting {username} -- {err:#}"),
+                }
+            }
+
+            println!("stopped reconnecting {username}");
+            pending.dec();
+            proxies.borrow_mut().release(rejoin.data.proxy.as_ref());
+            given_up.borrow_mut().push(bot_id);
+        });
+    }
+
+    /// every [`Self::EVICTION_INTERVAL`], evict chunks far away from every bot
+    /// if we are above the memory limit. The limit applies to each dimension
+    /// separately.
+    fn limit_world_memory(&mut self) {
+        let Some(limit) = self.max_world_memory else { return };
+
+        if self.last_eviction.elapsed() < Self::EVICTION_INTERVAL {
+            return;
+        }
+
+        self.last_eviction = Instant::now();
+
+        let mut over = false;
+        for (dimension, blocks) in self.global_state.blocks.iter_mut() {
+            let around: Vec<_> = self
+                .bots
+                .iter()
+                .filter(|bot| bot.state.dimension == dimension)
+                .map(|bot| ChunkLocation::from(BlockLocation::from(bot.state.physics.location())))
+                .collect();
+
+            if !blocks.evict(limit, &around) {
+                over = true;
+            }
+        }
+
+        if over && !self.over_world_memory {
+            println!("the chunks bots hold alone use more than the {limit} byte memory limit");
+        }
+
+        self.over_world_memory = over;
+    }
+
+    /// every [`Self::METRICS_INTERVAL`], record the columns and memory of each
+    /// dimension
+    fn update_world_metrics(&mut self) {
+        if self.last_metrics.elapsed() < Self::METRICS_INTERVAL {
+            return;
+        }
+
+        self.last_metrics = Instant::now();
+
+        let metrics = metrics();
+        for (dimension, blocks) in self.global_state.blocks.iter() {
+            let dimension = dimension.to_string();
+            let labels = [self.server.as_str(), dimension.as_str()];
+
+            metrics
+                .chunks_loaded
+                .with_label_values(&labels)
+                .set(blocks.column_count() as i64);
+            metrics
+                .world_memory
+                .with_label_values(&labels)
+                .set(blocks.memory() as i64);
+        }
+    }
+
+    /// Save the world if it is saved at all. It is copied during the tick and
+    /// written on a blocking thread. No save is started while the previous
+    /// one is still being written
+    fn save_world(&mut self) {
+        let Some(world_save) = &mut self.world_save else { return };
+
+        if self
+            .saving
+            .as_ref()
+            .map_or(false, |saving| !saving.is_finished())
+        {
+            return;
+        }
+
+        let GlobalState {
+            blocks, entities, ..
+        } = &self.global_state;
+
+        let snapshot = world_save.snapshot(blocks, entities);
+
+        // encoding and writing a large world takes longer than a tick
+        self.saving = Some(tokio::task::spawn_blocking(move || {
+            if let Err(err) = snapshot.write() {
+                println!("could not save the world -- {err:#}");
+            }
+        }));
+    }
+
+    /// wait until the save of the world which is being written is done
+    async fn finish_saving(&mut self) {
+        if let Some(saving) = self.saving.take() {
+            let _ = saving.await;
+        }
+    }
+
+    /// run `write` on a blocking thread so the bots are not held up by file
+    /// I/O. Once it is done, the command `id`, if any, is reported as finished
+    /// or failed
+    fn write_in_background(
+        &self,
+        id: Option<u64>,
+        what: &'static str,
+        write: impl FnOnce() -> anyhow::Result<()> + Send + 'static,
+    ) {
+        let publisher = self.command_receiver.publisher();
+
+        tokio::task::spawn_blocking(move || {
+            let result = write();
+
+            if let Err(err) = &result {
+                println!("could not {what} -- {err:#}");
+            }
+
+            let Some(id) = id else { return };
+            let event = match result {
+                Ok(()) => Event::Finished(Finished { id }),
+                Err(err) => Event::Failed(Failed {
+                    id,
+                    reason: format!("{err:#}"),
+                }),
+            };
+            publisher.send(&event);
+        });
+    }
+
+    /// export the world to a directory of the export directory. Players spawn
+    /// where the first bot in the overworld is. The world is copied during the
+    /// tick and written on a blocking thread
+    fn export_world(&self, id: Option<u64>, name: Option<String>) {
+        let name = name.unwrap_or_else(|| self.server.clone());
+        let dir = self.export_dir.join(persist::dir_name(&name));
+
+        let spawn = self
+            .bots
+            .iter()
+            .find(|bot| bot.state.dimension == Dimension::Overworld)
+            .map_or_else(
+                || BlockLocation::new(0, 128, 0),
+                |bot| BlockLocation::from(bot.state.physics.location()),
+            );
+
+        let snapshot = anvil::snapshot(&self.global_state.blocks);
+
+        self.write_in_background(id, "export the world", move || {
+            std::fs::create_dir_all(&dir)
+                .with_context(|| format!("could not create {}", dir.display()))?;
+
+            let start = Instant::now();
+            let exported = anvil::export(snapshot, spawn, &name, &dir)?;
+
+            println!(
+                "exported {} columns in {} regions to {} in {}ms",
+                exported.columns,
+                exported.regions,
+                dir.display(),
+                start.elapsed().as_millis()
+            );
+
+            if exported.skipped > 0 {
+                println!(
+                    "skipped {} columns which are only known as estimates",
+                    exported.skipped
+                );
+            }
+
+            Ok(())
+        });
+    }
+
+    /// save the blocks in `sel` as `<name>.schematic` in the export directory.
+    /// Blocks are copied from the dimension of the first bot during the tick
+    /// and written on a blocking thread
+    fn save_schematic(&self, id: u64, sel: Selection3D, name: &str) -> anyhow::Result<()> {
+        let dimension = self
+            .bots
+            .first()
+            .map_or(Dimension::Overworld, |bot| bot.state.dimension);
+
+        let schematic =
+            schematic::Schematic::from_world(&self.global_state.blocks[dimension], sel)?;
+
+        let export_dir = self.export_dir.clone();
+        let path = export_dir.join(format!("{}.schematic", persist::dir_name(name)));
+
+        self.write_in_background(Some(id), "save the schematic", move || {
+            std::fs::create_dir_all(&export_dir)
+                .with_context(|| format!("could not create {}", export_dir.display()))?;
+
+            let mut file = File::create(&path)
+                .with_context(|| format!("could not create {}", path.display()))?;
+            schematic.save(&mut file)?;
+
+            println!("saved {} blocks to {}", schematic.volume(), path.display());
+
+            Ok(())
+        });
+
+        Ok(())
     }
 
     /// turn pending logins into clients that are controller by the [`Runner`].
     fn pending_logins_to_client(&mut self) {
         let mut logins = self.pending_logins.borrow_mut();
 
-        for login in logins.drain(..) {
-            let Login { queue, out, info } = login;
+        for (login, mut rejoin) in logins.drain(..) {
+            let Login {
+                mut queue,
+                out,
+                info,
+            } = login;
+
+            if let Some(dir) = &self.capture_dir {
+                let path = dir.join(format!("{}.cap", info.username));
+                if let Err(err) = queue.capture(&path) {
+                    println!("could not capture packets of {} -- {err}", info.username);
+                }
+            }
+
+            // reconnected bots keep their id
+            let bot_id = rejoin.bot_id.unwrap_or_else(|| {
+                let id = self.id_on;
+                self.id_on += 1;
+                id
+            });
+            rejoin.bot_id = Some(bot_id);
+
+            let mut actions: ActionState = default();
+            if let Some(task) = rejoin.task.take() {
+                actions.resume(task);
+            }
+
+            let mut state = LocalState::new(bot_id, info);
+            state.tags = rejoin.data.tags.clone();
 
             let client = Bot {
-                state: LocalState::new(self.id_on, info),
-                actions: default(),
+                state,
+                actions,
                 queue,
                 out,
             };
-            self.id_on += 1;
+
+            self.away.borrow_mut().remove(&bot_id);
+            let event = WorldEvent::BotJoined {
+                bot: client.state.info.username.clone(),
+            };
+            self.command_receiver.send(&Event::World(event));
+
+            rejoin.joined = Instant::now();
+            self.rejoins.insert(bot_id, rejoin);
             self.bots.push(client);
         }
     }
 
+    /// record every bot so commands can pick the bots they are run by
+    fn update_roster(&mut self) {
+        let groups = &self.global_state.travel_config.members;
+
+        let members = self.bots.iter().map(|bot| {
+            let state = &bot.state;
+            let username = &state.info.username;
+
+            let mut tags = state.tags.clone();
+            tags.extend(groups.get(username).cloned());
+
+            Member {
+                bot_id: state.bot_id,
+                username: username.clone(),
+                tags,
+                location: state.physics.location(),
+                idle: bot.actions.is_idle(),
+            }
+        });
+
+        self.global_state.roster = Roster::new(members);
+    }
+
     /// process pending commands (generally from forge mod but more generally
     /// from a websocket)
     fn process_forge_mod_commands(&mut self) {
         while let Ok(command) = self.command_receiver.pending.try_recv() {
-            if let Err(err) = self.process_command(command) {
+            if let Some(event) = self.run_command(command) {
+                self.command_receiver.send(&event);
+            }
+        }
+    }
+
+    /// run `command`. Returns the event to send back straight away, if any
+    fn run_command(&mut self, command: Command) -> Option<Event> {
+        let Command { id, select, data } = command;
+
+        self.process_command(id, &select, data)
+            .unwrap_or_else(|err| {
                 println!("Error processing command: {err}");
+                Some(Event::Failed(Failed {
+                    id,
+                    reason: format!("{err:#}"),
+                }))
+            })
+    }
+
+    /// answer the requests of the management API
+    fn process_api_requests(&mut self) {
+        let Some(api) = &mut self.api else { return };
+
+        let mut requests = Vec::new();
+        while let Ok(request) = api.try_recv() {
+            requests.push(request);
+        }
+
+        // the client may be gone, so replies are allowed to fail
+        for request in requests {
+            match request {
+                api::Request::Bots(reply) => {
+                    let _ = reply.send(self.bot_states());
+                }
+                api::Request::Disconnect(name, reply) => {
+                    let _ = reply.send(self.disconnect(&name));
+                }
+                api::Request::Command(command, reply) => {
+                    let id = command.id;
+                    let event = match self.run_command(command) {
+                        Some(event) => {
+                            self.command_receiver.send(&event);
+                            event
+                        }
+                        None => Event::Accepted(Accepted { id }),
+                    };
+                    let _ = reply.send(event);
+                }
+                api::Request::Block(dimension, location, reply) => {
+                    let _ = reply.send(self.block_info(dimension, location));
+                }
+                api::Request::Chunk(dimension, location, reply) => {
+                    let _ = reply.send(self.chunk_info(dimension, location));
+                }
             }
         }
     }
 
+    /// the state of every bot, including the ones which are reconnecting
+    fn bot_states(&self) -> Vec<Telemetry> {
+        let mut states: Vec<_> = self
+            .bots
+            .iter()
+            .map(|bot| telemetry(&bot.state, &bot.actions))
+            .collect();
+
+        states.extend(self.away.borrow().values().cloned());
+        states
+    }
+
+    /// disconnect the bot `name` without reconnecting it. Returns if it was
+    /// connected or reconnecting
+    fn disconnect(&mut self, name: &str) -> bool {
+        let bot = self
+            .bots
+            .iter_mut()
+            .find(|bot| bot.state.info.username == name);
+        let Some(bot) = bot else { return self.cancel_reconnect(name) };
+
+        if let Some(rejoin) = self.rejoins.remove(&bot.state.bot_id) {
+            self.proxies
+                .borrow_mut()
+                .release(rejoin.data.proxy.as_ref());
+        }
+
+        // the bot is removed at the start of the next tick
+        bot.state.disconnected = true;
+        bot.state.disconnect_reason = Some("disconnected through the API".to_string());
+        true
+    }
+
+    /// stop reconnecting the bot called `name`. Returns false if it is not
+    /// reconnecting
+    fn cancel_reconnect(&self, name: &str) -> bool {
+        let mut away = self.away.borrow_mut();
+        let bot_id = away
+            .iter()
+            .find_map(|(bot_id, last)| (last.bot == name).then_some(*bot_id));
+        let Some(bot_id) = bot_id else { return false };
+
+        // the reconnect task stops once it sees the bot is no longer away
+        away.remove(&bot_id);
+
+        // unless it already logged the bot back in
+        let mut logins = self.pending_logins.borrow_mut();
+        let login = logins
+            .iter()
+            .position(|(_, rejoin)| rejoin.bot_id == Some(bot_id));
+        if let Some(idx) = login {
+            let (_, rejoin) = logins.remove(idx);
+            self.proxies
+                .borrow_mut()
+                .release(rejoin.data.proxy.as_ref());
+            self.given_up.borrow_mut().push(bot_id);
+        }
+
+        true
+    }
+
+    /// the block at `location`, if its column is known
+    fn block_info(&self, dimension: Dimension, location: BlockLocation) -> Option<BlockInfo> {
+        let block = self.global_state.blocks[dimension].get_block(location)?;
+
+        let state = match block {
+            BlockApprox::Realized(state) => Some(state),
+            BlockApprox::Estimate(_) => None,
+        };
+
+        Some(BlockInfo {
+            location,
+            state: state.map(|state| state.0),
+            id: state.map(|state| state.id()),
+            simple: block.s_type(),
+        })
+    }
+
+    /// the column at `location`, if it is known
+    fn chunk_info(&self, dimension: Dimension, location: ChunkLocation) -> Option<ChunkInfo> {
+        let blocks = &self.global_state.blocks[dimension];
+        let column = blocks.column(location)?;
+
+        Some(ChunkInfo {
+            x: location.0,
+            z: location.1,
+            holders: blocks.holders(location),
+            stale: blocks.is_stale(location),
+            low_memory: !column.is_high_memory(),
+            memory: column.memory(),
+        })
+    }
+
+    /// tell websocket clients which commands are done and, every
+    /// [`Self::PROGRESS_INTERVAL`], how far along bots are
+    fn report_commands(&mut self) {
+        for bot in &mut self.bots {
+            for (id, finished) in bot.actions.take_ended() {
+                if let Some(event) = self.tracker.stopped(id, bot.state.bot_id, finished) {
+                    self.command_receiver.send(&event);
+                }
+            }
+        }
+
+        if self.last_progress.elapsed() < Self::PROGRESS_INTERVAL {
+            return;
+        }
+
+        self.last_progress = Instant::now();
+
+        for bot in &self.bots {
+            let Some(id) = bot.actions.command() else { continue };
+            let Some(shared) = self.tracker.shared(id) else { continue };
+
+            let done = shared.and_then(|shared| match shared {
+                SharedWork::Mine => self.global_state.mine.progress(),
+                SharedWork::Build => self.global_state.build.progress(),
+            });
+
+            let progress = Progress {
+                id,
+                bot: bot.state.info.username.clone(),
+                done,
+                path_remaining: bot.actions.path_remaining(),
+            };
+
+            self.command_receiver.send(&Event::Progress(progress));
+        }
+    }
+
+    /// send websocket clients what happened in the world and, every
+    /// [`Self::TELEMETRY_INTERVAL`], the state of every bot
+    fn report_world(&mut self) {
+        for event in self.global_state.world_events.drain(..) {
+            self.command_receiver.send(&Event::World(event));
+        }
+
+        if self.last_telemetry.elapsed() < Self::TELEMETRY_INTERVAL {
+            return;
+        }
+
+        self.last_telemetry = Instant::now();
+
+        for bot in &self.bots {
+            let event = Event::Telemetry(telemetry(&bot.state, &bot.actions));
+            self.command_receiver.send(&event);
+        }
+
+        for last in self.away.borrow().values() {
+            self.command_receiver.send(&Event::Telemetry(last.clone()));
+        }
+    }
+
     fn process_incoming_minecraft_packets(&mut self) {
         for bot in &mut self.bots {
             let mut processor = SimpleInterfaceIn::new(
@@ -327,45 +1207,142 @@ impl<T: Minecraft + 'static> Runner<T> {
         thread_loop_end.notified().await;
     }
 
-    fn process_command(&mut self, command: CommandData) -> anyhow::Result<()> {
+    /// run the websocket command `id`. Returns the event to send back straight
+    /// away, if any. Otherwise, it is sent once the bots working on it or the
+    /// files it writes are done
+    fn process_command(
+        &mut self,
+        id: u64,
+        select: &Selector,
+        command: CommandData,
+    ) -> anyhow::Result<Option<Event>> {
+        // a client may give the id of a command which is still running
+        if !matches!(command, CommandData::Cancelled(_)) {
+            anyhow::ensure!(
+                !self.tracker.is_running(id),
+                "command {id} is already running"
+            );
+        }
+
         let global = &mut self.global_state;
-        let bots = &mut self.bots;
+
+        // the seed of random samples is the id so every command picks its own
+        let selected = global.roster.select(select, None, id)?;
+        let bots = self
+            .bots
+            .iter_mut()
+            .filter(|bot| selected.contains(&bot.state.bot_id));
+
+        // the bots which started working on the command
+        let mut started = HashSet::new();
+        let mut shared = None;
 
         match command {
             CommandData::Mine(interfaces::Mine { sel }) => {
                 let Selection2D { from, to } = sel.normalize();
                 global.mine.mine(from, to, Some(MinePreference::FromDist));
+                shared = Some(SharedWork::Mine);
 
                 for bot in bots {
-                    bot.actions.schedule(LazyStream::from(MineRegion));
+                    bot.actions
+                        .schedule_command(id, LazyStream::from(MineRegion));
+                    started.insert(bot.state.bot_id);
                 }
             }
             CommandData::GoTo(GoTo { location }) => {
                 for bot in bots {
                     bot.actions
-                        .schedule(BlockTravelTask::new(location, &bot.state));
+                        .schedule_command(id, BlockTravelTask::new(location, &bot.state));
+                    started.insert(bot.state.bot_id);
                 }
             }
             CommandData::Attack(Attack { name }) => {
-                let player = self
-                    .global_state
+                let player = global
                     .players
                     .by_name(&name)
                     .context("player does not exist")?;
-                let entity_id = self
-                    .global_state
-                    .entities
-                    .by_player_uuid(player.uuid)
-                    .context("could not find entity id for player")?;
 
+                // only bots in the same dimension as the player can see them
                 for bot in bots {
+                    let entities = &global.entities[bot.state.dimension];
+                    let Some(entity_id) = entities.by_player_uuid(player.uuid) else { continue };
+
                     let task = LazyStream::from(AttackEntity::new(entity_id));
-                    bot.actions.schedule(task);
+                    bot.actions.schedule_command(id, task);
+                    started.insert(bot.state.bot_id);
+                }
+
+                anyhow::ensure!(!started.is_empty(), "could not find entity id for player");
+            }
+            CommandData::Build(Build { path, origin }) => {
+                if global.build.is_done() {
+                    global.build.build_file(&path, origin)?;
+                }
+                shared = Some(SharedWork::Build);
+
+                for bot in bots {
+                    bot.actions
+                        .schedule_command(id, LazyStream::from(build::Build));
+                    started.insert(bot.state.bot_id);
+                }
+            }
+            CommandData::Export(Export { name }) => {
+                // reported as finished once it is written
+                self.export_world(Some(id), name);
+                return Ok(None);
+            }
+            CommandData::Schematic(Schematic { sel, name }) => {
+                // reported as finished once it is written
+                self.save_schematic(id, sel, &name)?;
+                return Ok(None);
+            }
+            CommandData::Stop(Stop {}) => {
+                // the commands of the bots are reported as cancelled
+                for bot in bots {
+                    bot.actions.clear();
                 }
             }
-            CommandData::Cancelled(_) | CommandData::Finished(_) => {}
+            CommandData::Cancelled(Cancelled { id }) => {
+                return self.cancel_command(id).map(Some);
+            }
+            CommandData::Finished(_) => return Ok(None),
         }
 
-        Ok(())
+        Ok(self.tracker.start(id, shared, started))
+    }
+
+    /// stop the bots working on the command `id`
+    fn cancel_command(&mut self, id: u64) -> anyhow::Result<Event> {
+        let tracked = self
+            .tracker
+            .cancel(id)
+            .with_context(|| format!("command {id} is not running"))?;
+
+        for bot in &mut self.bots {
+            bot.actions.cancel(id);
+        }
+
+        // the work which was not handed out yet is dropped
+        match tracked.shared {
+            Some(SharedWork::Mine) => self.global_state.mine = MineAlloc::default(),
+            Some(SharedWork::Build) => self.global_state.build.clear(),
+            None => {}
+        }
+
+        Ok(Event::Cancelled(Cancelled { id }))
+    }
+}
+
+/// the state of the bot with `state` and `actions`
+fn telemetry(state: &LocalState, actions: &ActionState) -> Telemetry {
+    Telemetry {
+        bot: state.info.username.clone(),
+        connection: Connection::Online,
+        location: state.physics.location(),
+        dimension: state.dimension.to_string(),
+        health: state.health,
+        food: state.food,
+        task: actions.stack().next().map(|(_, name)| name.to_string()),
+        inventory: state.inventory.counts(),
     }
 }
@@ -0,0 +1,163 @@
+//! Picking the bots a command is run by
+
+use std::collections::HashSet;
+
+use anyhow::Context;
+use float_ord::FloatOrd;
+use interfaces::Selector;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use crate::types::Location;
+
+/// What a [`Selector`] knows about a bot
+#[derive(Debug, Clone)]
+pub struct Member {
+    pub bot_id: u32,
+    pub username: String,
+    /// the tags of the account and the group of the bot
+    pub tags: Vec<String>,
+    pub location: Location,
+    /// if the bot has no task
+    pub idle: bool,
+}
+
+/// Every bot as of the start of the tick. Bots hearing the same chat command
+/// select the same bots from it
+#[derive(Debug, Default)]
+pub struct Roster {
+    /// ordered by bot id
+    members: Vec<Member>,
+}
+
+impl Roster {
+    pub fn new(members: impl IntoIterator<Item = Member>) -> Self {
+        let mut members: Vec<_> = members.into_iter().collect();
+        members.sort_unstable_by_key(|member| member.bot_id);
+        Self { members }
+    }
+
+    /// the bot with the lowest id, which reports what every bot sees
+    pub fn first(&self) -> Option<u32> {
+        self.members.first().map(|member| member.bot_id)
+    }
+
+    /// The ids of the bots `selector` picks. `origin` is used for the nearest
+    /// bots if the selector has no location of its own. The random sample only
+    /// depends on `seed` and the bots which match
+    pub fn select(
+        &self,
+        selector: &Selector,
+        origin: Option<Location>,
+        seed: u64,
+    ) -> anyhow::Result<HashSet<u32>> {
+        let mut members: Vec<_> = self
+            .members
+            .iter()
+            .filter(|member| {
+                selector.names.is_empty() || selector.names.contains(&member.username)
+            })
+            .filter(|member| {
+                selector.tags.is_empty()
+                    || member.tags.iter().any(|tag| selector.tags.contains(tag))
+            })
+            .filter(|member| !selector.idle || member.idle)
+            .collect();
+
+        if let Some(nearest) = &selector.nearest {
+            let location = nearest
+                .location
+                .map(|location| location.center_bottom())
+                .or(origin)
+                .context("the nearest bots need a location")?;
+
+            members.sort_by_key(|member| FloatOrd(member.location.dist2(location)));
+            members.truncate(nearest.count);
+        }
+
+        if let Some(count) = selector.random {
+            let mut rng = StdRng::seed_from_u64(seed);
+            members = members.choose_multiple(&mut rng, count).copied().collect();
+        }
+
+        Ok(members.iter().map(|member| member.bot_id).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use interfaces::{types::BlockLocation, Nearest, Selector};
+
+    use crate::{
+        client::select::{Member, Roster},
+        types::Location,
+    };
+
+    fn roster() -> Roster {
+        let member = |bot_id: u32, tags: &[&str], x: f64, idle: bool| Member {
+            bot_id,
+            username: format!("Bot{bot_id:0>4}"),
+            tags: tags.iter().map(ToString::to_string).collect(),
+            location: Location::new(x, 0.0, 0.0),
+            idle,
+        };
+
+        Roster::new([
+            member(0, &["miners"], 0.0, true),
+            member(1, &["miners"], 10.0, false),
+            member(2, &["builders"], 20.0, true),
+            member(3, &[], 30.0, true),
+        ])
+    }
+
+    #[test]
+    fn test_filters() {
+        let roster = roster();
+
+        let all = roster.select(&Selector::default(), None, 0).unwrap();
+        assert_eq!(all, [0, 1, 2, 3].into());
+
+        let selector = Selector {
+            names: vec!["Bot0001".to_string(), "Bot0003".to_string()],
+            ..Selector::default()
+        };
+        assert_eq!(roster.select(&selector, None, 0).unwrap(), [1, 3].into());
+
+        let selector = Selector {
+            tags: vec!["miners".to_string()],
+            idle: true,
+            ..Selector::default()
+        };
+        assert_eq!(roster.select(&selector, None, 0).unwrap(), [0].into());
+    }
+
+    #[test]
+    fn test_nearest_and_random() {
+        let roster = roster();
+
+        let nearest = |location| Selector {
+            nearest: Some(Nearest { count: 2, location }),
+            ..Selector::default()
+        };
+
+        let selector = nearest(Some(BlockLocation::new(25, 0, 0)));
+        assert_eq!(roster.select(&selector, None, 0).unwrap(), [2, 3].into());
+
+        // chat commands use the location of the player who sent them
+        let origin = Some(Location::new(1.0, 0.0, 0.0));
+        assert_eq!(
+            roster.select(&nearest(None), origin, 0).unwrap(),
+            [0, 1].into()
+        );
+        assert!(roster.select(&nearest(None), None, 0).is_err());
+
+        let selector = Selector {
+            random: Some(2),
+            ..Selector::default()
+        };
+
+        // every bot picks the same sample for the same seed
+        let sample = roster.select(&selector, None, 7).unwrap();
+        assert_eq!(sample.len(), 2);
+        assert_eq!(roster.select(&selector, None, 7).unwrap(), sample);
+    }
+}
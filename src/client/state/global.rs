@@ -1,29 +1,37 @@
-use interfaces::types::block_data::BlockData;
+use interfaces::{types::block_data::BlockData, WorldEvent};
 
 use crate::{
     client::{
-        pathfind::context::PathConfig,
-        state::global::{mine_alloc::MineAlloc, world_players::WorldPlayers},
+        pathfind::context::PathConfigs,
+        select::Roster,
+        state::global::{
+            build_alloc::BuildAlloc, mine_alloc::MineAlloc, world_players::WorldPlayers,
+        },
+    },
+    storage::{
+        anvil::ExportRequest, blocks::WorldBlocks, dimensions::Dimensions, entities::WorldEntities,
     },
-    storage::{blocks::WorldBlocks, entities::WorldEntities},
 };
 
+pub mod build_alloc;
 pub mod mine_alloc;
 pub mod world_players;
 
 /// The global state shared between bots
 #[derive(Default)]
 pub struct GlobalState {
-    /// all the blocks in the world
-    pub blocks: WorldBlocks,
+    /// all the blocks in each dimension
+    pub blocks: Dimensions<WorldBlocks>,
     /// mine tasks
     pub mine: MineAlloc,
+    /// build tasks
+    pub build: BuildAlloc,
 
     /// all block data (which ID is which block)
     pub block_data: BlockData,
 
-    /// all entities
-    pub entities: WorldEntities,
+    /// all entities in each dimension
+    pub entities: Dimensions<WorldEntities>,
 
     /// all players
     pub players: WorldPlayers,
@@ -31,8 +39,17 @@ pub struct GlobalState {
     /// ticks since the first bot has joined
     pub ticks: usize,
 
-    /// config for traveling
-    pub travel_config: PathConfig,
+    /// config for traveling, per group of bots
+    pub travel_config: PathConfigs,
+
+    /// an export of the world asked for by a chat command
+    pub export: Option<ExportRequest>,
+
+    /// every bot, for picking the bots a command is run by
+    pub roster: Roster,
+
+    /// what happened in the world since the last tick, for websocket clients
+    pub world_events: Vec<WorldEvent>,
 }
 
 impl GlobalState {
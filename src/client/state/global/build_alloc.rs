@@ -0,0 +1,357 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    path::{Component, Path, PathBuf},
+};
+
+use interfaces::types::{BlockKind, BlockLocation, BlockState};
+
+use crate::{
+    schematic::{self, Structure},
+    storage::{blocks::WorldBlocks, legacy::LegacyNames},
+};
+
+/// The blocks of one layer of a structure within a square of
+/// [`BuildAlloc::REGION_WIDTH`]
+#[derive(Debug)]
+struct BuildRegion {
+    y: i16,
+    blocks: VecDeque<(BlockLocation, BlockState)>,
+}
+
+/// What a bot should do next to help with the build
+#[derive(Debug, PartialEq, Eq)]
+pub enum BuildStep {
+    /// place `state` at the location
+    Place(BlockLocation, BlockState),
+    /// the layer below is still being built
+    Wait,
+    /// there is nothing left to build
+    Done,
+}
+
+/// Allocates regions of a structure to the bots building it. Layers are built
+/// from the bottom up so there is always a block to place against.
+#[derive(Debug, Default)]
+pub struct BuildAlloc {
+    /// the directory schematics are read from
+    dir: PathBuf,
+
+    /// regions which have not been handed out, lowest first
+    regions: VecDeque<BuildRegion>,
+
+    /// the region each bot is building
+    assigned: HashMap<u32, BuildRegion>,
+
+    /// the number of blocks of the last build
+    total: usize,
+
+    /// the number of blocks of each kind which could not be placed because
+    /// the bot building them did not have them
+    missing: HashMap<BlockKind, usize>,
+
+    /// the number of blocks which could not be placed for another reason, such
+    /// as being out of reach
+    given_up: usize,
+}
+
+impl BuildAlloc {
+    pub const REGION_WIDTH: i32 = 8;
+
+    /// an allocator which reads schematics from `dir`
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            ..Self::default()
+        }
+    }
+
+    /// Build the schematic `name` in the schematic directory (in any format
+    /// [`schematic::load`] supports) with its lowest corner at `origin`
+    pub fn build_file(&mut self, name: &str, origin: BlockLocation) -> anyhow::Result<()> {
+        let path = self.dir.join(file_name(name)?);
+
+        let names = LegacyNames::read()?;
+        let structure = schematic::load(&path, &names)?;
+
+        let unknown = structure.unknown_blocks();
+        if !unknown.is_empty() {
+            println!(
+                "{} are not 1.12 blocks and will not be built",
+                unknown.join(", ")
+            );
+        }
+
+        self.build_at(&*structure, origin);
+        Ok(())
+    }
+
+    /// Build `structure` with its lowest corner at `origin`
+    pub fn build_at(&mut self, structure: &dyn Structure, origin: BlockLocation) {
+        let Some(min) = structure.blocks().map(|(location, _)| location).reduce(|a, b| {
+            BlockLocation::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z))
+        }) else {
+            return;
+        };
+
+        let shift = BlockLocation::new(origin.x - min.x, origin.y - min.y, origin.z - min.z);
+
+        self.build(
+            structure
+                .blocks()
+                .map(|(location, state)| (location + shift, state)),
+        );
+    }
+
+    /// Build `blocks`. Air is not placed. Like [`MineAlloc`], a new build is
+    /// ignored until the previous one is done.
+    ///
+    /// [`MineAlloc`]: crate::client::state::global::mine_alloc::MineAlloc
+    pub fn build(&mut self, blocks: impl Iterator<Item = (BlockLocation, BlockState)>) {
+        if !self.is_done() {
+            return;
+        }
+
+        self.missing.clear();
+        self.given_up = 0;
+
+        let mut regions = BTreeMap::new();
+
+        for (location, state) in blocks {
+            if state == BlockState::AIR {
+                continue;
+            }
+
+            let key = (
+                location.y,
+                location.x.div_euclid(Self::REGION_WIDTH),
+                location.z.div_euclid(Self::REGION_WIDTH),
+            );
+
+            regions
+                .entry(key)
+                .or_insert_with(VecDeque::new)
+                .push_back((location, state));
+        }
+
+        self.regions = regions
+            .into_iter()
+            .map(|((y, ..), blocks)| BuildRegion { y, blocks })
+            .collect();
+
+        self.total = self.regions.iter().map(|region| region.blocks.len()).sum();
+    }
+
+    /// drop every block which has not been placed yet
+    pub fn clear(&mut self) {
+        let dir = std::mem::take(&mut self.dir);
+        *self = Self::new(dir);
+    }
+
+    /// the fraction of blocks which have been placed or given up on
+    #[allow(clippy::cast_precision_loss)]
+    pub fn progress(&self) -> Option<f64> {
+        if self.total == 0 {
+            return None;
+        }
+
+        let left: usize = self
+            .regions
+            .iter()
+            .chain(self.assigned.values())
+            .map(|region| region.blocks.len())
+            .sum();
+
+        Some(1.0 - left as f64 / self.total as f64)
+    }
+
+    /// if there is nothing queued or being built
+    pub fn is_done(&self) -> bool {
+        self.regions.is_empty() && self.assigned.is_empty()
+    }
+
+    /// The next block `bot_id` should place. Blocks of the right kind already
+    /// in `world` are skipped, and a new region is handed out once the bot's
+    /// region is finished.
+    pub fn next_step(&mut self, bot_id: u32, world: &WorldBlocks) -> BuildStep {
+        if let Some(region) = self.assigned.get_mut(&bot_id) {
+            // the metadata of a placed block depends on how it was placed so only
+            // the kind is compared
+            while let Some(&(location, state)) = region.blocks.front() {
+                if world.get_block_kind(location) == Some(state.kind()) {
+                    region.blocks.pop_front();
+                } else {
+                    return BuildStep::Place(location, state);
+                }
+            }
+
+            self.assigned.remove(&bot_id);
+            self.report_if_done();
+        }
+
+        let Some(front) = self.regions.front() else {
+            return if self.is_done() {
+                BuildStep::Done
+            } else {
+                BuildStep::Wait
+            };
+        };
+
+        let lowest_assigned = self.assigned.values().map(|region| region.y).min();
+        if lowest_assigned.map_or(false, |y| y < front.y) {
+            return BuildStep::Wait;
+        }
+
+        let Some(region) = self.regions.pop_front() else { return BuildStep::Wait };
+        self.assigned.insert(bot_id, region);
+
+        self.next_step(bot_id, world)
+    }
+
+    /// stop trying to place the block at `location`. It is reported once the
+    /// build is done
+    pub fn give_up(&mut self, bot_id: u32, location: BlockLocation) {
+        if self.remove(bot_id, location) {
+            self.given_up += 1;
+        }
+    }
+
+    /// remove the block at `location` from the region of `bot_id`. Returns if
+    /// it was there
+    fn remove(&mut self, bot_id: u32, location: BlockLocation) -> bool {
+        let Some(region) = self.assigned.get_mut(&bot_id) else { return false };
+
+        let before = region.blocks.len();
+        region.blocks.retain(|&(loc, _)| loc != location);
+        region.blocks.len() < before
+    }
+
+    /// `bot_id` does not have the block to place at `location`. Returns true if
+    /// this is the first time `kind` was missing in this build
+    pub fn report_missing(
+        &mut self,
+        bot_id: u32,
+        location: BlockLocation,
+        kind: BlockKind,
+    ) -> bool {
+        self.remove(bot_id, location);
+
+        let count = self.missing.entry(kind).or_default();
+        *count += 1;
+        *count == 1
+    }
+
+    /// put the region of a bot which disconnected back in the queue so another
+    /// bot can finish it
+    pub fn release(&mut self, bot_id: u32) {
+        if let Some(region) = self.assigned.remove(&bot_id) {
+            self.regions.push_front(region);
+        }
+    }
+
+    fn report_if_done(&self) {
+        if !self.is_done() {
+            return;
+        }
+
+        let missing: usize = self.missing.values().sum();
+        let given_up = self.given_up;
+
+        let mut report = String::from("finished building");
+        if missing > 0 {
+            report += &format!(". {missing} blocks could not be placed as bots did not have them");
+        }
+        if given_up > 0 {
+            report += &format!(
+                ". {given_up} blocks could not be placed as bots could not reach them or had \
+                 nothing to place them against"
+            );
+        }
+
+        println!("{report}");
+    }
+}
+
+/// `name` as a path relative to the schematic directory. Only the name of a
+/// file is accepted so players cannot make us read files anywhere else
+fn file_name(name: &str) -> anyhow::Result<&Path> {
+    let path = Path::new(name);
+
+    let mut components = path.components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(path),
+        _ => anyhow::bail!("{name} is not the name of a schematic file"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use interfaces::types::{BlockKind, BlockLocation, BlockState};
+
+    use crate::{
+        client::state::global::build_alloc::{file_name, BuildAlloc, BuildStep},
+        storage::blocks::WorldBlocks,
+    };
+
+    #[test]
+    fn test_file_name() {
+        assert_eq!(file_name("house.schem").unwrap(), Path::new("house.schem"));
+
+        for name in ["", ".", "..", "../house.schem", "/etc/passwd", "a/house.schem"] {
+            assert!(file_name(name).is_err(), "{name} was accepted");
+        }
+    }
+
+    #[test]
+    fn test_layers() {
+        let mut world = WorldBlocks::default();
+        world.set_block(BlockLocation::new(0, 0, 0), BlockState::AIR);
+
+        let blocks = [
+            (BlockLocation::new(0, 1, 0), BlockState::STONE),
+            (BlockLocation::new(0, 0, 0), BlockState::STONE),
+            (BlockLocation::new(20, 0, 0), BlockState::STONE),
+            (BlockLocation::new(1, 0, 0), BlockState::AIR),
+        ];
+
+        let mut alloc = BuildAlloc::default();
+        alloc.build(blocks.into_iter());
+
+        // the two regions at y = 0 are handed out first
+        let a = alloc.next_step(1, &world);
+        let b = alloc.next_step(2, &world);
+        assert_eq!(
+            a,
+            BuildStep::Place(BlockLocation::new(0, 0, 0), BlockState::STONE)
+        );
+        assert_eq!(
+            b,
+            BuildStep::Place(BlockLocation::new(20, 0, 0), BlockState::STONE)
+        );
+
+        // y = 1 waits for the layer below
+        assert_eq!(alloc.next_step(3, &world), BuildStep::Wait);
+
+        // a bot which disconnects has its region handed to another bot
+        alloc.release(1);
+        assert_eq!(alloc.next_step(3, &world), a);
+
+        world.set_block(BlockLocation::new(0, 0, 0), BlockState::STONE);
+        assert_eq!(alloc.next_step(3, &world), BuildStep::Wait);
+
+        assert!(alloc.report_missing(2, BlockLocation::new(20, 0, 0), BlockKind(1)));
+        assert_eq!(
+            alloc.next_step(2, &world),
+            BuildStep::Place(BlockLocation::new(0, 1, 0), BlockState::STONE)
+        );
+
+        alloc.give_up(2, BlockLocation::new(0, 1, 0));
+        assert_eq!(alloc.next_step(2, &world), BuildStep::Done);
+        assert!(alloc.is_done());
+
+        // missing blocks and blocks which were given up on are counted apart
+        assert_eq!(alloc.missing.values().sum::<usize>(), 1);
+        assert_eq!(alloc.given_up, 1);
+    }
+}
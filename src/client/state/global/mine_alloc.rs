@@ -12,6 +12,8 @@ struct MineRegion(BlockLocation2D);
 #[derive(Debug, Default)]
 pub struct MineAlloc {
     regions: VecDeque<MineRegion>,
+    /// the number of regions of the last mine
+    total: usize,
 }
 
 pub enum MinePreference {
@@ -32,6 +34,12 @@ impl MineAlloc {
         Some(centered)
     }
 
+    /// the fraction of regions which have been handed out
+    #[allow(clippy::cast_precision_loss)]
+    pub fn progress(&self) -> Option<f64> {
+        (self.total > 0).then(|| 1.0 - self.regions.len() as f64 / self.total as f64)
+    }
+
     fn locations_rad(center: BlockLocation2D, rad: i32) -> Locations {
         (0..256)
             .cartesian_product(-rad..=rad)
@@ -76,6 +84,8 @@ impl MineAlloc {
             }
         }
 
+        self.total = vec.len();
+
         for elem in vec {
             self.regions.push_back(elem);
         }
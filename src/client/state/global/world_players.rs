@@ -17,7 +17,6 @@ impl WorldPlayers {
         self.players.iter().find(|player| player.name == name)
     }
 
-    #[allow(unused)]
     pub fn by_uuid(&mut self, uuid: u128) -> Option<&Player> {
         self.players.iter().find(|player| player.uuid == uuid)
     }
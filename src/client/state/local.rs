@@ -19,8 +19,19 @@ pub struct LocalState {
     pub bot_id: u32,
     pub physics: Physics,
     pub disconnected: bool,
+
+    /// why the server disconnected us, if it told us
+    pub disconnect_reason: Option<String>,
+
+    /// the message of the death the server told us about before we died
+    pub death_message: Option<String>,
+
     pub inventory: PlayerInventory,
     pub info: ClientInfo,
+
+    /// the tags of the account, which commands can select the bot by
+    pub tags: Vec<String>,
+
     pub alive: bool,
     pub dimension: Dimension,
 }
@@ -49,10 +60,13 @@ impl LocalState {
             bot_id,
             physics: Physics::default(),
             disconnected: false,
+            disconnect_reason: None,
+            death_message: None,
             inventory: PlayerInventory::default(),
             alive: true,
             dimension: Dimension::Overworld,
             info,
+            tags: Vec::new(),
         }
     }
 }
@@ -1,4 +1,9 @@
-use interfaces::types::{block_data::BlockData, BlockKind};
+use std::collections::BTreeMap;
+
+use interfaces::{
+    types::{block_data::BlockData, BlockKind},
+    ItemCount,
+};
 
 use crate::{
     client::physics::tools::{Tool, ToolMat},
@@ -104,6 +109,14 @@ impl PlayerInventory {
         self.switch_selector(out, BlockKind::throw_away_block);
     }
 
+    /// if there is food in the hotbar
+    pub fn has_food(&self, data: &BlockData) -> bool {
+        self.hotbar()
+            .iter()
+            .flatten()
+            .any(|stack| data.is_food(stack.kind.id()))
+    }
+
     /// true if successful
     pub fn switch_food(&mut self, data: &BlockData, out: &mut impl InterfaceOut) -> bool {
         self.switch_selector(out, |kind| data.is_food(kind.id()))
@@ -173,4 +186,17 @@ impl PlayerInventory {
     pub fn add(&mut self, idx: usize, stack: ItemStack) {
         self.slots[idx] = Some(stack);
     }
+
+    /// the number of items of each kind, ordered by id
+    pub fn counts(&self) -> Vec<ItemCount> {
+        let mut counts = BTreeMap::new();
+        for stack in self.slots.iter().flatten() {
+            *counts.entry(stack.kind.0).or_default() += u32::from(stack.count);
+        }
+
+        counts
+            .into_iter()
+            .map(|(id, count)| ItemCount { id, count })
+            .collect()
+    }
 }
@@ -32,7 +32,7 @@ impl TaskStream for AttackEntity {
         let current_location = local.physics.location();
 
         // we cannot do anything if we do not know the location so we end the task
-        let entity_location = global.entities.by_id(self.id)?.location;
+        let entity_location = global.entities[local.dimension].by_id(self.id)?.location;
 
         let dist2 = entity_location.dist2(current_location);
 
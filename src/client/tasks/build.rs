@@ -0,0 +1,84 @@
+use interfaces::types::BlockLocation;
+
+use crate::{
+    client::{
+        pathfind::implementations::no_vehicle::TravelProblem,
+        state::{
+            global::{build_alloc::BuildStep, GlobalState},
+            local::LocalState,
+        },
+        tasks::{
+            compound::CompoundTask, delay::DelayTask, navigate::NavigateProblem,
+            place_block::PlaceBlockTask, stream::TaskStream, Task,
+        },
+    },
+    protocol::InterfaceOut,
+    types::Displacement,
+};
+
+/// Help build the structure in [`BuildAlloc`] until it is done
+///
+/// [`BuildAlloc`]: crate::client::state::global::build_alloc::BuildAlloc
+pub struct Build;
+
+impl Build {
+    /// ticks to wait before asking for work again while the layer below is
+    /// built
+    const WAIT_TICKS: u32 = 20;
+}
+
+impl TaskStream for Build {
+    fn poll(
+        &mut self,
+        _out: &mut impl InterfaceOut,
+        local: &mut LocalState,
+        global: &mut GlobalState,
+    ) -> Option<Task> {
+        loop {
+            let world = &global.blocks[local.dimension];
+
+            let (location, state) = match global.build.next_step(local.bot_id, world) {
+                BuildStep::Place(location, state) => (location, state),
+                BuildStep::Wait => return Some(DelayTask::new(Self::WAIT_TICKS).into()),
+                BuildStep::Done => return None,
+            };
+
+            let kind = state.kind();
+            let has_block = local
+                .inventory
+                .hotbar()
+                .iter()
+                .flatten()
+                .any(|stack| stack.kind == kind);
+
+            if !has_block {
+                if global.build.report_missing(local.bot_id, location, kind) {
+                    println!(
+                        "{} is missing block {} to build",
+                        local.info.username,
+                        kind.id()
+                    );
+                }
+                continue;
+            }
+
+            let place = PlaceBlockTask::new(location, state);
+
+            let eye = local.physics.location() + Displacement::EYE_HEIGHT;
+            let reach = PlaceBlockTask::REACH;
+            if location.true_center().dist2(eye) <= reach * reach {
+                return Some(place.into());
+            }
+
+            // stand next to the block, not in its column, so we do not block the
+            // placement
+            let start = BlockLocation::from(local.physics.location());
+            let problem = TravelProblem::navigate_near_block(start, location.into(), 9.0, true);
+
+            let mut compound = CompoundTask::default();
+            compound.add(NavigateProblem::from(problem)).add(place);
+
+            return Some(compound.into());
+        }
+    }
+}
@@ -52,4 +52,19 @@ impl TaskTrait for CompoundTask {
             Some(res) => res.expensive(end_at, local, global),
         };
     }
+
+    fn resume(
+        &mut self,
+        out: &mut impl InterfaceOut,
+        local: &mut LocalState,
+        global: &GlobalState,
+    ) {
+        if let Some(front) = self.tasks.front_mut() {
+            front.resume(out, local, global);
+        }
+    }
+
+    fn path_remaining(&self) -> Option<usize> {
+        self.tasks.front()?.path_remaining()
+    }
 }
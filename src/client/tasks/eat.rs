@@ -23,11 +23,15 @@ impl TaskTrait for EatTask {
     fn tick(
         &mut self,
         out: &mut impl InterfaceOut,
-        _: &mut LocalState,
-        _: &mut GlobalState,
+        local: &mut LocalState,
+        global: &mut GlobalState,
     ) -> bool {
-        // start eating
+        // start eating. The food is only held once the task runs, as urgent tasks
+        // scheduled before might be holding something else
         if self.ticks == TICKS {
+            if !local.inventory.switch_food(&global.block_data, out) {
+                return true;
+            }
             out.use_item();
         }
 
@@ -39,4 +43,8 @@ impl TaskTrait for EatTask {
             false
         }
     }
+
+    fn resume(&mut self, _: &mut impl InterfaceOut, _: &mut LocalState, _: &GlobalState) {
+        self.ticks = TICKS;
+    }
 }
@@ -35,7 +35,7 @@ impl TaskTrait for FallBucketTask {
             if self.ticks_since_place == BUCKET_LEAVE_TICKS {
                 out.use_item();
                 // out.place_block(place_loc, Face::PosY);
-                global.blocks.set_block(place_loc.above(), BlockState::AIR);
+                global.blocks[local.dimension].set_block(place_loc.above(), BlockState::AIR);
             }
 
             // this is so we don't have any conflicts with other tasks placing stuff and
@@ -48,7 +48,7 @@ impl TaskTrait for FallBucketTask {
         }
 
         let current_loc = local.physics.location();
-        let below = global.blocks.first_below(BlockLocation::from(current_loc));
+        let below = global.blocks[local.dimension].first_below(BlockLocation::from(current_loc));
         match below {
             None => {}
             Some((location, _)) => {
@@ -65,13 +65,15 @@ impl TaskTrait for FallBucketTask {
                 let dy = current_loc.y - (f64::from(location.y) + 1.0);
                 if dy < 3.4 {
                     // we don't have to place when going into water
-                    if global.blocks.get_block_simple(location) == Some(SimpleType::Water) {
+                    if global.blocks[local.dimension].get_block_simple(location)
+                        == Some(SimpleType::Water)
+                    {
                         return true;
                     }
 
                     out.use_item();
                     // out.place_block(location, Face::PosY);
-                    global.blocks.set_block(location.above(), BlockState::WATER);
+                    global.blocks[local.dimension].set_block(location.above(), BlockState::WATER);
                     self.place_loc = Some(location);
                     self.placed = true;
                     self.ticks_since_place = 0;
@@ -25,7 +25,7 @@ impl TaskTrait for HitEntityTask {
         global: &mut GlobalState,
     ) -> bool {
         let mut action = || {
-            let entity_loc = global.entities.by_id(self.id)?.location;
+            let entity_loc = global.entities[local.dimension].by_id(self.id)?.location;
 
             // hit the head
             let target_loc = entity_loc + Displacement::EYE_HEIGHT;
@@ -52,4 +52,18 @@ impl<T: Lazy> TaskTrait for LazyTask<T> {
         let task = self.get(local, global);
         task.expensive(end_at, local, global);
     }
+
+    fn resume(
+        &mut self,
+        out: &mut impl InterfaceOut,
+        local: &mut LocalState,
+        global: &GlobalState,
+    ) {
+        let Some(inner) = self.inner.as_mut() else { return };
+        inner.resume(out, local, global);
+    }
+
+    fn path_remaining(&self) -> Option<usize> {
+        self.inner.as_ref()?.path_remaining()
+    }
 }
@@ -61,4 +61,18 @@ impl<T: TaskStream> TaskTrait for LazyStream<T> {
         let Some(current) = self.current.as_mut() else { return };
         current.expensive(end_by, local, global);
     }
+
+    fn resume(
+        &mut self,
+        out: &mut impl InterfaceOut,
+        local: &mut LocalState,
+        global: &GlobalState,
+    ) {
+        let Some(current) = self.current.as_mut() else { return };
+        current.resume(out, local, global);
+    }
+
+    fn path_remaining(&self) -> Option<usize> {
+        self.current.as_ref()?.path_remaining()
+    }
 }
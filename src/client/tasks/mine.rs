@@ -22,7 +22,9 @@ impl MineTask {
         local: &mut LocalState,
         global: &GlobalState,
     ) -> Self {
-        let kind = global.blocks.get_block_kind(location).unwrap();
+        let kind = global.blocks[local.dimension]
+            .get_block_kind(location)
+            .unwrap();
 
         let tool = local.inventory.switch_tool(kind, &global.block_data, out);
 
@@ -62,11 +64,27 @@ impl TaskTrait for MineTask {
         out.swing_arm();
         if self.ticks == 0 {
             out.mine(self.location, Mine::Finished, self.face);
-            global.blocks.set_block(self.location, BlockState::AIR);
+            global.blocks[local.dimension].set_block(self.location, BlockState::AIR);
             true
         } else {
             self.ticks -= 1;
             false
         }
     }
+
+    /// the tool may have been switched and the server forgets an interrupted
+    /// dig, so dig again from the start
+    fn resume(
+        &mut self,
+        out: &mut impl InterfaceOut,
+        local: &mut LocalState,
+        global: &GlobalState,
+    ) {
+        self.first = true;
+
+        let world = &global.blocks[local.dimension];
+        let Some(kind) = world.get_block_kind(self.location) else { return };
+        let tool = local.inventory.switch_tool(kind, &global.block_data, out);
+        self.ticks = tool.wait_time(kind, false, true, &global.block_data) + 1;
+    }
 }
@@ -1,6 +1,7 @@
 use std::time::Instant;
 
 use bridge::BridgeTask;
+use build::Build;
 use center::CenterTask;
 use compound::CompoundTask;
 use delay::DelayTask;
@@ -16,6 +17,7 @@ use mine_region::MineRegion;
 use navigate::BlockTravelNearTask;
 use pillar::PillarTask;
 use pillar_and_mine::PillarAndMineTask;
+use place_block::PlaceBlockTask;
 
 use crate::{
     client::{
@@ -32,6 +34,7 @@ use crate::{
 
 pub mod attack_entity;
 pub mod bridge;
+pub mod build;
 pub mod center;
 pub mod compound;
 pub mod delay;
@@ -48,6 +51,7 @@ pub mod mine_region;
 pub mod navigate;
 pub mod pillar;
 pub mod pillar_and_mine;
+pub mod place_block;
 pub mod safe_mine_coord;
 pub mod stream;
 
@@ -68,12 +72,30 @@ pub trait TaskTrait {
     /// called every game loop cycle so if the task hasn't finished it by
     /// {`end_by`} it should instead until this function is called again.
     fn expensive(&mut self, _end_by: Instant, _local: &mut LocalState, _global: &GlobalState) {}
+
+    /// Called before the task ticks again after another task ran in between
+    /// (or the bot reconnected). Tasks which depend on the held item or on
+    /// an action in progress, such as digging, restart it here.
+    fn resume(
+        &mut self,
+        _out: &mut impl InterfaceOut,
+        _local: &mut LocalState,
+        _global: &GlobalState,
+    ) {
+    }
+
+    /// the number of moves left on the path the task is following, if it is
+    /// following one
+    fn path_remaining(&self) -> Option<usize> {
+        None
+    }
 }
 
 pub type GoMineTopTask = LazyTask<GoMineTop>;
 pub type MineRegionTask = LazyStream<MineRegion>;
 pub type SafeMineRegionTask = LazyTask<SafeMineRegion>;
 pub type AttackEntityTask = LazyStream<AttackEntity>;
+pub type BuildTask = LazyStream<Build>;
 
 #[allow(clippy::enum_variant_names)]
 #[enum_dispatch(TaskTrait)]
@@ -97,4 +119,6 @@ pub enum Task {
     PillarAndMineTask,
     MineLayerTask,
     FallBucketTask,
+    BuildTask,
+    PlaceBlockTask,
 }
@@ -112,4 +112,22 @@ impl<H: Heuristic + Send + Sync, G: GoalCheck + Send + Sync> TaskTrait for Navig
             Increment::InProgress => {}
         }
     }
+
+    /// the bot may have moved while the task was suspended, e.g. by falling,
+    /// so the path is calculated again from where it is now
+    fn resume(
+        &mut self,
+        _out: &mut impl InterfaceOut,
+        local: &mut LocalState,
+        _global: &GlobalState,
+    ) {
+        self.follower = None;
+        self.problem
+            .recalc(MoveNode::simple(local.physics.location().into()));
+        self.calculate = true;
+    }
+
+    fn path_remaining(&self) -> Option<usize> {
+        self.follower.as_ref().map(Follower::remaining)
+    }
 }
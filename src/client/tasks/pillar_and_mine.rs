@@ -43,7 +43,7 @@ impl TaskStream for PillarOrMine {
 
         let above1 = local.physics.location() + Displacement::new(0., 2.5, 0.);
         let mut set = HashSet::new();
-        Physics::in_cross_section(above1, &global.blocks, &mut set);
+        Physics::in_cross_section(above1, &global.blocks[local.dimension], &mut set);
 
         macro_rules! mine_task {
             ($position:expr) => {{
@@ -57,7 +57,7 @@ impl TaskStream for PillarOrMine {
             mine_task!(position)
         } else {
             let above2 = local.physics.location() + Displacement::new(0., 3.5, 0.);
-            Physics::in_cross_section(above2, &global.blocks, &mut set);
+            Physics::in_cross_section(above2, &global.blocks[local.dimension], &mut set);
             if let Some(&position) = set.iter().next() {
                 mine_task!(position)
             } else {
@@ -0,0 +1,121 @@
+use float_ord::FloatOrd;
+use interfaces::types::{BlockLocation, BlockState, SimpleType};
+
+use crate::{
+    client::{
+        state::{global::GlobalState, local::LocalState},
+        tasks::TaskTrait,
+    },
+    protocol::{Face, InterfaceOut},
+    types::Displacement,
+};
+
+/// Place a block of the kind of `state` at `location` for a build. Blocks
+/// which cannot be placed are given up on in the [`BuildAlloc`].
+///
+/// [`BuildAlloc`]: crate::client::state::global::build_alloc::BuildAlloc
+pub struct PlaceBlockTask {
+    location: BlockLocation,
+    state: BlockState,
+    attempts: u32,
+    /// ticks to wait for the server to confirm the last placement
+    wait: u32,
+}
+
+impl PlaceBlockTask {
+    /// how far away blocks can be placed
+    pub const REACH: f64 = 4.5;
+
+    const MAX_ATTEMPTS: u32 = 3;
+    const CONFIRM_TICKS: u32 = 10;
+
+    pub const fn new(location: BlockLocation, state: BlockState) -> Self {
+        Self {
+            location,
+            state,
+            attempts: 0,
+            wait: 0,
+        }
+    }
+
+    /// the solid block to place against and the face of it to click, closest to
+    /// the eyes first
+    fn against(&self, local: &LocalState, global: &GlobalState) -> Option<(BlockLocation, Face)> {
+        let world = &global.blocks[local.dimension];
+        let eye = local.physics.location() + Displacement::EYE_HEIGHT;
+
+        (0..6)
+            .map(Face::from)
+            .filter_map(|face| {
+                let unit = face.unit_location();
+                let against = BlockLocation::new(
+                    self.location.x - unit.x,
+                    self.location.y - unit.y,
+                    self.location.z - unit.z,
+                );
+
+                let solid = world.get_block_simple(against) == Some(SimpleType::Solid);
+                solid.then_some((against, face))
+            })
+            .min_by_key(|&(against, face)| {
+                let clicked = against.faces()[face as usize];
+                FloatOrd(clicked.dist2(eye))
+            })
+    }
+}
+
+impl TaskTrait for PlaceBlockTask {
+    fn tick(
+        &mut self,
+        out: &mut impl InterfaceOut,
+        local: &mut LocalState,
+        global: &mut GlobalState,
+    ) -> bool {
+        let kind = self.state.kind();
+        let world = &global.blocks[local.dimension];
+
+        if world.get_block_kind(self.location) == Some(kind) {
+            return true;
+        }
+
+        if self.wait > 0 {
+            self.wait -= 1;
+            return false;
+        }
+
+        // another block is in the way. We do not mine it
+        let replaceable = matches!(
+            world.get_block_simple(self.location),
+            Some(SimpleType::WalkThrough | SimpleType::Water)
+        );
+
+        let eye = local.physics.location() + Displacement::EYE_HEIGHT;
+        let in_reach = self.location.true_center().dist2(eye) <= Self::REACH * Self::REACH;
+
+        let against = self.against(local, global);
+
+        let (Some((against, face)), true, true) = (against, replaceable, in_reach) else {
+            global.build.give_up(local.bot_id, self.location);
+            return true;
+        };
+
+        if self.attempts >= Self::MAX_ATTEMPTS {
+            global.build.give_up(local.bot_id, self.location);
+            return true;
+        }
+
+        if !local.inventory.switch_selector(out, |held| held == kind) {
+            global
+                .build
+                .report_missing(local.bot_id, self.location, kind);
+            return true;
+        }
+
+        local.physics.place_hand_face(against, face);
+
+        self.attempts += 1;
+        self.wait = Self::CONFIRM_TICKS;
+
+        false
+    }
+}
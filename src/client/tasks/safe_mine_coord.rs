@@ -32,14 +32,17 @@ impl Lazy for SafeMineRegion {
                 return false;
             }
 
-            match global.blocks.get_block_exact(loc).map(|x| x.kind().id()) {
+            match global.blocks[local.dimension]
+                .get_block_exact(loc)
+                .map(|x| x.kind().id())
+            {
                 // water or lava
                 Some(8..=11) => {
                     println!(
                         "skipping region {}, {} because of {:?} at {}",
                         center.x,
                         center.z,
-                        global.blocks.get_block_exact(loc),
+                        global.blocks[local.dimension].get_block_exact(loc),
                         loc
                     );
                     true
@@ -11,6 +11,7 @@
 #![feature(default_free_fn)]
 #![feature(fs_try_exists)]
 #![feature(async_fn_in_trait)]
+#![feature(once_cell)]
 #![deny(
     clippy::await_holding_refcell_ref,
     clippy::await_holding_lock,
@@ -77,7 +78,7 @@ extern crate enum_dispatch;
 #[macro_use]
 extern crate swarm_bot_packets;
 
-use std::pin::Pin;
+use std::{pin::Pin, time::Duration};
 
 use anyhow::Context;
 use futures::Stream;
@@ -86,13 +87,28 @@ use tokio_stream::{wrappers::ReceiverStream, StreamExt};
 
 use crate::{
     bootstrap::{
-        dns::normalize_address, opts::CliOptions, storage::BotConnectionData, BotConnection,
+        config::{Config, DEFAULT_CONFIG_FILE},
+        dns::normalize_address,
+        microsoft::MicrosoftEndpoints,
+        opts::{AuthMethod, CliOptions, Command},
+        proxy_pool,
+        proxy_pool::{PoolOptions, ProxyPool},
+        quota::Quota,
+        storage::{Authentication, BotConnectionData, UserCache, CACHE_FILE},
+        BotConnection,
     },
-    client::runner::{Runner, RunnerOptions},
+    client::{
+        api,
+        commands::CommandReceiver,
+        reconnect::ReconnectPolicy,
+        runner::{Runner, RunnerOptions},
+    },
+    storage::persist::WorldSave,
 };
 
 mod bootstrap;
 mod client;
+mod metrics;
 mod protocol;
 mod schematic;
 mod storage;
@@ -106,8 +122,9 @@ fn main() {
     let local = task::LocalSet::new();
     local.block_on(&rt, async move {
         match run().await {
-            // this should never happen as this should be an infinite loop
-            Ok(_) => println!("Program exited without errors somehow"),
+            // the runner is an infinite loop so this only happens for one-off modes such
+            // as --ping
+            Ok(_) => {}
 
             // print the error in non-debug fashion
             Err(err) => println!("{err}"),
@@ -117,58 +134,287 @@ fn main() {
 
 async fn run() -> anyhow::Result<()> {
     // grab options from CLI
+    let cli = CliOptions::get();
+
+    if let Some(command) = &cli.command {
+        return match command {
+            Command::RotatePassphrase => UserCache::rotate_passphrase(CACHE_FILE.into()),
+        };
+    }
+
+    // options given on the command line override the config file
+    let mut config = Config::load(cli.config.as_deref())?;
+    config.apply(&cli);
+
+    if cli.print_config {
+        print!("{}", config.to_toml()?);
+        return Ok(());
+    }
+
+    let travel_config = config.path_configs()?;
+    let ws_address = config.ws_address();
+
+    let servers: Vec<_> = config
+        .servers
+        .iter()
+        .cloned()
+        .zip(config.servers()?)
+        .collect();
+    if servers.is_empty() {
+        anyhow::bail!("no server was given. Pass a host or set servers in {DEFAULT_CONFIG_FILE}");
+    }
+
     let CliOptions {
-        users_file,
-        proxies_file,
-        host,
+        ping,
+        capture,
+        max_world_mb,
+        world_dir,
+        world_save_secs,
+        export_dir,
+        schematic_dir,
+        reconnect_attempts,
+        reconnect_delay_ms,
+        max_reconnect_delay_ms,
+        no_reconnect_on,
+        msa_client_id,
+        encrypt_cache,
+        ..
+    } = cli;
+
+    let Config {
         count,
         version,
-        port,
-        delay_ms,
-        ws_port,
+        users_file,
+        proxies_file,
         proxy,
         offline,
-    } = CliOptions::get();
+        auth,
+        delay_ms,
+        proxy_strategy,
+        max_bots_per_proxy,
+        proxy_check_secs,
+        api_address,
+        api_token,
+        metrics_address,
+        ..
+    } = config;
+
+    let mut targets = Vec::new();
+
+    for (name, (host, port)) in servers {
+        // looks up DNS records, etc. This is important where there is a redirect
+        // for instance, 2b2t.org has a DNS redirect
+        let server_address = normalize_address(&host, port).await;
+
+        if ping {
+            let protocol::status::Ping { status, latency } =
+                protocol::status::ping(&server_address)
+                    .await
+                    .with_context(|| format!("could not ping {name}"))?;
+
+            let protocol::status::ServerStatus {
+                version, players, ..
+            } = &status;
+
+            println!("{name}");
+            println!("version: {} (protocol {})", version.name, version.protocol);
+            println!("players: {}/{}", players.online, players.max);
+            println!("latency: {}ms", latency.as_millis());
+            println!("motd: {}", status.motd());
+            continue;
+        }
+
+        let version = if let Some(version) = version {
+            version
+        } else {
+            let protocol::status::Ping { status, .. } = protocol::status::ping(&server_address)
+                .await
+                .with_context(|| {
+                    format!("could not detect the version of {name}. Specify it with --version")
+                })?;
+
+            let version = status.version.protocol;
+            println!(
+                "{name}: detected {} (protocol {version}) — {}",
+                status.version.name,
+                status.motd()
+            );
+            version
+        };
+
+        targets.push((name, server_address, version));
+    }
+
+    if ping {
+        return Ok(());
+    }
+
+    let proxies = if proxy && !offline {
+        let options = PoolOptions {
+            strategy: proxy_strategy,
+            max_bots_per_proxy,
+            ..PoolOptions::default()
+        };
+        let proxies = ProxyPool::load(&proxies_file, options)?.shared();
 
-    // A list of users we will login
+        // proxies are checked against every server
+        let probe_addresses: Vec<_> = targets
+            .iter()
+            .map(|(_, address, _)| address.clone())
+            .collect();
 
-    // looks up DNS records, etc. This is important where there is a redirect
-    // for instance, 2b2t.org has a DNS redirect
-    let server_address = normalize_address(&host, port).await;
+        println!("checking proxies");
+        proxy_pool::probe(&proxies, &probe_addresses).await;
+        proxies.borrow().print_stats();
+
+        if proxy_check_secs > 0 {
+            proxy_pool::spawn_health_checks(
+                proxies.clone(),
+                probe_addresses,
+                Duration::from_secs(proxy_check_secs),
+            );
+        }
+        proxies
+    } else {
+        ProxyPool::default().shared()
+    };
+
+    // every server gets `count` bots from the same pool of accounts. The API
+    // can raise the quota to start more
+    let quota = Quota::new(count * targets.len());
 
     let connection_data: Pin<Box<dyn Stream<Item = BotConnectionData>>> = match offline {
-        true => Box::pin(BotConnectionData::offline_random().take(count)),
+        true => Box::pin(quota.clone().gate(BotConnectionData::offline_random())),
         false => {
-            let bot_receiver =
-                BotConnectionData::load_from_files(&users_file, &proxies_file, proxy, count)?;
+            let auth = match auth {
+                AuthMethod::Mojang => Authentication::Mojang,
+                AuthMethod::Microsoft => {
+                    let mut endpoints = MicrosoftEndpoints::default();
+                    if let Some(client_id) = msa_client_id {
+                        endpoints.client_id = client_id;
+                    }
+                    Authentication::Microsoft(endpoints)
+                }
+            };
+
+            let bot_receiver = BotConnectionData::load_from_files(
+                &users_file,
+                proxies.clone(),
+                quota.clone(),
+                auth,
+                encrypt_cache,
+            )?;
 
             Box::pin(ReceiverStream::new(bot_receiver))
         }
     };
 
-    // taking the users and generating connections to the Minecraft server
-    let connections: ReceiverStream<_> =
-        BotConnection::stream(server_address, connection_data).into();
+    let namespaces: Vec<_> = targets.iter().map(|(name, ..)| name.clone()).collect();
+    let command_receivers = CommandReceiver::init(&ws_address, &namespaces).await?;
+
+    if namespaces.len() > 1 {
+        println!("commands for a server are sent to ws://{ws_address}/<server>");
+    }
+
+    if let Some(address) = &metrics_address {
+        metrics::serve(address).await?;
+        println!("serving metrics on http://{address}/metrics");
+    }
 
-    // only return bot connections which were successful
-    let connections = connections.filter_map(|elem| match elem {
-        Ok(v) => Some(v),
-        Err(e) => {
-            println!("was unable to create a connection for a user: {e}");
-            None
+    let api_receivers: Vec<_> = match api_address {
+        Some(address) => {
+            let token = api_token.context("the API needs a token. Set api_token")?;
+            anyhow::ensure!(!token.is_empty(), "the API token must not be empty");
+            let receivers = api::init(&address, token, &namespaces, quota).await?;
+            println!("serving the API on http://{address}");
+            receivers.into_iter().map(Some).collect()
         }
-    });
+        None => namespaces.iter().map(|_| None).collect(),
+    };
+
+    let data_per_target = bootstrap::distribute(connection_data, targets.len());
+
+    let runners = targets
+        .into_iter()
+        .zip(data_per_target)
+        .zip(command_receivers)
+        .zip(api_receivers)
+        .map(
+            |((((name, server_address, version), connection_data), commands), api)| {
+                // taking the users and generating connections to the Minecraft server
+                let connections: ReceiverStream<_> = BotConnection::stream(
+                    server_address,
+                    ReceiverStream::new(connection_data),
+                    proxies.clone(),
+                )
+                .into();
+
+                // only return bot connections which were successful
+                let connections = connections.filter_map(|elem| match elem {
+                    Ok(v) => Some(v),
+                    Err(e) => {
+                        println!("was unable to create a connection for a user: {e}");
+                        None
+                    }
+                });
+
+                let run_options = RunnerOptions {
+                    delay_ms,
+                    commands,
+                    api,
+                    travel_config: travel_config.clone(),
+                    capture_dir: capture.clone(),
+                    max_world_memory: max_world_mb.map(|mb| mb * 1024 * 1024),
+                    reconnect: ReconnectPolicy {
+                        initial_delay: Duration::from_millis(reconnect_delay_ms),
+                        max_delay: Duration::from_millis(max_reconnect_delay_ms),
+                        max_attempts: reconnect_attempts,
+                        never_on: no_reconnect_on.clone(),
+                        ..ReconnectPolicy::default()
+                    },
+                    proxies: proxies.clone(),
+                    world_save: world_dir.as_deref().map(|dir| {
+                        WorldSave::new(dir, &name, Duration::from_secs(world_save_secs))
+                    }),
+                    server: name.clone(),
+                    export_dir: export_dir.clone(),
+                    schematic_dir: schematic_dir.clone(),
+                };
+
+                async move {
+                    run_server(version, connections, run_options)
+                        .await
+                        .with_context(|| format!("error running {name}"))
+                }
+            },
+        );
 
-    let run_options = RunnerOptions { delay_ms, ws_port };
+    // every server runs on this thread. Expensive tasks of all of them share
+    // the same thread pool
+    futures::future::try_join_all(runners).await?;
 
-    // launch the runner with the appropriate protocol version
+    Ok(())
+}
+
+/// launch the runner of one server with the appropriate protocol version
+async fn run_server(
+    version: usize,
+    connections: impl Stream<Item = BotConnection> + 'static,
+    run_options: RunnerOptions,
+) -> anyhow::Result<()> {
     match version {
+        47 => Runner::<protocol::v47::Protocol>::run(connections, run_options)
+            .await
+            .context("Error starting up 1.8")?, // 1.8.9
         340 => Runner::<protocol::v340::Protocol>::run(connections, run_options)
             .await
             .context("Error starting up 1.12")?, // 1.12.2
-        _ => {
-            panic!("version {version} does not exist")
-        }
+        754 => Runner::<protocol::v754::Protocol>::run(connections, run_options)
+            .await
+            .context("Error starting up 1.16")?, // 1.16.5
+        _ => anyhow::bail!(
+            "version {version} is not supported. Supported versions are 47, 340 and 754"
+        ),
     }
 
     Ok(())
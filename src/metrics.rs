@@ -0,0 +1,280 @@
+//! Prometheus metrics, served at `/metrics` on `--metrics-address`
+
+use std::{collections::HashMap, convert::Infallible, sync::OnceLock};
+
+use anyhow::Context;
+use hyper::{
+    header::{HeaderValue, CONTENT_TYPE},
+    server::conn::Http,
+    service::service_fn,
+    Body, Method, Response, StatusCode,
+};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use swarm_bot_packets::types::PacketState;
+use tokio::net::TcpListener;
+
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+/// the metrics of this process
+pub fn metrics() -> &'static Metrics {
+    METRICS.get_or_init(Metrics::new)
+}
+
+/// the buckets of durations, in seconds. A tick should take 50ms
+const DURATION_BUCKETS: &[f64] = &[
+    0.001, 0.005, 0.01, 0.02, 0.03, 0.04, 0.05, 0.075, 0.1, 0.25, 0.5, 1.0,
+];
+
+/// Every metric. The metrics of a server are labelled with its name
+pub struct Metrics {
+    registry: Registry,
+    /// how long each tick of the game loop takes
+    pub tick_duration: HistogramVec,
+    /// how long the expensive tasks (such as A*) of a tick take
+    pub expensive_duration: HistogramVec,
+    /// the bots which are logged in
+    pub bots_connected: IntGaugeVec,
+    /// the bots which are logging in or waiting to reconnect
+    pub bots_pending: IntGaugeVec,
+    /// packets by direction, protocol, state and id
+    packets: IntCounterVec,
+    /// the bytes of packets (as sent over the socket) by direction, protocol,
+    /// state and id
+    packet_bytes: IntCounterVec,
+    /// the nodes A* took off its open set
+    pub astar_nodes: IntCounter,
+    /// the chunk columns kept in memory, by server and dimension
+    pub chunks_loaded: IntGaugeVec,
+    /// the approximate bytes used by chunk columns, by server and dimension
+    pub world_memory: IntGaugeVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+
+        let histogram = |name: &str, help: &str| {
+            let opts = HistogramOpts::new(name, help).buckets(DURATION_BUCKETS.to_vec());
+            let histogram = HistogramVec::new(opts, &["server"]).unwrap();
+            registry.register(Box::new(histogram.clone())).unwrap();
+            histogram
+        };
+
+        let gauge = |name: &str, help: &str, labels: &[&str]| {
+            let gauge = IntGaugeVec::new(Opts::new(name, help), labels).unwrap();
+            registry.register(Box::new(gauge.clone())).unwrap();
+            gauge
+        };
+
+        let counter = |name: &str, help: &str| {
+            let labels = &["direction", "protocol", "state", "id"];
+            let counter = IntCounterVec::new(Opts::new(name, help), labels).unwrap();
+            registry.register(Box::new(counter.clone())).unwrap();
+            counter
+        };
+
+        let astar_nodes = IntCounter::new(
+            "swarmbot_astar_nodes_expanded_total",
+            "The nodes A* expanded",
+        )
+        .unwrap();
+        registry.register(Box::new(astar_nodes.clone())).unwrap();
+
+        Self {
+            tick_duration: histogram(
+                "swarmbot_tick_duration_seconds",
+                "How long each tick of the game loop takes",
+            ),
+            expensive_duration: histogram(
+                "swarmbot_expensive_tasks_duration_seconds",
+                "How long the multithreaded tasks (such as A*) of a tick take",
+            ),
+            bots_connected: gauge(
+                "swarmbot_bots_connected",
+                "The bots which are logged in",
+                &["server"],
+            ),
+            bots_pending: gauge(
+                "swarmbot_bots_pending",
+                "The bots which are logging in or waiting to reconnect",
+                &["server"],
+            ),
+            packets: counter(
+                "swarmbot_packets_total",
+                "The packets by direction, protocol, state and id",
+            ),
+            packet_bytes: counter(
+                "swarmbot_packet_bytes_total",
+                "The bytes of packets by direction, protocol, state and id",
+            ),
+            astar_nodes,
+            chunks_loaded: gauge(
+                "swarmbot_chunks_loaded",
+                "The chunk columns kept in memory",
+                &["server", "dimension"],
+            ),
+            world_memory: gauge(
+                "swarmbot_world_memory_bytes",
+                "The approximate memory used by chunk columns",
+                &["server", "dimension"],
+            ),
+            registry,
+        }
+    }
+
+    /// counters of the packets a connection using `protocol` receives
+    pub fn received(&self, protocol: i32) -> PacketCounters {
+        self.packet_counters("in", protocol)
+    }
+
+    /// counters of the packets a connection using `protocol` sends
+    pub fn sent(&self, protocol: i32) -> PacketCounters {
+        self.packet_counters("out", protocol)
+    }
+
+    fn packet_counters(&self, direction: &'static str, protocol: i32) -> PacketCounters {
+        PacketCounters {
+            direction,
+            protocol: protocol.to_string(),
+            packets: self.packets.clone(),
+            packet_bytes: self.packet_bytes.clone(),
+            cached: HashMap::new(),
+        }
+    }
+
+    /// every metric in the Prometheus text format
+    fn encode(&self) -> anyhow::Result<Vec<u8>> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(buffer)
+    }
+}
+
+/// Counts the packets of one direction of a connection. The counters of each
+/// packet are only looked up the first time it is counted, as bots send and
+/// receive thousands of packets a second
+pub struct PacketCounters {
+    direction: &'static str,
+    protocol: String,
+    packets: IntCounterVec,
+    packet_bytes: IntCounterVec,
+    /// the packet and byte counters of each state and id
+    cached: HashMap<(PacketState, u32), (IntCounter, IntCounter)>,
+}
+
+impl PacketCounters {
+    /// count a packet of `state` with `id` of `bytes` bytes
+    pub fn count(&mut self, state: PacketState, id: u32, bytes: usize) {
+        let (packets, packet_bytes) = self.cached.entry((state, id)).or_insert_with(|| {
+            let state = state.to_string();
+            let id = format!("{id:#04x}");
+            let labels = [
+                self.direction,
+                self.protocol.as_str(),
+                state.as_str(),
+                id.as_str(),
+            ];
+
+            (
+                self.packets.with_label_values(&labels),
+                self.packet_bytes.with_label_values(&labels),
+            )
+        });
+
+        packets.inc();
+        packet_bytes.inc_by(bytes as u64);
+    }
+}
+
+/// the response to a request to the metrics server
+fn respond(request: &hyper::Request<Body>) -> Response<Body> {
+    if *request.method() != Method::GET || request.uri().path() != "/metrics" {
+        let mut response = Response::new(Body::from("not found"));
+        *response.status_mut() = StatusCode::NOT_FOUND;
+        return response;
+    }
+
+    match metrics().encode() {
+        Ok(text) => {
+            let mut response = Response::new(Body::from(text));
+            let content_type = HeaderValue::from_static(prometheus::TEXT_FORMAT);
+            response.headers_mut().insert(CONTENT_TYPE, content_type);
+            response
+        }
+        Err(err) => {
+            let mut response = Response::new(Body::from(format!("{err:#}")));
+            *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+            response
+        }
+    }
+}
+
+/// serve the metrics at `/metrics` on `address`
+pub async fn serve(address: &str) -> anyhow::Result<()> {
+    let server = TcpListener::bind(address)
+        .await
+        .with_context(|| format!("could not bind the metrics to {address}"))?;
+
+    tokio::task::spawn_local(async move {
+        loop {
+            let (stream, _) = match server.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    println!("error accepting metrics connection: {e}");
+                    continue;
+                }
+            };
+
+            let service =
+                service_fn(|request| async move { Ok::<_, Infallible>(respond(&request)) });
+
+            tokio::task::spawn_local(async move {
+                if let Err(e) = Http::new().serve_connection(stream, service).await {
+                    println!("error with metrics connection: {e}");
+                }
+            });
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use swarm_bot_packets::types::PacketState;
+
+    use crate::metrics::Metrics;
+
+    #[test]
+    fn test_encode() {
+        let metrics = Metrics::new();
+
+        let mut received = metrics.received(340);
+        received.count(PacketState::Play, 0x20, 10);
+        received.count(PacketState::Play, 0x20, 5);
+        received.count(PacketState::Login, 0x02, 7);
+
+        // ids of other protocols are counted apart
+        metrics.received(47).count(PacketState::Play, 0x20, 1);
+        metrics.sent(340).count(PacketState::Play, 0x0f, 3);
+        metrics.astar_nodes.inc_by(42);
+        metrics.bots_connected.with_label_values(&["hub"]).set(3);
+
+        let text = String::from_utf8(metrics.encode().unwrap()).unwrap();
+
+        for line in [
+            r#"swarmbot_packets_total{direction="in",id="0x20",protocol="340",state="play"} 2"#,
+            r#"swarmbot_packet_bytes_total{direction="in",id="0x20",protocol="340",state="play"} 15"#,
+            r#"swarmbot_packets_total{direction="in",id="0x02",protocol="340",state="login"} 1"#,
+            r#"swarmbot_packets_total{direction="in",id="0x20",protocol="47",state="play"} 1"#,
+            r#"swarmbot_packets_total{direction="out",id="0x0f",protocol="340",state="play"} 1"#,
+            "swarmbot_astar_nodes_expanded_total 42",
+            r#"swarmbot_bots_connected{server="hub"} 3"#,
+        ] {
+            assert!(text.lines().any(|l| l == line), "{line} is not in {text}");
+        }
+    }
+}
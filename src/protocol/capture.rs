@@ -0,0 +1,116 @@
+//! Capturing the packets a bot receives so they can be replayed offline.
+//!
+//! A capture starts with [`MAGIC`] and the protocol version. It is followed by
+//! one record per packet: the tick it was processed on (`u64`), the packet id
+//! (`VarInt`) and the packet data (`VarInt` prefixed). The data is stored
+//! after decryption and decompression.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::Context;
+use swarm_bot_packets::{
+    types::{RawVec, VarInt},
+    write::ByteWriter,
+};
+
+use crate::types::PacketData;
+
+/// the first bytes of every capture
+const MAGIC: &[u8; 4] = b"SBCP";
+
+/// Records every packet a bot receives. Enabled with `--capture`.
+pub struct PacketRecorder {
+    writer: BufWriter<File>,
+}
+
+impl PacketRecorder {
+    /// create (or truncate) a capture at `path` for `protocol`
+    pub fn create(path: &Path, protocol: u32) -> anyhow::Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("could not create capture {}", path.display()))?;
+
+        let mut writer = BufWriter::new(file);
+
+        let mut header = ByteWriter::new();
+        header.write(&MAGIC[..]).write(VarInt(protocol as i32));
+        writer.write_all(&header.freeze())?;
+
+        Ok(Self { writer })
+    }
+
+    /// record a packet processed on `tick`
+    pub fn record(&mut self, tick: u64, data: &PacketData) -> anyhow::Result<()> {
+        // reading advances the reader so we need our own copy
+        let mut reader = data.reader.clone();
+        let len = reader.len();
+        let RawVec(bytes): RawVec = reader.read_like(&len);
+
+        let mut record = ByteWriter::new();
+        record
+            .write(tick)
+            .write(VarInt(data.id as i32))
+            .write(bytes);
+
+        self.writer.write_all(&record.freeze())?;
+        Ok(())
+    }
+
+    /// flush buffered records to disk. Called at the end of every tick so a
+    /// crash loses at most one tick of packets.
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// A packet read from a capture
+#[cfg(test)]
+pub struct CapturedPacket {
+    pub tick: u64,
+    pub data: PacketData,
+}
+
+/// Read a capture that was recorded with `protocol`.
+#[cfg(test)]
+pub fn read_capture(
+    mut input: impl std::io::Read,
+    protocol: u32,
+) -> anyhow::Result<Vec<CapturedPacket>> {
+    use swarm_bot_packets::read::ByteReader;
+
+    let mut bytes = Vec::new();
+    input.read_to_end(&mut bytes)?;
+
+    anyhow::ensure!(bytes.starts_with(MAGIC), "not a packet capture");
+
+    let mut reader = ByteReader::new(bytes);
+    let RawVec(_magic): RawVec = reader.read_like(&MAGIC.len());
+
+    let VarInt(recorded) = reader.read();
+    anyhow::ensure!(
+        recorded as u32 == protocol,
+        "capture was recorded with protocol {recorded}, not {protocol}"
+    );
+
+    let mut packets = Vec::new();
+
+    while !reader.is_empty() {
+        let tick: u64 = reader.read();
+        let VarInt(id) = reader.read();
+        let data: Vec<u8> = reader.read();
+
+        packets.push(CapturedPacket {
+            tick,
+            data: PacketData {
+                id: id as u32,
+                reader: ByteReader::new(data),
+            },
+        });
+    }
+
+    Ok(packets)
+}
@@ -3,9 +3,10 @@ use std::{
     task::{Context, Poll},
 };
 
+use anyhow::Context as _;
 use swarm_bot_packets::{
     read::{ByteReadable, ByteReader, LenRead},
-    types::{Packet, RawVec, VarInt},
+    types::{Packet, PacketState, RawVec, VarInt},
 };
 use tokio::{
     io::{AsyncRead, AsyncReadExt, BufReader, ReadBuf},
@@ -13,6 +14,7 @@ use tokio::{
 };
 
 use crate::{
+    metrics::{metrics, PacketCounters},
     protocol::io::{Aes, ZLib},
     types::PacketData,
 };
@@ -20,6 +22,9 @@ use crate::{
 pub struct PacketReader {
     reader: EncryptedReader,
     compression: Option<ZLib>,
+    /// the state of the connection, which packets are counted by
+    state: PacketState,
+    counters: PacketCounters,
 }
 
 struct EncryptedReader {
@@ -46,8 +51,9 @@ impl AsyncRead for EncryptedReader {
     }
 }
 
-impl From<OwnedReadHalf> for PacketReader {
-    fn from(read: OwnedReadHalf) -> Self {
+impl PacketReader {
+    /// a reader of packets of `protocol`, starting in the login state
+    pub fn new(read: OwnedReadHalf, protocol: i32) -> Self {
         let reader = BufReader::new(read);
 
         let reader = EncryptedReader {
@@ -58,11 +64,16 @@ impl From<OwnedReadHalf> for PacketReader {
         Self {
             reader,
             compression: None,
+            state: PacketState::Login,
+            counters: metrics().received(protocol),
         }
     }
-}
 
-impl PacketReader {
+    /// the connection moved to `state`
+    pub fn state(&mut self, state: PacketState) {
+        self.state = state;
+    }
+
     pub fn encryption(&mut self, key: &[u8]) {
         self.reader.cipher = Some(Aes::new(key));
     }
@@ -71,22 +82,27 @@ impl PacketReader {
         self.compression = Some(ZLib::new(threshold));
     }
 
+    /// read the next packet. Fails once the server closes the socket
     pub async fn read(&mut self) -> anyhow::Result<PacketData> {
         let pkt_len;
 
         // ignore 0-sized packets
         loop {
-            let len = VarInt::read_async(Pin::new(&mut self.reader)).await;
-            let len = len.0;
+            let VarInt(len) = VarInt::read_async(Pin::new(&mut self.reader))
+                .await
+                .context("could not read the length of a packet")?;
             if len != 0 {
-                pkt_len = len as usize;
+                pkt_len = usize::try_from(len).context("negative packet length")?;
                 break;
             }
         }
 
         // the raw bytes with length determined by pkt_len
         let mut data = vec![0_u8; pkt_len];
-        self.reader.read_exact(&mut data).await.unwrap();
+        self.reader
+            .read_exact(&mut data)
+            .await
+            .context("could not read a packet")?;
 
         let mut reader = ByteReader::new(data);
 
@@ -98,6 +114,8 @@ impl PacketReader {
         let mut reader = ByteReader::new(data);
         let VarInt(id) = reader.read();
 
+        self.counters.count(self.state, id as u32, pkt_len);
+
         Ok(PacketData {
             id: id as u32,
             reader,
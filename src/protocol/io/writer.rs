@@ -4,11 +4,15 @@ use swarm_bot_packets::{
 };
 use tokio::{io::AsyncWriteExt, net::tcp::OwnedWriteHalf, sync::mpsc::UnboundedSender};
 
-use crate::protocol::io::{Aes, ZLib};
+use crate::{
+    metrics::{metrics, PacketCounters},
+    protocol::io::{Aes, ZLib},
+};
 
 pub struct PacketWriter {
     writer: EncryptedWriter,
     compression: Option<ZLib>,
+    counters: PacketCounters,
 }
 
 struct EncryptedWriter {
@@ -26,21 +30,11 @@ impl EncryptedWriter {
     }
 }
 
-impl From<OwnedWriteHalf> for PacketWriter {
-    fn from(writer: OwnedWriteHalf) -> Self {
-        let writer = EncryptedWriter {
-            writer,
-            cipher: None,
-        };
-
-        Self {
-            writer,
-            compression: None,
-        }
-    }
-}
-
-fn data<T: Packet + ByteWritable>(packet: T, compression: Option<ZLib>) -> Vec<u8> {
+fn data<T: Packet + ByteWritable>(
+    packet: T,
+    compression: Option<ZLib>,
+    counters: &mut PacketCounters,
+) -> Vec<u8> {
     let data = PktData::from(packet);
 
     let complete_packet = CompletePacket { data };
@@ -48,23 +42,57 @@ fn data<T: Packet + ByteWritable>(packet: T, compression: Option<ZLib>) -> Vec<u
     let mut writer = ByteWriter::new();
 
     complete_packet.write_to_bytes_like(&mut writer, &compression);
-    writer.freeze()
+    let data = writer.freeze();
+
+    counters.count(T::STATE, T::ID, data.len());
+    data
 }
 
 pub struct PacketWriteChannel {
     tx: UnboundedSender<Vec<u8>>,
     compression: Option<ZLib>,
+    counters: PacketCounters,
 }
 
 impl PacketWriteChannel {
+    /// A channel which is not connected to a socket. Written packets can be
+    /// inspected with the returned receiver.
+    #[cfg(test)]
+    pub fn detached(protocol: i32) -> (Self, tokio::sync::mpsc::UnboundedReceiver<Vec<u8>>) {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        (
+            Self {
+                tx,
+                compression: None,
+                counters: metrics().sent(protocol),
+            },
+            rx,
+        )
+    }
+
     pub fn write<T: Packet + ByteWritable>(&mut self, packet: T) {
-        let data = data(packet, self.compression);
+        let data = data(packet, self.compression, &mut self.counters);
 
-        self.tx.send(data).unwrap();
+        // the socket closed. The bot is removed once the reading side notices
+        let _ = self.tx.send(data);
     }
 }
 
 impl PacketWriter {
+    /// a writer of packets of `protocol`
+    pub fn new(writer: OwnedWriteHalf, protocol: i32) -> Self {
+        let writer = EncryptedWriter {
+            writer,
+            cipher: None,
+        };
+
+        Self {
+            writer,
+            compression: None,
+            counters: metrics().sent(protocol),
+        }
+    }
+
     pub fn encryption(&mut self, key: &[u8]) {
         self.writer.cipher = Some(Aes::new(key));
     }
@@ -74,22 +102,32 @@ impl PacketWriter {
     }
 
     pub async fn write<T: Packet + ByteWritable>(&mut self, packet: T) -> anyhow::Result<()> {
-        let mut data = data(packet, self.compression);
+        let mut data = data(packet, self.compression, &mut self.counters);
         self.writer.write_all(&mut data).await
     }
 
     pub fn into_channel(self) -> PacketWriteChannel {
-        let compression = self.compression;
-        let mut writer = self.writer;
+        let Self {
+            mut writer,
+            compression,
+            counters,
+        } = self;
         let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<Vec<u8>>();
 
         tokio::task::spawn_local(async move {
             while let Some(mut elem) = rx.recv().await {
-                writer.write_all(&mut elem).await.unwrap();
+                // the socket closed. The reading side handles the disconnect
+                if writer.write_all(&mut elem).await.is_err() {
+                    return;
+                }
             }
         });
 
-        PacketWriteChannel { tx, compression }
+        PacketWriteChannel {
+            tx,
+            compression,
+            counters,
+        }
     }
 }
 
@@ -142,10 +180,17 @@ impl ByteWritableLike for CompletePacket {
                         .write(data);
                 } else {
                     let data: RawVec = zlib.compress(&data.inner()).unwrap().into();
-                    let compressed_len: VarInt = (data.len() as i32 + uncompressed_len).into();
+
+                    // the packet length includes the length of the VarInt holding the
+                    // uncompressed length, not its value
+                    let mut data_len = ByteWriter::new();
+                    data_len.write(VarInt(uncompressed_len));
+                    let data_len = data_len.freeze();
+
+                    let compressed_len = VarInt((data.len() + data_len.len()) as i32);
                     writer
                         .write(compressed_len)
-                        .write(VarInt(uncompressed_len))
+                        .write(&data_len[..])
                         .write(data);
                 }
             }
@@ -1,3 +1,5 @@
+use std::path::Path;
+
 use interfaces::types::BlockLocation;
 use swarm_bot_packets::types::UUID;
 
@@ -10,7 +12,11 @@ use crate::{
     types::{Direction, Location},
 };
 
+pub mod capture;
+pub mod status;
 pub mod v340;
+pub mod v47;
+pub mod v754;
 
 mod encrypt;
 mod io;
@@ -198,6 +204,13 @@ pub trait Minecraft: Sized {
 pub trait EventQueue {
     /// flush the queue (process all events)
     fn flush(&mut self, processor: &mut impl InterfaceIn);
+
+    /// record every packet that is processed from now on to a capture at
+    /// `path`. See [`capture`](crate::protocol::capture) for the format.
+    fn capture(&mut self, path: &Path) -> anyhow::Result<()> {
+        let path = path.display();
+        anyhow::bail!("cannot capture to {path}: packet capture is not supported for this version")
+    }
 }
 
 /// Information about the client
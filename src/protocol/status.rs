@@ -0,0 +1,212 @@
+//! The status state (server list ping). This is the same for every version
+//! since 1.7 so it is used to find out which [`Minecraft`] implementation a
+//! server needs before any bot logs in.
+//!
+//! <https://wiki.vg/Server_List_Ping>
+//!
+//! [`Minecraft`]: crate::protocol::Minecraft
+
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
+use interfaces::types::Chat;
+use serde::Deserialize;
+use swarm_bot_packets::types::{PacketState, VarInt};
+use tokio::net::TcpStream;
+
+use crate::{
+    bootstrap::Address,
+    protocol::io::{reader::PacketReader, writer::PacketWriter},
+};
+
+mod serverbound {
+    use swarm_bot_packets::{
+        types::VarInt,
+        write::{ByteWritable, ByteWriter},
+        EnumWritable, Packet, Writable,
+    };
+
+    #[derive(Packet, Writable)]
+    #[packet(0x00, Handshake)]
+    pub struct Handshake {
+        /// `-1` as we do not know the version yet
+        pub protocol_version: VarInt,
+        pub host: String,
+        pub port: u16,
+        pub next_state: HandshakeNextState,
+    }
+
+    #[derive(Copy, Clone, EnumWritable)]
+    #[repr(i32)]
+    #[allow(dead_code)]
+    pub enum HandshakeNextState {
+        #[deprecated]
+        Invalid,
+
+        Status,
+        Login,
+    }
+
+    #[derive(Packet)]
+    #[packet(0x00, Status)]
+    pub struct Request;
+
+    impl ByteWritable for Request {
+        fn write_to_bytes(self, _writer: &mut ByteWriter) {}
+    }
+
+    #[derive(Packet, Writable)]
+    #[packet(0x01, Status)]
+    pub struct Ping {
+        pub payload: u64,
+    }
+}
+
+mod clientbound {
+    use swarm_bot_packets::{Packet, Readable};
+
+    #[derive(Packet, Readable)]
+    #[packet(0x00, Status)]
+    pub struct Response {
+        pub json: String,
+    }
+
+    #[derive(Packet, Readable)]
+    #[packet(0x01, Status)]
+    pub struct Pong {
+        pub payload: u64,
+    }
+}
+
+/// The version the server is running
+#[derive(Deserialize, Debug, Clone)]
+pub struct Version {
+    /// e.g. `1.12.2` or `Paper 1.16.5`
+    pub name: String,
+
+    /// the protocol number. This is what `--version` takes
+    pub protocol: usize,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Players {
+    pub max: usize,
+    pub online: usize,
+}
+
+/// Older servers send the MOTD as a plain string instead of a chat object
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+enum Description {
+    Plain(String),
+    Chat(Chat),
+}
+
+/// The JSON response of the server
+#[derive(Deserialize, Debug, Clone)]
+pub struct ServerStatus {
+    pub version: Version,
+    pub players: Players,
+    description: Description,
+}
+
+impl ServerStatus {
+    /// the MOTD without any formatting
+    pub fn motd(&self) -> String {
+        match &self.description {
+            Description::Plain(text) => text.clone(),
+            Description::Chat(chat) => {
+                let mut res = chat.text.clone().unwrap_or_default();
+                for section in chat.extra.iter().flatten() {
+                    res.push_str(&section.text);
+                }
+                res
+            }
+        }
+    }
+}
+
+/// The result of a server list ping
+#[derive(Debug)]
+pub struct Ping {
+    pub status: ServerStatus,
+    pub latency: Duration,
+}
+
+/// Ping `address` without logging in.
+pub async fn ping(address: &Address) -> anyhow::Result<Ping> {
+    let Address { host, port } = address.clone();
+    let target = String::from(address);
+
+    let conn = TcpStream::connect(target.as_str())
+        .await
+        .with_context(|| format!("could not connect to {target}"))?;
+
+    let (read, write) = conn.into_split();
+    // the version is not known yet
+    let mut reader = PacketReader::new(read, -1);
+    let mut writer = PacketWriter::new(write, -1);
+    reader.state(PacketState::Status);
+
+    writer
+        .write(serverbound::Handshake {
+            protocol_version: VarInt(-1),
+            host,
+            port,
+            next_state: serverbound::HandshakeNextState::Status,
+        })
+        .await?;
+
+    writer.write(serverbound::Request).await?;
+
+    let clientbound::Response { json } = reader
+        .read_exact_packet()
+        .await
+        .context("server did not respond to the status request")?;
+
+    let status: ServerStatus =
+        serde_json::from_str(&json).with_context(|| format!("invalid status response: {json}"))?;
+
+    let start = Instant::now();
+    let payload = rand::random();
+
+    writer.write(serverbound::Ping { payload }).await?;
+
+    let clientbound::Pong { payload: received } = reader.read_exact_packet().await?;
+    let latency = start.elapsed();
+
+    anyhow::ensure!(
+        received == payload,
+        "pong payload {received} does not match ping payload {payload}"
+    );
+
+    Ok(Ping { status, latency })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::status::ServerStatus;
+
+    #[test]
+    fn test_parse_status() {
+        let json = r#"{
+            "version": {"name": "1.12.2", "protocol": 340},
+            "players": {"max": 100, "online": 5, "sample": []},
+            "description": {"text": "A ", "extra": [{"text": "server", "color": "red"}]}
+        }"#;
+
+        let status: ServerStatus = serde_json::from_str(json).unwrap();
+        assert_eq!(status.version.protocol, 340);
+        assert_eq!(status.players.online, 5);
+        assert_eq!(status.motd(), "A server");
+
+        let json = r#"{
+            "version": {"name": "1.8.9", "protocol": 47},
+            "players": {"max": 20, "online": 0},
+            "description": "old server"
+        }"#;
+
+        let status: ServerStatus = serde_json::from_str(json).unwrap();
+        assert_eq!(status.motd(), "old server");
+    }
+}
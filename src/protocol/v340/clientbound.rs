@@ -73,6 +73,13 @@ pub struct MultiBlock {
     pub records: Vec<Record>,
 }
 
+#[derive(Packet, Debug, Readable)]
+#[packet(0x1d, Play)]
+pub struct UnloadChunk {
+    pub chunk_x: i32,
+    pub chunk_z: i32,
+}
+
 #[derive(Packet, Debug, Readable)]
 #[packet(0x23, Play)]
 pub struct JoinGame {
@@ -407,6 +414,28 @@ pub struct PlayDisconnect {
     pub reason: String,
 }
 
+/// Only the death of the player is read
+#[derive(Packet, Debug)]
+#[packet(0x2d, Play)]
+pub struct CombatEvent {
+    /// the death message as a JSON chat component
+    pub death_message: Option<String>,
+}
+
+impl ByteReadable for CombatEvent {
+    fn read_from_bytes(byte_reader: &mut ByteReader) -> Self {
+        // 2 is the death of the player
+        let VarInt(event) = byte_reader.read();
+        let death_message = (event == 2).then(|| {
+            let _player_id: VarInt = byte_reader.read();
+            let _entity_id: i32 = byte_reader.read();
+            byte_reader.read()
+        });
+
+        Self { death_message }
+    }
+}
+
 // #[derive(Packet)]
 // #[packet(0x20, Play)]
 
@@ -0,0 +1,645 @@
+//! A minimal offline-mode 1.12.2 server so the whole login → [`Runner`] path
+//! can be tested without a real server.
+//!
+//! The server does the handshake, login and compression, sends `JoinGame`,
+//! the world's chunks and a spawn position. After that it tracks each
+//! player's position, applies digging and block placement to its world and
+//! echoes chat to every player.
+//!
+//! [`Runner`]: crate::client::runner::Runner
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc, time::Duration};
+
+use interfaces::types::{BlockLocation, BlockState, ChunkLocation, Location};
+use swarm_bot_packets::{
+    types::{PacketState, VarInt},
+    write::{ByteWritable, ByteWriter},
+};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::{
+    bootstrap::Address,
+    protocol::{
+        io::{
+            reader::PacketReader,
+            writer::{PacketWriteChannel, PacketWriter},
+        },
+        Face,
+    },
+    schematic::Schematic,
+    storage::{
+        blocks::WorldBlocks,
+        chunk::{ChunkData, HighMemoryChunkSection},
+    },
+    types::Position,
+};
+
+/// the compression threshold sent to clients
+const COMPRESSION_THRESHOLD: i32 = 256;
+
+mod clientbound {
+    use interfaces::types::Location;
+    use swarm_bot_packets::{types::VarInt, Packet, Writable};
+
+    use crate::types::Position;
+
+    #[derive(Packet, Writable)]
+    #[packet(0x03, Login)]
+    pub struct SetCompression {
+        pub threshold: VarInt,
+    }
+
+    #[derive(Packet, Writable)]
+    #[packet(0x02, Login)]
+    pub struct LoginSuccess {
+        pub uuid: String,
+        pub username: String,
+    }
+
+    #[derive(Packet, Writable)]
+    #[packet(0x23, Play)]
+    pub struct JoinGame {
+        pub entity_id: i32,
+        pub game_mode: u8,
+        pub dimension: i32,
+        pub difficulty: u8,
+        pub max_players: u8,
+        pub level_type: String,
+        pub reduced_debug_info: bool,
+    }
+
+    #[derive(Packet, Writable)]
+    #[packet(0x2f, Play)]
+    pub struct PlayerPositionAndLook {
+        pub location: Location,
+        pub yaw: f32,
+        pub pitch: f32,
+
+        /// all absolute
+        pub flags: u8,
+        pub teleport_id: VarInt,
+    }
+
+    #[derive(Packet, Writable)]
+    #[packet(0x0b, Play)]
+    pub struct BlockChange {
+        pub location: Position,
+        pub block_id: VarInt,
+    }
+
+    #[derive(Packet, Writable)]
+    #[packet(0x0f, Play)]
+    pub struct ChatMessage {
+        pub json: String,
+        pub position: u8,
+    }
+
+    #[derive(Packet, Writable)]
+    #[packet(0x1a, Play)]
+    pub struct Disconnect {
+        pub reason: String,
+    }
+}
+
+/// A full column in the 1.12.2 format. Sections always use the global palette
+/// (13 bits per block).
+struct ChunkColumn {
+    location: ChunkLocation,
+    bitmask: i32,
+    data: Vec<u8>,
+}
+
+impl swarm_bot_packets::types::Packet for ChunkColumn {
+    const ID: u32 = 0x20;
+    const STATE: PacketState = PacketState::Play;
+}
+
+/// bits per block of the global palette
+const GLOBAL_BITS: usize = 13;
+
+fn pack_section(section: &HighMemoryChunkSection) -> Vec<u64> {
+    let mut storage = vec![0_u64; 4096 * GLOBAL_BITS / 64];
+
+    for (block_number, state) in section.palette.all_states().into_iter().enumerate() {
+        let value = u64::from(state.0);
+        let start_long = (block_number * GLOBAL_BITS) / 64;
+        let start_offset = (block_number * GLOBAL_BITS) % 64;
+        let end_long = ((block_number + 1) * GLOBAL_BITS - 1) / 64;
+
+        storage[start_long] |= value << start_offset;
+        if start_long != end_long {
+            storage[end_long] |= value >> (64 - start_offset);
+        }
+    }
+
+    storage
+}
+
+impl ChunkColumn {
+    fn new(location: ChunkLocation, column: &ChunkData<HighMemoryChunkSection>) -> Self {
+        let mut bitmask = 0;
+        let mut data = ByteWriter::new();
+
+        for (idx, section) in column.sections.iter().enumerate() {
+            let Some(section) = section else { continue };
+            bitmask |= 1 << idx;
+
+            let storage = pack_section(section);
+            data.write(GLOBAL_BITS as u8)
+                .write(VarInt(0))
+                .write(VarInt(storage.len() as i32));
+            for long in storage {
+                data.write(long);
+            }
+
+            // block light and sky light. Everything is fully lit
+            data.write(&[0xFF; 2048 * 2][..]);
+        }
+
+        // biomes
+        data.write(&[1; 256][..]);
+
+        Self {
+            location,
+            bitmask,
+            data: data.freeze(),
+        }
+    }
+}
+
+impl ByteWritable for ChunkColumn {
+    fn write_to_bytes(self, writer: &mut ByteWriter) {
+        let ChunkLocation(x, z) = self.location;
+
+        writer
+            .write(x)
+            .write(z)
+            .write(true)
+            .write(VarInt(self.bitmask))
+            .write(self.data)
+            // no block entities
+            .write(VarInt(0));
+    }
+}
+
+/// A player connected to the [`MockServer`]
+pub struct MockPlayer {
+    pub location: Location,
+    tx: PacketWriteChannel,
+}
+
+#[derive(Default)]
+struct ServerState {
+    world: WorldBlocks,
+    spawn: Location,
+    players: HashMap<String, MockPlayer>,
+
+    /// every chat message the server received, as `<username> message`
+    chat: Vec<String>,
+}
+
+impl ServerState {
+    fn broadcast_block(&mut self, location: BlockLocation, state: BlockState) {
+        self.world.set_block(location, state);
+        for player in self.players.values_mut() {
+            player.tx.write(clientbound::BlockChange {
+                location,
+                block_id: VarInt(state.0 as i32),
+            });
+        }
+    }
+
+    fn broadcast_chat(&mut self, text: String) {
+        let json = serde_json::json!({ "text": "", "extra": [{ "text": text }] }).to_string();
+        for player in self.players.values_mut() {
+            player.tx.write(clientbound::ChatMessage {
+                json: json.clone(),
+                position: 0,
+            });
+        }
+        self.chat.push(text);
+    }
+}
+
+/// A minimal 1.12.2 server running on localhost
+pub struct MockServer {
+    address: Address,
+    state: Rc<RefCell<ServerState>>,
+}
+
+impl MockServer {
+    /// Start a server on a free port serving `world`. Players spawn at
+    /// `spawn`. Must be called from within a [`tokio::task::LocalSet`].
+    pub async fn start(world: WorldBlocks, spawn: Location) -> anyhow::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+
+        let state = Rc::new(RefCell::new(ServerState {
+            world,
+            spawn,
+            ..ServerState::default()
+        }));
+
+        {
+            let state = state.clone();
+            tokio::task::spawn_local(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let state = state.clone();
+                    tokio::task::spawn_local(async move {
+                        if let Err(err) = handle(stream, state).await {
+                            println!("mock server connection closed: {err}");
+                        }
+                    });
+                }
+            });
+        }
+
+        Ok(Self {
+            address: Address {
+                host: "127.0.0.1".to_string(),
+                port,
+            },
+            state,
+        })
+    }
+
+    /// Start a server with `schematic` pasted into an otherwise empty world
+    pub async fn with_schematic(schematic: &Schematic, spawn: Location) -> anyhow::Result<Self> {
+        let mut world = WorldBlocks::default();
+        world.paste(schematic);
+        Self::start(world, spawn).await
+    }
+
+    pub fn address(&self) -> Address {
+        self.address.clone()
+    }
+
+    /// send a chat message as if it was from `player`
+    pub fn say(&self, player: &str, message: &str) {
+        self.state
+            .borrow_mut()
+            .broadcast_chat(format!("<{player}> {message}"));
+    }
+
+    /// the usernames of all connected players
+    pub fn players(&self) -> Vec<String> {
+        self.state.borrow().players.keys().cloned().collect()
+    }
+
+    /// the last location `username` reported
+    pub fn location(&self, username: &str) -> Option<Location> {
+        self.state
+            .borrow()
+            .players
+            .get(username)
+            .map(|player| player.location)
+    }
+
+    pub fn block(&self, location: BlockLocation) -> Option<BlockState> {
+        self.state.borrow().world.get_block_exact(location)
+    }
+
+    /// disconnect `username` for `reason`
+    pub fn kick(&self, username: &str, reason: &str) {
+        let player = self.state.borrow_mut().players.remove(username);
+        if let Some(mut player) = player {
+            let reason = serde_json::json!({ "text": reason }).to_string();
+            player.tx.write(clientbound::Disconnect { reason });
+        }
+    }
+
+    /// all chat messages the server has received or sent
+    pub fn chat(&self) -> Vec<String> {
+        self.state.borrow().chat.clone()
+    }
+
+    /// poll `condition` every tick until it is true or `timeout` passes
+    pub async fn wait_until(
+        &self,
+        timeout: Duration,
+        mut condition: impl FnMut(&Self) -> bool,
+    ) -> bool {
+        let deadline = tokio::time::Instant::now() + timeout;
+        while tokio::time::Instant::now() < deadline {
+            if condition(self) {
+                return true;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        condition(self)
+    }
+}
+
+/// handle a single connection from handshake until it closes
+async fn handle(stream: TcpStream, state: Rc<RefCell<ServerState>>) -> anyhow::Result<()> {
+    let (read, write) = stream.into_split();
+    let mut reader = PacketReader::new(read, 340);
+    let mut writer = PacketWriter::new(write, 340);
+
+    // handshake. We do not care about the contents
+    reader.state(PacketState::Handshake);
+    reader.read().await?;
+    reader.state(PacketState::Login);
+
+    // login start
+    let mut login_start = reader.read().await?;
+    let username: String = login_start.read();
+
+    writer
+        .write(clientbound::SetCompression {
+            threshold: VarInt(COMPRESSION_THRESHOLD),
+        })
+        .await?;
+    writer.compression(COMPRESSION_THRESHOLD as u32);
+    reader.compression(COMPRESSION_THRESHOLD as u32);
+
+    let entity_id = state.borrow().players.len() as i32 + 1;
+
+    writer
+        .write(clientbound::LoginSuccess {
+            uuid: format!("00000000-0000-0000-0000-{entity_id:012x}"),
+            username: username.clone(),
+        })
+        .await?;
+
+    writer
+        .write(clientbound::JoinGame {
+            entity_id,
+            game_mode: 0,
+            dimension: 0,
+            difficulty: 0,
+            max_players: 20,
+            level_type: "flat".to_string(),
+            reduced_debug_info: false,
+        })
+        .await?;
+
+    let (columns, spawn) = {
+        let state = state.borrow();
+        let columns: Vec<_> = state
+            .world
+            .real_chunks()
+            .map(|(&location, column)| ChunkColumn::new(location, column))
+            .collect();
+        (columns, state.spawn)
+    };
+
+    for column in columns {
+        writer.write(column).await?;
+    }
+
+    writer
+        .write(clientbound::PlayerPositionAndLook {
+            location: spawn,
+            yaw: 0.0,
+            pitch: 0.0,
+            flags: 0,
+            teleport_id: VarInt(1),
+        })
+        .await?;
+
+    reader.state(PacketState::Play);
+    let tx = writer.into_channel();
+
+    state.borrow_mut().players.insert(
+        username.clone(),
+        MockPlayer {
+            location: spawn,
+            tx,
+        },
+    );
+
+    loop {
+        let mut data = reader.read().await?;
+        let mut server = state.borrow_mut();
+
+        match data.id {
+            // chat message
+            0x02 => {
+                let message: String = data.read();
+                server.broadcast_chat(format!("<{username}> {message}"));
+            }
+
+            // player position, player position and rotation
+            0x0d | 0x0e => {
+                let location: Location = data.read();
+                if let Some(player) = server.players.get_mut(&username) {
+                    player.location = location;
+                }
+            }
+
+            // player dig
+            0x14 => {
+                let VarInt(status) = data.read();
+                let location: Position = data.read();
+
+                // finished digging
+                if status == 2 {
+                    server.broadcast_block(location, BlockState::AIR);
+                }
+            }
+
+            // place block. We do not track inventories so this is always stone
+            0x1f => {
+                let against: Position = data.read();
+                let VarInt(face) = data.read();
+                let location = against + Face::from(face as u8).unit_location();
+                server.broadcast_block(location, BlockState::STONE);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::OpenOptions, future::Future, path::PathBuf, time::Duration};
+
+    use interfaces::types::{BlockLocation, BlockState, Location};
+    use tokio::{runtime::Runtime, task::LocalSet};
+    use tokio_stream::{wrappers::ReceiverStream, StreamExt};
+
+    use crate::{
+        bootstrap::{proxy_pool::ProxyPool, storage::BotConnectionData, BotConnection},
+        client::{
+            commands::CommandReceiver,
+            pathfind::context::PathConfigs,
+            reconnect::ReconnectPolicy,
+            runner::{Runner, RunnerOptions},
+        },
+        protocol::v340::{mock::MockServer, Protocol},
+        schematic::Schematic,
+        storage::blocks::WorldBlocks,
+    };
+
+    const BOT: &str = "Bot0000";
+
+    /// hand-breakable, so mining is quick
+    const DIRT: BlockState = BlockState(3 << 4);
+
+    fn run_local<F: Future<Output = ()>>(test: impl FnOnce() -> F) {
+        let rt = Runtime::new().unwrap();
+        LocalSet::new().block_on(&rt, test());
+    }
+
+    /// log a single offline bot into `server` and wait until it has joined
+    async fn join(server: &MockServer) {
+        let users = Box::pin(BotConnectionData::offline_random().take(1));
+        let proxies = ProxyPool::default().shared();
+        let connections = ReceiverStream::new(BotConnection::stream(
+            server.address(),
+            users,
+            proxies.clone(),
+        ))
+        .filter_map(Result::ok);
+
+        let opts = RunnerOptions {
+            delay_ms: 0,
+            commands: CommandReceiver::init("127.0.0.1:0", &[String::new()])
+                .await
+                .unwrap()
+                .remove(0),
+            api: None,
+            travel_config: PathConfigs::default(),
+            capture_dir: None,
+            max_world_memory: None,
+            reconnect: ReconnectPolicy {
+                initial_delay: Duration::from_millis(100),
+                ..ReconnectPolicy::default()
+            },
+            proxies,
+            world_save: None,
+            server: "mock".to_string(),
+            export_dir: PathBuf::from("exports"),
+            schematic_dir: PathBuf::from("schematics"),
+        };
+
+        tokio::task::spawn_local(Runner::<Protocol>::run(connections, opts));
+
+        let joined = server
+            .wait_until(Duration::from_secs(10), |server| {
+                server.players().contains(&BOT.to_string())
+            })
+            .await;
+        assert!(joined, "bot did not join");
+    }
+
+    #[test]
+    fn test_chat_echo() {
+        run_local(|| async {
+            let server = MockServer::start(WorldBlocks::flat(), Location::new(0.5, 1.0, 0.5))
+                .await
+                .unwrap();
+            join(&server).await;
+
+            server.say("tester", "hello");
+            assert_eq!(server.chat(), vec!["<tester> hello".to_string()]);
+        });
+    }
+
+    #[test]
+    fn test_reconnect() {
+        run_local(|| async {
+            let server = MockServer::start(WorldBlocks::flat(), Location::new(0.5, 1.0, 0.5))
+                .await
+                .unwrap();
+            join(&server).await;
+
+            let joined = |server: &MockServer| server.players().contains(&BOT.to_string());
+
+            server.kick(BOT, "Server restarting");
+            let rejoined = server.wait_until(Duration::from_secs(10), joined).await;
+            assert!(rejoined, "bot did not reconnect");
+
+            server.kick(BOT, "You are banned from this server");
+            let rejoined = server.wait_until(Duration::from_secs(1), joined).await;
+            assert!(!rejoined, "banned bot reconnected");
+        });
+    }
+
+    #[test]
+    fn test_goto() {
+        run_local(|| async {
+            let server = MockServer::start(WorldBlocks::flat(), Location::new(0.5, 1.0, 0.5))
+                .await
+                .unwrap();
+            join(&server).await;
+
+            server.say("tester", "#goto 10 1 10");
+
+            let goal = Location::new(10.5, 1.0, 10.5);
+            let arrived = server
+                .wait_until(Duration::from_secs(30), |server| {
+                    let location = server.location(BOT).unwrap();
+                    location.dist2(goal) < 1.5 * 1.5
+                })
+                .await;
+
+            assert!(arrived, "bot is at {:?}", server.location(BOT));
+        });
+    }
+
+    #[test]
+    fn test_mine() {
+        run_local(|| async {
+            let mut world = WorldBlocks::default();
+
+            // a floor that is too low to be mined
+            for x in -20..=20 {
+                for z in -20..=20 {
+                    world.set_block(BlockLocation::new(x, 10, z), BlockState::STONE);
+                }
+            }
+
+            // the center of the region with its corner at 0,0 is 3,3
+            let dirt: Vec<_> = (2..=4)
+                .flat_map(|x| (2..=4).map(move |z| BlockLocation::new(x, 11, z)))
+                .collect();
+
+            for &location in &dirt {
+                world.set_block(location, DIRT);
+            }
+
+            let server = MockServer::start(world, Location::new(3.5, 12.0, 3.5))
+                .await
+                .unwrap();
+            join(&server).await;
+
+            server.say("tester", "#mine 0 0 0 0");
+
+            let mined = server
+                .wait_until(Duration::from_secs(60), |server| {
+                    dirt.iter()
+                        .all(|&location| server.block(location) == Some(BlockState::AIR))
+                })
+                .await;
+
+            assert!(mined, "bot is at {:?}", server.location(BOT));
+        });
+    }
+
+    #[test]
+    fn test_schematic() {
+        let mut reader = OpenOptions::new()
+            .read(true)
+            .open("test-data/parkour.schematic")
+            .unwrap();
+        let schematic = Schematic::load(&mut reader).unwrap();
+
+        run_local(|| async {
+            let glass = BlockLocation::new(-162, 81, -357);
+            let spawn = Location::new(-161.5, 82.0, -356.5);
+
+            let server = MockServer::with_schematic(&schematic, spawn).await.unwrap();
+            assert_eq!(server.block(glass).unwrap().id(), 95);
+
+            join(&server).await;
+
+            // give the bot time to fall if it did not receive the chunks
+            tokio::time::sleep(Duration::from_secs(2)).await;
+
+            let location = server.location(BOT).unwrap();
+            assert_eq!(BlockLocation::from(location), BlockLocation::from(spawn));
+        });
+    }
+}
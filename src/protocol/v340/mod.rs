@@ -1,9 +1,9 @@
-use std::{cell::RefCell, rc::Rc, sync::mpsc::TryRecvError};
+use std::{cell::RefCell, path::Path, rc::Rc, sync::mpsc::TryRecvError};
 
 use anyhow::Context;
 use interfaces::types::{BlockLocation, BlockState, ChunkLocation};
 use swarm_bot_packets::{
-    types::{Packet, VarInt, UUID},
+    types::{Packet, PacketState, VarInt, UUID},
     write::ByteWritable,
 };
 
@@ -11,6 +11,7 @@ use crate::{
     bootstrap::{mojang::calc_hash, storage::BotData, Address, BotConnection},
     client::processor::InterfaceIn,
     protocol::{
+        capture::PacketRecorder,
         encrypt::{rand_bits, Rsa},
         io::{
             reader::PacketReader,
@@ -29,6 +30,10 @@ use crate::{
 };
 
 mod clientbound;
+#[cfg(test)]
+mod mock;
+#[cfg(test)]
+mod replay;
 mod serverbound;
 
 pub struct EventQueue340 {
@@ -40,6 +45,13 @@ pub struct EventQueue340 {
     /// we need to store state because sometimes death packets occur twice and
     /// we only want to send one event
     alive: bool,
+
+    /// the number of times the queue has been flushed. Used to timestamp
+    /// captured packets
+    tick: u64,
+
+    /// if set, every packet is recorded before it is processed
+    recorder: Option<PacketRecorder>,
 }
 
 impl EventQueue for EventQueue340 {
@@ -47,6 +59,7 @@ impl EventQueue for EventQueue340 {
         loop {
             match self.rx.try_recv() {
                 Ok(data) => {
+                    self.record(&data);
                     self.process_packet(data, processor);
                 }
                 Err(err) => {
@@ -56,14 +69,40 @@ impl EventQueue for EventQueue340 {
                             processor.on_socket_close();
                         }
                     }
+                    self.end_tick();
                     return;
                 }
             }
         }
     }
+
+    fn capture(&mut self, path: &Path) -> anyhow::Result<()> {
+        self.recorder = Some(PacketRecorder::create(path, 340)?);
+        Ok(())
+    }
 }
 
 impl EventQueue340 {
+    fn record(&mut self, data: &PacketData) {
+        let Some(recorder) = self.recorder.as_mut() else { return };
+
+        if let Err(err) = recorder.record(self.tick, data) {
+            println!("stopping packet capture. Reason: {err}");
+            self.recorder = None;
+        }
+    }
+
+    fn end_tick(&mut self) {
+        self.tick += 1;
+
+        let Some(recorder) = self.recorder.as_mut() else { return };
+
+        if let Err(err) = recorder.flush() {
+            println!("stopping packet capture. Reason: {err}");
+            self.recorder = None;
+        }
+    }
+
     fn process_packet(&mut self, mut data: PacketData, processor: &mut impl InterfaceIn) {
         use clientbound::*;
         match data.id {
@@ -190,6 +229,10 @@ impl EventQueue340 {
                 } = data.reader.read_like(&overworld);
                 processor.on_recv_chunk(ChunkLocation(chunk_x, chunk_z), column, new_chunk);
             }
+            UnloadChunk::ID => {
+                let UnloadChunk { chunk_x, chunk_z } = data.read();
+                processor.on_unload_chunk(ChunkLocation(chunk_x, chunk_z));
+            }
             MultiBlock::ID => {
                 let MultiBlock {
                     chunk_x,
@@ -228,6 +271,12 @@ impl EventQueue340 {
                 // "accept" the packet
                 self.out.write(serverbound::TeleportConfirm { teleport_id });
             }
+            CombatEvent::ID => {
+                let CombatEvent { death_message } = data.read();
+                if let Some(message) = death_message {
+                    processor.on_death_message(message);
+                }
+            }
             PlayDisconnect::ID => {
                 let PlayDisconnect { reason } = data.read();
                 processor.on_disconnect(&reason);
@@ -410,14 +459,15 @@ impl Minecraft for Protocol {
             bot,
             read,
             write,
+            ..
         } = conn;
 
         let username = bot.username();
 
         let Address { host, port } = server_address;
 
-        let mut reader = PacketReader::from(read);
-        let mut writer = PacketWriter::from(write);
+        let mut reader = PacketReader::new(read, 340);
+        let mut writer = PacketWriter::new(write, 340);
 
         // ----------- START: handshake ------------
         // 1. C -> S
@@ -503,13 +553,16 @@ impl Minecraft for Protocol {
             }
         };
 
+        reader.state(PacketState::Play);
+
         let (tx, rx) = std::sync::mpsc::channel();
         let (os_tx, os_rx) = tokio::sync::oneshot::channel();
 
         tokio::task::spawn_local(async move {
             let mut oneshot = Some(os_tx);
             loop {
-                let packet = reader.read().await.unwrap();
+                // dropping `tx` lets the queue know the socket closed
+                let Ok(packet) = reader.read().await else { return };
                 if packet.id == JoinGame::ID {
                     if let Some(os_tx) = oneshot.take() {
                         let mut packet = packet.clone();
@@ -543,6 +596,8 @@ impl Minecraft for Protocol {
             out: out.clone(),
             location: Default::default(),
             alive: true,
+            tick: 0,
+            recorder: None,
         };
 
         let login = Login {
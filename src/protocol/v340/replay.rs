@@ -0,0 +1,283 @@
+//! Replaying a capture recorded with `--capture` through [`EventQueue340`], so
+//! bugs seen on live servers can be reproduced in tests.
+
+use std::{fs::File, iter::Peekable, path::Path, sync::mpsc::Sender, vec::IntoIter};
+
+use anyhow::Context;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use crate::{
+    client::processor::InterfaceIn,
+    protocol::{
+        capture::{read_capture, CapturedPacket},
+        io::writer::PacketWriteChannel,
+        v340::{EventQueue340, Interface340},
+        EventQueue,
+    },
+    types::{Dimension, PacketData},
+};
+
+/// Feeds captured packets to an [`EventQueue340`] on the same tick they were
+/// originally processed on.
+pub struct ReplayQueue340 {
+    queue: EventQueue340,
+
+    /// dropped once all packets have been sent, which closes the "socket"
+    tx: Option<Sender<PacketData>>,
+
+    packets: Peekable<IntoIter<CapturedPacket>>,
+    tick: u64,
+
+    /// the packets the bot sent in response
+    pub sent: UnboundedReceiver<Vec<u8>>,
+}
+
+impl ReplayQueue340 {
+    pub fn open(path: &Path) -> anyhow::Result<(Self, Interface340)> {
+        let file = File::open(path)
+            .with_context(|| format!("could not open capture {}", path.display()))?;
+        let packets = read_capture(file, 340)?;
+        Ok(Self::new(packets))
+    }
+
+    pub fn new(packets: Vec<CapturedPacket>) -> (Self, Interface340) {
+        let (channel, sent) = PacketWriteChannel::detached(340);
+        let out = Interface340::new(channel);
+
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        let queue = EventQueue340 {
+            rx,
+            out: out.clone(),
+            location: Default::default(),
+            dimension: Dimension::Overworld,
+            alive: true,
+            tick: 0,
+            recorder: None,
+        };
+
+        let replay = Self {
+            queue,
+            tx: Some(tx),
+            packets: packets.into_iter().peekable(),
+            tick: 0,
+            sent,
+        };
+
+        (replay, out)
+    }
+
+    /// if every captured packet has been processed
+    pub const fn is_finished(&self) -> bool {
+        self.tx.is_none()
+    }
+}
+
+impl EventQueue for ReplayQueue340 {
+    fn flush(&mut self, processor: &mut impl InterfaceIn) {
+        if let Some(tx) = &self.tx {
+            while let Some(packet) = self.packets.next_if(|packet| packet.tick <= self.tick) {
+                tx.send(packet.data).unwrap();
+            }
+        }
+
+        if self.packets.peek().is_none() {
+            self.tx = None;
+        }
+
+        self.queue.flush(processor);
+        self.tick += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use interfaces::types::{BlockLocation, BlockState, Location};
+    use swarm_bot_packets::{
+        types::{Packet, VarInt},
+        write::ByteWriter,
+    };
+
+    use crate::{
+        client::{
+            bot::ActionState,
+            processor::SimpleInterfaceIn,
+            state::{global::GlobalState, local::LocalState},
+        },
+        protocol::{
+            capture::{read_capture, CapturedPacket, PacketRecorder},
+            v340::{
+                clientbound::{entity, BlockChange, JoinGame, PlayerPositionAndLook, Respawn},
+                replay::ReplayQueue340,
+            },
+            EventQueue,
+        },
+        types::{Dimension, PacketData},
+    };
+
+    fn packet(tick: u64, id: u32, build: impl FnOnce(&mut ByteWriter)) -> CapturedPacket {
+        let mut writer = ByteWriter::new();
+        build(&mut writer);
+        CapturedPacket {
+            tick,
+            data: PacketData {
+                id,
+                reader: swarm_bot_packets::read::ByteReader::new(writer.freeze()),
+            },
+        }
+    }
+
+    fn capture() -> Vec<CapturedPacket> {
+        let spawn = Location::new(0.5, 64.0, 0.5);
+
+        vec![
+            packet(0, JoinGame::ID, |w| {
+                w.write(&7_i32.to_be_bytes()[..]) // entity id
+                    .write(0_u8) // survival
+                    .write(&0_i32.to_be_bytes()[..]) // overworld
+                    .write(2_u8)
+                    .write(20_u8)
+                    .write("default".to_string())
+                    .write(false);
+            }),
+            packet(0, PlayerPositionAndLook::ID, |w| {
+                w.write(spawn)
+                    .write(0.0_f32)
+                    .write(0.0_f32)
+                    .write(0_u8)
+                    .write(VarInt(1));
+            }),
+            packet(1, BlockChange::ID, |w| {
+                w.write(BlockLocation::new(3, 63, 4))
+                    .write(VarInt(BlockState::STONE.0 as i32));
+            }),
+            packet(2, entity::LivingSpawn::ID, |w| {
+                w.write(VarInt(42))
+                    .write(0_u128)
+                    .write(VarInt(54)) // zombie
+                    .write(Location::new(10.0, 64.0, 10.0))
+                    .write(0_u8)
+                    .write(0_u8)
+                    .write(0_u8)
+                    .write(0_i16)
+                    .write(0_i16)
+                    .write(0_i16);
+            }),
+        ]
+    }
+
+    #[test]
+    fn test_replay() {
+        let (mut replay, mut out) = ReplayQueue340::new(capture());
+
+        let mut global = GlobalState::init();
+        let mut local = LocalState::mock();
+        let mut actions = ActionState::default();
+
+        let mut tick = |replay: &mut ReplayQueue340, global: &mut GlobalState| {
+            let mut processor = SimpleInterfaceIn::new(&mut local, &mut actions, global, &mut out);
+            replay.flush(&mut processor);
+            local.physics.location()
+        };
+
+        let location = tick(&mut replay, &mut global);
+        assert_eq!(BlockLocation::from(location), BlockLocation::new(0, 64, 0));
+        assert_eq!(
+            global.blocks[Dimension::Overworld].get_block_exact(BlockLocation::new(3, 63, 4)),
+            None
+        );
+
+        tick(&mut replay, &mut global);
+        assert_eq!(
+            global.blocks[Dimension::Overworld].get_block_exact(BlockLocation::new(3, 63, 4)),
+            Some(BlockState::STONE)
+        );
+        assert!(global.entities[Dimension::Overworld].by_id(42).is_none());
+
+        tick(&mut replay, &mut global);
+        assert!(global.entities[Dimension::Overworld].by_id(42).is_some());
+        assert!(replay.is_finished());
+
+        // the teleport was confirmed
+        assert!(replay.sent.try_recv().is_ok());
+    }
+
+    #[test]
+    fn test_dimensions() {
+        let location = BlockLocation::new(3, 63, 4);
+
+        let mut packets = capture();
+        packets.extend([
+            packet(3, Respawn::ID, |w| {
+                w.write(&(-1_i32).to_be_bytes()[..]) // nether
+                    .write(2_u8)
+                    .write(0_u8)
+                    .write("default".to_string());
+            }),
+            packet(4, BlockChange::ID, |w| {
+                w.write(location).write(VarInt(BlockState::AIR.0 as i32));
+            }),
+        ]);
+
+        let (mut replay, mut out) = ReplayQueue340::new(packets);
+
+        let mut global = GlobalState::init();
+        let mut local = LocalState::mock();
+        let mut actions = ActionState::default();
+
+        while !replay.is_finished() {
+            let mut processor =
+                SimpleInterfaceIn::new(&mut local, &mut actions, &mut global, &mut out);
+            replay.flush(&mut processor);
+        }
+
+        assert_eq!(local.dimension, Dimension::Nether);
+
+        // the nether block change did not overwrite the overworld
+        assert_eq!(
+            global.blocks[Dimension::Overworld].get_block_exact(location),
+            Some(BlockState::STONE)
+        );
+        assert_eq!(
+            global.blocks[Dimension::Nether].get_block_exact(location),
+            Some(BlockState::AIR)
+        );
+
+        // the zombie is still known but no longer updated by the bot
+        let zombie = global.entities[Dimension::Overworld].by_id(42).unwrap();
+        assert_eq!(zombie.owner, None);
+        assert!(global.entities[Dimension::Nether].by_id(42).is_none());
+    }
+
+    #[test]
+    fn test_capture_round_trip() {
+        let path = std::env::temp_dir().join(format!("swarmbot-{}.cap", std::process::id()));
+
+        let mut recorder = PacketRecorder::create(&path, 340).unwrap();
+        for CapturedPacket { tick, data } in capture() {
+            recorder.record(tick, &data).unwrap();
+        }
+        recorder.flush().unwrap();
+
+        let file = std::fs::File::open(&path).unwrap();
+        let read = read_capture(file, 340).unwrap();
+
+        let expected = capture();
+        assert_eq!(read.len(), expected.len());
+
+        for (read, expected) in read.into_iter().zip(expected) {
+            assert_eq!(read.tick, expected.tick);
+            assert_eq!(read.data.id, expected.data.id);
+            assert_eq!(read.data.reader.len(), expected.data.reader.len());
+        }
+
+        // a capture of another version is rejected
+        let file = std::fs::File::open(&path).unwrap();
+        assert!(read_capture(file, 754).is_err());
+
+        let (replay, _) = ReplayQueue340::open(&path).unwrap();
+        assert!(!replay.is_finished());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,669 @@
+use interfaces::types::{BlockLocation, BlockState};
+use swarm_bot_packets::{
+    read::{ByteReadable, ByteReadableLike, ByteReader},
+    types::{BitField, RawVec, UUIDHyphenated, VarInt, VarUInt, UUID},
+    Packet, Readable,
+};
+
+use crate::{
+    storage::chunk::{ChunkData, Column, HighMemoryChunkSection, Palette},
+    types::{
+        Chat, Dimension, Direction, DirectionOrigin, Location, LocationFloat, LocationOrigin,
+        Origin, Position,
+    },
+};
+
+#[derive(Debug)]
+pub enum GameMode {
+    Survival,
+    Creative,
+    Adventure,
+    Spectator,
+}
+
+impl ByteReadable for GameMode {
+    fn read_from_bytes(byte_reader: &mut ByteReader) -> Self {
+        use GameMode::{Adventure, Creative, Spectator, Survival};
+        let val: u8 = byte_reader.read();
+
+        // bit 3 is the hardcore flag
+        match val & 0b111 {
+            0 => Survival,
+            1 => Creative,
+            2 => Adventure,
+            3 => Spectator,
+            val => panic!("gamemode {val} is not valid"),
+        }
+    }
+}
+
+/// In 1.8 the join game dimension is a byte instead of an int
+#[derive(Debug, Copy, Clone)]
+pub struct DimensionByte(pub Dimension);
+
+impl ByteReadable for DimensionByte {
+    fn read_from_bytes(byte_reader: &mut ByteReader) -> Self {
+        let val: i8 = byte_reader.read();
+        let dimension = match val {
+            -1 => Dimension::Nether,
+            0 => Dimension::Overworld,
+            1 => Dimension::End,
+            val => panic!("dimension {val} is not valid"),
+        };
+        Self(dimension)
+    }
+}
+
+/// A location stored as fixed-point integers (1/32 of a block)
+#[derive(Debug, Readable)]
+pub struct FixedLocation {
+    x: i32,
+    y: i32,
+    z: i32,
+}
+
+impl From<FixedLocation> for Location {
+    fn from(loc: FixedLocation) -> Self {
+        Self {
+            x: f64::from(loc.x) / 32.0,
+            y: f64::from(loc.y) / 32.0,
+            z: f64::from(loc.z) / 32.0,
+        }
+    }
+}
+
+/// A relative move stored as fixed-point bytes (1/32 of a block)
+#[derive(Debug, Readable)]
+pub struct ByteLoc {
+    dx: i8,
+    dy: i8,
+    dz: i8,
+}
+
+impl From<ByteLoc> for LocationOrigin {
+    fn from(loc: ByteLoc) -> Self {
+        Self {
+            x: Origin::Rel(f64::from(loc.dx) / 32.0),
+            y: Origin::Rel(f64::from(loc.dy) / 32.0),
+            z: Origin::Rel(f64::from(loc.dz) / 32.0),
+        }
+    }
+}
+
+#[derive(Packet, Debug, Readable)]
+#[packet(0x01, Play)]
+pub struct JoinGame {
+    pub entity_id: u32,
+    pub game_mode: GameMode,
+    pub dimension: DimensionByte,
+    pub difficulty: u8,
+    pub max_players: u8,
+    pub level_type: String,
+    pub reduced_debug_info: bool,
+}
+
+#[derive(Packet, Debug, Readable)]
+#[packet(0x07, Play)]
+pub struct Respawn {
+    pub dimension: Dimension,
+    pub difficulty: u8,
+    pub gamemode: GameMode,
+    pub level_type: String,
+}
+
+#[derive(Packet, Readable)]
+#[packet(0x03, Login)]
+pub struct SetCompression {
+    pub threshold: VarInt,
+}
+
+#[derive(Debug, Clone, Packet, Readable)]
+#[packet(0x01, Login)]
+pub struct EncryptionRequest {
+    pub server_id: String,
+    pub public_key_der: Vec<u8>,
+    pub verify_token: Vec<u8>,
+}
+
+#[derive(Debug, Packet, Readable)]
+#[packet(0x02, Login)]
+pub struct LoginSuccess {
+    pub uuid: UUIDHyphenated,
+    pub username: String,
+}
+
+#[derive(Debug, Packet, Readable)]
+#[packet(0x00, Login)]
+pub struct LoginDisconnect {
+    pub reason: Chat,
+}
+
+#[derive(Debug)]
+pub struct PlayerProperty {
+    pub name: String,
+    pub value: String,
+    pub signature: Option<String>,
+}
+
+impl ByteReadable for PlayerProperty {
+    fn read_from_bytes(byte_reader: &mut ByteReader) -> Self {
+        let name = byte_reader.read();
+        let value = byte_reader.read();
+        let is_signed: bool = byte_reader.read();
+        let signature = is_signed.then(|| byte_reader.read());
+        Self {
+            name,
+            value,
+            signature,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AddPlayer {
+    pub name: String,
+    pub properties: Vec<PlayerProperty>,
+    pub gamemode: VarInt,
+    pub ping: VarInt,
+    pub display_name: Option<Chat>,
+}
+
+impl ByteReadable for AddPlayer {
+    fn read_from_bytes(br: &mut ByteReader) -> Self {
+        let (name, properties, gamemode, ping) = br.read();
+        let has_display_name: bool = br.read();
+        let display_name = has_display_name.then(|| br.read());
+        Self {
+            name,
+            properties,
+            gamemode,
+            ping,
+            display_name,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PlayerListType {
+    AddPlayer(AddPlayer),
+    UpdateGamemode(VarInt),
+    UpdateLatency(VarInt),
+    UpdateDisplayName(Option<Chat>),
+    RemovePlayer,
+}
+
+impl ByteReadableLike for PlayerListType {
+    type Param = usize;
+
+    fn read_from_bytes(byte_reader: &mut ByteReader, param: &Self::Param) -> Self {
+        match *param {
+            0 => Self::AddPlayer(byte_reader.read()),
+            1 => Self::UpdateGamemode(byte_reader.read()),
+            2 => Self::UpdateLatency(byte_reader.read()),
+            3 => Self::UpdateDisplayName({
+                let has_val: bool = byte_reader.read();
+                has_val.then(|| byte_reader.read())
+            }),
+            4 => Self::RemovePlayer,
+            _ => panic!("invalid id"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Player {
+    pub uuid: UUID,
+    pub list_type: PlayerListType,
+}
+
+impl ByteReadableLike for Player {
+    type Param = usize;
+
+    fn read_from_bytes(byte_reader: &mut ByteReader, param: &Self::Param) -> Self {
+        Self {
+            uuid: byte_reader.read(),
+            list_type: byte_reader.read_like(param),
+        }
+    }
+}
+
+#[derive(Debug, Packet)]
+#[packet(0x38, Play)]
+pub struct PlayerListItem {
+    pub players: Vec<Player>,
+}
+
+impl ByteReadable for PlayerListItem {
+    fn read_from_bytes(byte_reader: &mut ByteReader) -> Self {
+        let VarUInt(action_id) = byte_reader.read();
+        let VarUInt(number_players) = byte_reader.read();
+        let mut players = Vec::with_capacity(number_players);
+        for _ in 0..number_players {
+            players.push(byte_reader.read_like(&action_id));
+        }
+        Self { players }
+    }
+}
+
+pub mod entity {
+    use swarm_bot_packets::{
+        types::{Angle, VarInt, UUID},
+        Packet, Readable,
+    };
+
+    use super::{ByteLoc, FixedLocation};
+
+    #[derive(Packet, Debug, Readable)]
+    #[packet(0x0f, Play)]
+    pub struct LivingSpawn {
+        pub entity_id: VarInt,
+        pub type_id: u8,
+        pub location: FixedLocation,
+        pub yaw: Angle,
+        pub pitch: Angle,
+        pub head_pitch: Angle,
+        pub velocity_x: i16,
+        pub velocity_y: i16,
+        pub velocity_z: i16,
+        // TODO metadata
+    }
+
+    #[derive(Packet, Debug, Readable)]
+    #[packet(0x0c, Play)]
+    pub struct PlayerSpawn {
+        pub entity_id: VarInt,
+        pub player_uuid: UUID,
+        pub location: FixedLocation,
+        pub yaw: Angle,
+        pub pitch: Angle,
+        pub current_item: i16,
+        // TODO metadata
+    }
+
+    #[derive(Packet, Debug, Readable)]
+    #[packet(0x15, Play)]
+    pub struct RelativeMove {
+        pub entity_id: VarInt,
+        pub loc: ByteLoc,
+        pub on_ground: bool,
+    }
+
+    #[derive(Packet, Debug, Readable)]
+    #[packet(0x17, Play)]
+    pub struct LookAndRelativeMove {
+        pub entity_id: VarInt,
+        pub loc: ByteLoc,
+        pub yaw: Angle,
+        pub pitch: Angle,
+        pub on_ground: bool,
+    }
+
+    #[derive(Packet, Debug, Readable)]
+    #[packet(0x13, Play)]
+    pub struct Destroy {
+        pub ids: Vec<VarInt>,
+    }
+
+    #[derive(Packet, Debug, Readable)]
+    #[packet(0x18, Play)]
+    pub struct Teleport {
+        pub entity_id: VarInt,
+        pub location: FixedLocation,
+        pub yaw: Angle,
+        pub pitch: Angle,
+        pub on_ground: bool,
+    }
+}
+
+#[derive(Readable)]
+pub struct PlayerPositionAndLookRaw {
+    location: Location,
+    rotation: Direction,
+    flags: BitField,
+}
+
+/// There is no teleport id to confirm in 1.8
+#[derive(Packet, Debug)]
+#[packet(0x08, Play)]
+pub struct PlayerPositionAndLook {
+    pub location: LocationOrigin,
+    pub rotation: DirectionOrigin,
+}
+
+impl ByteReadable for PlayerPositionAndLook {
+    fn read_from_bytes(byte_reader: &mut ByteReader) -> Self {
+        let raw: PlayerPositionAndLookRaw = byte_reader.read();
+        raw.into_origin()
+    }
+}
+
+impl PlayerPositionAndLookRaw {
+    fn into_origin(self) -> PlayerPositionAndLook {
+        let [x, y, z, y_rot, x_rot, ..] = self.flags.values;
+
+        let location = LocationOrigin::from(self.location, x, y, z);
+        let rotation = DirectionOrigin::from(self.rotation, y_rot, x_rot);
+
+        PlayerPositionAndLook { location, rotation }
+    }
+}
+
+#[derive(Packet, Debug, Readable)]
+#[packet(0x02, Play)]
+pub struct ChatMessage {
+    pub chat: Chat,
+    pub position: u8,
+}
+
+#[derive(Readable, Debug)]
+pub struct Offset {
+    pub x: i8,
+    pub y: i8,
+    pub z: i8,
+}
+
+#[derive(Packet, Debug)]
+#[packet(0x27, Play)]
+pub struct Explosion {
+    pub location: Location,
+    pub radius: f32,
+    pub records: Vec<BlockLocation>,
+    // TODO: velocity
+}
+
+impl ByteReadable for Explosion {
+    fn read_from_bytes(byte_reader: &mut ByteReader) -> Self {
+        let location: LocationFloat = byte_reader.read();
+        let radius = byte_reader.read();
+        let records = {
+            let record_size: i32 = byte_reader.read();
+            let record_size = record_size as usize;
+            let records: RawVec<Offset> = byte_reader.read_like(&record_size);
+            records.0
+        };
+
+        let origin_block = BlockLocation::from_flts(location.x, location.y, location.z);
+        let location: Location = location.into();
+        let records = records
+            .into_iter()
+            .map(|record| {
+                BlockLocation::new(
+                    origin_block.x + i32::from(record.x),
+                    origin_block.y + i16::from(record.y),
+                    origin_block.z + i32::from(record.z),
+                )
+            })
+            .collect();
+        Self {
+            location,
+            radius,
+            records,
+        }
+    }
+}
+
+#[derive(Packet, Debug, Readable)]
+#[packet(0x23, Play)]
+pub struct BlockChange {
+    pub location: Position,
+    pub block_id: VarInt,
+}
+
+#[derive(Debug)]
+pub struct Record {
+    pub x: u8,
+    pub y: u8,
+    pub z: u8,
+    pub block_state: VarUInt,
+}
+
+impl ByteReadable for Record {
+    fn read_from_bytes(byte_reader: &mut ByteReader) -> Self {
+        let horizontal: u8 = byte_reader.read();
+        let y: u8 = byte_reader.read();
+        Self {
+            x: horizontal >> 4,
+            y,
+            z: horizontal & 0xF,
+            block_state: byte_reader.read(),
+        }
+    }
+}
+
+#[derive(Packet, Debug, Readable)]
+#[packet(0x22, Play)]
+pub struct MultiBlock {
+    pub chunk_x: i32,
+    pub chunk_z: i32,
+    pub records: Vec<Record>,
+}
+
+#[derive(Packet, Debug, Readable)]
+#[packet(0x06, Play)]
+pub struct UpdateHealth {
+    pub health: f32,
+
+    // 0-20
+    pub food: VarInt,
+    pub food_saturation: f32,
+}
+
+#[derive(Packet, Debug, Readable)]
+#[packet(0x00, Play)]
+pub struct KeepAlive {
+    pub id: VarInt,
+}
+
+#[derive(Packet, Debug, Readable)]
+#[packet(0x40, Play)]
+pub struct PlayDisconnect {
+    pub reason: String,
+}
+
+/// Only the death of the player is read
+#[derive(Packet, Debug)]
+#[packet(0x42, Play)]
+pub struct CombatEvent {
+    /// the death message as a JSON chat component
+    pub death_message: Option<String>,
+}
+
+impl ByteReadable for CombatEvent {
+    fn read_from_bytes(byte_reader: &mut ByteReader) -> Self {
+        // 2 is the death of the player
+        let VarInt(event) = byte_reader.read();
+        let death_message = (event == 2).then(|| {
+            let _player_id: VarInt = byte_reader.read();
+            let _entity_id: i32 = byte_reader.read();
+            byte_reader.read()
+        });
+
+        Self { death_message }
+    }
+}
+
+pub mod window {
+    use crate::types::{ShortVec, Slot};
+
+    #[derive(Packet, Debug, Readable)]
+    #[packet(0x2f, Play)]
+    pub struct Set {
+        pub window_id: u8,
+        pub slot: u16,
+        pub data: Slot,
+    }
+
+    #[derive(Packet, Readable)]
+    #[packet(0x30, Play)]
+    pub struct Items {
+        /// 0 if player inventory. Unlike 1.9+ there is no offhand slot (45)
+        pub window_id: u8,
+
+        // number of elements in the array
+        pub slots: ShortVec<Slot>,
+    }
+}
+
+/// bytes of block light (and sky light) per section
+const LIGHT_BYTES: usize = 2048;
+
+/// bytes of biomes in a ground-up chunk
+const BIOME_BYTES: usize = 256;
+
+/// Read the sections of a 1.8 column. All block arrays come first (as
+/// little-endian `id << 4 | meta`, the same format as [`BlockState`]), followed
+/// by light and biomes.
+fn read_column(byte_reader: &mut ByteReader, primary_bitmask: u16) -> Column {
+    const INIT: Option<Box<HighMemoryChunkSection>> = None;
+    let mut sections = [INIT; 16];
+
+    for (idx, section) in sections.iter_mut().enumerate() {
+        if primary_bitmask & (1 << idx) == 0 {
+            continue;
+        }
+
+        let states = (0..4096).map(|_| {
+            let low: u8 = byte_reader.read();
+            let high: u8 = byte_reader.read();
+            BlockState(u32::from(u16::from_le_bytes([low, high])))
+        });
+
+        *section = Some(box HighMemoryChunkSection::new(Palette::from_states(
+            states,
+        )));
+    }
+
+    let data = ChunkData { sections };
+    Column::HighMemory { data }
+}
+
+pub const CHUNK_PKT_ID: u32 = 0x21;
+
+pub struct ChunkColumnPacket {
+    pub chunk_x: i32,
+    pub chunk_z: i32,
+    pub new_chunk: bool,
+
+    /// 1.8 has no unload packet. Instead, a full column without sections is
+    /// sent
+    pub unload: bool,
+    pub column: Column,
+}
+
+impl ByteReadable for ChunkColumnPacket {
+    fn read_from_bytes(byte_reader: &mut ByteReader) -> Self {
+        let chunk_x = byte_reader.read();
+        let chunk_z = byte_reader.read();
+        let ground_up_continuous: bool = byte_reader.read();
+        let primary_bitmask: u16 = byte_reader.read();
+        let _size: VarUInt = byte_reader.read();
+
+        // light and biomes follow the blocks but we do not need them
+        let column = read_column(byte_reader, primary_bitmask);
+
+        Self {
+            chunk_x,
+            chunk_z,
+            new_chunk: ground_up_continuous,
+            unload: ground_up_continuous && primary_bitmask == 0,
+            column,
+        }
+    }
+}
+
+#[derive(Readable)]
+struct ChunkMeta {
+    chunk_x: i32,
+    chunk_z: i32,
+    primary_bitmask: u16,
+}
+
+/// Several full columns at once. Only exists before 1.9.
+#[derive(Packet)]
+#[packet(0x26, Play)]
+pub struct MapChunkBulk {
+    pub columns: Vec<ChunkColumnPacket>,
+}
+
+impl ByteReadable for MapChunkBulk {
+    fn read_from_bytes(byte_reader: &mut ByteReader) -> Self {
+        let sky_light_sent: bool = byte_reader.read();
+        let VarUInt(count) = byte_reader.read();
+
+        let metas: RawVec<ChunkMeta> = byte_reader.read_like(&count);
+
+        let columns = metas
+            .0
+            .into_iter()
+            .map(|meta| {
+                let column = read_column(byte_reader, meta.primary_bitmask);
+
+                let section_count = meta.primary_bitmask.count_ones() as usize;
+                let light_arrays = if sky_light_sent { 2 } else { 1 };
+                let skip = section_count * LIGHT_BYTES * light_arrays + BIOME_BYTES;
+                let _light_and_biomes: RawVec = byte_reader.read_like(&skip);
+
+                ChunkColumnPacket {
+                    chunk_x: meta.chunk_x,
+                    chunk_z: meta.chunk_z,
+                    new_chunk: true,
+                    unload: false,
+                    column,
+                }
+            })
+            .collect();
+
+        Self { columns }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use interfaces::types::BlockState;
+    use swarm_bot_packets::{read::ByteReader, types::VarInt, write::ByteWriter};
+
+    use crate::{protocol::v47::clientbound::MapChunkBulk, storage::chunk::Column};
+
+    #[test]
+    fn test_map_chunk_bulk() {
+        let mut writer = ByteWriter::new();
+        writer.write(true).write(VarInt(2));
+
+        // section 0 of (3, -4) and sections 0 and 2 of (5, 6)
+        for (x, z, bitmask) in [(3_i32, -4_i32, 0b1_u16), (5, 6, 0b101)] {
+            writer
+                .write(&x.to_be_bytes()[..])
+                .write(&z.to_be_bytes()[..])
+                .write(bitmask);
+        }
+
+        for sections in [1, 2] {
+            for _ in 0..sections {
+                writer.write(&(BlockState::STONE.0 as u16).to_le_bytes()[..]);
+                writer.write(&[0; 4095 * 2][..]);
+            }
+
+            // block light, sky light and biomes
+            writer.write(&vec![0; sections * 2048 * 2 + 256][..]);
+        }
+
+        let mut reader = ByteReader::new(writer.freeze());
+        let MapChunkBulk { columns } = reader.read();
+
+        assert_eq!(columns.len(), 2);
+        assert_eq!((columns[0].chunk_x, columns[0].chunk_z), (3, -4));
+        assert_eq!((columns[1].chunk_x, columns[1].chunk_z), (5, 6));
+
+        let Column::HighMemory { data } = &columns[1].column else {
+            panic!("expected high memory column")
+        };
+
+        assert!(data.sections[0].is_some());
+        assert!(data.sections[1].is_none());
+        assert!(data.sections[2].is_some());
+
+        let layer = data.all_at(32);
+        assert_eq!(layer[0], BlockState::STONE);
+        assert_eq!(layer[1], BlockState::AIR);
+    }
+}
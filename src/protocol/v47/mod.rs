@@ -0,0 +1,572 @@
+use std::{cell::RefCell, rc::Rc, sync::mpsc::TryRecvError};
+
+use anyhow::Context;
+use interfaces::types::{BlockLocation, BlockState, ChunkLocation};
+use swarm_bot_packets::{
+    types::{Packet, PacketState, VarInt, UUID},
+    write::ByteWritable,
+};
+
+use crate::{
+    bootstrap::{mojang::calc_hash, storage::BotData, Address, BotConnection},
+    client::processor::InterfaceIn,
+    protocol::{
+        encrypt::{rand_bits, Rsa},
+        io::{
+            reader::PacketReader,
+            writer::{PacketWriteChannel, PacketWriter},
+        },
+        v47::{
+            clientbound::{JoinGame, LoginDisconnect, LoginSuccess},
+            serverbound::{ClientStatusAction, DigStatus, HandshakeNextState, InteractEntityKind},
+        },
+        ClientInfo, EventQueue, Face, InterfaceOut, InvAction, Login, Mine, Minecraft,
+    },
+    storage::entities::EntityKind,
+    types::{Direction, Location, PacketData, Slot},
+};
+
+mod clientbound;
+mod serverbound;
+
+pub struct EventQueue47 {
+    rx: std::sync::mpsc::Receiver<PacketData>,
+    out: Interface47,
+    location: Location,
+
+    /// we need to store state because sometimes death packets occur twice and
+    /// we only want to send one event
+    alive: bool,
+}
+
+impl EventQueue for EventQueue47 {
+    fn flush(&mut self, processor: &mut impl InterfaceIn) {
+        loop {
+            match self.rx.try_recv() {
+                Ok(data) => {
+                    self.process_packet(data, processor);
+                }
+                Err(err) => {
+                    match err {
+                        TryRecvError::Empty => {}
+                        TryRecvError::Disconnected => {
+                            processor.on_socket_close();
+                        }
+                    }
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl EventQueue47 {
+    fn process_packet(&mut self, mut data: PacketData, processor: &mut impl InterfaceIn) {
+        use clientbound::*;
+        match data.id {
+            JoinGame::ID => {
+                let JoinGame { dimension, .. } = data.read();
+                processor.on_join();
+                processor.on_dimension_change(dimension.0);
+            }
+
+            window::Set::ID => {
+                let window::Set {
+                    window_id,
+                    slot: idx,
+                    data,
+                } = data.read();
+                if window_id == 0 {
+                    match data.into() {
+                        None => processor.on_lose_item(idx as usize),
+                        Some(item_stack) => processor.on_pickup_item(idx as usize, item_stack),
+                    }
+                }
+            }
+
+            window::Items::ID => {
+                let window::Items { window_id, slots } = data.read();
+
+                if window_id == 0 {
+                    // is player inventory
+                    for (idx, slot) in slots.0.into_iter().enumerate() {
+                        match slot.into() {
+                            None => processor.on_lose_item(idx),
+                            Some(item_stack) => processor.on_pickup_item(idx, item_stack),
+                        }
+                    }
+                }
+            }
+
+            BlockChange::ID => {
+                let BlockChange { block_id, location } = data.read();
+                processor.on_block_change(location, BlockState(block_id.0 as u32));
+            }
+            Explosion::ID => {
+                let Explosion { records, .. } = data.read();
+
+                for record in records {
+                    processor.on_block_change(record, BlockState::AIR);
+                }
+            }
+            KeepAlive::ID => {
+                // auto keep alive
+                let KeepAlive { id } = data.read();
+
+                self.out.write(serverbound::KeepAlive { id });
+            }
+            entity::RelativeMove::ID => {
+                let entity::RelativeMove { entity_id, loc, .. } = data.read();
+                processor.on_entity_move(entity_id.into(), loc.into());
+            }
+            entity::LookAndRelativeMove::ID => {
+                let entity::LookAndRelativeMove { entity_id, loc, .. } = data.read();
+                processor.on_entity_move(entity_id.into(), loc.into());
+            }
+            entity::Destroy::ID => {
+                let entity::Destroy { ids } = data.read();
+                for id in ids {
+                    processor.on_entity_destroy(id.into());
+                }
+            }
+            entity::Teleport::ID => {
+                let entity::Teleport {
+                    entity_id,
+                    location,
+                    ..
+                } = data.read();
+                let location: Location = location.into();
+                processor.on_entity_move(entity_id.into(), location.into());
+            }
+            entity::LivingSpawn::ID => {
+                let entity::LivingSpawn {
+                    entity_id,
+                    location,
+                    ..
+                } = data.read();
+                processor.on_entity_spawn(entity_id.into(), location.into(), EntityKind::Normal);
+            }
+            entity::PlayerSpawn::ID => {
+                let entity::PlayerSpawn {
+                    entity_id,
+                    location,
+                    player_uuid,
+                    ..
+                } = data.read();
+                processor.on_entity_spawn(
+                    entity_id.into(),
+                    location.into(),
+                    EntityKind::Player {
+                        uuid: player_uuid.0,
+                    },
+                );
+            }
+            UpdateHealth::ID => {
+                let UpdateHealth { health, food, .. } = data.read();
+                if health > 0.0 {
+                    processor.on_update_health(health, food.0 as u8);
+                    self.alive = true;
+                } else if self.alive {
+                    processor.on_death();
+                    self.alive = false;
+                }
+            }
+            Respawn::ID => {
+                let Respawn { dimension, .. } = data.read();
+                processor.on_dimension_change(dimension);
+            }
+            CHUNK_PKT_ID => {
+                let ChunkColumnPacket {
+                    chunk_x,
+                    chunk_z,
+                    column,
+                    new_chunk,
+                    unload,
+                } = data.read();
+
+                let location = ChunkLocation(chunk_x, chunk_z);
+                if unload {
+                    processor.on_unload_chunk(location);
+                } else {
+                    processor.on_recv_chunk(location, column, new_chunk);
+                }
+            }
+            MapChunkBulk::ID => {
+                let MapChunkBulk { columns } = data.read();
+                for ChunkColumnPacket {
+                    chunk_x,
+                    chunk_z,
+                    column,
+                    new_chunk,
+                    ..
+                } in columns
+                {
+                    processor.on_recv_chunk(ChunkLocation(chunk_x, chunk_z), column, new_chunk);
+                }
+            }
+            MultiBlock::ID => {
+                let MultiBlock {
+                    chunk_x,
+                    chunk_z,
+                    records,
+                } = data.read();
+
+                let base_x = chunk_x << 4;
+                let base_z = chunk_z << 4;
+
+                for Record {
+                    x,
+                    y,
+                    z,
+                    block_state,
+                } in records
+                {
+                    let location = BlockLocation::new(
+                        base_x + i32::from(x),
+                        i16::from(y),
+                        base_z + i32::from(z),
+                    );
+                    processor.on_block_change(location, BlockState(block_state.0 as u32));
+                }
+            }
+            PlayerPositionAndLook::ID => {
+                let PlayerPositionAndLook { location, .. } = data.read();
+
+                self.location.apply_change(location);
+                processor.on_move(self.location);
+
+                // 1.8 has no teleport confirm. The vanilla client responds with its
+                // position instead
+                self.out.teleport(self.location);
+            }
+            CombatEvent::ID => {
+                let CombatEvent { death_message } = data.read();
+                if let Some(message) = death_message {
+                    processor.on_death_message(message);
+                }
+            }
+            PlayDisconnect::ID => {
+                let PlayDisconnect { reason } = data.read();
+                processor.on_disconnect(&reason);
+            }
+            PlayerListItem::ID => {
+                let PlayerListItem { players } = data.read();
+                for Player { uuid, list_type } in players {
+                    match list_type {
+                        PlayerListType::AddPlayer(add) => {
+                            processor.on_player_join(uuid.0, add.name);
+                        }
+                        PlayerListType::RemovePlayer => processor.on_player_leave(uuid.0),
+                        _ => {}
+                    }
+                }
+            }
+            ChatMessage::ID => {
+                let ChatMessage { chat, .. } = data.read();
+                processor.on_chat(chat);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Interface47 {
+    tx: Rc<RefCell<PacketWriteChannel>>,
+    inv_action_id: u16,
+}
+
+impl Interface47 {
+    fn new(tx: PacketWriteChannel) -> Self {
+        Self {
+            tx: Rc::new(RefCell::new(tx)),
+            inv_action_id: 0,
+        }
+    }
+
+    fn click(&mut self, slot: u16, button: impl Into<u8>, mode: u8, clicked: impl Into<Slot>) {
+        let action_number = self.inv_action_id;
+        let to_send = serverbound::ClickWindow {
+            window_id: 0,
+            slot,
+            button: button.into(),
+            action_number,
+            mode,
+            clicked: clicked.into(),
+        };
+
+        self.write(to_send);
+
+        self.inv_action_id += 1;
+    }
+
+    #[inline]
+    fn write<T: Packet + ByteWritable>(&self, packet: T) {
+        self.tx.borrow_mut().write(packet);
+    }
+}
+
+impl InterfaceOut for Interface47 {
+    fn place_block(&mut self, against: BlockLocation, face: Face) {
+        // cursor position is in 1/16 of a block
+        self.write(serverbound::PlaceBlock {
+            location: against,
+            face: face as u8,
+            held_item: Slot::EMPTY,
+            cursor: serverbound::BlockCursor { x: 8, y: 8, z: 8 },
+        });
+    }
+
+    fn attack_entity(&mut self, id: u32) {
+        self.write(serverbound::InteractEntity {
+            id: id.into(),
+            kind: InteractEntityKind::Attack,
+        });
+    }
+
+    fn send_chat(&mut self, message: &str) {
+        self.write(serverbound::ChatMessage {
+            message: message.to_string(),
+        });
+    }
+
+    fn inventory_action(&mut self, action: InvAction) {
+        match action {
+            InvAction::Q(slot) => self.click(slot, 0, 4, Slot::EMPTY),
+            InvAction::CtrlQ(slot) => self.click(slot, 1, 4, Slot::EMPTY),
+            InvAction::Click(slot, button, clicked) => self.click(slot, button, 0, clicked),
+            InvAction::ShiftClick(slot, button, clicked) => self.click(slot, button, 1, clicked),
+        }
+    }
+
+    fn swing_arm(&mut self) {
+        self.write(serverbound::ArmAnimation);
+    }
+
+    fn finish_eating(&mut self) {
+        self.write(serverbound::PlayerDig::status(
+            DigStatus::ShootArrowOrFinishEat,
+        ));
+    }
+
+    fn use_item(&mut self) {
+        self.write(serverbound::PlaceBlock::use_item());
+    }
+
+    fn change_slot(&mut self, number: u8) {
+        self.write(serverbound::ChangeSlot {
+            slot: u16::from(number),
+        });
+    }
+
+    fn mine(&mut self, position: BlockLocation, mine: Mine, face: Face) {
+        let status = match mine {
+            Mine::Start => DigStatus::Started,
+            Mine::Cancel => DigStatus::Cancelled,
+            Mine::Finished => DigStatus::Finished,
+        };
+
+        if status == DigStatus::Started {
+            self.swing_arm();
+        }
+
+        self.write(serverbound::PlayerDig {
+            status,
+            position,
+            face: face as u8,
+        });
+    }
+
+    fn respawn(&mut self) {
+        self.write(serverbound::ClientStatus {
+            action: ClientStatusAction::Respawn,
+        });
+    }
+
+    fn teleport(&mut self, location: Location) {
+        self.write(serverbound::PlayerPosition {
+            location,
+            on_ground: true,
+        });
+    }
+
+    fn look(&mut self, direction: Direction) {
+        self.write(serverbound::PlayerLook {
+            direction,
+            on_ground: false,
+        });
+    }
+
+    fn teleport_and_look(&mut self, location: Location, direction: Direction, on_ground: bool) {
+        self.write(serverbound::PlayerPositionAndRotation {
+            location,
+            direction,
+            on_ground,
+        });
+    }
+}
+
+pub struct Protocol;
+
+impl Minecraft for Protocol {
+    type Queue = EventQueue47;
+    type Interface = Interface47;
+
+    /// Login for 1.8.9
+    ///
+    /// Look <https://wiki.vg/index.php?title=Protocol&oldid=7368#Login>
+    async fn login(conn: BotConnection) -> anyhow::Result<Login<EventQueue47, Interface47>> {
+        let BotConnection {
+            server_address,
+            bot,
+            read,
+            write,
+            ..
+        } = conn;
+
+        let username = bot.username();
+
+        let Address { host, port } = server_address;
+
+        let mut reader = PacketReader::new(read, 47);
+        let mut writer = PacketWriter::new(write, 47);
+
+        // ----------- START: handshake ------------
+        // 1. C -> S
+        writer
+            .write(serverbound::Handshake {
+                protocol_version: VarInt(47),
+                host,
+                port,
+                next_state: HandshakeNextState::Login,
+            })
+            .await?;
+
+        // ------------ START: login ----------------
+        // 2 C -> S     LOGIN START
+        writer
+            .write(serverbound::LoginStart {
+                username: username.to_string(),
+            })
+            .await?;
+
+        // 3. S -> C: Encryption Request
+
+        let uuid = if let BotData::Online { user, mojang } = &bot {
+            let access_id = &user.access_id;
+            let uuid = user.uuid();
+
+            let clientbound::EncryptionRequest {
+                public_key_der,
+                verify_token,
+                server_id,
+            } = reader.read_exact_packet().await?;
+
+            let rsa = Rsa::from_der(&public_key_der);
+
+            let shared_secret = rand_bits();
+
+            let encrypted_ss = rsa.encrypt(&shared_secret).unwrap();
+            let encrypted_verify = rsa.encrypt(&verify_token).unwrap();
+
+            // 4. Mojang online mode requests
+            let hash = calc_hash(&server_id, &shared_secret, &public_key_der);
+            mojang.join(uuid, &hash, access_id).await?;
+
+            // 5. Encryption Response
+            writer
+                .write(serverbound::EncryptionResponse {
+                    shared_secret: encrypted_ss,
+                    verify_token: encrypted_verify,
+                })
+                .await?;
+
+            // we now do everything encrypted
+            writer.encryption(&shared_secret);
+            reader.encryption(&shared_secret);
+
+            uuid
+        } else {
+            // TODO: remove
+            UUID::default()
+        };
+
+        // set compression or login success
+        let mut data = reader.read().await?;
+
+        let LoginSuccess { .. } = match data.id {
+            clientbound::SetCompression::ID => {
+                let clientbound::SetCompression { threshold } = data.read();
+
+                reader.compression(threshold.into());
+                writer.compression(threshold.into());
+
+                reader.read_exact_packet().await?
+            }
+            LoginSuccess::ID => data.reader.read(),
+            LoginDisconnect::ID => {
+                let LoginDisconnect { reason } = data.reader.read();
+                let reason = reason.colorize();
+                anyhow::bail!("Disconnected while logging in. Reason: {reason}")
+            }
+            actual => {
+                let expected = LoginSuccess::ID;
+                anyhow::bail!("wrong packet for logging in. Expected {expected}, got {actual}")
+            }
+        };
+
+        reader.state(PacketState::Play);
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        let (os_tx, os_rx) = tokio::sync::oneshot::channel();
+
+        tokio::task::spawn_local(async move {
+            let mut oneshot = Some(os_tx);
+            loop {
+                // dropping `tx` lets the queue know the socket closed
+                let Ok(packet) = reader.read().await else { return };
+                if packet.id == JoinGame::ID {
+                    if let Some(os_tx) = oneshot.take() {
+                        let mut packet = packet.clone();
+                        let processed: JoinGame = packet.read();
+                        os_tx.send(processed.entity_id).unwrap();
+                    }
+                }
+                match tx.send(packet) {
+                    Ok(..) => {}
+                    Err(..) => {
+                        // the other end is stopped and should have printed the error
+                        return;
+                    }
+                }
+            }
+        });
+
+        let tx = writer.into_channel();
+
+        let entity_id = os_rx
+            .await
+            .context("disconnected before join game packet")?;
+
+        let out = Interface47::new(tx);
+
+        let queue = EventQueue47 {
+            rx,
+            out: out.clone(),
+            location: Location::default(),
+            alive: true,
+        };
+
+        let login = Login {
+            queue,
+            out,
+            info: ClientInfo {
+                username: username.to_string(),
+                uuid,
+                entity_id,
+            },
+        };
+
+        Ok(login)
+    }
+}
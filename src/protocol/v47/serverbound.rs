@@ -0,0 +1,209 @@
+use swarm_bot_packets::{
+    types::VarInt,
+    write::{ByteWritable, ByteWriter},
+    AdtWritable, EnumWritable, Packet, Writable,
+};
+
+use crate::types::{Direction, Location, Position, Slot};
+
+#[derive(Packet, Writable)]
+#[packet(0x00, Handshake)]
+pub struct Handshake {
+    pub protocol_version: VarInt,
+    pub host: String,
+
+    /// hostname or IP
+    pub port: u16,
+
+    /// default 25565
+    pub next_state: HandshakeNextState, // 1 for status, 2 for login
+}
+
+#[derive(Copy, Clone, EnumWritable)]
+#[repr(i32)]
+#[allow(dead_code)]
+pub enum HandshakeNextState {
+    #[deprecated]
+    Invalid,
+
+    Status,
+    Login,
+}
+
+#[derive(Debug, Packet, Writable)]
+#[packet(0x00, Login)]
+pub struct LoginStart {
+    /// player's username
+    pub username: String,
+}
+
+#[derive(Packet, Writable)]
+#[packet(0x01, Login)]
+pub struct EncryptionResponse {
+    pub shared_secret: Vec<u8>,
+    pub verify_token: Vec<u8>,
+}
+
+#[derive(Writable, Packet)]
+#[packet(0x00, Play)]
+pub struct KeepAlive {
+    pub id: VarInt,
+}
+
+#[derive(Writable, Packet)]
+#[packet(0x01, Play)]
+pub struct ChatMessage {
+    pub message: String,
+}
+
+/// 1.8 has no offhand so there is no hand to interact with
+#[derive(Debug, AdtWritable)]
+#[repr(i32)]
+#[allow(dead_code)]
+pub enum InteractEntityKind {
+    Interact,
+    Attack,
+    InteractAt {
+        target_x: f32,
+        target_y: f32,
+        target_z: f32,
+    },
+}
+
+#[derive(Writable, Packet)]
+#[packet(0x02, Play)]
+pub struct InteractEntity {
+    pub id: VarInt,
+    pub kind: InteractEntityKind,
+}
+
+#[derive(Writable, Packet)]
+#[packet(0x04, Play)]
+pub struct PlayerPosition {
+    /// True if the client is on the ground, false otherwise.
+    pub location: Location,
+    pub on_ground: bool,
+}
+
+#[derive(Writable, Packet)]
+#[packet(0x05, Play)]
+pub struct PlayerLook {
+    pub(crate) direction: Direction,
+    pub(crate) on_ground: bool,
+}
+
+#[derive(Writable, Packet)]
+#[packet(0x06, Play)]
+pub struct PlayerPositionAndRotation {
+    pub location: Location,
+    pub direction: Direction,
+    pub on_ground: bool,
+}
+
+/// written as a byte in 1.8
+#[derive(Eq, PartialEq, Copy, Clone)]
+#[repr(u8)]
+#[allow(dead_code)]
+pub enum DigStatus {
+    Started,
+    Cancelled,
+    Finished,
+    DropItemStack,
+    DropItem,
+    ShootArrowOrFinishEat,
+}
+
+impl ByteWritable for DigStatus {
+    fn write_to_bytes(self, writer: &mut ByteWriter) {
+        writer.write(self as u8);
+    }
+}
+
+#[derive(Writable, Packet)]
+#[packet(0x07, Play)]
+pub struct PlayerDig {
+    pub status: DigStatus,
+    pub position: Position,
+    pub face: u8,
+}
+
+impl PlayerDig {
+    pub fn status(status: DigStatus) -> Self {
+        Self {
+            status,
+            position: Position::default(),
+            face: 0,
+        }
+    }
+}
+
+#[derive(Writable, Default)]
+pub struct BlockCursor {
+    pub x: u8,
+    pub y: u8,
+    pub z: u8,
+}
+
+/// Also used for using items. In that case the location is `(-1, -1, -1)` and
+/// the face is `255`.
+#[derive(Writable, Packet)]
+#[packet(0x08, Play)]
+pub struct PlaceBlock {
+    pub location: Position,
+    pub face: u8,
+    pub held_item: Slot,
+    pub cursor: BlockCursor,
+}
+
+impl PlaceBlock {
+    pub fn use_item() -> Self {
+        Self {
+            location: Position::new(-1, -1, -1),
+            face: 255,
+            held_item: Slot::EMPTY,
+            cursor: BlockCursor::default(),
+        }
+    }
+}
+
+#[derive(Writable, Packet)]
+#[packet(0x09, Play)]
+pub struct ChangeSlot {
+    pub slot: u16,
+}
+
+/// There is no hand to swing in 1.8
+#[derive(Packet)]
+#[packet(0x0a, Play)]
+pub struct ArmAnimation;
+
+impl ByteWritable for ArmAnimation {
+    fn write_to_bytes(self, _writer: &mut ByteWriter) {}
+}
+
+#[derive(Writable, Packet)]
+#[packet(0x0e, Play)]
+pub struct ClickWindow {
+    pub window_id: u8,
+    pub slot: u16,
+    pub button: u8,
+    pub action_number: u16,
+    pub mode: u8,
+    pub clicked: Slot,
+}
+
+/// Respawning and show stats
+#[derive(Writable, Packet)]
+#[packet(0x16, Play)]
+pub struct ClientStatus {
+    pub(crate) action: ClientStatusAction,
+}
+
+#[repr(i32)]
+#[derive(EnumWritable)]
+#[allow(dead_code)]
+pub enum ClientStatusAction {
+    Respawn = 0,
+    Stats = 1,
+    OpenInventory = 2,
+}
@@ -0,0 +1,696 @@
+use std::cmp::max;
+
+use anyhow::ensure;
+use interfaces::types::{BlockLocation, BlockState};
+use serde::Deserialize;
+use swarm_bot_packets::{
+    read::{ByteReadable, ByteReadableLike, ByteReader},
+    types::{BitField, Identifier, RawVec, VarInt, VarLong, VarUInt, UUID},
+    Packet, Readable,
+};
+
+use crate::{
+    protocol::v754::{registry::Registry, types::Position},
+    storage::chunk::{ChunkData, Column, HighMemoryChunkSection, Palette},
+    types::{Chat, Dimension, Direction, DirectionOrigin, Location, LocationFloat, LocationOrigin},
+};
+
+#[derive(Debug)]
+pub enum GameMode {
+    Survival,
+    Creative,
+    Adventure,
+    Spectator,
+}
+
+impl ByteReadable for GameMode {
+    fn read_from_bytes(byte_reader: &mut ByteReader) -> Self {
+        use GameMode::{Adventure, Creative, Spectator, Survival};
+        let val: u8 = byte_reader.read();
+        match val {
+            0 => Survival,
+            1 => Creative,
+            2 => Adventure,
+            3 => Spectator,
+            val => panic!("gamemode {val} is not valid"),
+        }
+    }
+}
+
+/// The fields of a dimension type we use. Sent in [`JoinGame`] and
+/// [`Respawn`] and as elements of the dimension codec.
+///
+/// <https://wiki.vg/index.php?title=Protocol&oldid=16681#Join_Game>
+#[derive(Deserialize, Debug)]
+pub struct DimensionType {
+    /// `minecraft:overworld`, `minecraft:the_nether`, or `minecraft:the_end`.
+    /// Custom dimensions look like one of these.
+    pub effects: Option<String>,
+}
+
+impl DimensionType {
+    pub fn dimension(&self) -> Dimension {
+        match self.effects.as_deref() {
+            Some("minecraft:the_nether") => Dimension::Nether,
+            Some("minecraft:the_end") => Dimension::End,
+            _ => Dimension::Overworld,
+        }
+    }
+}
+
+impl ByteReadable for DimensionType {
+    fn read_from_bytes(byte_reader: &mut ByteReader) -> Self {
+        nbt::from_reader(byte_reader).unwrap()
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RegistryEntry<T> {
+    pub name: Identifier,
+    pub id: i32,
+    pub element: T,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RegistryEntries<T> {
+    pub value: Vec<RegistryEntry<T>>,
+}
+
+/// The registries the server sends on join. We only need the dimension types.
+#[derive(Deserialize, Debug)]
+pub struct DimensionCodec {
+    #[serde(rename = "minecraft:dimension_type")]
+    pub dimension_types: RegistryEntries<DimensionType>,
+}
+
+impl DimensionCodec {
+    /// the dimension of a dimension type registered in the codec
+    pub fn dimension(&self, name: &str) -> Option<Dimension> {
+        self.dimension_types
+            .value
+            .iter()
+            .find(|entry| entry.name == name)
+            .map(|entry| entry.element.dimension())
+    }
+}
+
+impl ByteReadable for DimensionCodec {
+    fn read_from_bytes(byte_reader: &mut ByteReader) -> Self {
+        nbt::from_reader(byte_reader).unwrap()
+    }
+}
+
+/// Any NBT compound we do not care about the contents of
+pub struct SkipNbt;
+
+impl ByteReadable for SkipNbt {
+    fn read_from_bytes(byte_reader: &mut ByteReader) -> Self {
+        nbt::Blob::from_reader(byte_reader).unwrap();
+        Self
+    }
+}
+
+#[derive(Packet, Debug, Readable)]
+#[packet(0x24, Play)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct JoinGame {
+    pub entity_id: u32,
+    pub hardcore: bool,
+    pub game_mode: GameMode,
+    pub previous_game_mode: u8,
+    pub world_names: Vec<Identifier>,
+    pub dimension_codec: DimensionCodec,
+    pub dimension_type: DimensionType,
+    pub world_name: Identifier,
+    pub hashed_seed: u64,
+    pub max_players: VarInt,
+    pub view_distance: VarInt,
+    pub reduced_debug_info: bool,
+    pub enable_respawn_screen: bool,
+    pub is_debug: bool,
+    pub is_flat: bool,
+}
+
+impl JoinGame {
+    pub fn dimension(&self) -> Dimension {
+        self.dimension_codec
+            .dimension(&self.world_name)
+            .unwrap_or_else(|| self.dimension_type.dimension())
+    }
+}
+
+#[derive(Packet, Debug, Readable)]
+#[packet(0x39, Play)]
+pub struct Respawn {
+    pub dimension_type: DimensionType,
+    pub world_name: Identifier,
+    pub hashed_seed: u64,
+    pub game_mode: GameMode,
+    pub previous_game_mode: u8,
+    pub is_debug: bool,
+    pub is_flat: bool,
+    pub copy_metadata: bool,
+}
+
+#[derive(Packet, Readable)]
+#[packet(0x03, Login)]
+pub struct SetCompression {
+    pub threshold: VarInt,
+}
+
+#[derive(Debug, Clone, Packet, Readable)]
+#[packet(0x01, Login)]
+pub struct EncryptionRequest {
+    pub server_id: String,
+    pub public_key_der: Vec<u8>,
+    pub verify_token: Vec<u8>,
+}
+
+#[derive(Debug, Packet, Readable)]
+#[packet(0x02, Login)]
+pub struct LoginSuccess {
+    /// 1.16 this is just a raw UUID, 1.12 hyphenated
+    pub uuid: UUID,
+    pub username: String,
+}
+
+#[derive(Debug, Packet, Readable)]
+#[packet(0x00, Login)]
+pub struct LoginDisconnect {
+    pub reason: Chat,
+}
+
+/// Sent by modded servers (and proxies such as Velocity)
+#[derive(Debug, Packet, Readable)]
+#[packet(0x04, Login)]
+pub struct LoginPluginRequest {
+    pub message_id: VarInt,
+    pub channel: Identifier,
+    pub data: RawVec,
+}
+
+#[derive(Debug)]
+pub struct PlayerProperty {
+    pub name: String,
+    pub value: String,
+    pub signature: Option<String>,
+}
+
+impl ByteReadable for PlayerProperty {
+    fn read_from_bytes(byte_reader: &mut ByteReader) -> Self {
+        let name = byte_reader.read();
+        let value = byte_reader.read();
+        let is_signed: bool = byte_reader.read();
+        let signature = is_signed.then(|| byte_reader.read());
+        Self {
+            name,
+            value,
+            signature,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AddPlayer {
+    pub name: String,
+    pub properties: Vec<PlayerProperty>,
+    pub gamemode: VarInt,
+    pub ping: VarInt,
+    pub display_name: Option<Chat>,
+}
+
+impl ByteReadable for AddPlayer {
+    fn read_from_bytes(br: &mut ByteReader) -> Self {
+        let (name, properties, gamemode, ping) = br.read();
+        let has_display_name: bool = br.read();
+        let display_name = has_display_name.then(|| br.read());
+        Self {
+            name,
+            properties,
+            gamemode,
+            ping,
+            display_name,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PlayerListType {
+    AddPlayer(AddPlayer),
+    UpdateGamemode(VarInt),
+    UpdateLatency(VarInt),
+    UpdateDisplayName(Option<Chat>),
+    RemovePlayer,
+}
+
+impl ByteReadableLike for PlayerListType {
+    type Param = usize;
+
+    fn read_from_bytes(byte_reader: &mut ByteReader, param: &Self::Param) -> Self {
+        match *param {
+            0 => Self::AddPlayer(byte_reader.read()),
+            1 => Self::UpdateGamemode(byte_reader.read()),
+            2 => Self::UpdateLatency(byte_reader.read()),
+            3 => Self::UpdateDisplayName({
+                let has_val: bool = byte_reader.read();
+                has_val.then(|| byte_reader.read())
+            }),
+            4 => Self::RemovePlayer,
+            _ => panic!("invalid id"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Player {
+    pub uuid: UUID,
+    pub list_type: PlayerListType,
+}
+
+impl ByteReadableLike for Player {
+    type Param = usize;
+
+    fn read_from_bytes(byte_reader: &mut ByteReader, param: &Self::Param) -> Self {
+        Self {
+            uuid: byte_reader.read(),
+            list_type: byte_reader.read_like(param),
+        }
+    }
+}
+
+#[derive(Debug, Packet)]
+#[packet(0x32, Play)]
+pub struct PlayerInfo {
+    pub players: Vec<Player>,
+}
+
+impl ByteReadable for PlayerInfo {
+    fn read_from_bytes(byte_reader: &mut ByteReader) -> Self {
+        let VarUInt(action_id) = byte_reader.read();
+        let VarUInt(number_players) = byte_reader.read();
+        let mut players = Vec::with_capacity(number_players);
+        for _ in 0..number_players {
+            players.push(byte_reader.read_like(&action_id));
+        }
+        Self { players }
+    }
+}
+
+pub mod entity {
+    use swarm_bot_packets::{
+        types::{Angle, VarInt, UUID},
+        Packet, Readable,
+    };
+
+    use crate::types::{Location, ShortLoc};
+
+    #[derive(Packet, Debug, Readable)]
+    #[packet(0x02, Play)]
+    pub struct LivingSpawn {
+        pub entity_id: VarInt,
+        pub entity_uuid: UUID,
+        pub type_id: VarInt,
+        pub location: Location,
+        pub yaw: Angle,
+        pub pitch: Angle,
+        pub head_pitch: Angle,
+        pub velocity_x: i16,
+        pub velocity_y: i16,
+        pub velocity_z: i16,
+    }
+
+    #[derive(Packet, Debug, Readable)]
+    #[packet(0x04, Play)]
+    pub struct PlayerSpawn {
+        pub entity_id: VarInt,
+        pub player_uuid: UUID,
+        pub location: Location,
+        pub yaw: Angle,
+        pub pitch: Angle,
+    }
+
+    #[derive(Packet, Debug, Readable)]
+    #[packet(0x27, Play)]
+    pub struct RelativeMove {
+        pub entity_id: VarInt,
+        pub loc: ShortLoc,
+        pub on_ground: bool,
+    }
+
+    #[derive(Packet, Debug, Readable)]
+    #[packet(0x28, Play)]
+    pub struct LookAndRelativeMove {
+        pub entity_id: VarInt,
+        pub loc: ShortLoc,
+        pub yaw: Angle,
+        pub pitch: Angle,
+        pub on_ground: bool,
+    }
+
+    #[derive(Packet, Debug, Readable)]
+    #[packet(0x36, Play)]
+    pub struct Destroy {
+        pub ids: Vec<VarInt>,
+    }
+
+    #[derive(Packet, Debug, Readable)]
+    #[packet(0x56, Play)]
+    pub struct Teleport {
+        pub entity_id: VarInt,
+        pub location: Location,
+        pub yaw: Angle,
+        pub pitch: Angle,
+        pub on_ground: bool,
+    }
+}
+
+#[derive(Readable)]
+pub struct PlayerPositionAndLookRaw {
+    location: Location,
+    rotation: Direction,
+    flags: BitField,
+    teleport_id: VarInt,
+}
+
+#[derive(Packet, Debug)]
+#[packet(0x34, Play)]
+pub struct PlayerPositionAndLook {
+    pub location: LocationOrigin,
+    pub rotation: DirectionOrigin,
+    pub teleport_id: VarInt,
+}
+
+impl ByteReadable for PlayerPositionAndLook {
+    fn read_from_bytes(byte_reader: &mut ByteReader) -> Self {
+        let raw: PlayerPositionAndLookRaw = byte_reader.read();
+        raw.into_origin()
+    }
+}
+
+impl PlayerPositionAndLookRaw {
+    fn into_origin(self) -> PlayerPositionAndLook {
+        let [x, y, z, y_rot, x_rot, ..] = self.flags.values;
+
+        let location = LocationOrigin::from(self.location, x, y, z);
+        let rotation = DirectionOrigin::from(self.rotation, y_rot, x_rot);
+
+        PlayerPositionAndLook {
+            location,
+            rotation,
+            teleport_id: self.teleport_id,
+        }
+    }
+}
+
+#[derive(Packet, Debug, Readable)]
+#[packet(0x0e, Play)]
+pub struct ChatMessage {
+    pub chat: Chat,
+    pub position: u8,
+    pub sender: UUID,
+}
+
+#[derive(Readable, Debug)]
+pub struct Offset {
+    pub x: i8,
+    pub y: i8,
+    pub z: i8,
+}
+
+#[derive(Packet, Debug)]
+#[packet(0x1b, Play)]
+pub struct Explosion {
+    pub location: Location,
+    pub radius: f32,
+    pub records: Vec<BlockLocation>,
+    // TODO: velocity
+}
+
+impl ByteReadable for Explosion {
+    fn read_from_bytes(byte_reader: &mut ByteReader) -> Self {
+        let location: LocationFloat = byte_reader.read();
+        let radius = byte_reader.read();
+        let records = {
+            let record_size: i32 = byte_reader.read();
+            let record_size = record_size as usize;
+            let records: RawVec<Offset> = byte_reader.read_like(&record_size);
+            records.0
+        };
+
+        let origin_block = BlockLocation::from_flts(location.x, location.y, location.z);
+        let location: Location = location.into();
+        let records = records
+            .into_iter()
+            .map(|record| {
+                BlockLocation::new(
+                    origin_block.x + i32::from(record.x),
+                    origin_block.y + i16::from(record.y),
+                    origin_block.z + i32::from(record.z),
+                )
+            })
+            .collect();
+        Self {
+            location,
+            radius,
+            records,
+        }
+    }
+}
+
+#[derive(Packet, Debug, Readable)]
+#[packet(0x0b, Play)]
+pub struct BlockChange {
+    pub location: Position,
+    pub block_id: VarInt,
+}
+
+#[derive(Debug)]
+pub struct Record {
+    pub x: u8,
+    pub y: u8,
+    pub z: u8,
+    pub block_id: u32,
+}
+
+impl ByteReadable for Record {
+    fn read_from_bytes(byte_reader: &mut ByteReader) -> Self {
+        let VarLong(record) = byte_reader.read();
+        let record = record as u64;
+        Self {
+            x: ((record >> 8) & 0xF) as u8,
+            y: (record & 0xF) as u8,
+            z: ((record >> 4) & 0xF) as u8,
+            block_id: (record >> 12) as u32,
+        }
+    }
+}
+
+#[derive(Packet, Debug, Readable)]
+#[packet(0x1c, Play)]
+pub struct UnloadChunk {
+    pub chunk_x: i32,
+    pub chunk_z: i32,
+}
+
+/// Since 1.16.2 a multi block change only covers a single 16×16×16 section
+#[derive(Packet, Debug)]
+#[packet(0x3b, Play)]
+pub struct MultiBlock {
+    pub section_x: i32,
+    pub section_y: i32,
+    pub section_z: i32,
+    pub records: Vec<Record>,
+}
+
+impl ByteReadable for MultiBlock {
+    fn read_from_bytes(byte_reader: &mut ByteReader) -> Self {
+        let section: u64 = byte_reader.read();
+
+        // arithmetic shifts sign-extend each component
+        let section = section as i64;
+        let section_x = (section >> 42) as i32;
+        let section_y = (section << 44 >> 44) as i32;
+        let section_z = (section << 22 >> 42) as i32;
+
+        let _trust_edges: bool = byte_reader.read();
+        let records = byte_reader.read();
+
+        Self {
+            section_x,
+            section_y,
+            section_z,
+            records,
+        }
+    }
+}
+
+#[derive(Packet, Debug, Readable)]
+#[packet(0x49, Play)]
+pub struct UpdateHealth {
+    pub health: f32,
+
+    // 0-20
+    pub food: VarInt,
+    pub food_saturation: f32,
+}
+
+#[derive(Packet, Debug, Readable)]
+#[packet(0x1f, Play)]
+pub struct KeepAlive {
+    pub id: u64,
+}
+
+#[derive(Packet, Debug, Readable)]
+#[packet(0x19, Play)]
+pub struct PlayDisconnect {
+    pub reason: String,
+}
+
+/// Only the death of the player is read
+#[derive(Packet, Debug)]
+#[packet(0x31, Play)]
+pub struct CombatEvent {
+    /// the death message as a JSON chat component
+    pub death_message: Option<String>,
+}
+
+impl ByteReadable for CombatEvent {
+    fn read_from_bytes(byte_reader: &mut ByteReader) -> Self {
+        // 2 is the death of the player
+        let VarInt(event) = byte_reader.read();
+        let death_message = (event == 2).then(|| {
+            let _player_id: VarInt = byte_reader.read();
+            let _entity_id: i32 = byte_reader.read();
+            byte_reader.read()
+        });
+
+        Self { death_message }
+    }
+}
+
+pub const CHUNK_PKT_ID: u32 = 0x20;
+
+pub struct ChunkColumnPacket {
+    pub chunk_x: i32,
+    pub chunk_z: i32,
+    pub new_chunk: bool,
+    pub column: Column,
+}
+
+impl ChunkColumnPacket {
+    /// Read the packet. Fails if a section has an invalid number of bits per
+    /// block
+    pub fn read(byte_reader: &mut ByteReader, registry: &Registry) -> anyhow::Result<Self> {
+        let chunk_x = byte_reader.read();
+        let chunk_z = byte_reader.read();
+        let full_chunk: bool = byte_reader.read();
+        let VarUInt(mut primary_bitmask) = byte_reader.read();
+        let SkipNbt = byte_reader.read(); // heightmaps
+
+        if full_chunk {
+            let _biomes: Vec<VarInt> = byte_reader.read();
+        }
+
+        let _size: VarUInt = byte_reader.read();
+
+        const INIT: Option<Box<HighMemoryChunkSection>> = None;
+        let mut sections = [INIT; 16];
+
+        let mut idx = 0;
+        while primary_bitmask != 0 {
+            if primary_bitmask & 0b1 == 1 {
+                let section = ChunkSection::read(byte_reader, registry)?;
+                sections[idx] = Some(box HighMemoryChunkSection::new(section.palette));
+            }
+            primary_bitmask >>= 1;
+            idx += 1;
+        }
+
+        let data = ChunkData { sections };
+
+        let column = Column::HighMemory { data };
+
+        Ok(Self {
+            chunk_x,
+            chunk_z,
+            new_chunk: full_chunk,
+            column,
+        })
+    }
+}
+
+pub mod window {
+    use crate::protocol::v754::types::Slot;
+
+    #[derive(Packet, Debug, Readable)]
+    #[packet(0x15, Play)]
+    pub struct Set {
+        pub window_id: u8,
+        pub slot: u16,
+        pub data: Slot,
+    }
+
+    #[derive(Packet, Readable)]
+    #[packet(0x13, Play)]
+    pub struct Items {
+        /// 0 if player inventory
+        pub window_id: u8,
+
+        // number of elements in the array
+        pub slots: crate::types::ShortVec<Slot>,
+    }
+}
+
+pub struct ChunkSection {
+    palette: Palette,
+}
+
+impl ChunkSection {
+    /// 1.16 sections are always sent with a palette of global ids and, unlike
+    /// 1.12, entries never span two longs. Indirect palettes use at most 8
+    /// bits per block and the direct palette 15, so other values are rejected
+    fn read(byte_reader: &mut ByteReader, registry: &Registry) -> anyhow::Result<Self> {
+        let _block_count: i16 = byte_reader.read();
+        let bits_per_block: u8 = byte_reader.read();
+
+        ensure!(
+            (1..=8).contains(&bits_per_block) || bits_per_block == 15,
+            "invalid chunk section with {bits_per_block} bits per block"
+        );
+
+        let id_to_state = (bits_per_block <= 8).then(|| {
+            let ids: Vec<VarInt> = byte_reader.read();
+            ids.into_iter()
+                .map(|VarInt(id)| registry.state(id as u32))
+                .collect::<Vec<_>>()
+        });
+
+        let bits_per_block = if id_to_state.is_some() {
+            max(bits_per_block, 4)
+        } else {
+            bits_per_block
+        };
+
+        let storage: Vec<u64> = byte_reader.read();
+
+        let bits_per_block = usize::from(bits_per_block);
+        let per_long = 64 / bits_per_block;
+        let mask = (1_u64 << bits_per_block) - 1;
+
+        let states = (0..4096).map(|idx| {
+            let long = storage.get(idx / per_long).copied().unwrap_or_default();
+            let value = (long >> ((idx % per_long) * bits_per_block)) & mask;
+            match &id_to_state {
+                Some(id_to_state) => id_to_state
+                    .get(value as usize)
+                    .copied()
+                    .unwrap_or(BlockState::AIR),
+                None => registry.state(value as u32),
+            }
+        });
+
+        Ok(Self {
+            palette: Palette::from_states(states),
+        })
+    }
+}
@@ -0,0 +1,146 @@
+//! The 1.16.5 block state and item registries, translated to 1.12 ids.
+//!
+//! Uses prismarine.js `blocks.json` and `items.json` for 1.16.5, which must be
+//! in the working directory as `blocks-754.json` and `items-754.json`.
+
+use std::{collections::HashMap, fs::OpenOptions, sync::OnceLock};
+
+use anyhow::Context;
+use interfaces::types::{BlockKind, BlockState};
+use serde::Deserialize;
+
+use crate::storage::legacy::LegacyNames;
+
+/// items which do not exist in 1.12 get a kind of `UNMAPPED_ITEM_OFFSET + id`
+/// so they do not collide with any 1.12 item but can still be sent back to the
+/// server
+const UNMAPPED_ITEM_OFFSET: u32 = 1 << 16;
+
+/// Uses prismarine.js block data. We only need the fields below
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawBlock {
+    name: String,
+    min_state_id: u32,
+    max_state_id: u32,
+    bounding_box: String,
+}
+
+/// Uses prismarine.js item data. We only need the fields below
+#[derive(Deserialize)]
+struct RawItem {
+    id: i32,
+    name: String,
+}
+
+fn read_json<T: for<'de> Deserialize<'de>>(path: &str) -> anyhow::Result<T> {
+    let reader = OpenOptions::new()
+        .read(true)
+        .open(path)
+        .with_context(|| format!("could not open {path} (required for 1.16.5)"))?;
+    serde_json::from_reader(reader).with_context(|| format!("could not parse {path}"))
+}
+
+pub struct Registry {
+    /// global palette id → 1.12 state
+    states: Vec<BlockState>,
+
+    /// 1.16 item id → 1.12 (id, damage)
+    items: HashMap<i32, (BlockKind, u16)>,
+
+    /// 1.12 (id, damage) → 1.16 item id
+    legacy_items: HashMap<(BlockKind, u16), i32>,
+}
+
+static REGISTRY: OnceLock<Registry> = OnceLock::new();
+
+impl Registry {
+    /// The registry shared by all 1.16.5 bots. It is read from disk the first
+    /// time this is called.
+    pub fn global() -> anyhow::Result<&'static Self> {
+        if let Some(registry) = REGISTRY.get() {
+            return Ok(registry);
+        }
+
+        let registry = Self::read()?;
+        Ok(REGISTRY.get_or_init(|| registry))
+    }
+
+    fn read() -> anyhow::Result<Self> {
+        let legacy = LegacyNames::read()?;
+
+        let blocks: Vec<RawBlock> = read_json("blocks-754.json")?;
+        let raw_items: Vec<RawItem> = read_json("items-754.json")?;
+
+        let state_count = blocks
+            .iter()
+            .map(|block| block.max_state_id as usize + 1)
+            .max()
+            .unwrap_or_default();
+
+        let mut states = vec![BlockState::AIR; state_count];
+
+        for block in blocks {
+            // blocks without a 1.12 equivalent are approximated by their collision
+            let state = legacy
+                .block(&block.name)
+                .unwrap_or(if block.bounding_box == "block" {
+                    BlockState::STONE
+                } else {
+                    BlockState::AIR
+                });
+
+            for id in block.min_state_id..=block.max_state_id {
+                states[id as usize] = state;
+            }
+        }
+
+        let mut items = HashMap::new();
+        let mut legacy_items = HashMap::new();
+
+        for RawItem { id, name } in raw_items {
+            let item = legacy
+                .item(&name)
+                .unwrap_or((BlockKind(UNMAPPED_ITEM_OFFSET + id as u32), 0));
+
+            items.insert(id, item);
+
+            // several 1.16 items can map to the same 1.12 item. Prefer the lowest id
+            legacy_items.entry(item).or_insert(id);
+        }
+
+        Ok(Self {
+            states,
+            items,
+            legacy_items,
+        })
+    }
+
+    /// the 1.12 state of a global palette id
+    pub fn state(&self, id: u32) -> BlockState {
+        self.states
+            .get(id as usize)
+            .copied()
+            .unwrap_or(BlockState::AIR)
+    }
+
+    /// the 1.12 (id, damage) of a 1.16 item id
+    pub fn item(&self, id: i32) -> (BlockKind, u16) {
+        self.items
+            .get(&id)
+            .copied()
+            .unwrap_or((BlockKind(UNMAPPED_ITEM_OFFSET + id as u32), 0))
+    }
+
+    /// the 1.16 item id of a 1.12 (id, damage)
+    pub fn item_id(&self, kind: BlockKind, damage: u16) -> Option<i32> {
+        if kind.0 >= UNMAPPED_ITEM_OFFSET {
+            return Some((kind.0 - UNMAPPED_ITEM_OFFSET) as i32);
+        }
+
+        self.legacy_items
+            .get(&(kind, damage))
+            .or_else(|| self.legacy_items.get(&(kind, 0)))
+            .copied()
+    }
+}
@@ -0,0 +1,221 @@
+use swarm_bot_packets::{
+    types::{RawVec, VarInt},
+    write::{ByteWritable, ByteWriter},
+    AdtWritable, EnumWritable, Packet, Writable,
+};
+
+use crate::{
+    protocol::v754::types::{Position, Slot},
+    types::{Direction, Location},
+};
+
+#[derive(Packet, Writable)]
+#[packet(0x00, Handshake)]
+pub struct Handshake {
+    pub protocol_version: VarInt,
+    pub host: String,
+
+    /// hostname or IP
+    pub port: u16,
+
+    /// default 25565
+    pub next_state: HandshakeNextState, // 1 for status, 2 for login
+}
+
+#[derive(Copy, Clone, EnumWritable)]
+#[repr(i32)]
+#[allow(dead_code)]
+pub enum HandshakeNextState {
+    #[deprecated]
+    Invalid,
+
+    Status,
+    Login,
+}
+
+#[derive(Debug, Packet, Writable)]
+#[packet(0x00, Login)]
+pub struct LoginStart {
+    /// player's username
+    pub username: String,
+}
+
+#[derive(Packet, Writable)]
+#[packet(0x01, Login)]
+pub struct EncryptionResponse {
+    pub shared_secret: Vec<u8>,
+    pub verify_token: Vec<u8>,
+}
+
+/// We do not understand any login plugin channels, so we always respond
+/// unsuccessfully
+#[derive(Packet, Writable)]
+#[packet(0x02, Login)]
+pub struct LoginPluginResponse {
+    pub message_id: VarInt,
+    pub successful: bool,
+    pub data: RawVec,
+}
+
+#[derive(Writable, Packet)]
+#[packet(0x00, Play)]
+pub struct TeleportConfirm {
+    pub teleport_id: VarInt,
+}
+
+#[derive(Writable, Packet)]
+#[packet(0x03, Play)]
+pub struct ChatMessage {
+    pub message: String,
+}
+
+/// Respawning and show stats
+#[derive(Writable, Packet)]
+#[packet(0x04, Play)]
+pub struct ClientStatus {
+    pub(crate) action: ClientStatusAction,
+}
+
+#[repr(i32)]
+#[derive(EnumWritable)]
+#[allow(dead_code)]
+pub enum ClientStatusAction {
+    Respawn = 0,
+    Stats = 1,
+}
+
+#[derive(Writable, Packet)]
+#[packet(0x09, Play)]
+pub struct ClickWindow {
+    pub window_id: u8,
+    pub slot: u16,
+    pub button: u8,
+    pub action_number: u16,
+    pub mode: VarInt,
+    pub clicked: Slot,
+}
+
+#[derive(EnumWritable, Debug)]
+#[allow(dead_code)]
+pub enum Hand {
+    Main,
+    Off,
+}
+
+#[derive(Debug, AdtWritable)]
+#[repr(i32)]
+#[allow(dead_code)]
+pub enum InteractEntityKind {
+    Interact {
+        hand: Hand,
+    },
+    Attack,
+    InteractAt {
+        target_x: f32,
+        target_y: f32,
+        target_z: f32,
+        hand: Hand,
+    },
+}
+
+#[derive(Writable, Packet)]
+#[packet(0x0e, Play)]
+pub struct InteractEntity {
+    pub id: VarInt,
+    pub kind: InteractEntityKind,
+    pub sneaking: bool,
+}
+
+#[derive(Writable, Packet)]
+#[packet(0x10, Play)]
+pub struct KeepAlive {
+    pub id: u64,
+}
+
+#[derive(Writable, Packet)]
+#[packet(0x12, Play)]
+pub struct PlayerPosition {
+    /// True if the client is on the ground, false otherwise.
+    pub location: Location,
+    pub on_ground: bool,
+}
+
+#[derive(Writable, Packet)]
+#[packet(0x13, Play)]
+pub struct PlayerPositionAndRotation {
+    pub location: Location,
+    pub direction: Direction,
+    pub on_ground: bool,
+}
+
+#[derive(Writable, Packet)]
+#[packet(0x14, Play)]
+pub struct PlayerLook {
+    pub(crate) direction: Direction,
+    pub(crate) on_ground: bool,
+}
+
+#[derive(EnumWritable, Eq, PartialEq, Copy, Clone)]
+#[allow(dead_code)]
+pub enum DigStatus {
+    Started,
+    Cancelled,
+    Finished,
+    DropItemStack,
+    DropItem,
+    ShootArrowOrFinishEat,
+    SwapItem, // location 0,0,0 face-y
+}
+
+#[derive(Writable, Packet)]
+#[packet(0x1b, Play)]
+pub struct PlayerDig {
+    pub status: DigStatus,
+    pub position: Position,
+    pub face: u8,
+}
+
+impl PlayerDig {
+    pub fn status(status: DigStatus) -> Self {
+        Self {
+            status,
+            position: Position::default(),
+            face: 0,
+        }
+    }
+}
+
+#[derive(Writable, Packet)]
+#[packet(0x25, Play)]
+pub struct ChangeSlot {
+    pub slot: u16,
+}
+
+#[derive(Writable, Packet)]
+#[packet(0x2c, Play)]
+pub struct ArmAnimation {
+    pub hand: Hand,
+}
+
+#[derive(Writable, Default)]
+pub struct BlockCursor {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+#[derive(Writable, Packet)]
+#[packet(0x2e, Play)]
+pub struct PlaceBlock {
+    pub hand: Hand,
+    pub location: Position,
+    pub face: VarInt,
+    pub cursor: BlockCursor,
+    pub inside_block: bool,
+}
+
+#[derive(Packet, Writable)]
+#[packet(0x2f, Play)]
+pub struct UseItem {
+    pub(crate) hand: Hand,
+}
@@ -0,0 +1,169 @@
+//! Encodings which changed between 1.12 and 1.16
+
+use interfaces::types::{BlockLocation, Enchantment};
+use nbt::{Blob, Value};
+use swarm_bot_packets::{
+    read::{ByteReadable, ByteReader},
+    types::VarInt,
+    write::{ByteWritable, ByteWriter},
+};
+
+use crate::types::ItemNbt;
+
+/// A block position. Since 1.14 y is stored in the lowest bits.
+///
+/// <https://wiki.vg/index.php?title=Protocol&oldid=16681#Position>
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Position(pub BlockLocation);
+
+impl From<BlockLocation> for Position {
+    fn from(location: BlockLocation) -> Self {
+        Self(location)
+    }
+}
+
+impl ByteReadable for Position {
+    fn read_from_bytes(byte_reader: &mut ByteReader) -> Self {
+        let val: u64 = byte_reader.read();
+
+        // arithmetic shifts sign-extend each component
+        let val = val as i64;
+        let x = (val >> 38) as i32;
+        let y = (val << 52 >> 52) as i16;
+        let z = (val << 26 >> 38) as i32;
+
+        Self(BlockLocation { x, y, z })
+    }
+}
+
+impl ByteWritable for Position {
+    fn write_to_bytes(self, writer: &mut ByteWriter) {
+        let BlockLocation { x, y, z } = self.0;
+        let write =
+            ((x as u64 & 0x3FF_FFFF) << 38) | ((z as u64 & 0x3FF_FFFF) << 12) | (y as u64 & 0xFFF);
+        writer.write(write);
+    }
+}
+
+/// 1.13+ slot data. Item ids are in the 1.16 item registry.
+///
+/// <https://wiki.vg/index.php?title=Slot_Data&oldid=16657>
+#[derive(Debug)]
+pub struct Slot {
+    pub item: Option<SlotItem>,
+}
+
+#[derive(Debug)]
+pub struct SlotItem {
+    pub id: i32,
+    pub count: u8,
+    pub nbt: Option<ItemNbt>,
+}
+
+impl Slot {
+    pub const EMPTY: Self = Self { item: None };
+}
+
+impl ByteReadable for Slot {
+    fn read_from_bytes(byte_reader: &mut ByteReader) -> Self {
+        let present: bool = byte_reader.read();
+        if !present {
+            return Self::EMPTY;
+        }
+
+        let VarInt(id) = byte_reader.read();
+        let count = byte_reader.read();
+
+        let first: u8 = byte_reader.read();
+        let nbt = (first != 0).then(|| {
+            byte_reader.back(1);
+            let blob = Blob::from_reader(byte_reader).unwrap();
+            legacy_nbt(&blob)
+        });
+
+        Self {
+            item: Some(SlotItem { id, count, nbt }),
+        }
+    }
+}
+
+impl ByteWritable for Slot {
+    fn write_to_bytes(self, writer: &mut ByteWriter) {
+        match self.item {
+            None => {
+                writer.write(false);
+            }
+            Some(SlotItem { id, count, .. }) => {
+                // the server only uses the clicked item to check if the client is in sync,
+                // so we do not bother writing the NBT
+                writer
+                    .write(true)
+                    .write(VarInt(id))
+                    .write(count)
+                    .write(0_u8);
+            }
+        }
+    }
+}
+
+/// 1.12 numeric enchantment ids of the enchantments we care about
+const ENCHANTMENTS: [(&str, u16); 5] = [
+    ("minecraft:efficiency", 32),
+    ("minecraft:silk_touch", 33),
+    ("minecraft:unbreaking", 34),
+    ("minecraft:fortune", 35),
+    ("minecraft:sharpness", 16),
+];
+
+/// convert 1.13+ item NBT (`Enchantments` with string ids) to the 1.12 format
+fn legacy_nbt(blob: &Blob) -> ItemNbt {
+    let Some(Value::List(enchantments)) = blob.get("Enchantments") else {
+        return ItemNbt { ench: None };
+    };
+
+    let ench = enchantments
+        .iter()
+        .filter_map(|enchantment| {
+            let Value::Compound(enchantment) = enchantment else { return None };
+            let Some(Value::String(name)) = enchantment.get("id") else { return None };
+            let Some(&Value::Short(lvl)) = enchantment.get("lvl") else { return None };
+
+            let &(_, id) = ENCHANTMENTS.iter().find(|(elem, _)| elem == name)?;
+
+            Some(Enchantment {
+                lvl: lvl as u16,
+                id,
+            })
+        })
+        .collect();
+
+    ItemNbt { ench: Some(ench) }
+}
+
+#[cfg(test)]
+mod tests {
+    use interfaces::types::BlockLocation;
+    use swarm_bot_packets::{read::ByteReader, write::ByteWriter};
+
+    use crate::protocol::v754::types::Position;
+
+    #[test]
+    fn test_position_round_trip() {
+        let locations = [
+            BlockLocation::new(0, 0, 0),
+            BlockLocation::new(18_357_644, 831, -20_882_616),
+            BlockLocation::new(-1, -1, -1),
+            BlockLocation::new(-33_554_432, 255, 33_554_431),
+        ];
+
+        for location in locations {
+            let mut writer = ByteWriter::new();
+            writer.write(Position(location));
+
+            let mut reader = ByteReader::new(writer.freeze());
+            let Position(read) = reader.read();
+
+            assert_eq!(read, location);
+        }
+    }
+}
@@ -1,8 +1,108 @@
-use std::io::Read;
+use std::{
+    fs::OpenOptions,
+    io::{Read, Write},
+    path::Path,
+};
 
-use anyhow::Context;
-use interfaces::types::{BlockLocation, BlockState};
-use serde::{Deserialize, Serialize};
+use anyhow::{bail, ensure, Context};
+use interfaces::types::{BlockLocation, BlockState, Selection3D};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::storage::{blocks::WorldBlocks, legacy::LegacyNames};
+
+pub mod litematica;
+pub mod sponge;
+
+/// Blocks which can be placed in the world, no matter the file format they
+/// were read from
+pub trait Structure {
+    /// every block along with its location. Blocks which do not exist in 1.12
+    /// are left out
+    fn blocks(&self) -> Box<dyn Iterator<Item = (BlockLocation, BlockState)> + '_>;
+
+    /// the names of blocks which do not exist in 1.12
+    fn unknown_blocks(&self) -> &[String] {
+        &[]
+    }
+}
+
+/// Load a structure, choosing the format from the extension of `path`.
+/// `names` translates the flattened block names of newer formats to 1.12
+pub fn load(path: &Path, names: &LegacyNames) -> anyhow::Result<Box<dyn Structure>> {
+    let mut reader = OpenOptions::new()
+        .read(true)
+        .open(path)
+        .with_context(|| format!("could not open {}", path.display()))?;
+
+    let extension = path
+        .extension()
+        .and_then(std::ffi::OsStr::to_str)
+        .unwrap_or("");
+
+    let res: Box<dyn Structure> = match extension {
+        "schematic" => Box::new(Schematic::load(&mut reader)?),
+        "schem" => Box::new(sponge::Sponge::load(&mut reader, names)?),
+        "litematic" => Box::new(litematica::Litematic::load(&mut reader, names)?),
+        _ => bail!(
+            "{} is not a .schematic, .schem or .litematic file",
+            path.display()
+        ),
+    };
+
+    Ok(res)
+}
+
+/// The 1.12 state of each entry in the palette of a format which stores
+/// flattened block names
+struct Palette {
+    states: Vec<Option<BlockState>>,
+    unknown: Vec<String>,
+}
+
+impl Palette {
+    /// `entries` are (palette index, block name) pairs. Block properties in the
+    /// name (e.g., `[facing=north]`) are not taken into account
+    fn new(
+        entries: impl IntoIterator<Item = (u32, String)>,
+        names: &LegacyNames,
+    ) -> anyhow::Result<Self> {
+        let entries: Vec<_> = entries.into_iter().collect();
+
+        let mut states = vec![None; entries.len()];
+        let mut unknown = Vec::new();
+
+        for (idx, name) in entries {
+            let slot = states
+                .get_mut(idx as usize)
+                .with_context(|| format!("palette index {idx} of {name} is out of range"))?;
+
+            let without_properties = name.split('[').next().unwrap_or_default();
+
+            match names.block(without_properties) {
+                Some(state) => *slot = Some(state),
+                None => unknown.push(without_properties.to_string()),
+            }
+        }
+
+        unknown.sort_unstable();
+        unknown.dedup();
+
+        Ok(Self { states, unknown })
+    }
+
+    /// the state of the palette entry `idx`, if it exists in 1.12
+    fn get(&self, idx: u32) -> Option<BlockState> {
+        self.states.get(idx as usize).copied().flatten()
+    }
+
+    fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    fn unknown(&self) -> &[String] {
+        &self.unknown
+    }
+}
 
 /// The `WorldEdit` schematic format
 /// <https://minecraft.fandom.com/wiki/Schematic_file_format>
@@ -13,8 +113,14 @@ pub struct Schematic {
     pub height: i16,
     pub length: i16,
     materials: String,
+    #[serde(serialize_with = "nbt::i8_array")]
     blocks: Vec<i8>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "option_i8_array"
+    )]
     add_blocks: Option<Vec<i8>>,
+    #[serde(serialize_with = "nbt::i8_array")]
     data: Vec<i8>,
     w_e_origin_x: Option<i32>,
     w_e_origin_y: Option<i32>,
@@ -24,7 +130,102 @@ pub struct Schematic {
     w_e_offset_z: Option<i32>,
 }
 
+/// serialize as a byte array rather than a list of bytes
+fn option_i8_array<S: Serializer>(
+    array: &Option<Vec<i8>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match array {
+        Some(array) => nbt::i8_array(array, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// The most blocks [`Schematic::from_world`] will copy
+const MAX_VOLUME: u64 = 1 << 27;
+
 impl Schematic {
+    /// Copy the blocks in `sel` from `world`. Blocks which are unknown or only
+    /// estimated are copied as air. The `WorldEdit` offset is relative to
+    /// `sel.from`, so pasting puts that corner where the player stands
+    pub fn from_world(world: &WorldBlocks, sel: Selection3D) -> anyhow::Result<Self> {
+        let copy_position = sel.from;
+        let Selection3D { from, to } = sel.normalize();
+
+        let size = |min: i32, max: i32| {
+            i16::try_from(max - min + 1).context("the selection is too large to be a schematic")
+        };
+
+        let width = size(from.x, to.x)?;
+        let height = size(i32::from(from.y), i32::from(to.y))?;
+        let length = size(from.z, to.z)?;
+
+        let volume = u64::from(width.unsigned_abs())
+            * u64::from(height.unsigned_abs())
+            * u64::from(length.unsigned_abs());
+        ensure!(
+            volume <= MAX_VOLUME,
+            "the selection has {volume} blocks but at most {MAX_VOLUME} can be copied"
+        );
+
+        let volume = volume as usize;
+        let mut blocks = Vec::with_capacity(volume);
+        let mut data = Vec::with_capacity(volume);
+        let mut add_blocks = vec![0_u8; (volume + 1) / 2];
+        let mut has_add_blocks = false;
+
+        for y in from.y..=to.y {
+            for z in from.z..=to.z {
+                for x in from.x..=to.x {
+                    let state = world
+                        .get_block_exact(BlockLocation::new(x, y, z))
+                        .unwrap_or(BlockState::AIR);
+
+                    let id = state.id();
+                    let idx = blocks.len();
+
+                    blocks.push(i8::from_ne_bytes([(id & 0xFF) as u8]));
+                    data.push(i8::from_ne_bytes([state.metadata()]));
+
+                    // ids above 255 keep their upper bits in a nibble array
+                    let add = ((id >> 8) & 0xF) as u8;
+                    if add != 0 {
+                        has_add_blocks = true;
+                        add_blocks[idx / 2] |= if idx % 2 == 0 { add } else { add << 4 };
+                    }
+                }
+            }
+        }
+
+        let add_blocks = has_add_blocks.then(|| {
+            add_blocks
+                .into_iter()
+                .map(|nibbles| i8::from_ne_bytes([nibbles]))
+                .collect()
+        });
+
+        Ok(Self {
+            width,
+            height,
+            length,
+            materials: "Alpha".to_string(),
+            blocks,
+            add_blocks,
+            data,
+            w_e_origin_x: Some(from.x),
+            w_e_origin_y: Some(i32::from(from.y)),
+            w_e_origin_z: Some(from.z),
+            w_e_offset_x: Some(from.x - copy_position.x),
+            w_e_offset_y: Some(i32::from(from.y) - i32::from(copy_position.y)),
+            w_e_offset_z: Some(from.z - copy_position.z),
+        })
+    }
+
+    /// Write the schematic as gzipped NBT
+    pub fn save(&self, writer: &mut impl Write) -> anyhow::Result<()> {
+        nbt::to_gzip_writer(writer, self, Some("Schematic")).context("could not save schematic")
+    }
+
     #[allow(unused)]
     pub const fn volume(&self) -> u64 {
         let v = (self.width as i64) * (self.height as i64) * (self.length as i64);
@@ -75,7 +276,12 @@ impl Schematic {
         u64::try_from(self.length).unwrap()
     }
 
-    #[allow(unused, clippy::unwrap_used, clippy::indexing_slicing)]
+    #[allow(
+        unused,
+        clippy::unwrap_used,
+        clippy::indexing_slicing,
+        clippy::cast_sign_loss
+    )]
     pub fn blocks(&self) -> impl Iterator<Item = (BlockLocation, BlockState)> + '_ {
         let origin = self.origin().unwrap_or_default();
 
@@ -89,23 +295,45 @@ impl Schematic {
 
             let location = BlockLocation::new(x as i32, y as i16, z as i32) + origin;
 
-            let id = self.blocks[idx as usize].abs_diff(0);
-            let data = self.data[idx as usize].abs_diff(0);
-            let state = BlockState::from(u32::from(id), u16::from(data));
+            let idx = idx as usize;
+
+            // ids above 255 keep their upper bits in a nibble array
+            let add = self
+                .add_blocks
+                .as_ref()
+                .and_then(|add_blocks| add_blocks.get(idx / 2))
+                .map_or(0, |&nibbles| {
+                    let nibbles = nibbles as u8;
+                    if idx % 2 == 0 {
+                        nibbles & 0xF
+                    } else {
+                        nibbles >> 4
+                    }
+                });
+
+            let id = (u32::from(add) << 8) | u32::from(self.blocks[idx] as u8);
+            let data = self.data[idx] as u8 & 0xF;
+            let state = BlockState::from(id, u16::from(data));
 
             (location, state)
         })
     }
 }
 
+impl Structure for Schematic {
+    fn blocks(&self) -> Box<dyn Iterator<Item = (BlockLocation, BlockState)> + '_> {
+        Box::new(Self::blocks(self))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::{collections::HashMap, fs::OpenOptions};
 
-    use interfaces::types::BlockLocation;
+    use interfaces::types::{BlockLocation, BlockState, Selection3D};
     use more_asserts::*;
 
-    use crate::schematic::Schematic;
+    use crate::{schematic::Schematic, storage::blocks::WorldBlocks};
 
     #[test]
     fn test_load() {
@@ -136,4 +364,42 @@ mod tests {
         let stained_glass = map[&BlockLocation::new(-162, 81, -357)];
         assert_eq!(stained_glass.id(), 95);
     }
+
+    #[test]
+    fn test_from_world() {
+        let mut world = WorldBlocks::default();
+        world.set_block(BlockLocation::new(0, 0, 0), BlockState::STONE);
+        world.set_block(BlockLocation::new(-3, 10, 2), BlockState::from(35, 14));
+        world.set_block(BlockLocation::new(-1, 5, 1), BlockState::from(300, 2));
+
+        let sel = Selection3D {
+            from: BlockLocation::new(0, 10, 2),
+            to: BlockLocation::new(-3, 0, 0),
+        };
+
+        let schematic = Schematic::from_world(&world, sel).unwrap();
+
+        let mut bytes = Vec::new();
+        schematic.save(&mut bytes).unwrap();
+        let schematic = Schematic::load(&mut bytes.as_slice()).unwrap();
+
+        assert!(schematic.is_valid());
+        assert_eq!(
+            (schematic.width, schematic.height, schematic.length),
+            (4, 11, 3)
+        );
+        assert_eq!(schematic.origin(), Some(BlockLocation::new(-3, 0, 0)));
+        assert_eq!(schematic.offset(), Some(BlockLocation::new(-3, -10, -2)));
+
+        let map: HashMap<_, _> = schematic.blocks().collect();
+        assert_eq!(map.len(), 4 * 11 * 3);
+
+        assert_eq!(map[&BlockLocation::new(0, 0, 0)], BlockState::STONE);
+        assert_eq!(
+            map[&BlockLocation::new(-3, 10, 2)],
+            BlockState::from(35, 14)
+        );
+        assert_eq!(map[&BlockLocation::new(-1, 5, 1)], BlockState::from(300, 2));
+        assert_eq!(map[&BlockLocation::new(-2, 3, 1)], BlockState::AIR);
+    }
 }
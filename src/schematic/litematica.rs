@@ -0,0 +1,292 @@
+//! The Litematica format (`.litematic`). A file holds several regions, each
+//! with its own palette and blocks bit-packed into longs
+//! <https://github.com/maruohon/litematica>.
+
+use std::{collections::HashMap, io::Read};
+
+use anyhow::{ensure, Context};
+use interfaces::types::{BlockLocation, BlockState};
+use serde::Deserialize;
+
+use crate::{
+    schematic::{Palette, Structure},
+    storage::legacy::LegacyNames,
+};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Root {
+    regions: HashMap<String, RawRegion>,
+}
+
+#[derive(Deserialize)]
+struct Vec3 {
+    x: i32,
+    y: i32,
+    z: i32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RawRegion {
+    position: Vec3,
+    size: Vec3,
+    block_state_palette: Vec<PaletteEntry>,
+    block_states: Vec<i64>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct PaletteEntry {
+    name: String,
+}
+
+/// The value at `idx` of an array of `bits` wide values packed into longs.
+/// Values can span two longs
+const fn packed(states: &[u64], bits: u32, idx: usize) -> u32 {
+    let bits = bits as usize;
+    let mask = (1_u64 << bits) - 1;
+
+    let start_bit = idx * bits;
+    let start = start_bit / 64;
+    let end = (start_bit + bits - 1) / 64;
+    let offset = start_bit % 64;
+
+    let value = if start == end {
+        states[start] >> offset
+    } else {
+        (states[start] >> offset) | (states[end] << (64 - offset))
+    };
+
+    (value & mask) as u32
+}
+
+/// A region of a Litematica schematic
+struct Region {
+    /// the lowest corner
+    min: BlockLocation,
+    size_x: usize,
+    size_y: usize,
+    size_z: usize,
+    palette: Palette,
+    bits: u32,
+    states: Vec<u64>,
+}
+
+impl Region {
+    #[allow(clippy::cast_sign_loss)]
+    fn new(raw: RawRegion, names: &LegacyNames) -> anyhow::Result<Self> {
+        // a negative size means the region extends from the position towards
+        // lower coordinates
+        let min_of = |position: i32, size: i32| {
+            if size < 0 {
+                position + size + 1
+            } else {
+                position
+            }
+        };
+
+        let min = BlockLocation::new(
+            min_of(raw.position.x, raw.size.x),
+            min_of(raw.position.y, raw.size.y) as i16,
+            min_of(raw.position.z, raw.size.z),
+        );
+
+        let size_x = raw.size.x.unsigned_abs() as usize;
+        let size_y = raw.size.y.unsigned_abs() as usize;
+        let size_z = raw.size.z.unsigned_abs() as usize;
+
+        ensure!(
+            !raw.block_state_palette.is_empty(),
+            "a region has an empty palette"
+        );
+
+        let palette = Palette::new(
+            raw.block_state_palette
+                .into_iter()
+                .enumerate()
+                .map(|(idx, entry)| (idx as u32, entry.name)),
+            names,
+        )?;
+
+        // at least 2 bits are used per block
+        let bits = usize::BITS - (palette.len() - 1).leading_zeros();
+        let bits = bits.max(2);
+
+        let states: Vec<_> = raw.block_states.into_iter().map(|x| x as u64).collect();
+
+        let volume = size_x * size_y * size_z;
+        let needed = (volume * bits as usize + 63) / 64;
+        ensure!(
+            states.len() >= needed,
+            "a region has {} longs of blocks instead of {needed}",
+            states.len()
+        );
+
+        Ok(Self {
+            min,
+            size_x,
+            size_y,
+            size_z,
+            palette,
+            bits,
+            states,
+        })
+    }
+
+    fn blocks(&self) -> impl Iterator<Item = (BlockLocation, BlockState)> + '_ {
+        let volume = self.size_x * self.size_y * self.size_z;
+
+        (0..volume).filter_map(move |idx| {
+            let state = self.palette.get(packed(&self.states, self.bits, idx))?;
+
+            let x = idx % self.size_x;
+            let z = (idx / self.size_x) % self.size_z;
+            let y = idx / (self.size_x * self.size_z);
+
+            let location = BlockLocation::new(x as i32, y as i16, z as i32) + self.min;
+            Some((location, state))
+        })
+    }
+}
+
+/// A Litematica schematic
+pub struct Litematic {
+    regions: Vec<Region>,
+
+    /// the names of blocks in any region which do not exist in 1.12
+    unknown: Vec<String>,
+}
+
+impl Litematic {
+    /// Load a gzipped Litematica schematic. Block names are translated to
+    /// 1.12 with `names`
+    pub fn load(reader: &mut impl Read, names: &LegacyNames) -> anyhow::Result<Self> {
+        let root: Root = nbt::from_gzip_reader(reader).context("could not load schematic")?;
+
+        let regions = root
+            .regions
+            .into_values()
+            .map(|raw| Region::new(raw, names))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let mut unknown: Vec<_> = regions
+            .iter()
+            .flat_map(|region| region.palette.unknown())
+            .cloned()
+            .collect();
+
+        unknown.sort_unstable();
+        unknown.dedup();
+
+        Ok(Self { regions, unknown })
+    }
+}
+
+impl Structure for Litematic {
+    fn blocks(&self) -> Box<dyn Iterator<Item = (BlockLocation, BlockState)> + '_> {
+        Box::new(self.regions.iter().flat_map(Region::blocks))
+    }
+
+    fn unknown_blocks(&self) -> &[String] {
+        &self.unknown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use interfaces::types::{BlockLocation, BlockState};
+
+    use crate::{
+        schematic::{
+            litematica::{packed, Litematic},
+            Structure,
+        },
+        storage::legacy::LegacyNames,
+    };
+
+    fn vec3(x: i32, y: i32, z: i32) -> nbt::Value {
+        let map = [("x", x), ("y", y), ("z", z)]
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), nbt::Value::Int(value)))
+            .collect();
+        nbt::Value::Compound(map)
+    }
+
+    fn entry(name: &str) -> nbt::Value {
+        let map = [("Name".to_string(), nbt::Value::String(name.to_string()))]
+            .into_iter()
+            .collect();
+        nbt::Value::Compound(map)
+    }
+
+    #[test]
+    fn test_packed() {
+        // 3 bit values 0..=7 repeated. The 22nd value spans both longs
+        let mut states = [0_u64; 2];
+        for idx in 0..42 {
+            let bit = idx * 3;
+            let value = (idx % 8) as u64;
+            states[bit / 64] |= value << (bit % 64);
+            if bit % 64 > 61 {
+                states[bit / 64 + 1] |= value >> (64 - bit % 64);
+            }
+        }
+
+        for idx in 0..42 {
+            assert_eq!(packed(&states, 3, idx), (idx % 8) as u32);
+        }
+    }
+
+    #[test]
+    fn test_load() {
+        let names = LegacyNames::read().unwrap();
+
+        // a 2×1×2 region spanning to lower x and z. Its blocks are stone,
+        // air, an unknown block and oak planks with 2 bits each
+        let palette = [
+            "minecraft:air",
+            "minecraft:stone",
+            "minecraft:not_a_block",
+            "minecraft:oak_planks",
+        ];
+
+        let mut region = nbt::Map::new();
+        region.insert("Position".to_string(), vec3(5, 70, 5));
+        region.insert("Size".to_string(), vec3(-2, 1, -2));
+        region.insert(
+            "BlockStatePalette".to_string(),
+            nbt::Value::List(palette.into_iter().map(entry).collect()),
+        );
+        region.insert(
+            "BlockStates".to_string(),
+            nbt::Value::LongArray(vec![0b11_10_00_01]),
+        );
+
+        let mut regions = nbt::Map::new();
+        regions.insert("main".to_string(), nbt::Value::Compound(region));
+
+        let mut blob = nbt::Blob::new();
+        blob.insert("Version", nbt::Value::Int(5)).unwrap();
+        blob.insert("Regions", nbt::Value::Compound(regions))
+            .unwrap();
+
+        let mut bytes = Vec::new();
+        blob.to_gzip_writer(&mut bytes).unwrap();
+
+        let litematic = Litematic::load(&mut bytes.as_slice(), &names).unwrap();
+        let blocks: HashMap<_, _> = litematic.blocks().collect();
+
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(litematic.unknown_blocks(), ["minecraft:not_a_block"]);
+
+        assert_eq!(blocks[&BlockLocation::new(4, 70, 4)], BlockState::STONE);
+        assert_eq!(blocks[&BlockLocation::new(5, 70, 4)], BlockState::AIR);
+        assert_eq!(
+            blocks[&BlockLocation::new(5, 70, 5)],
+            BlockState::from(5, 0)
+        );
+    }
+}
@@ -0,0 +1,279 @@
+//! The Sponge schematic format (`.schem`) written by `WorldEdit` since 1.13
+//! <https://github.com/SpongePowered/Schematic-Specification>. Versions 1 and
+//! 2 keep the palette and blocks at the top level and version 3 nests them in
+//! a `Schematic` compound.
+
+use std::{collections::HashMap, io::Read};
+
+use anyhow::{bail, ensure, Context};
+use interfaces::types::{BlockLocation, BlockState};
+use serde::Deserialize;
+
+use crate::{
+    schematic::{Palette, Structure},
+    storage::legacy::LegacyNames,
+};
+
+#[derive(Deserialize)]
+struct V3Root {
+    #[serde(rename = "Schematic")]
+    schematic: Body,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Body {
+    version: i32,
+    width: i16,
+    height: i16,
+    length: i16,
+    offset: Option<Vec<i32>>,
+
+    // versions 1 and 2
+    palette: Option<HashMap<String, i32>>,
+    block_data: Option<Vec<i8>>,
+
+    // version 3
+    blocks: Option<BlockContainer>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct BlockContainer {
+    palette: HashMap<String, i32>,
+    data: Vec<i8>,
+}
+
+/// decode the palette index of every block, which are stored as varints
+pub fn read_varints(data: &[i8]) -> anyhow::Result<Vec<u32>> {
+    let mut res = Vec::with_capacity(data.len());
+
+    let mut value = 0_u32;
+    let mut shift = 0;
+
+    for &byte in data {
+        let byte = byte as u8;
+        ensure!(shift < 32, "a block has a palette index which is too large");
+
+        value |= u32::from(byte & 0x7F) << shift;
+        if byte & 0x80 == 0 {
+            res.push(value);
+            value = 0;
+            shift = 0;
+        } else {
+            shift += 7;
+        }
+    }
+
+    ensure!(shift == 0, "the block data ends in the middle of a block");
+    Ok(res)
+}
+
+/// A Sponge schematic
+pub struct Sponge {
+    pub width: u16,
+    pub height: u16,
+    pub length: u16,
+
+    /// the location of the lowest corner
+    offset: BlockLocation,
+
+    palette: Palette,
+
+    /// the palette index of every block, ordered by `(y * length + z) *
+    /// width + x`
+    indices: Vec<u32>,
+}
+
+impl Sponge {
+    /// Load a gzipped Sponge schematic. Block names are translated to 1.12
+    /// with `names`
+    #[allow(clippy::cast_sign_loss)]
+    pub fn load(reader: &mut impl Read, names: &LegacyNames) -> anyhow::Result<Self> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .context("could not read schematic")?;
+
+        let blob = nbt::Blob::from_gzip_reader(&mut bytes.as_slice())
+            .context("could not load schematic")?;
+
+        let body: Body = if blob.get("Schematic").is_some() {
+            nbt::from_gzip_reader::<_, V3Root>(bytes.as_slice())
+                .context("could not load schematic")?
+                .schematic
+        } else {
+            nbt::from_gzip_reader(bytes.as_slice()).context("could not load schematic")?
+        };
+
+        let (palette, data) = match body.version {
+            1 | 2 => (
+                body.palette.context("the schematic has no palette")?,
+                body.block_data.context("the schematic has no block data")?,
+            ),
+            3 => {
+                let blocks = body.blocks.context("the schematic has no blocks")?;
+                (blocks.palette, blocks.data)
+            }
+            version => bail!("sponge schematic version {version} is not supported"),
+        };
+
+        let (width, height, length) = (body.width as u16, body.height as u16, body.length as u16);
+
+        let indices = read_varints(&data)?;
+        let volume = usize::from(width) * usize::from(height) * usize::from(length);
+        ensure!(
+            indices.len() == volume,
+            "the schematic has {} blocks instead of {volume}",
+            indices.len()
+        );
+
+        let offset = match body.offset.as_deref() {
+            Some(&[x, y, z]) => BlockLocation::new(x, y as i16, z),
+            _ => BlockLocation::default(),
+        };
+
+        let palette = Palette::new(
+            palette.into_iter().map(|(name, idx)| (idx as u32, name)),
+            names,
+        )?;
+
+        Ok(Self {
+            width,
+            height,
+            length,
+            offset,
+            palette,
+            indices,
+        })
+    }
+}
+
+impl Structure for Sponge {
+    fn blocks(&self) -> Box<dyn Iterator<Item = (BlockLocation, BlockState)> + '_> {
+        let width = usize::from(self.width);
+        let length = usize::from(self.length);
+
+        let iter = self
+            .indices
+            .iter()
+            .enumerate()
+            .filter_map(move |(idx, &palette_idx)| {
+                let state = self.palette.get(palette_idx)?;
+
+                let x = idx % width;
+                let z = (idx / width) % length;
+                let y = idx / (width * length);
+
+                let location = BlockLocation::new(x as i32, y as i16, z as i32) + self.offset;
+                Some((location, state))
+            });
+
+        Box::new(iter)
+    }
+
+    fn unknown_blocks(&self) -> &[String] {
+        self.palette.unknown()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use interfaces::types::{BlockLocation, BlockState};
+
+    use crate::{
+        schematic::{
+            sponge::{read_varints, Sponge},
+            Structure,
+        },
+        storage::legacy::LegacyNames,
+    };
+
+    /// a gzipped sponge schematic of a 2×1×2 area. If `v3`, the body is
+    /// nested in a `Schematic` compound
+    fn schematic(v3: bool) -> Vec<u8> {
+        let palette: nbt::Map<_, _> = [
+            ("minecraft:air", 0),
+            ("minecraft:stone", 1),
+            ("minecraft:oak_log[axis=x]", 2),
+            ("minecraft:not_a_block", 3),
+        ]
+        .into_iter()
+        .map(|(name, idx)| (name.to_string(), nbt::Value::Int(idx)))
+        .collect();
+
+        let data = nbt::Value::ByteArray(vec![1, 2, 0, 3]);
+
+        let mut body: nbt::Map<_, _> = [
+            ("Width", nbt::Value::Short(2)),
+            ("Height", nbt::Value::Short(1)),
+            ("Length", nbt::Value::Short(2)),
+            ("Offset", nbt::Value::IntArray(vec![10, 64, -5])),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect();
+
+        let mut blob = nbt::Blob::new();
+
+        if v3 {
+            let blocks: nbt::Map<_, _> = [
+                ("Palette".to_string(), nbt::Value::Compound(palette)),
+                ("Data".to_string(), data),
+            ]
+            .into_iter()
+            .collect();
+
+            body.insert("Version".to_string(), nbt::Value::Int(3));
+            body.insert("Blocks".to_string(), nbt::Value::Compound(blocks));
+            blob.insert("Schematic", nbt::Value::Compound(body))
+                .unwrap();
+        } else {
+            body.insert("Version".to_string(), nbt::Value::Int(2));
+            body.insert("Palette".to_string(), nbt::Value::Compound(palette));
+            body.insert("BlockData".to_string(), data);
+            for (key, value) in body {
+                blob.insert(key, value).unwrap();
+            }
+        }
+
+        let mut bytes = Vec::new();
+        blob.to_gzip_writer(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_varints() {
+        assert_eq!(
+            read_varints(&[5, -128, 1, -1, 127]).unwrap(),
+            [5, 128, 16383]
+        );
+        assert!(read_varints(&[-128]).is_err());
+    }
+
+    #[test]
+    fn test_load() {
+        let names = LegacyNames::read().unwrap();
+
+        for v3 in [false, true] {
+            let bytes = schematic(v3);
+            let sponge = Sponge::load(&mut bytes.as_slice(), &names).unwrap();
+
+            let blocks: HashMap<_, _> = sponge.blocks().collect();
+
+            // unknown blocks are left out
+            assert_eq!(blocks.len(), 3);
+            assert_eq!(sponge.unknown_blocks(), ["minecraft:not_a_block"]);
+
+            assert_eq!(blocks[&BlockLocation::new(10, 64, -5)], BlockState::STONE);
+            // properties are not taken into account
+            assert_eq!(
+                blocks[&BlockLocation::new(11, 64, -5)],
+                BlockState::from(17, 0)
+            );
+            assert_eq!(blocks[&BlockLocation::new(10, 64, -4)], BlockState::AIR);
+        }
+    }
+}
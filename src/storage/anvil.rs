@@ -0,0 +1,532 @@
+//! Export of the world bots have seen as a 1.12.2 singleplayer world in the
+//! Anvil format <https://minecraft.fandom.com/wiki/Anvil_file_format>, so it
+//! can be opened in the game or other tools.
+//!
+//! Only columns with exact blocks are exported. Columns which are only known
+//! as estimates (see [`Column::LowMemory`]) are skipped. The world is a void
+//! superflat world so chunks the swarm has not seen stay empty.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::Write,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{ensure, Context};
+use interfaces::types::{BlockLocation, BlockState, ChunkLocation};
+use serde::Serialize;
+
+use crate::{
+    storage::{
+        blocks::WorldBlocks,
+        chunk::{ChunkData, Column, HighMemoryChunkSection},
+        dimensions::Dimensions,
+    },
+    types::Dimension,
+};
+
+/// the data version of 1.12.2
+const DATA_VERSION: i32 = 1343;
+
+/// the version of the level format used since 1.0
+const LEVEL_VERSION: i32 = 19133;
+
+/// the size of a sector of a region file
+const SECTOR: usize = 4096;
+
+/// zlib, the compression of chunks in a region file
+const ZLIB: u8 = 2;
+
+/// the biome of every column (plains)
+const BIOME: i8 = 1;
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct ChunkRoot {
+    data_version: i32,
+    level: Level,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct Level {
+    #[serde(rename = "xPos")]
+    x_pos: i32,
+    #[serde(rename = "zPos")]
+    z_pos: i32,
+    last_update: i64,
+    inhabited_time: i64,
+    terrain_populated: i8,
+    /// 0 so the game calculates the light itself
+    light_populated: i8,
+    #[serde(serialize_with = "nbt::i8_array")]
+    biomes: Vec<i8>,
+    #[serde(serialize_with = "nbt::i32_array")]
+    height_map: Vec<i32>,
+    sections: Vec<Section>,
+    entities: Vec<nbt::Value>,
+    tile_entities: Vec<nbt::Value>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct Section {
+    y: i8,
+    /// the lowest 8 bits of each block id
+    #[serde(serialize_with = "nbt::i8_array")]
+    blocks: Vec<i8>,
+    /// the highest 4 bits of each block id, if any block needs them
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_add"
+    )]
+    add: Option<Vec<i8>>,
+    /// the metadata of each block
+    #[serde(serialize_with = "nbt::i8_array")]
+    data: Vec<i8>,
+    #[serde(serialize_with = "nbt::i8_array")]
+    block_light: Vec<i8>,
+    #[serde(serialize_with = "nbt::i8_array")]
+    sky_light: Vec<i8>,
+}
+
+fn serialize_add<S: serde::Serializer>(
+    add: &Option<Vec<i8>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    nbt::i8_array(add.as_deref().unwrap_or_default(), serializer)
+}
+
+#[derive(Serialize)]
+struct LevelRoot {
+    #[serde(rename = "Data")]
+    data: LevelData,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct LevelData {
+    #[serde(rename = "version")]
+    level_version: i32,
+    data_version: i32,
+    level_name: String,
+    #[serde(rename = "generatorName")]
+    generator_name: String,
+    #[serde(rename = "generatorVersion")]
+    generator_version: i32,
+    #[serde(rename = "generatorOptions")]
+    generator_options: String,
+    map_features: i8,
+    game_type: i32,
+    #[serde(rename = "allowCommands")]
+    allow_commands: i8,
+    #[serde(rename = "initialized")]
+    initialized: i8,
+    #[serde(rename = "hardcore")]
+    hardcore: i8,
+    difficulty: i8,
+    last_played: i64,
+    time: i64,
+    day_time: i64,
+    spawn_x: i32,
+    spawn_y: i32,
+    spawn_z: i32,
+    version: GameVersion,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct GameVersion {
+    id: i32,
+    name: String,
+    snapshot: i8,
+}
+
+/// set the nibble at `idx` of a nibble array
+#[allow(clippy::indexing_slicing)]
+fn set_nibble(array: &mut [i8], idx: usize, value: u8) {
+    let byte = &mut array[idx >> 1];
+    let value = (value & 0xF) as i8;
+    if idx & 1 == 0 {
+        *byte = (*byte & !0xF) | value;
+    } else {
+        *byte = (*byte & 0xF) | (value << 4);
+    }
+}
+
+/// the section at height `y` in the Anvil format. None if it is all air.
+/// Raises `height_map` to above the blocks of the section
+fn section(y: usize, section: &HighMemoryChunkSection, height_map: &mut [i32]) -> Option<Section> {
+    let states = section.palette.all_states();
+    if states.iter().all(|&state| state == BlockState::AIR) {
+        return None;
+    }
+
+    let mut blocks = vec![0; 4096];
+    let mut add = vec![0; 2048];
+    let mut data = vec![0; 2048];
+    let mut needs_add = false;
+
+    // both orders are ((y * 16) + z) * 16 + x
+    for (idx, state) in states.into_iter().enumerate() {
+        let id = state.id();
+        blocks[idx] = (id & 0xFF) as u8 as i8;
+        if id > 0xFF {
+            needs_add = true;
+            set_nibble(&mut add, idx, (id >> 8) as u8);
+        }
+        set_nibble(&mut data, idx, (state.0 & 0xF) as u8);
+
+        if state != BlockState::AIR {
+            let height = (y * 16 + idx / 256 + 1) as i32;
+            let column = &mut height_map[idx % 256];
+            *column = (*column).max(height);
+        }
+    }
+
+    Some(Section {
+        y: y as i8,
+        blocks,
+        add: needs_add.then_some(add),
+        data,
+        block_light: vec![0; 2048],
+        // full sky light until the game calculates it
+        sky_light: vec![-1; 2048],
+    })
+}
+
+/// the zlib compressed NBT of a chunk column
+fn chunk(
+    location: ChunkLocation,
+    data: &ChunkData<HighMemoryChunkSection>,
+) -> anyhow::Result<Vec<u8>> {
+    // the height of the block above the highest block which is not air, by
+    // (z * 16) + x
+    let mut height_map = vec![0; 256];
+
+    let sections: Vec<_> = data
+        .sections
+        .iter()
+        .enumerate()
+        .filter_map(|(y, section)| self::section(y, section.as_deref()?, &mut height_map))
+        .collect();
+
+    let root = ChunkRoot {
+        data_version: DATA_VERSION,
+        level: Level {
+            x_pos: location.0,
+            z_pos: location.1,
+            last_update: 0,
+            inhabited_time: 0,
+            terrain_populated: 1,
+            light_populated: 0,
+            biomes: vec![BIOME; 256],
+            height_map,
+            sections,
+            entities: Vec::new(),
+            tile_entities: Vec::new(),
+        },
+    };
+
+    let mut bytes = Vec::new();
+    nbt::to_zlib_writer(&mut bytes, &root, None).context("could not encode chunk")?;
+    Ok(bytes)
+}
+
+/// the number of sectors `len` bytes take up
+const fn sectors_of(len: usize) -> usize {
+    (len + SECTOR - 1) / SECTOR
+}
+
+/// A region file of `chunks`, which are compressed chunks which must all be
+/// in the same region
+pub fn region(chunks: &[(ChunkLocation, Vec<u8>)], timestamp: u32) -> anyhow::Result<Vec<u8>> {
+    let mut locations = vec![0_u8; SECTOR];
+    let mut timestamps = vec![0_u8; SECTOR];
+    let mut body = Vec::new();
+
+    for (location, chunk) in chunks {
+        let idx = ((location.0 & 31) + (location.1 & 31) * 32) as usize * 4;
+
+        // the length includes the compression byte
+        let length = chunk.len() + 1;
+        let sectors = sectors_of(4 + length);
+        ensure!(
+            sectors < 256,
+            "chunk {}, {} is too large for a region file",
+            location.0,
+            location.1
+        );
+
+        let offset = 2 + body.len() / SECTOR;
+        ensure!(offset < 1 << 24, "the region file is too large");
+
+        locations[idx..idx + 3].copy_from_slice(&(offset as u32).to_be_bytes()[1..]);
+        locations[idx + 3] = sectors as u8;
+        timestamps[idx..idx + 4].copy_from_slice(&timestamp.to_be_bytes());
+
+        body.extend_from_slice(&(length as u32).to_be_bytes());
+        body.push(ZLIB);
+        body.extend_from_slice(chunk);
+        body.resize(sectors_of(body.len()) * SECTOR, 0);
+    }
+
+    let mut file = locations;
+    file.extend(timestamps);
+    file.extend(body);
+    Ok(file)
+}
+
+/// the directory of a dimension in a world folder
+fn dimension_dir(world: &Path, dimension: Dimension) -> std::path::PathBuf {
+    match dimension {
+        Dimension::Overworld => world.to_path_buf(),
+        Dimension::Nether => world.join("DIM-1"),
+        Dimension::End => world.join("DIM1"),
+    }
+}
+
+/// An export asked for by a chat command, which the runner performs once all
+/// bots have processed their packets
+pub struct ExportRequest {
+    /// the name of the world. The name of the server if not given
+    pub name: Option<String>,
+}
+
+/// What was exported
+#[derive(Debug, Default)]
+pub struct Exported {
+    /// the number of columns exported
+    pub columns: usize,
+    /// the number of region files written
+    pub regions: usize,
+    /// the number of columns skipped as they are only estimates
+    pub skipped: usize,
+}
+
+/// A copy of the columns with exact blocks, so they can be exported away from
+/// the game loop
+pub struct Snapshot {
+    dimensions: Vec<(
+        Dimension,
+        Vec<(ChunkLocation, ChunkData<HighMemoryChunkSection>)>,
+    )>,
+    skipped: usize,
+}
+
+/// Copy the columns of `blocks` with exact blocks
+pub fn snapshot(blocks: &Dimensions<WorldBlocks>) -> Snapshot {
+    let mut skipped = 0;
+    let dimensions = blocks
+        .iter()
+        .map(|(dimension, blocks)| {
+            let columns = blocks
+                .columns()
+                .filter_map(|(location, column)| {
+                    let Column::HighMemory { data } = column else {
+                        skipped += 1;
+                        return None;
+                    };
+                    Some((location, data.clone()))
+                })
+                .collect();
+            (dimension, columns)
+        })
+        .collect();
+
+    Snapshot {
+        dimensions,
+        skipped,
+    }
+}
+
+/// Export `snapshot` to a world folder `dir` named `name`. Players spawn at
+/// `spawn`. Region files in `dir` from previous exports are replaced
+pub fn export(
+    snapshot: Snapshot,
+    spawn: BlockLocation,
+    name: &str,
+    dir: &Path,
+) -> anyhow::Result<Exported> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let millis = i64::try_from(now.as_millis()).unwrap_or(i64::MAX);
+    let secs = u32::try_from(now.as_secs()).unwrap_or(u32::MAX);
+
+    let mut exported = Exported {
+        skipped: snapshot.skipped,
+        ..Exported::default()
+    };
+
+    for (dimension, columns) in snapshot.dimensions {
+        // the chunks of each region
+        let mut regions: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for (location, data) in columns {
+            let chunk = chunk(location, &data)?;
+            regions
+                .entry((location.0 >> 5, location.1 >> 5))
+                .or_default()
+                .push((location, chunk));
+            exported.columns += 1;
+        }
+
+        let region_dir = dimension_dir(dir, dimension).join("region");
+        if region_dir.exists() {
+            for entry in fs::read_dir(&region_dir)? {
+                let path = entry?.path();
+                if path.extension().map_or(false, |ext| ext == "mca") {
+                    fs::remove_file(&path)
+                        .with_context(|| format!("could not remove {}", path.display()))?;
+                }
+            }
+        }
+
+        if regions.is_empty() {
+            continue;
+        }
+
+        fs::create_dir_all(&region_dir)
+            .with_context(|| format!("could not create {}", region_dir.display()))?;
+
+        for ((x, z), chunks) in regions {
+            let path = region_dir.join(format!("r.{x}.{z}.mca"));
+            let bytes = region(&chunks, secs)?;
+            fs::write(&path, bytes)
+                .with_context(|| format!("could not write {}", path.display()))?;
+            exported.regions += 1;
+        }
+    }
+
+    let level = LevelRoot {
+        data: LevelData {
+            level_version: LEVEL_VERSION,
+            data_version: DATA_VERSION,
+            level_name: name.to_string(),
+            generator_name: "flat".to_string(),
+            generator_version: 0,
+            // the void preset
+            generator_options: "3;minecraft:air;127;".to_string(),
+            map_features: 0,
+            // creative
+            game_type: 1,
+            allow_commands: 1,
+            initialized: 1,
+            hardcore: 0,
+            difficulty: 0,
+            last_played: millis,
+            time: 0,
+            day_time: 6000,
+            spawn_x: spawn.x,
+            spawn_y: i32::from(spawn.y),
+            spawn_z: spawn.z,
+            version: GameVersion {
+                id: DATA_VERSION,
+                name: "1.12.2".to_string(),
+                snapshot: 0,
+            },
+        },
+    };
+
+    let path = dir.join("level.dat");
+    let mut file =
+        fs::File::create(&path).with_context(|| format!("could not create {}", path.display()))?;
+    nbt::to_gzip_writer(&mut file, &level, None).context("could not write level.dat")?;
+    file.flush()?;
+
+    Ok(exported)
+}
+
+#[cfg(test)]
+mod tests {
+    use interfaces::types::{BlockLocation, BlockState, ChunkLocation};
+
+    use crate::storage::{
+        anvil::{chunk, region, SECTOR},
+        blocks::WorldBlocks,
+    };
+
+    /// the NBT of the chunk at `idx` of a region file
+    fn read_chunk(region: &[u8], idx: usize) -> nbt::Blob {
+        let entry = &region[idx * 4..idx * 4 + 4];
+        let offset = u32::from_be_bytes([0, entry[0], entry[1], entry[2]]) as usize * SECTOR;
+        assert_ne!(offset, 0, "chunk {idx} is not in the region");
+
+        let length = u32::from_be_bytes(region[offset..offset + 4].try_into().unwrap()) as usize;
+        assert_eq!(region[offset + 4], 2);
+
+        let mut data = &region[offset + 5..offset + 4 + length];
+        nbt::Blob::from_zlib_reader(&mut data).unwrap()
+    }
+
+    #[test]
+    fn test_region() {
+        let mut world = WorldBlocks::default();
+
+        // 1 is stone and 5:2 birch planks
+        world.set_block(BlockLocation::new(1, 2, 3), BlockState::STONE);
+        world.set_block(BlockLocation::new(33, 20, 2), BlockState::from(5, 2));
+
+        // a block id which needs the Add array
+        world.set_block(BlockLocation::new(34, 21, 2), BlockState::from(300, 1));
+
+        let chunks: Vec<_> = [ChunkLocation(0, 0), ChunkLocation(2, 0)]
+            .into_iter()
+            .map(|location| {
+                let data = world.get_real_column(location).unwrap();
+                (location, chunk(location, data).unwrap())
+            })
+            .collect();
+
+        let region = region(&chunks, 1).unwrap();
+        assert_eq!(region.len() % SECTOR, 0);
+
+        let nbt::Value::Compound(level) = read_chunk(&region, 2)["Level"].clone() else {
+            panic!("Level is not a compound");
+        };
+        assert_eq!(level["xPos"], nbt::Value::Int(2));
+        assert_eq!(level["zPos"], nbt::Value::Int(0));
+
+        let nbt::Value::List(sections) = &level["Sections"] else {
+            panic!("Sections is not a list");
+        };
+        assert_eq!(sections.len(), 1);
+
+        let nbt::Value::Compound(section) = &sections[0] else {
+            panic!("a section is not a compound");
+        };
+        assert_eq!(section["Y"], nbt::Value::Byte(1));
+
+        let (nbt::Value::ByteArray(blocks), nbt::Value::ByteArray(data), nbt::Value::ByteArray(add)) =
+            (&section["Blocks"], &section["Data"], &section["Add"])
+        else {
+            panic!("the blocks of a section are not byte arrays");
+        };
+
+        // odd indices are in the high nibble
+        let nibble = |array: &[i8], idx: usize| (array[idx / 2] >> (idx % 2 * 4)) & 0xF;
+
+        // y = 4, z = 2, x = 1
+        let idx = (4 * 16 + 2) * 16 + 1;
+        assert_eq!(blocks[idx], 5);
+        assert_eq!(nibble(data, idx), 2);
+
+        // y = 5, z = 2, x = 2
+        let idx = (5 * 16 + 2) * 16 + 2;
+        assert_eq!(blocks[idx], (300 & 0xFF) as u8 as i8);
+        assert_eq!(nibble(add, idx), 1);
+        assert_eq!(nibble(data, idx), 1);
+
+        let nbt::Value::IntArray(height_map) = &level["HeightMap"] else {
+            panic!("HeightMap is not an int array");
+        };
+        assert_eq!(height_map[2 * 16 + 2], 22);
+        assert_eq!(height_map[0], 0);
+
+        // the first chunk is there as well
+        let root = read_chunk(&region, 0);
+        assert_eq!(root["DataVersion"], nbt::Value::Int(1343));
+    }
+}
@@ -1,6 +1,6 @@
 #![allow(clippy::cast_sign_loss, unused, clippy::cast_possible_wrap)]
 
-use std::collections::{BinaryHeap, HashMap};
+use std::collections::{hash_map::Entry, BinaryHeap, HashMap, HashSet};
 
 use float_ord::FloatOrd;
 use interfaces::types::{
@@ -10,7 +10,7 @@ use rand::{rngs::StdRng, Rng, SeedableRng};
 
 use crate::{
     client::pathfind::MinHeapNode,
-    schematic::Schematic,
+    schematic::Structure,
     storage::chunk::{ChunkData, Column, HighMemoryChunkSection},
 };
 
@@ -19,6 +19,13 @@ use crate::{
 pub struct WorldBlocks {
     /// we hash a chunk coordinate to a chunk column, to store data
     storage: HashMap<ChunkLocation, Column>,
+
+    /// the ids of the bots which currently have each column loaded
+    holders: HashMap<ChunkLocation, HashSet<u32>>,
+
+    /// columns loaded from a save which the server has not sent since. Their
+    /// blocks are only estimates
+    stale: HashSet<ChunkLocation>,
 }
 
 struct HeapIter<T> {
@@ -153,20 +160,162 @@ impl WorldBlocks {
         Some(res)
     }
 
-    pub fn paste(&mut self, schematic: &Schematic) {
+    pub fn paste(&mut self, schematic: &dyn Structure) {
         for (location, state) in schematic.blocks() {
             self.set_block(location, state);
         }
     }
 
     pub fn add_column(&mut self, location: ChunkLocation, column: Column) {
+        self.stale.remove(&location);
         self.storage.insert(location, column);
     }
 
+    /// add a column loaded from a save. It is stale until the server sends it
+    /// again, and columns which are already known are kept
+    pub fn add_stale_column(&mut self, location: ChunkLocation, column: Column) {
+        if let Entry::Vacant(entry) = self.storage.entry(location) {
+            entry.insert(column);
+            self.stale.insert(location);
+        }
+    }
+
+    /// if the column at `location` was loaded from a save and has not been sent
+    /// by the server since
+    pub fn is_stale(&self, location: ChunkLocation) -> bool {
+        self.stale.contains(&location)
+    }
+
+    /// every column along with its location
+    pub fn columns(&self) -> impl Iterator<Item = (ChunkLocation, &Column)> + '_ {
+        self.storage
+            .iter()
+            .map(|(&location, column)| (location, column))
+    }
+
     pub fn modify_column(&mut self, location: ChunkLocation, column: Column) {
         self.storage.get_mut(&location).unwrap().modify(column);
     }
 
+    /// mark the column at `location` as loaded by `bot_id`
+    pub fn hold(&mut self, location: ChunkLocation, bot_id: u32) {
+        self.holders.entry(location).or_default().insert(bot_id);
+    }
+
+    /// `bot_id` no longer has the column at `location` loaded. Once no bot
+    /// holds a column it is demoted to [`Column::LowMemory`] (if that is
+    /// smaller) as it will no longer receive block updates.
+    pub fn release(&mut self, location: ChunkLocation, bot_id: u32) {
+        let Some(holders) = self.holders.get_mut(&location) else { return };
+
+        holders.remove(&bot_id);
+
+        if holders.is_empty() {
+            self.holders.remove(&location);
+            if let Some(column) = self.storage.get_mut(&location) {
+                if column.demoted_memory() < column.memory() {
+                    column.demote();
+                }
+            }
+        }
+    }
+
+    /// release every column `bot_id` holds. Used when a bot disconnects.
+    pub fn release_all(&mut self, bot_id: u32) {
+        let held: Vec<_> = self
+            .holders
+            .iter()
+            .filter(|(_, holders)| holders.contains(&bot_id))
+            .map(|(&location, _)| location)
+            .collect();
+
+        for location in held {
+            self.release(location, bot_id);
+        }
+    }
+
+    pub fn is_held(&self, location: ChunkLocation) -> bool {
+        self.holders.contains_key(&location)
+    }
+
+    /// the number of bots which have the column at `location` loaded
+    pub fn holders(&self, location: ChunkLocation) -> usize {
+        self.holders.get(&location).map_or(0, HashSet::len)
+    }
+
+    /// the column at `location`, if it is known
+    pub fn column(&self, location: ChunkLocation) -> Option<&Column> {
+        self.storage.get(&location)
+    }
+
+    /// the number of columns kept in memory
+    pub fn column_count(&self) -> usize {
+        self.storage.len()
+    }
+
+    /// the approximate memory used by all columns in bytes
+    pub fn memory(&self) -> usize {
+        self.storage.values().map(Column::memory).sum()
+    }
+
+    /// Evict columns until at most `limit` bytes are used. Columns furthest
+    /// from every location in `around` (generally the bots) go first. They
+    /// are demoted first and only dropped if that is not enough. Columns a
+    /// bot holds are never demoted or dropped, as bots need their exact
+    /// blocks. Returns if at most `limit` bytes are used.
+    pub fn evict(&mut self, limit: usize, around: &[ChunkLocation]) -> bool {
+        let mut usage = self.memory();
+        if usage <= limit {
+            return true;
+        }
+
+        let dist2 = |location: ChunkLocation| {
+            around
+                .iter()
+                .map(|other| {
+                    let dx = i64::from(location.0 - other.0);
+                    let dz = i64::from(location.1 - other.1);
+                    dx * dx + dz * dz
+                })
+                .min()
+                .unwrap_or_default()
+        };
+
+        // furthest first
+        let mut order: Vec<_> = self
+            .storage
+            .keys()
+            .copied()
+            .filter(|&location| !self.is_held(location))
+            .collect();
+        order.sort_by_cached_key(|&location| std::cmp::Reverse(dist2(location)));
+
+        for location in &order {
+            if usage <= limit {
+                return true;
+            }
+
+            let column = self.storage.get_mut(location).unwrap();
+            let (before, after) = (column.memory(), column.demoted_memory());
+            if after < before {
+                column.demote();
+                usage -= before - after;
+            }
+        }
+
+        for location in order {
+            if usage <= limit {
+                return true;
+            }
+
+            self.stale.remove(&location);
+            let column = self.storage.remove(&location).unwrap();
+            usage -= column.memory();
+        }
+
+        usage <= limit
+    }
+
     pub fn get_block(&self, location: BlockLocation) -> Option<BlockApprox> {
         let BlockLocation { x, y, z } = location;
 
@@ -190,6 +339,14 @@ impl WorldBlocks {
         }
 
         let block = column.get_block(x, y as u8, z);
+
+        // the block may have changed since the column was saved
+        if let BlockApprox::Realized(state) = block {
+            if self.is_stale(loc) {
+                return Some(BlockApprox::Estimate(state.simple_type()));
+            }
+        }
+
         Some(block)
     }
 
@@ -200,14 +357,10 @@ impl WorldBlocks {
         selector: impl FnMut(BlockState) -> bool + 'a + Copy,
     ) -> Option<BlockLocation> {
         let loc = ChunkLocation::from(origin);
-        let chunk = self.storage.get(&loc)?;
+        let data = self.get_real_column(loc)?;
 
-        if let Column::HighMemory { data } = chunk {
-            block_chunk_iter(&loc, data, selector)
-                .min_by_key(|&location| FloatOrd(origin.dist2(location)))
-        } else {
-            None
-        }
+        block_chunk_iter(&loc, data, selector)
+            .min_by_key(|&location| FloatOrd(origin.dist2(location)))
     }
 
     #[allow(unused)]
@@ -237,11 +390,12 @@ impl WorldBlocks {
         iterator.map(|node| node.contents)
     }
     #[allow(unused)]
-    fn real_chunks(
+    pub fn real_chunks(
         &self,
     ) -> impl Iterator<Item = (&ChunkLocation, &ChunkData<HighMemoryChunkSection>)> + '_ {
         self.storage
             .iter()
+            .filter(|(loc, _)| !self.stale.contains(loc))
             .filter_map(|(loc, column)| match column {
                 Column::HighMemory { data } => Some((loc, data)),
                 Column::LowMemory { .. } => None,
@@ -264,6 +418,10 @@ impl WorldBlocks {
         &self,
         location: ChunkLocation,
     ) -> Option<&ChunkData<HighMemoryChunkSection>> {
+        if self.is_stale(location) {
+            return None;
+        }
+
         let res = self.storage.get(&location)?;
         match res {
             Column::HighMemory { data } => Some(data),
@@ -327,11 +485,101 @@ mod tests {
     use std::fs::OpenOptions;
 
     use assert_matches::assert_matches as am;
-    use interfaces::types::{BlockApprox, BlockLocation, BlockState};
+    use interfaces::types::{BlockApprox, BlockLocation, BlockState, ChunkLocation};
     use rand::Rng;
 
     use crate::{schematic::Schematic, storage::blocks::WorldBlocks};
 
+    /// a world with a column at every chunk in `0..width` × `0..width`. Each
+    /// column uses enough states that demoting it saves memory.
+    fn varied_world(width: i32) -> WorldBlocks {
+        let mut world = WorldBlocks::default();
+        for chunk_x in 0..width {
+            for chunk_z in 0..width {
+                for x in 0..16 {
+                    for z in 0..16 {
+                        let state = BlockState(((x * 16 + z) % 32) as u32);
+                        let location =
+                            BlockLocation::new((chunk_x << 4) + x, 0, (chunk_z << 4) + z);
+                        world.set_block(location, state);
+                    }
+                }
+            }
+        }
+        world
+    }
+
+    fn is_realized(world: &WorldBlocks, chunk: ChunkLocation) -> bool {
+        let location = BlockLocation::new(chunk.0 << 4, 0, chunk.1 << 4);
+        matches!(world.get_block(location), Some(BlockApprox::Realized(_)))
+    }
+
+    #[test]
+    fn test_hold_release() {
+        let mut world = varied_world(1);
+        let chunk = ChunkLocation(0, 0);
+
+        world.hold(chunk, 1);
+        world.hold(chunk, 2);
+
+        world.release(chunk, 1);
+        assert!(world.is_held(chunk));
+        assert!(is_realized(&world, chunk));
+
+        let before = world.memory();
+
+        // the last holder disconnects
+        world.release_all(2);
+        assert!(!world.is_held(chunk));
+        assert!(!is_realized(&world, chunk));
+        assert!(world.memory() < before);
+
+        // the estimate is still usable for path finding
+        assert!(world.get_block(BlockLocation::new(0, 0, 0)).is_some());
+    }
+
+    #[test]
+    fn test_evict() {
+        let mut world = varied_world(4);
+        world.hold(ChunkLocation(0, 0), 1);
+
+        let near = ChunkLocation(0, 0);
+        let far = ChunkLocation(3, 3);
+
+        // demoting the furthest column is enough
+        let limit = world.memory() - 1;
+        world.evict(limit, &[near]);
+        assert!(world.memory() <= limit);
+        assert!(!is_realized(&world, far));
+        assert!(is_realized(&world, near));
+
+        // the limit cannot be met as the held column is kept
+        assert!(!world.evict(0, &[near]));
+        assert!(world.get_block(BlockLocation::new(48, 0, 48)).is_none());
+        assert!(world.get_block(BlockLocation::new(0, 0, 0)).is_some());
+    }
+
+    #[test]
+    fn test_evict_held() {
+        let mut world = varied_world(2);
+        let held = ChunkLocation(1, 1);
+        world.hold(held, 1);
+
+        // the held column is the furthest, but it is neither demoted nor dropped
+        assert!(!world.evict(0, &[ChunkLocation(0, 0)]));
+        assert!(is_realized(&world, held));
+
+        for x in 16..32 {
+            for z in 16..32 {
+                let expected = BlockState((((x - 16) * 16 + (z - 16)) % 32) as u32);
+                let location = BlockLocation::new(x, 0, z);
+                assert_eq!(world.get_block_exact(location), Some(expected));
+            }
+        }
+
+        assert!(world.get_block(BlockLocation::new(0, 0, 0)).is_none());
+    }
+
     #[test]
     fn test_get_set() {
         let mut world = WorldBlocks::default();
@@ -8,7 +8,7 @@ const SECTION_ELEMENTS: usize = 16 * 16 * 16;
 const BITS_PER_ENUM: usize = 2;
 const SECTION_BYTES: usize = SECTION_ELEMENTS * BITS_PER_ENUM / 8;
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct HighMemoryChunkSection {
     pub palette: Palette,
 }
@@ -46,6 +46,22 @@ pub const fn bits_needed(number: usize) -> u8 {
 }
 
 impl LowMemoryChunkSection {
+    /// the simple types of the section, packed 2 bits per block
+    pub const fn bytes(&self) -> &[u8; SECTION_BYTES] {
+        &self.storage
+    }
+
+    /// a section from the bytes returned by [`LowMemoryChunkSection::bytes`]
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let storage = bytes.try_into().map_err(|_| {
+            anyhow::anyhow!(
+                "a low memory section has {} bytes instead of {SECTION_BYTES}",
+                bytes.len()
+            )
+        })?;
+        Ok(Self { storage })
+    }
+
     #[allow(clippy::indexing_slicing)]
     fn get_simple_type(&self, x: u8, y: u8, z: u8) -> SimpleType {
         let block_number =
@@ -56,13 +72,25 @@ impl LowMemoryChunkSection {
         let offset = block_number - (idx << 2);
 
         let mut res = self.storage[idx];
-        res >>= offset;
+        res >>= offset * BITS_PER_ENUM;
         res &= 0b11;
 
         SimpleType::from(res)
     }
 
-    #[allow(unused, clippy::indexing_slicing)]
+    /// the simple types of every block in `section`
+    fn from_high(section: &HighMemoryChunkSection) -> Self {
+        let mut res = Self::default();
+        for (block_number, state) in section.palette.all_states().into_iter().enumerate() {
+            let x = (block_number % SECTION_WIDTH) as u8;
+            let z = ((block_number / SECTION_WIDTH) % SECTION_WIDTH) as u8;
+            let y = (block_number / (SECTION_WIDTH * SECTION_WIDTH)) as u8;
+            res.set_simple_type(x, y, z, state.simple_type());
+        }
+        res
+    }
+
+    #[allow(clippy::indexing_slicing)]
     fn set_simple_type(&mut self, x: u8, y: u8, z: u8, input: SimpleType) {
         let block_number =
             (((y as usize * SECTION_HEIGHT) + z as usize) * SECTION_WIDTH) + x as usize;
@@ -75,16 +103,18 @@ impl LowMemoryChunkSection {
 
         let id = input.id();
 
-        let zero_out = !(0b11 << offset);
+        let shift = offset * BITS_PER_ENUM;
+
+        let zero_out = !(0b11 << shift);
         block &= zero_out;
 
-        block |= id << offset;
+        block |= id << shift;
 
         self.storage[idx] = block;
     }
 }
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct ChunkData<T> {
     pub sections: [Option<Box<T>>; 16],
 }
@@ -196,6 +226,7 @@ impl ChunkData<HighMemoryChunkSection> {
 const SECTION_HEIGHT: usize = 16;
 const SECTION_WIDTH: usize = 16;
 
+#[derive(Clone)]
 pub struct Palette {
     bits_per_block: u8,
     id_to_state: Option<Vec<BlockState>>,
@@ -236,6 +267,82 @@ impl Palette {
         }
     }
 
+    /// a palette from the parts returned by [`Palette::parts`]. Fails if they
+    /// are inconsistent rather than panicking once a block is read
+    pub fn from_parts(
+        bits_per_block: u8,
+        id_to_state: Option<Vec<BlockState>>,
+        storage: Vec<u64>,
+    ) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            (1..=31).contains(&bits_per_block),
+            "a palette cannot use {bits_per_block} bits per block"
+        );
+        anyhow::ensure!(
+            storage.len() == 4096 * bits_per_block as usize / 64,
+            "a palette with {bits_per_block} bits per block has {} longs",
+            storage.len()
+        );
+
+        let palette = Self {
+            bits_per_block,
+            id_to_state,
+            storage,
+        };
+
+        if let Some(id_to_state) = &palette.id_to_state {
+            let max = (0..4096)
+                .map(|idx| palette.get_id(idx))
+                .max()
+                .unwrap_or_default();
+            anyhow::ensure!(
+                (max as usize) < id_to_state.len(),
+                "a palette of {} states uses state {max}",
+                id_to_state.len()
+            );
+        }
+
+        Ok(palette)
+    }
+
+    /// the bits per block, the states of the palette (if it is not direct)
+    /// and the packed ids of each block
+    pub fn parts(&self) -> (u8, Option<&[BlockState]>, &[u64]) {
+        (
+            self.bits_per_block,
+            self.id_to_state.as_deref(),
+            &self.storage,
+        )
+    }
+
+    /// create a palette from the 4096 states of a section, ordered by
+    /// `((y * 16) + z) * 16 + x` (the same order as [`Palette::all_states`])
+    pub fn from_states(states: impl IntoIterator<Item = BlockState>) -> Self {
+        let mut palette = Self::default();
+        for (block_number, state) in states.into_iter().enumerate() {
+            // the default palette is all air
+            if state == BlockState::AIR {
+                continue;
+            }
+
+            let x = (block_number % SECTION_WIDTH) as u8;
+            let z = ((block_number / SECTION_WIDTH) % SECTION_WIDTH) as u8;
+            let y = (block_number / (SECTION_WIDTH * SECTION_WIDTH)) as u8;
+            palette.set_block(x, y, z, state);
+        }
+        palette
+    }
+
+    /// approximate heap and stack size in bytes
+    pub fn memory(&self) -> usize {
+        let palette = self
+            .id_to_state
+            .as_ref()
+            .map_or(0, |states| states.len() * std::mem::size_of::<BlockState>());
+
+        std::mem::size_of::<Self>() + self.storage.len() * std::mem::size_of::<u64>() + palette
+    }
+
     #[allow(unused, clippy::indexing_slicing)]
     pub fn all_states(&self) -> [BlockState; 4096] {
         let mut res = [BlockState::AIR; 4096];
@@ -346,7 +453,9 @@ impl Palette {
         }
     }
 
-    fn get_block_by_idx(&self, block_number: usize) -> BlockState {
+    /// the id of the block at `block_number`. This is the index into the
+    /// palette if there is one and the state otherwise
+    fn get_id(&self, block_number: usize) -> u32 {
         let data_arr = &self.storage;
 
         let bits_per_block = self.bits_per_block as usize;
@@ -364,7 +473,11 @@ impl Palette {
             (data_arr[start_long] >> start_offset | data_arr[end_long] << end_offset) as u32
         };
 
-        let data = data & indv_value_mask;
+        data & indv_value_mask
+    }
+
+    fn get_block_by_idx(&self, block_number: usize) -> BlockState {
+        let data = self.get_id(block_number);
 
         match &self.id_to_state {
             None => BlockState(data),
@@ -395,7 +508,6 @@ impl Palette {
 /// A chunk storage module
 pub enum Column {
     /// low memory data. each block takes 2 bits
-    #[allow(unused)]
     LowMemory {
         /// the data
         data: ChunkData<LowMemoryChunkSection>,
@@ -419,18 +531,68 @@ impl Column {
     /// modify a column
     /// TODO: is this needed? can we just use *
     pub fn modify(&mut self, column: Self) {
-        if let (Self::HighMemory { data: left }, Self::HighMemory { data: right }) = (self, column)
-        {
-            for (idx, new_section) in IntoIterator::into_iter(right.sections).enumerate() {
-                if let Some(section) = new_section {
-                    left.sections[idx] = Some(section);
+        let Self::HighMemory { data: right } = column else {
+            panic!("cannot modify with low memory chunks");
+        };
+
+        for (idx, new_section) in IntoIterator::into_iter(right.sections).enumerate() {
+            let Some(section) = new_section else { continue };
+
+            match self {
+                Self::HighMemory { data: left } => left.sections[idx] = Some(section),
+
+                // the column was demoted while the server still considered it loaded
+                Self::LowMemory { data: left } => {
+                    left.sections[idx] = Some(Box::new(LowMemoryChunkSection::from_high(&section)));
                 }
             }
-        } else {
-            panic!("cannot modify low memory chunks");
         }
     }
 
+    /// Convert to [`Column::LowMemory`], keeping only the [`SimpleType`] of
+    /// each block. This is a no-op if the column is already low memory.
+    pub fn demote(&mut self) {
+        let Self::HighMemory { data } = self else { return };
+
+        let mut low = ChunkData::<LowMemoryChunkSection>::default();
+        for (idx, section) in data.sections.iter().enumerate() {
+            if let Some(section) = section {
+                low.sections[idx] = Some(Box::new(LowMemoryChunkSection::from_high(section)));
+            }
+        }
+
+        *self = Self::LowMemory { data: low };
+    }
+
+    pub const fn is_high_memory(&self) -> bool {
+        matches!(self, Self::HighMemory { .. })
+    }
+
+    /// the approximate size in bytes after [`Column::demote`]
+    pub fn demoted_memory(&self) -> usize {
+        let sections = match self {
+            Self::LowMemory { data } => data.sections.iter().flatten().count(),
+            Self::HighMemory { data } => data.sections.iter().flatten().count(),
+        };
+
+        std::mem::size_of::<Self>() + sections * SECTION_BYTES
+    }
+
+    /// approximate size in bytes
+    pub fn memory(&self) -> usize {
+        let sections: usize = match self {
+            Self::LowMemory { .. } => return self.demoted_memory(),
+            Self::HighMemory { data } => data
+                .sections
+                .iter()
+                .flatten()
+                .map(|section| section.palette.memory())
+                .sum(),
+        };
+
+        std::mem::size_of::<Self>() + sections
+    }
+
     /// set a block in the column
     pub fn set_block(&mut self, x: u8, y: u8, z: u8, state: BlockState) {
         let section_idx = y >> 4;
@@ -479,10 +641,10 @@ impl Column {
 mod tests {
     use std::collections::HashMap;
 
-    use interfaces::types::BlockState;
+    use interfaces::types::{BlockApprox, BlockState, SimpleType};
     use itertools::Itertools;
 
-    use crate::storage::chunk::{bits_needed, Palette};
+    use crate::storage::chunk::{bits_needed, Column, Palette};
 
     #[test]
     fn test_bits_needed() {
@@ -493,6 +655,16 @@ mod tests {
         assert_eq!(3, bits_needed(5)); // 100
     }
 
+    #[test]
+    fn test_palette_from_states() {
+        let states = (0..4096_u32).map(|idx| BlockState(idx % 300));
+        let palette = Palette::from_states(states);
+
+        for (idx, state) in palette.all_states().into_iter().enumerate() {
+            assert_eq!(state, BlockState(idx as u32 % 300));
+        }
+    }
+
     #[test]
     fn test_palette_expand() {
         let mut palette = Palette::default();
@@ -561,4 +733,49 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_demote() {
+        let mut column = Column::default();
+
+        let block = |x: u8, y: u8, z: u8| match (x + y + z) % 3 {
+            0 => BlockState::STONE,
+            1 => BlockState::WATER,
+            _ => BlockState::AIR,
+        };
+
+        for ((x, y), z) in (0..16).cartesian_product(0..32).cartesian_product(0..16) {
+            column.set_block(x, y, z, block(x, y, z));
+        }
+
+        assert!(column.is_high_memory());
+        let demoted_memory = column.demoted_memory();
+
+        column.demote();
+        assert!(!column.is_high_memory());
+        assert_eq!(column.memory(), demoted_memory);
+
+        let estimate = |approx| match approx {
+            BlockApprox::Estimate(simple) => simple,
+            BlockApprox::Realized(state) => panic!("{state:?} is not an estimate"),
+        };
+
+        for ((x, y), z) in (0..16).cartesian_product(0..32).cartesian_product(0..16) {
+            assert_eq!(
+                estimate(column.get_block(x, y, z)),
+                block(x, y, z).simple_type(),
+                "not eq at {x} {y} {z}"
+            );
+        }
+
+        // sections that were never sent are empty
+        assert_eq!(
+            estimate(column.get_block(0, 100, 0)),
+            SimpleType::WalkThrough
+        );
+
+        // blocks can still be changed
+        column.set_block(1, 1, 1, BlockState::STONE);
+        assert_eq!(estimate(column.get_block(1, 1, 1)), SimpleType::Solid);
+    }
 }
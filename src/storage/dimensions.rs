@@ -0,0 +1,55 @@
+use std::ops::{Index, IndexMut};
+
+use crate::types::Dimension;
+
+/// One `T` for each [`Dimension`]. Bots in different dimensions share the
+/// same [`crate::client::state::global::GlobalState`] so anything which
+/// depends on the world they are in is indexed by their dimension.
+#[derive(Default)]
+pub struct Dimensions<T> {
+    overworld: T,
+    nether: T,
+    end: T,
+}
+
+impl<T> Dimensions<T> {
+    pub fn iter(&self) -> impl Iterator<Item = (Dimension, &T)> + '_ {
+        [
+            (Dimension::Overworld, &self.overworld),
+            (Dimension::Nether, &self.nether),
+            (Dimension::End, &self.end),
+        ]
+        .into_iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Dimension, &mut T)> + '_ {
+        [
+            (Dimension::Overworld, &mut self.overworld),
+            (Dimension::Nether, &mut self.nether),
+            (Dimension::End, &mut self.end),
+        ]
+        .into_iter()
+    }
+}
+
+impl<T> Index<Dimension> for Dimensions<T> {
+    type Output = T;
+
+    fn index(&self, dimension: Dimension) -> &Self::Output {
+        match dimension {
+            Dimension::Overworld => &self.overworld,
+            Dimension::Nether => &self.nether,
+            Dimension::End => &self.end,
+        }
+    }
+}
+
+impl<T> IndexMut<Dimension> for Dimensions<T> {
+    fn index_mut(&mut self, dimension: Dimension) -> &mut Self::Output {
+        match dimension {
+            Dimension::Overworld => &mut self.overworld,
+            Dimension::Nether => &mut self.nether,
+            Dimension::End => &mut self.end,
+        }
+    }
+}
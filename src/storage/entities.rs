@@ -11,6 +11,10 @@ pub struct EntityData {
     pub location: Location,
     pub owner: Option<u32>,
     pub kind: EntityKind,
+
+    /// loaded from a save. The server may have reused the id for another
+    /// entity, so stale entities are not targeted
+    pub stale: bool,
 }
 
 #[derive(Default)]
@@ -22,18 +26,22 @@ pub struct WorldEntities {
 impl WorldEntities {
     pub fn update_entity(&mut self, entity_id: u32, bot_id: u32, location: LocationOrigin) {
         let Some(entity) = self.entities.get_mut(&entity_id) else { return };
+        if entity.stale {
+            return;
+        }
         let id = entity.owner.get_or_insert(bot_id);
         if *id == bot_id {
             entity.location.apply_change(location);
         }
     }
 
+    /// every entity which is not stale
     pub fn iter(&self) -> impl Iterator<Item = (&u32, &EntityData)> + '_ {
-        self.entities.iter()
+        self.entities.iter().filter(|(_, data)| !data.stale)
     }
 
     pub fn by_id(&self, id: u32) -> Option<&EntityData> {
-        self.entities.get(&id)
+        self.entities.get(&id).filter(|data| !data.stale)
     }
 
     pub fn by_player_uuid(&self, uuid: u128) -> Option<u32> {
@@ -52,6 +60,16 @@ impl WorldEntities {
         }
     }
 
+    /// stop updating every entity `bot_id` owns. Used when a bot leaves the
+    /// dimension.
+    pub fn release_all(&mut self, bot_id: u32) {
+        for entity in self.entities.values_mut() {
+            if entity.owner == Some(bot_id) {
+                entity.owner = None;
+            }
+        }
+    }
+
     pub fn put_entity(
         &mut self,
         entity_id: u32,
@@ -65,7 +83,19 @@ impl WorldEntities {
                 location,
                 owner: Some(bot_id),
                 kind,
+                stale: false,
             },
         );
     }
+
+    /// add an entity loaded from a save. Entities which are already known are
+    /// kept
+    pub fn put_stale_entity(&mut self, entity_id: u32, location: Location, kind: EntityKind) {
+        self.entities.entry(entity_id).or_insert(EntityData {
+            location,
+            owner: None,
+            kind,
+            stale: true,
+        });
+    }
 }
@@ -0,0 +1,339 @@
+//! Translation from flattened (1.13+) block and item names to the 1.12 ids
+//! that the rest of the bot works with.
+//!
+//! 1.12 names are read from the same prismarine `blocks.json`/`foods.json` used
+//! by [`BlockData`](interfaces::types::block_data::BlockData). Everything that
+//! was renamed or split into its own id by the flattening is listed in the
+//! tables below.
+
+use std::{collections::HashMap, fs::OpenOptions};
+
+use anyhow::Context;
+use interfaces::types::{BlockKind, BlockState};
+use serde::Deserialize;
+
+/// The 16 dye colors in the order of their 1.12 metadata value
+const COLORS: [&str; 16] = [
+    "white",
+    "orange",
+    "magenta",
+    "light_blue",
+    "yellow",
+    "lime",
+    "pink",
+    "gray",
+    "light_gray",
+    "cyan",
+    "purple",
+    "blue",
+    "brown",
+    "green",
+    "red",
+    "black",
+];
+
+/// The wood types in the order of their 1.12 metadata value
+const WOODS: [&str; 6] = ["oak", "spruce", "birch", "jungle", "acacia", "dark_oak"];
+
+/// `{color}_{suffix}` blocks which were a single id with a color as metadata
+const COLORED: [(&str, u32); 7] = [
+    ("wool", 35),
+    ("stained_glass", 95),
+    ("terracotta", 159),
+    ("stained_glass_pane", 160),
+    ("carpet", 171),
+    ("concrete", 251),
+    ("concrete_powder", 252),
+];
+
+/// flattened name → (1.12 id, metadata). These take precedence over the 1.12
+/// names as some names (`grass`, `snow`) changed meaning.
+const BLOCK_RENAMES: &[(&str, u32, u16)] = &[
+    ("cave_air", 0, 0),
+    ("void_air", 0, 0),
+    ("granite", 1, 1),
+    ("polished_granite", 1, 2),
+    ("diorite", 1, 3),
+    ("polished_diorite", 1, 4),
+    ("andesite", 1, 5),
+    ("polished_andesite", 1, 6),
+    ("grass_block", 2, 0),
+    ("coarse_dirt", 3, 1),
+    ("podzol", 3, 2),
+    ("red_sand", 12, 1),
+    ("sponge", 19, 0),
+    ("wet_sponge", 19, 1),
+    ("chiseled_sandstone", 24, 1),
+    ("cut_sandstone", 24, 2),
+    ("note_block", 25, 0),
+    ("powered_rail", 27, 0),
+    ("cobweb", 30, 0),
+    ("grass", 31, 1),
+    ("fern", 31, 2),
+    ("dead_bush", 32, 0),
+    ("seagrass", 9, 0),
+    ("tall_seagrass", 9, 0),
+    ("kelp", 9, 0),
+    ("kelp_plant", 9, 0),
+    ("bubble_column", 9, 0),
+    ("moving_piston", 36, 0),
+    ("dandelion", 37, 0),
+    ("poppy", 38, 0),
+    ("blue_orchid", 38, 1),
+    ("allium", 38, 2),
+    ("azure_bluet", 38, 3),
+    ("red_tulip", 38, 4),
+    ("orange_tulip", 38, 5),
+    ("white_tulip", 38, 6),
+    ("pink_tulip", 38, 7),
+    ("oxeye_daisy", 38, 8),
+    ("cornflower", 38, 0),
+    ("lily_of_the_valley", 38, 0),
+    ("smooth_stone", 43, 8),
+    ("smooth_stone_slab", 44, 0),
+    ("stone_slab", 44, 0),
+    ("sandstone_slab", 44, 1),
+    ("petrified_oak_slab", 44, 2),
+    ("cobblestone_slab", 44, 3),
+    ("brick_slab", 44, 4),
+    ("stone_brick_slab", 44, 5),
+    ("nether_brick_slab", 44, 6),
+    ("quartz_slab", 44, 7),
+    ("bricks", 45, 0),
+    ("wall_torch", 50, 0),
+    ("spawner", 52, 0),
+    ("oak_sign", 63, 0),
+    ("oak_door", 64, 0),
+    ("cobblestone_stairs", 67, 0),
+    ("oak_wall_sign", 68, 0),
+    ("oak_pressure_plate", 72, 0),
+    ("redstone_wall_torch", 76, 0),
+    ("snow", 78, 0),
+    ("snow_block", 80, 0),
+    ("sugar_cane", 83, 0),
+    ("oak_fence", 85, 0),
+    ("carved_pumpkin", 86, 0),
+    ("nether_portal", 90, 0),
+    ("jack_o_lantern", 91, 0),
+    ("repeater", 93, 0),
+    ("oak_trapdoor", 96, 0),
+    ("infested_stone", 97, 0),
+    ("infested_cobblestone", 97, 1),
+    ("infested_stone_bricks", 97, 2),
+    ("infested_mossy_stone_bricks", 97, 3),
+    ("infested_cracked_stone_bricks", 97, 4),
+    ("infested_chiseled_stone_bricks", 97, 5),
+    ("stone_bricks", 98, 0),
+    ("mossy_stone_bricks", 98, 1),
+    ("cracked_stone_bricks", 98, 2),
+    ("chiseled_stone_bricks", 98, 3),
+    ("mushroom_stem", 99, 10),
+    ("melon", 103, 0),
+    ("attached_pumpkin_stem", 104, 0),
+    ("attached_melon_stem", 105, 0),
+    ("oak_fence_gate", 107, 0),
+    ("lily_pad", 111, 0),
+    ("nether_bricks", 112, 0),
+    ("cobblestone_wall", 139, 0),
+    ("mossy_cobblestone_wall", 139, 1),
+    ("oak_button", 143, 0),
+    ("skeleton_skull", 144, 0),
+    ("chipped_anvil", 145, 4),
+    ("damaged_anvil", 145, 8),
+    ("comparator", 149, 0),
+    ("nether_quartz_ore", 153, 0),
+    ("chiseled_quartz_block", 155, 1),
+    ("quartz_pillar", 155, 2),
+    ("slime_block", 165, 0),
+    ("prismarine_bricks", 168, 1),
+    ("dark_prismarine", 168, 2),
+    ("terracotta", 172, 0),
+    ("sunflower", 175, 0),
+    ("lilac", 175, 1),
+    ("tall_grass", 175, 2),
+    ("large_fern", 175, 3),
+    ("rose_bush", 175, 4),
+    ("peony", 175, 5),
+    ("chiseled_red_sandstone", 179, 1),
+    ("cut_red_sandstone", 179, 2),
+    ("red_sandstone_slab", 182, 0),
+    ("end_stone_bricks", 206, 0),
+    ("magma_block", 213, 0),
+    ("red_nether_bricks", 215, 0),
+    ("shulker_box", 229, 0),
+];
+
+/// flattened item name → (1.12 id, damage) for items which are not blocks
+const ITEM_RENAMES: &[(&str, u32, u16)] = &[
+    ("iron_shovel", 256, 0),
+    ("iron_pickaxe", 257, 0),
+    ("iron_axe", 258, 0),
+    ("flint_and_steel", 259, 0),
+    ("iron_sword", 267, 0),
+    ("wooden_sword", 268, 0),
+    ("wooden_shovel", 269, 0),
+    ("wooden_pickaxe", 270, 0),
+    ("wooden_axe", 271, 0),
+    ("stone_sword", 272, 0),
+    ("stone_shovel", 273, 0),
+    ("stone_pickaxe", 274, 0),
+    ("stone_axe", 275, 0),
+    ("diamond_sword", 276, 0),
+    ("diamond_shovel", 277, 0),
+    ("diamond_pickaxe", 278, 0),
+    ("diamond_axe", 279, 0),
+    ("golden_sword", 283, 0),
+    ("golden_shovel", 284, 0),
+    ("golden_pickaxe", 285, 0),
+    ("golden_axe", 286, 0),
+    // netherite did not exist. diamond is the closest in mining speed
+    ("netherite_sword", 276, 0),
+    ("netherite_shovel", 277, 0),
+    ("netherite_pickaxe", 278, 0),
+    ("netherite_axe", 279, 0),
+    ("bucket", 325, 0),
+    ("water_bucket", 326, 0),
+    ("lava_bucket", 327, 0),
+    ("cake", 354, 0),
+    ("enchanted_golden_apple", 322, 1),
+    ("cod", 349, 0),
+    ("salmon", 349, 1),
+    ("tropical_fish", 349, 2),
+    ("pufferfish", 349, 3),
+    ("cooked_cod", 350, 0),
+    ("cooked_salmon", 350, 1),
+    ("melon_slice", 360, 0),
+];
+
+/// Only the fields we need from prismarine.js data
+#[derive(Deserialize)]
+struct RawNamed {
+    id: u32,
+    name: String,
+}
+
+fn read_named(path: &str) -> anyhow::Result<Vec<RawNamed>> {
+    let reader = OpenOptions::new()
+        .read(true)
+        .open(path)
+        .with_context(|| format!("could not open {path}"))?;
+    serde_json::from_reader(reader).with_context(|| format!("could not parse {path}"))
+}
+
+/// strip the `minecraft:` namespace if present
+fn strip_namespace(name: &str) -> &str {
+    name.strip_prefix("minecraft:").unwrap_or(name)
+}
+
+/// Lookup from flattened names to 1.12 ids
+pub struct LegacyNames {
+    blocks: HashMap<String, BlockState>,
+    items: HashMap<String, BlockState>,
+}
+
+impl LegacyNames {
+    pub fn read() -> anyhow::Result<Self> {
+        let mut blocks: HashMap<_, _> = read_named("blocks.json")?
+            .into_iter()
+            .map(|block| (block.name, BlockState::from(block.id, 0)))
+            .collect();
+
+        for (meta, color) in COLORS.into_iter().enumerate() {
+            for (suffix, id) in COLORED {
+                blocks.insert(
+                    format!("{color}_{suffix}"),
+                    BlockState::from(id, meta as u16),
+                );
+            }
+            blocks.insert(format!("{color}_bed"), BlockState::from(26, 0));
+            blocks.insert(format!("{color}_banner"), BlockState::from(176, 0));
+            blocks.insert(format!("{color}_wall_banner"), BlockState::from(177, 0));
+        }
+
+        for (meta, wood) in WOODS.into_iter().enumerate() {
+            let meta = meta as u16;
+
+            // 1.12 split logs and leaves across two ids as only 4 fit in the metadata
+            let (log, leaves, log_meta) = if meta < 4 {
+                (17, 18, meta)
+            } else {
+                (162, 161, meta - 4)
+            };
+
+            blocks.insert(format!("{wood}_planks"), BlockState::from(5, meta));
+            blocks.insert(format!("{wood}_sapling"), BlockState::from(6, meta));
+            blocks.insert(format!("{wood}_log"), BlockState::from(log, log_meta));
+            blocks.insert(
+                format!("stripped_{wood}_log"),
+                BlockState::from(log, log_meta),
+            );
+            blocks.insert(format!("{wood}_wood"), BlockState::from(log, log_meta | 12));
+            blocks.insert(
+                format!("stripped_{wood}_wood"),
+                BlockState::from(log, log_meta | 12),
+            );
+            blocks.insert(format!("{wood}_leaves"), BlockState::from(leaves, log_meta));
+            blocks.insert(format!("{wood}_slab"), BlockState::from(126, meta));
+        }
+
+        for &(name, id, meta) in BLOCK_RENAMES {
+            blocks.insert(name.to_string(), BlockState::from(id, meta));
+        }
+
+        let mut items = blocks.clone();
+
+        for food in read_named("foods.json")? {
+            items.insert(food.name, BlockState::from(food.id, 0));
+        }
+
+        for &(name, id, damage) in ITEM_RENAMES {
+            items.insert(name.to_string(), BlockState::from(id, damage));
+        }
+
+        Ok(Self { blocks, items })
+    }
+
+    /// the 1.12 state of a flattened block name. Block properties (e.g.,
+    /// `facing`) are not taken into account.
+    pub fn block(&self, name: &str) -> Option<BlockState> {
+        self.blocks.get(strip_namespace(name)).copied()
+    }
+
+    /// the 1.12 (id, damage) of a flattened item name
+    pub fn item(&self, name: &str) -> Option<(BlockKind, u16)> {
+        self.items
+            .get(strip_namespace(name))
+            .map(|state| (state.kind(), u16::from(state.metadata())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use interfaces::types::{BlockKind, BlockState};
+
+    use crate::storage::legacy::LegacyNames;
+
+    #[test]
+    fn test_flattened_names() {
+        let names = LegacyNames::read().unwrap();
+
+        assert_eq!(names.block("minecraft:stone"), Some(BlockState::STONE));
+        assert_eq!(names.block("granite"), Some(BlockState::from(1, 1)));
+        assert_eq!(names.block("grass_block"), Some(BlockState::from(2, 0)));
+        assert_eq!(names.block("grass"), Some(BlockState::from(31, 1)));
+        assert_eq!(
+            names.block("light_blue_wool"),
+            Some(BlockState::from(35, 3))
+        );
+        assert_eq!(names.block("dark_oak_log"), Some(BlockState::from(162, 1)));
+        assert_eq!(names.block("warped_planks"), None);
+
+        assert_eq!(names.item("water_bucket"), Some((BlockKind(326), 0)));
+        assert_eq!(names.item("cooked_beef"), Some((BlockKind(364), 0)));
+        assert_eq!(names.item("cake"), Some((BlockKind(354), 0)));
+        assert_eq!(
+            names.item("minecraft:diamond_pickaxe"),
+            Some((BlockKind(278), 0))
+        );
+    }
+}
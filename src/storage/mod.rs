@@ -1,3 +1,7 @@
+pub mod anvil;
 pub mod blocks;
 pub mod chunk;
+pub mod dimensions;
 pub mod entities;
+pub mod legacy;
+pub mod persist;
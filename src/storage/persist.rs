@@ -0,0 +1,466 @@
+//! Saving the blocks and entities bots have seen so they do not have to be
+//! learned again after a restart. Each dimension of a server is saved to
+//! `<world dir>/<server>/<dimension>.world`.
+//!
+//! Loaded columns are stale until the server sends them again. Until then
+//! their blocks are estimates, which is enough to plan paths through chunks
+//! bots have not loaded yet. Loaded entities are stale as well, and are not
+//! saved again unless the server sends them, as their ids may have been
+//! reused.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::{ensure, Context};
+use bincode::{Decode, Encode};
+use interfaces::types::{BlockState, ChunkLocation};
+
+use crate::{
+    storage::{
+        blocks::WorldBlocks,
+        chunk::{ChunkData, Column, HighMemoryChunkSection, LowMemoryChunkSection, Palette},
+        dimensions::Dimensions,
+        entities::{EntityKind, WorldEntities},
+    },
+    types::{Dimension, Location},
+};
+
+/// bumped whenever the format changes. Saves of other versions are ignored
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Encode, Decode)]
+struct SavedWorld {
+    version: u32,
+    columns: Vec<SavedColumn>,
+    entities: Vec<SavedEntity>,
+}
+
+#[derive(Encode, Decode)]
+enum SavedColumn {
+    Low {
+        x: i32,
+        z: i32,
+        /// the index of each section and its packed simple types
+        sections: Vec<(u8, Vec<u8>)>,
+    },
+    High {
+        x: i32,
+        z: i32,
+        sections: Vec<(u8, SavedPalette)>,
+    },
+}
+
+#[derive(Encode, Decode)]
+struct SavedPalette {
+    bits_per_block: u8,
+    /// the states of the palette. None if the palette is direct
+    states: Option<Vec<u32>>,
+    storage: Vec<u64>,
+}
+
+#[derive(Encode, Decode)]
+struct SavedEntity {
+    id: u32,
+    x: f64,
+    y: f64,
+    z: f64,
+    /// the uuid if the entity is a player
+    player: Option<u128>,
+}
+
+/// the index and section of every section in `data`
+fn sections<T>(data: &ChunkData<T>) -> impl Iterator<Item = (u8, &T)> + '_ {
+    data.sections
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, section)| Some((idx as u8, section.as_deref()?)))
+}
+
+impl SavedColumn {
+    fn new(location: ChunkLocation, column: &Column) -> Self {
+        let ChunkLocation(x, z) = location;
+        match column {
+            Column::LowMemory { data } => Self::Low {
+                x,
+                z,
+                sections: sections(data)
+                    .map(|(idx, section)| (idx, section.bytes().to_vec()))
+                    .collect(),
+            },
+            Column::HighMemory { data } => Self::High {
+                x,
+                z,
+                sections: sections(data)
+                    .map(|(idx, section)| {
+                        let (bits_per_block, states, storage) = section.palette.parts();
+                        let palette = SavedPalette {
+                            bits_per_block,
+                            states: states.map(|states| states.iter().map(|s| s.0).collect()),
+                            storage: storage.to_vec(),
+                        };
+                        (idx, palette)
+                    })
+                    .collect(),
+            },
+        }
+    }
+
+    fn into_column(self) -> anyhow::Result<(ChunkLocation, Column)> {
+        /// put `section` at `idx` of `data`
+        fn insert<T>(data: &mut ChunkData<T>, idx: u8, section: T) -> anyhow::Result<()> {
+            let slot = data
+                .sections
+                .get_mut(idx as usize)
+                .with_context(|| format!("there is no section {idx}"))?;
+            *slot = Some(Box::new(section));
+            Ok(())
+        }
+
+        match self {
+            Self::Low { x, z, sections } => {
+                let mut data = ChunkData::default();
+                for (idx, bytes) in sections {
+                    insert(&mut data, idx, LowMemoryChunkSection::from_bytes(&bytes)?)?;
+                }
+                Ok((ChunkLocation(x, z), Column::LowMemory { data }))
+            }
+            Self::High { x, z, sections } => {
+                let mut data = ChunkData::default();
+                for (idx, palette) in sections {
+                    let SavedPalette {
+                        bits_per_block,
+                        states,
+                        storage,
+                    } = palette;
+                    let states = states.map(|states| states.into_iter().map(BlockState).collect());
+                    let palette = Palette::from_parts(bits_per_block, states, storage)?;
+                    insert(&mut data, idx, HighMemoryChunkSection::new(palette))?;
+                }
+                Ok((ChunkLocation(x, z), Column::HighMemory { data }))
+            }
+        }
+    }
+}
+
+impl SavedWorld {
+    /// `blocks` and `entities` of one dimension in the save format. Stale
+    /// entities are left out
+    fn new(blocks: &WorldBlocks, entities: &WorldEntities) -> Self {
+        let columns = blocks
+            .columns()
+            .map(|(location, column)| SavedColumn::new(location, column))
+            .collect();
+
+        let entities = entities
+            .iter()
+            .map(|(&id, data)| SavedEntity {
+                id,
+                x: data.location.x,
+                y: data.location.y,
+                z: data.location.z,
+                player: match data.kind {
+                    EntityKind::Normal => None,
+                    EntityKind::Player { uuid } => Some(uuid),
+                },
+            })
+            .collect();
+
+        Self {
+            version: FORMAT_VERSION,
+            columns,
+            entities,
+        }
+    }
+
+    fn encode(&self) -> anyhow::Result<Vec<u8>> {
+        bincode::encode_to_vec(self, bincode::config::standard()).context("could not encode world")
+    }
+}
+
+/// Add a save made by [`Snapshot::write`] to `blocks` and `entities` as stale columns
+/// and entities. Returns the number of columns in the save
+pub fn decode(
+    bytes: &[u8],
+    blocks: &mut WorldBlocks,
+    entities: &mut WorldEntities,
+) -> anyhow::Result<usize> {
+    let (world, _): (SavedWorld, _) =
+        bincode::decode_from_slice(bytes, bincode::config::standard())
+            .context("could not decode world")?;
+
+    ensure!(
+        world.version == FORMAT_VERSION,
+        "the world was saved with format {} but {FORMAT_VERSION} is supported",
+        world.version
+    );
+
+    // decode everything before changing the world so a corrupt save adds nothing
+    let columns = world
+        .columns
+        .into_iter()
+        .map(SavedColumn::into_column)
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let count = columns.len();
+
+    for (location, column) in columns {
+        blocks.add_stale_column(location, column);
+    }
+
+    for entity in world.entities {
+        let kind = match entity.player {
+            None => EntityKind::Normal,
+            Some(uuid) => EntityKind::Player { uuid },
+        };
+        let location = Location::new(entity.x, entity.y, entity.z);
+        entities.put_stale_entity(entity.id, location, kind);
+    }
+
+    Ok(count)
+}
+
+/// `name` (a server or world name) as the name of a directory. Only letters,
+/// digits and `.` are kept so it cannot escape the directory it is put in
+pub fn dir_name(name: &str) -> String {
+    // `:` separates the port but is not allowed in paths on Windows
+    let name = name.replace(|c: char| !c.is_alphanumeric() && c != '.', "_");
+
+    // `.` and `..` are not names
+    if name.chars().all(|c| c == '.') {
+        name.replace('.', "_")
+    } else {
+        name
+    }
+}
+
+/// The dimensions of a world copied by [`WorldSave::snapshot`]
+pub struct Snapshot {
+    /// the directory of the server
+    dir: PathBuf,
+
+    /// each dimension and the file it is saved to
+    worlds: Vec<(PathBuf, SavedWorld)>,
+}
+
+impl Snapshot {
+    /// Encode and write every dimension. The previous save of a dimension is
+    /// only replaced once the new one is written completely
+    pub fn write(self) -> anyhow::Result<()> {
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("could not create {}", self.dir.display()))?;
+
+        for (path, world) in self.worlds {
+            let bytes = world.encode()?;
+
+            let partial = path.with_extension("world.partial");
+            fs::write(&partial, bytes)
+                .with_context(|| format!("could not write {}", partial.display()))?;
+            fs::rename(&partial, &path)
+                .with_context(|| format!("could not replace {}", path.display()))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Where the world of one server is saved and how often
+pub struct WorldSave {
+    /// the directory of the server
+    dir: PathBuf,
+
+    /// the time between saves
+    every: Duration,
+
+    /// when the world was last saved
+    last: Instant,
+}
+
+impl WorldSave {
+    /// Save the world of `server` in a directory of `world_dir` every `every`
+    pub fn new(world_dir: &Path, server: &str, every: Duration) -> Self {
+        Self {
+            dir: world_dir.join(dir_name(server)),
+            every,
+            last: Instant::now(),
+        }
+    }
+
+    fn path(&self, dimension: Dimension) -> PathBuf {
+        self.dir.join(format!("{dimension}.world"))
+    }
+
+    /// Load every dimension which has been saved
+    pub fn load(
+        &self,
+        blocks: &mut Dimensions<WorldBlocks>,
+        entities: &mut Dimensions<WorldEntities>,
+    ) -> anyhow::Result<()> {
+        for (dimension, blocks) in blocks.iter_mut() {
+            let path = self.path(dimension);
+            if !path.exists() {
+                continue;
+            }
+
+            let bytes =
+                fs::read(&path).with_context(|| format!("could not read {}", path.display()))?;
+
+            let columns = decode(&bytes, blocks, &mut entities[dimension])
+                .with_context(|| format!("invalid world save {}", path.display()))?;
+
+            println!(
+                "loaded {columns} columns of the {dimension} from {}",
+                path.display()
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Copy every dimension which has columns or entities, to be written by
+    /// [`Snapshot::write`] off the game loop
+    pub fn snapshot(
+        &mut self,
+        blocks: &Dimensions<WorldBlocks>,
+        entities: &Dimensions<WorldEntities>,
+    ) -> Snapshot {
+        self.last = Instant::now();
+
+        let worlds = blocks
+            .iter()
+            .filter_map(|(dimension, blocks)| {
+                let entities = &entities[dimension];
+                if blocks.columns().next().is_none() && entities.iter().next().is_none() {
+                    return None;
+                }
+
+                Some((self.path(dimension), SavedWorld::new(blocks, entities)))
+            })
+            .collect();
+
+        Snapshot {
+            dir: self.dir.clone(),
+            worlds,
+        }
+    }
+
+    /// if it is time to save again
+    pub fn is_due(&self) -> bool {
+        self.last.elapsed() >= self.every
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_matches::assert_matches as am;
+    use interfaces::types::{BlockApprox, BlockLocation, BlockState, ChunkLocation, SimpleType};
+
+    use crate::{
+        storage::{
+            blocks::WorldBlocks,
+            chunk::Column,
+            entities::{EntityKind, WorldEntities},
+            persist::{decode, SavedWorld},
+        },
+        types::Location,
+    };
+
+    fn encode(blocks: &WorldBlocks, entities: &WorldEntities) -> anyhow::Result<Vec<u8>> {
+        SavedWorld::new(blocks, entities).encode()
+    }
+
+    fn saved(bytes: &[u8]) -> SavedWorld {
+        bincode::decode_from_slice(bytes, bincode::config::standard())
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut blocks = WorldBlocks::default();
+        for x in 0..16 {
+            for z in 0..16 {
+                // enough states for an indirect palette
+                let state = BlockState(((x * 16 + z) % 20 + 1) as u32);
+                blocks.set_block(BlockLocation::new(x, 70, z), state);
+            }
+        }
+        blocks.set_block(BlockLocation::new(40, 3, 40), BlockState::STONE);
+
+        // a column no bot has loaded any more is low memory
+        blocks.hold(ChunkLocation(2, 2), 1);
+        blocks.release(ChunkLocation(2, 2), 1);
+
+        let mut entities = WorldEntities::default();
+        entities.put_entity(
+            7,
+            1,
+            Location::new(1.0, 2.0, 3.0),
+            EntityKind::Player { uuid: 9 },
+        );
+
+        let bytes = encode(&blocks, &entities).unwrap();
+
+        let mut loaded_blocks = WorldBlocks::default();
+        let mut loaded_entities = WorldEntities::default();
+        let columns = decode(&bytes, &mut loaded_blocks, &mut loaded_entities).unwrap();
+        assert_eq!(columns, 2);
+
+        // the saved blocks match the original ones
+        let original = blocks.get_real_column(ChunkLocation(0, 0)).unwrap();
+        let Some((_, Column::HighMemory { data })) = loaded_blocks
+            .columns()
+            .find(|(location, _)| *location == ChunkLocation(0, 0)) else {
+            panic!("the column was not loaded with its palette");
+        };
+        assert_eq!(data.all_at(70), original.all_at(70));
+
+        // loaded columns are stale so their blocks are estimates
+        let location = BlockLocation::new(3, 70, 5);
+        assert!(loaded_blocks.is_stale(ChunkLocation(0, 0)));
+        am!(
+            loaded_blocks.get_block(location),
+            Some(BlockApprox::Estimate(_))
+        );
+        assert_eq!(
+            loaded_blocks.get_block_simple(location),
+            blocks.get_block_simple(location)
+        );
+        assert!(loaded_blocks.get_block_exact(location).is_none());
+        assert!(loaded_blocks.get_real_column(ChunkLocation(0, 0)).is_none());
+        assert_eq!(
+            loaded_blocks.get_block_simple(BlockLocation::new(40, 3, 40)),
+            Some(SimpleType::Solid)
+        );
+
+        // stale entities are not targeted
+        assert_eq!(saved(&bytes).entities.len(), 1);
+        assert!(loaded_entities.by_id(7).is_none());
+
+        // or saved again, so saves do not grow with ids which are never used
+        let bytes = encode(&loaded_blocks, &loaded_entities).unwrap();
+        assert!(saved(&bytes).entities.is_empty());
+
+        // once the server sends a column it is no longer stale
+        loaded_blocks.add_column(ChunkLocation(0, 0), Column::default());
+        assert!(!loaded_blocks.is_stale(ChunkLocation(0, 0)));
+        assert_eq!(
+            loaded_blocks.get_block_exact(location),
+            Some(BlockState::AIR)
+        );
+    }
+
+    #[test]
+    fn test_corrupt_save() {
+        let mut blocks = WorldBlocks::default();
+        blocks.set_block(BlockLocation::new(0, 0, 0), BlockState::STONE);
+        let mut bytes = encode(&blocks, &WorldEntities::default()).unwrap();
+        bytes.truncate(bytes.len() / 2);
+
+        let mut loaded = WorldBlocks::default();
+        assert!(decode(&bytes, &mut loaded, &mut WorldEntities::default()).is_err());
+        assert!(loaded.columns().next().is_none());
+    }
+}
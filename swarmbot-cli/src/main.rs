@@ -4,8 +4,10 @@ use std::{io, io::Write};
 
 use anyhow::{bail, Context};
 use clap::Parser;
-use futures::SinkExt;
-use swarmbot_interfaces::{types::BlockLocation, CommandData, GoTo};
+use futures::{SinkExt, StreamExt};
+use swarmbot_interfaces::{
+    types::BlockLocation, Cancelled, CommandData, Event, GoTo, Selector, Stop, Subscribe,
+};
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio_tungstenite::tungstenite::Message;
 
@@ -15,6 +17,13 @@ use tokio_tungstenite::tungstenite::Message;
 ///
 ///
 /// goto {x} {y} {z}    — go to the coordinates {x} {y} {z}
+/// cancel {id}         — cancel the command {id}
+/// stop                — stop every task
+/// subscribe [telemetry] [world] [{bot}...]
+///                     — get the state of bots and what happens in the world
+///
+/// Commands run on every bot unless they have `@` arguments, which are the
+/// same as the ones of chat commands, e.g. `goto @tag=miners @near=3 0 64 0`
 #[derive(Parser, Debug)]
 #[clap(version = "1.0", author = "Andrew Gazelka")]
 struct CliOptions {
@@ -32,10 +41,23 @@ struct CliOptions {
 async fn run() -> anyhow::Result<!> {
     let CliOptions { ip, port } = CliOptions::parse();
 
-    let (mut web_socket, _) = tokio_tungstenite::connect_async(format!("ws://{ip}:{port}"))
+    let (web_socket, _) = tokio_tungstenite::connect_async(format!("ws://{ip}:{port}"))
         .await
         .context("could not create websocket")?;
 
+    let (mut web_socket, mut events) = web_socket.split();
+
+    // print what the bots report back about our commands
+    tokio::spawn(async move {
+        while let Some(Ok(msg)) = events.next().await {
+            let Ok(text) = msg.into_text() else { continue };
+            match serde_json::from_str::<Event>(&text) {
+                Ok(event) => println!("{event:?}"),
+                Err(_) => println!("unknown event {text}"),
+            }
+        }
+    });
+
     println!("connected to websocket");
     println!();
     let stdin = BufReader::new(tokio::io::stdin());
@@ -56,17 +78,67 @@ async fn run() -> anyhow::Result<!> {
 }
 
 fn send_string(input: &str) -> anyhow::Result<String> {
+    if let Some(subscribe) = to_subscribe(input) {
+        let mut value = serde_json::to_value(subscribe).context("converting to JSON")?;
+        value["path"] = "subscribe".into();
+        return Ok(value.to_string());
+    }
+
     let cmd_data = to_command_data(input)
         .with_context(|| format!("invalid converting to command data for {input}"))?;
-    let to_send = serde_json::to_string(&cmd_data).context("converting to JSON")?;
+    let mut value = serde_json::to_value(&cmd_data).context("converting to JSON")?;
+
+    let selector = to_selector(input)?;
+    if selector != Selector::default() {
+        value["select"] = serde_json::to_value(selector).context("converting to JSON")?;
+    }
+
+    let to_send = value.to_string();
 
     println!("sending {to_send}");
     println!();
     Ok(to_send)
 }
 
+/// the subscription of `subscribe [telemetry] [world] [{bot}...]`
+fn to_subscribe(input_str: &str) -> Option<Subscribe> {
+    let mut input = input_str.split_whitespace();
+    if input.next()? != "subscribe" {
+        return None;
+    }
+
+    let mut subscribe = Subscribe::default();
+    for arg in input {
+        match arg {
+            "telemetry" => subscribe.telemetry = true,
+            "world" => subscribe.world = true,
+            bot => subscribe.bots.push(bot.to_string()),
+        }
+    }
+
+    Some(subscribe)
+}
+
+/// the bots picked by the `@` arguments of `input_str`
+fn to_selector(input_str: &str) -> anyhow::Result<Selector> {
+    let mut selector = Selector::default();
+
+    for arg in input_str
+        .split_whitespace()
+        .filter(|arg| arg.starts_with('@'))
+    {
+        selector
+            .add_chat_arg(arg)
+            .with_context(|| format!("invalid selector {arg}"))?;
+    }
+
+    Ok(selector)
+}
+
 fn to_command_data(input_str: &str) -> anyhow::Result<CommandData> {
-    let mut input = input_str.trim().split(' ');
+    let mut input = input_str
+        .split_whitespace()
+        .filter(|arg| !arg.starts_with('@'));
 
     let cmd_name = input.next().context("no command name specified")?;
 
@@ -78,6 +150,10 @@ fn to_command_data(input_str: &str) -> anyhow::Result<CommandData> {
                 z: input.next().context("no z in goto")?.parse()?,
             },
         })),
+        "cancel" => Ok(CommandData::Cancelled(Cancelled {
+            id: input.next().context("no id in cancel")?.parse()?,
+        })),
+        "stop" => Ok(CommandData::Stop(Stop {})),
         _ => bail!("input '{input_str}' could not be parsed into a CommandData struct"),
     }
 }